@@ -0,0 +1,143 @@
+//! Tails the app's log output during a session so it shows up next to the
+//! debugger: `simctl spawn <sim> log stream` on a simulator and
+//! `idevicesyslog` on a device, both filtered to the app's process.
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    thread,
+};
+
+use crate::app_process::AppHost;
+
+/// The command that streams `process`'s log lines on `host`.
+pub fn log_command(process: &str, host: &AppHost) -> Command {
+    match host {
+        AppHost::Simulator(simulator) => {
+            let mut command = Command::new("xcrun");
+            command.args(["simctl", "spawn", simulator, "log", "stream"]);
+            command.args(["--style", "compact", "--level", "debug"]);
+            command.arg("--predicate").arg(process_predicate(process));
+            command
+        }
+        AppHost::Device(device) => {
+            let mut command = Command::new("idevicesyslog");
+            command.args(["-u", device, "-p", process, "--no-colors"]);
+            command
+        }
+    }
+}
+
+/// `log stream` predicate matching the process by executable name.
+fn process_predicate(process: &str) -> String {
+    format!(
+        "process == \"{}\"",
+        process.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// A running log tail; dropping it stops the tool.
+pub struct LogStream {
+    child: Child,
+}
+
+impl LogStream {
+    /// Spawns `command` and hands each line it prints to `sink` from a
+    /// background thread.
+    pub fn spawn(
+        mut command: Command,
+        sink: impl Fn(String) + Send + 'static,
+    ) -> Result<Self, String> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("failed to spawn {program}: {err}"))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) if !is_banner(&line) => sink(line),
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(Self { child })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The header lines both tools print before any log output.
+fn is_banner(line: &str) -> bool {
+    line.starts_with("Filtering the log data using")
+        || line.starts_with("Timestamp  ")
+        || line.starts_with("[connected:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn commands_filter_by_process() {
+        let command = log_command("My \"App\"", &AppHost::Simulator("booted".into()));
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+        assert_eq!(command.get_program(), "xcrun");
+        assert_eq!(
+            args,
+            [
+                "simctl",
+                "spawn",
+                "booted",
+                "log",
+                "stream",
+                "--style",
+                "compact",
+                "--level",
+                "debug",
+                "--predicate",
+                "process == \"My \\\"App\\\"\"",
+            ]
+        );
+
+        let command = log_command("MyApp", &AppHost::Device("00008120-0001".into()));
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+        assert_eq!(command.get_program(), "idevicesyslog");
+        assert_eq!(args, ["-u", "00008120-0001", "-p", "MyApp", "--no-colors"]);
+    }
+
+    #[test]
+    fn lines_reach_the_sink_without_banners() {
+        let (tx, rx) = mpsc::channel();
+        let mut command = Command::new("printf");
+        command.arg("Filtering the log data using \"process == x\"\nhello\nworld\n");
+        let _stream = LogStream::spawn(command, move |line| {
+            let _ = tx.send(line);
+        })
+        .unwrap();
+        let lines: Vec<String> = (0..2)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(lines, ["hello", "world"]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Finds the pid of a running app from its bundle identifier, so attach
+//! configurations can name the app rather than a pid that changes on every
+//! launch. Simulators are asked through `simctl spawn launchctl list`,
+//! devices through `devicectl`. Also lists running processes for attach
+//! configurations that ask the adapter to pick one.
+
+use std::{
+    env, fs,
+    process::Command,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde_json::Value;
+
+/// Where the app runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppHost {
+    /// A simulator UDID or name, or `booted`.
+    Simulator(String),
+    /// A device identifier as understood by `devicectl --device`.
+    Device(String),
+}
+
+/// The pid of the running app with `bundle_id` on `host`.
+pub fn pid_for_bundle_id(bundle_id: &str, host: &AppHost) -> Result<u32, String> {
+    let pid = match host {
+        AppHost::Simulator(simulator) => {
+            let listing = xcrun(&["simctl", "spawn", simulator, "launchctl", "list"])?;
+            parse_launchctl_pid(&listing, bundle_id)
+        }
+        AppHost::Device(device) => {
+            let apps = devicectl_json(&[
+                "device",
+                "info",
+                "apps",
+                "--device",
+                device,
+                "--bundle-id",
+                bundle_id,
+            ])?;
+            let app_url = parse_app_url(&apps, bundle_id)
+                .ok_or_else(|| format!("{bundle_id} is not installed on {device}"))?;
+            let processes = devicectl_json(&["device", "info", "processes", "--device", device])?;
+            parse_process_pid(&processes, &app_url)
+        }
+    };
+    pid.ok_or_else(|| format!("{bundle_id} is not running"))
+}
+
+/// A process as the `pickProcess` listing shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningProcess {
+    pub pid: u32,
+    pub path: String,
+}
+
+/// Every process on `host`, or on this Mac without one. Simulator apps are
+/// Mac processes, so a simulator narrows the Mac's list to those inside a
+/// simulator's data (that simulator's, unless it is `booted`).
+pub fn running_processes(host: Option<&AppHost>) -> Result<Vec<RunningProcess>, String> {
+    let simulator = match host {
+        Some(AppHost::Device(device)) => {
+            let processes = devicectl_json(&["device", "info", "processes", "--device", device])?;
+            return Ok(parse_device_processes(&processes));
+        }
+        Some(AppHost::Simulator(simulator)) => Some(simulator),
+        None => None,
+    };
+    let output = Command::new("ps")
+        .args(["-Ao", "pid=,comm="])
+        .output()
+        .map_err(|err| format!("failed to run ps: {err}"))?;
+    let mut processes = parse_ps(&String::from_utf8_lossy(&output.stdout));
+    if let Some(simulator) = simulator {
+        processes.retain(|process| runs_in_simulator(process, simulator));
+    }
+    Ok(processes)
+}
+
+/// Whether a Mac process is an app in `simulator`, or in any simulator for
+/// `booted`.
+pub fn runs_in_simulator(process: &RunningProcess, simulator: &str) -> bool {
+    process.path.contains("/CoreSimulator/Devices/")
+        && (simulator == "booted" || process.path.contains(simulator))
+}
+
+/// The pid of the process whose executable is called `name`, or an error
+/// listing the candidates (newest first) to choose a `pid` from.
+pub fn pick_process(processes: &[RunningProcess], name: &str) -> Result<u32, String> {
+    let mut candidates: Vec<&RunningProcess> = processes
+        .iter()
+        .filter(|process| process.path.rsplit('/').next() == Some(name))
+        .collect();
+    if let [only] = candidates.as_slice() {
+        return Ok(only.pid);
+    }
+    if candidates.is_empty() {
+        return Err(format!("no running process is named {name}"));
+    }
+    candidates.sort_by_key(|process| std::cmp::Reverse(process.pid));
+    let listing: Vec<String> = candidates
+        .iter()
+        .take(PICK_LIMIT)
+        .map(|process| format!("  {} {}", process.pid, process.path))
+        .collect();
+    let more = candidates.len().saturating_sub(PICK_LIMIT);
+    Err(format!(
+        "{} processes could be meant; set `pid` (or `program`) to pick one:\n{}{}",
+        candidates.len(),
+        listing.join("\n"),
+        if more > 0 {
+            format!("\n  ... and {more} more")
+        } else {
+            String::new()
+        }
+    ))
+}
+
+/// How many candidates an ambiguous `pickProcess` lists.
+const PICK_LIMIT: usize = 20;
+
+/// `ps -o pid=,comm=` output; on macOS `comm` is the executable's path.
+fn parse_ps(output: &str) -> Vec<RunningProcess> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pid, path) = line.trim_start().split_once(char::is_whitespace)?;
+            Some(RunningProcess {
+                pid: pid.parse().ok()?,
+                path: path.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Every process in `devicectl device info processes` output.
+fn parse_device_processes(processes: &Value) -> Vec<RunningProcess> {
+    let Some(processes) = processes
+        .pointer("/result/runningProcesses")
+        .and_then(Value::as_array)
+    else {
+        return Vec::new();
+    };
+    processes
+        .iter()
+        .filter_map(|process| {
+            let executable = process.get("executable")?.as_str()?;
+            Some(RunningProcess {
+                pid: u32::try_from(process.get("processIdentifier")?.as_u64()?).ok()?,
+                path: executable
+                    .strip_prefix("file://")
+                    .unwrap_or(executable)
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+fn xcrun(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("xcrun")
+        .args(args)
+        .output()
+        .map_err(|err| format!("failed to run xcrun {}: {err}", args[0]))?;
+    if !output.status.success() {
+        return Err(format!(
+            "xcrun {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Numbers `devicectl_json`'s output files, which sessions served side by
+/// side in one adapter process would otherwise share.
+static DEVICECTL_CALLS: AtomicU64 = AtomicU64::new(0);
+
+fn devicectl_json(args: &[&str]) -> Result<Value, String> {
+    let call = DEVICECTL_CALLS.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!(
+        "swiftscope-devicectl-{}-{call}.json",
+        std::process::id()
+    ));
+    let mut command = vec!["devicectl"];
+    command.extend_from_slice(args);
+    let path_arg = path.to_string_lossy().into_owned();
+    command.extend_from_slice(&["--json-output", &path_arg]);
+    let result = xcrun(&command).and_then(|_| {
+        let json = fs::read(&path).map_err(|err| format!("devicectl wrote no output: {err}"))?;
+        serde_json::from_slice(&json).map_err(|err| format!("invalid devicectl output: {err}"))
+    });
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Running apps are listed as `UIKitApplication:<bundle id>[...]`, with `-`
+/// in the pid column once they have exited.
+fn parse_launchctl_pid(listing: &str, bundle_id: &str) -> Option<u32> {
+    let label = format!("UIKitApplication:{bundle_id}[");
+    listing.lines().find_map(|line| {
+        let mut columns = line.split_whitespace();
+        let pid = columns.next()?;
+        let name = columns.nth(1)?;
+        if name.starts_with(&label) {
+            pid.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// The bundle URL `devicectl device info apps` reports for `bundle_id`.
+fn parse_app_url(apps: &Value, bundle_id: &str) -> Option<String> {
+    apps.pointer("/result/apps")?
+        .as_array()?
+        .iter()
+        .find(|app| app.get("bundleIdentifier").and_then(Value::as_str) == Some(bundle_id))?
+        .get("url")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The first process whose executable lives inside the bundle at `app_url`.
+fn parse_process_pid(processes: &Value, app_url: &str) -> Option<u32> {
+    let app_url = app_url.trim_end_matches('/');
+    processes
+        .pointer("/result/runningProcesses")?
+        .as_array()?
+        .iter()
+        .find(|process| {
+            process
+                .get("executable")
+                .and_then(Value::as_str)
+                .and_then(|executable| executable.strip_prefix(app_url))
+                .is_some_and(|rest| rest.starts_with('/'))
+        })?
+        .get("processIdentifier")?
+        .as_u64()
+        .and_then(|pid| u32::try_from(pid).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn simulator_pid_comes_from_the_launchctl_label() {
+        let listing = "PID\tStatus\tLabel\n\
+            -\t0\tUIKitApplication:com.example.Other[1a2b][rb-legacy]\n\
+            4242\t0\tUIKitApplication:com.example.MyApp[9f3c][rb-legacy]\n\
+            -\t0\tUIKitApplication:com.example.MyAppWidget[77aa][rb-legacy]\n";
+        assert_eq!(
+            parse_launchctl_pid(listing, "com.example.MyApp"),
+            Some(4242)
+        );
+        assert_eq!(parse_launchctl_pid(listing, "com.example.Other"), None);
+        assert_eq!(parse_launchctl_pid(listing, "com.example.Missing"), None);
+    }
+
+    #[test]
+    fn device_pid_matches_processes_inside_the_bundle() {
+        let apps = json!({ "result": { "apps": [{
+            "bundleIdentifier": "com.example.MyApp",
+            "url": "file:///private/var/containers/Bundle/Application/ABC/MyApp.app/"
+        }]}});
+        let app_url = parse_app_url(&apps, "com.example.MyApp").unwrap();
+        let processes = json!({ "result": { "runningProcesses": [
+            { "executable": "file:///usr/libexec/backboardd", "processIdentifier": 10 },
+            {
+                "executable": "file:///private/var/containers/Bundle/Application/ABC/MyApp.app.old/MyApp",
+                "processIdentifier": 11
+            },
+            {
+                "executable": "file:///private/var/containers/Bundle/Application/ABC/MyApp.app/MyApp",
+                "processIdentifier": 812
+            }
+        ]}});
+        assert_eq!(parse_process_pid(&processes, &app_url), Some(812));
+        assert_eq!(parse_app_url(&apps, "com.example.Other"), None);
+        assert_eq!(
+            parse_device_processes(&processes)[0],
+            RunningProcess {
+                pid: 10,
+                path: "/usr/libexec/backboardd".into()
+            }
+        );
+    }
+
+    #[test]
+    fn picking_a_process_needs_a_single_match() {
+        let processes = parse_ps(
+            "  1 /sbin/launchd\n 812 /Apps/MyApp.app/MyApp\n 900 /bin/zsh\n 901 /bin/zsh\n",
+        );
+        assert_eq!(pick_process(&processes, "MyApp"), Ok(812));
+        let err = pick_process(&processes, "zsh").unwrap_err();
+        assert!(err.ends_with("  901 /bin/zsh\n  900 /bin/zsh"), "{err}");
+        assert!(pick_process(&processes, "Gone").is_err());
+    }
+
+    #[test]
+    fn simulator_processes_live_in_its_data() {
+        let processes = parse_ps(
+            "4120 /Applications/MyApp.app/Contents/MacOS/MyApp\n\
+             5210 /Users/me/Library/Developer/CoreSimulator/Devices/AAAA-1111/data/MyApp.app/MyApp\n",
+        );
+        let in_simulator = |simulator| {
+            processes
+                .iter()
+                .filter(|process| runs_in_simulator(process, simulator))
+                .map(|process| process.pid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(in_simulator("booted"), [5210]);
+        assert_eq!(in_simulator("AAAA-1111"), [5210]);
+        assert!(in_simulator("BBBB-2222").is_empty());
+    }
+}
@@ -1,53 +1,197 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    net::TcpStream,
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 use anyhow::{Context as AnyhowContext, Result as AnyResult};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::{
-    gdb_remote::{GdbRemoteClient, StopReason, StopReply},
-    symbols::SymbolContext,
+    gdb_remote::{
+        GdbRemoteClient, GdbRemoteError, LoadedImage, MemoryRegion, ProcessInfo, RegisterFile,
+        StopReason, StopReply,
+    },
+    handles::{HandleAllocator, ScopeHandle, ScopeKind},
+    signals::{self, SignalAction, SignalPolicy},
+    source_map::SourceMap,
+    symbol_cache::SymbolCache,
+    symbols::{
+        format_uuid, slide_address, DeviceSupport, FunctionSymbol, MappedFile, SymbolContext,
+        SymbolizedFrame,
+    },
 };
 use gimli::{
     self, EndianSlice, IncompleteLineProgram, LineProgramHeader, LineRow, RunTimeEndian, SectionId,
     Unit,
 };
-use object::{Object, ObjectSection};
+use object::{CompressionFormat, Object, ObjectSection};
+use regex::Regex;
 
 type FrameProvider = dyn Fn(i64) -> Vec<(i64, u64)> + Send + Sync;
 
+/// How long `pause` and running breakpoint edits wait for debugserver to halt.
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// No gdb-remote connection.
+    Detached,
+    /// The target is halted and the connection accepts packets.
+    Stopped,
+    /// The target was resumed and no stop reply has been read yet.
+    Running,
+}
+
+/// Everything debugserver needs to spawn the debuggee itself.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub cwd: Option<String>,
+    pub disable_aslr: bool,
+    /// Device paths for stdin, stdout and stderr, in that order; `None`
+    /// leaves a stream with debugserver.
+    pub stdio: Vec<Option<String>>,
+}
+
+/// A process for debugserver to attach to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachTarget {
+    Pid(u32),
+    /// Wait for the next launch of an executable with this name.
+    WaitFor(String),
+}
+
+/// Reconnection attempts after the debugserver socket drops, each waiting
+/// `RECONNECT_DELAY` longer than the last.
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// How long an attach-wait waits for the app to be launched.
+const ATTACH_WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// [`SymbolCache`] kind for serialized [`LineIndex`] tables.
+const LINE_INDEX_CACHE_KIND: &str = "line-index";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResumeKind {
+    Continue,
+    Step,
+}
+
 /// Backend stub that pretends to talk to debugserver/LLDB.
 pub struct Backend {
-    pub symbol_ctx: SymbolContext,
+    /// `None` until a program is known: an attach by pid, or a program
+    /// that only exists on the device, symbolicates once the process does.
+    pub symbol_ctx: Option<SymbolContext>,
+    connected_host: Option<String>,
     connected_port: Option<u16>,
     breakpoints: HashMap<String, Vec<i64>>,
+    breakpoint_sites: Vec<BreakpointSite>,
+    /// Remote addresses holding a `Z0` on the current connection.
+    planted: HashSet<u64>,
     frame_provider: Option<Box<FrameProvider>>,
     line_index: Option<LineIndex>,
     gdb_client: Option<GdbRemoteClient>,
+    signal_policy: SignalPolicy,
+    run_state: RunState,
+    last_resume: Option<ResumeKind>,
+    last_resume_single_thread: bool,
+    last_resume_thread: i64,
+    /// A real stop that raced with an interrupt, reported by the next poll.
+    deferred_stop: Option<StopReply>,
+    /// A signal the target stopped on under the "stop" policy; the next
+    /// continue delivers it rather than letting it vanish.
+    pending_signal: Option<u8>,
+    /// The reply for the current stop; its expedited threads and pcs spare
+    /// follow-up queries until the target resumes.
+    last_stop: Option<StopReply>,
+    /// Every thread's name and stop reason for the current stop, fetched
+    /// once and dropped when the target resumes.
+    thread_stops: Option<Vec<ThreadStop>>,
+    source_map: SourceMap,
+    frame_threads: HashMap<i64, i64>,
+    variable_handles: HandleAllocator,
+    process_info: Option<ProcessInfo>,
+    images: Vec<LoadedImage>,
+    /// Where system library symbols are looked up: Xcode's DeviceSupport
+    /// directory, when there is one.
+    device_support: Option<DeviceSupport>,
+    /// Load addresses of images already looked up in `device_support`.
+    system_images_tried: HashSet<u64>,
+    /// Messages for the user about connection recovery, drained by the
+    /// adapter into `output` events.
+    notices: Vec<String>,
+    /// Set while `reconnect` re-plants breakpoints, so a connection that
+    /// drops again fails the request instead of recursing.
+    reconnecting: bool,
 }
 
 impl Backend {
-    fn from_symbol_context(symbol_ctx: SymbolContext) -> Self {
+    fn from_symbol_context(symbol_ctx: Option<SymbolContext>) -> Self {
         Self {
             symbol_ctx,
+            connected_host: None,
             connected_port: None,
             breakpoints: HashMap::new(),
+            breakpoint_sites: Vec::new(),
+            planted: HashSet::new(),
             frame_provider: None,
             line_index: None,
             gdb_client: None,
+            signal_policy: SignalPolicy::default(),
+            run_state: RunState::Detached,
+            last_resume: None,
+            last_resume_single_thread: false,
+            last_resume_thread: 1,
+            deferred_stop: None,
+            pending_signal: None,
+            last_stop: None,
+            thread_stops: None,
+            source_map: SourceMap::default(),
+            frame_threads: HashMap::new(),
+            variable_handles: HandleAllocator::default(),
+            process_info: None,
+            images: Vec::new(),
+            device_support: None,
+            system_images_tried: HashSet::new(),
+            notices: Vec::new(),
+            reconnecting: false,
         }
     }
 
-    #[allow(dead_code)]
     pub fn new_for_testing(symbol_ctx: SymbolContext) -> Self {
-        Self::from_symbol_context(symbol_ctx)
+        Self::from_symbol_context(Some(symbol_ctx))
+    }
+
+    /// Symbolicates against `app_path`, reading its DWARF from `dsym` when
+    /// one is configured and matches.
+    pub fn new_from_app(app_path: &Path, dsym: Option<&Path>) -> AnyResult<Self> {
+        let symbol_ctx = SymbolContext::with_dsym(app_path, dsym)?;
+        let mut backend = Self::from_symbol_context(Some(symbol_ctx));
+        backend.device_support = DeviceSupport::from_home();
+        Ok(backend)
     }
 
-    pub fn new_from_app(app_path: &Path) -> AnyResult<Self> {
-        let symbol_ctx = SymbolContext::new(app_path)?;
-        Ok(Self::from_symbol_context(symbol_ctx))
+    /// A backend with no program yet, for attaching to a process whose
+    /// executable isn't here; see [`Backend::load_symbols_for_process`].
+    pub fn without_symbols() -> Self {
+        let mut backend = Self::from_symbol_context(None);
+        backend.device_support = DeviceSupport::from_home();
+        backend
     }
 
-    #[allow(dead_code)]
     pub fn set_frame_provider<F>(&mut self, provider: F)
     where
         F: Fn(i64) -> Vec<(i64, u64)> + Send + Sync + 'static,
@@ -55,37 +199,444 @@ impl Backend {
         self.frame_provider = Some(Box::new(provider));
     }
 
-    #[allow(dead_code)]
+    /// Slides the main executable's symbols to where it loaded, moving any
+    /// breakpoints planted at the old slide along with them.
     pub fn update_slide_from_remote_text_base(&mut self, remote_text_base: u64) {
-        let vmaddr_text = self.symbol_ctx.main.vmaddr_text;
-        let slide = remote_text_base as i64 - vmaddr_text as i64;
-        self.symbol_ctx.set_slide(slide);
+        let Some(symbol_ctx) = self.symbol_ctx.as_mut() else {
+            return;
+        };
+        let slide = remote_text_base as i64 - symbol_ctx.main.vmaddr_text as i64;
+        if slide == symbol_ctx.main.slide {
+            return;
+        }
+        symbol_ctx.set_slide(slide);
+        if let Some(client) = self.gdb_client.as_mut() {
+            for remote_addr in self.planted.drain() {
+                if let Err(err) = client.clear_software_breakpoint(remote_addr) {
+                    eprintln!("failed to clear breakpoint at 0x{remote_addr:x}: {err}");
+                }
+            }
+        }
+        self.replant_breakpoints();
+    }
+
+    pub fn set_signal_policy(&mut self, policy: SignalPolicy) {
+        self.signal_policy = policy;
     }
 
-    pub fn connect_debugserver(&mut self, port: u16) -> Result<(), String> {
-        match GdbRemoteClient::connect(port) {
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        if let Some(index) = self.line_index.as_mut() {
+            index.set_source_map(source_map.clone());
+        }
+        self.source_map = source_map;
+    }
+
+    pub fn connect_debugserver(&mut self, host: &str, port: u16) -> Result<(), String> {
+        match GdbRemoteClient::connect(host, port) {
             Ok(client) => {
+                self.connected_host = Some(host.to_string());
                 self.connected_port = Some(port);
                 self.gdb_client = Some(client);
+                self.planted.clear();
+                self.run_state = RunState::Stopped;
+                self.last_resume = None;
+                self.pending_signal = None;
+                self.process_info = None;
+                self.images.clear();
+                self.system_images_tried.clear();
+                if self.has_process() {
+                    self.refresh_process_info();
+                    self.refresh_images();
+                }
+                self.replant_breakpoints();
                 Ok(())
             }
             Err(err) => Err(format!(
-                "failed to connect to debugserver on port {port}: {err}"
+                "failed to connect to debugserver at {host}:{port}: {err}"
             )),
         }
     }
 
+    /// Messages describing connection recovery since the last call.
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+
+    /// Re-establishes a dropped debugserver connection, retrying a bounded
+    /// number of times, then resynchronizes with `?` and re-plants
+    /// breakpoints. Returns the stop debugserver reports for the process.
+    fn reconnect(&mut self, cause: &GdbRemoteError) -> Result<Option<StopReply>, String> {
+        let (Some(host), Some(port)) = (self.connected_host.clone(), self.connected_port) else {
+            return Err(cause.to_string());
+        };
+        self.notices.push(format!(
+            "Lost connection to debugserver at {host}:{port} ({cause}); reconnecting"
+        ));
+        let mut last_err = None;
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            std::thread::sleep(RECONNECT_DELAY * attempt);
+            let mut client = match GdbRemoteClient::connect(&host, port) {
+                Ok(client) => client,
+                Err(err) => {
+                    last_err = Some(err);
+                    continue;
+                }
+            };
+            let stop = match client.stop_status() {
+                Ok(stop) => stop,
+                Err(err) => {
+                    last_err = Some(err);
+                    continue;
+                }
+            };
+            self.gdb_client = Some(client);
+            self.planted.clear();
+            self.run_state = RunState::Stopped;
+            self.deferred_stop = None;
+            self.invalidate_frames();
+            self.reconnecting = true;
+            self.replant_breakpoints();
+            self.reconnecting = false;
+            self.notices.push(format!(
+                "Reconnected to debugserver at {host}:{port} after {attempt} attempt(s)"
+            ));
+            return Ok(stop);
+        }
+        self.gdb_client = None;
+        self.run_state = RunState::Detached;
+        let reason = last_err.map(|err| err.to_string()).unwrap_or_default();
+        self.notices.push(format!(
+            "Giving up on debugserver at {host}:{port} after {RECONNECT_ATTEMPTS} attempts: {reason}"
+        ));
+        Err(format!("lost connection to debugserver: {cause}"))
+    }
+
+    /// True when the connected debugserver already controls a process.
+    pub fn has_process(&self) -> bool {
+        self.gdb_client
+            .as_ref()
+            .is_some_and(GdbRemoteClient::has_process)
+    }
+
+    /// Spawns the debuggee through the connected debugserver. The process is
+    /// left stopped at its entry point.
+    pub fn launch_process(&mut self, spec: &LaunchSpec) -> Result<(), String> {
+        self.require_stopped()?;
+        let client = self.ensure_gdb()?;
+        client
+            .set_disable_aslr(spec.disable_aslr)
+            .map_err(|err| format!("QSetDisableASLR failed: {err}"))?;
+        if let Some(cwd) = &spec.cwd {
+            client
+                .set_working_dir(cwd)
+                .map_err(|err| format!("QSetWorkingDir failed: {err}"))?;
+        }
+        for (key, value) in &spec.env {
+            client
+                .set_environment_variable(key, value)
+                .map_err(|err| format!("failed to set environment variable {key}: {err}"))?;
+        }
+        for (stream, path) in spec.stdio.iter().enumerate().take(3) {
+            if let Some(path) = path {
+                client
+                    .set_stdio_path(stream as u8, path)
+                    .map_err(|err| format!("failed to redirect stdio {stream} to {path}: {err}"))?;
+            }
+        }
+        client
+            .launch(&spec.program, &spec.args)
+            .map_err(|err| format!("failed to launch {}: {err}", spec.program))?;
+        self.run_state = RunState::Stopped;
+        self.last_resume = None;
+        self.pending_signal = None;
+        self.thread_stops = None;
+        // The new process starts without any of the sites.
+        self.planted.clear();
+        self.refresh_process_info();
+        self.refresh_images();
+        self.replant_breakpoints();
+        Ok(())
+    }
+
+    /// Attaches the connected debugserver to a running process, which is
+    /// left stopped.
+    pub fn attach_process(&mut self, target: &AttachTarget) -> Result<(), String> {
+        self.require_stopped()?;
+        let client = self.ensure_gdb()?;
+        let reply = match target {
+            AttachTarget::Pid(pid) => client
+                .attach(*pid)
+                .map_err(|err| format!("failed to attach to pid {pid}: {err}"))?,
+            AttachTarget::WaitFor(name) => client
+                .attach_wait(name, ATTACH_WAIT_TIMEOUT)
+                .map_err(|err| format!("failed to attach to {name}: {err}"))?,
+        };
+        self.run_state = RunState::Stopped;
+        self.last_resume = None;
+        self.pending_signal = None;
+        self.last_stop = Some(reply);
+        self.thread_stops = None;
+        self.refresh_process_info();
+        self.refresh_images();
+        self.replant_breakpoints();
+        Ok(())
+    }
+
+    /// Images loaded in the debuggee, as last reported by debugserver.
+    pub fn loaded_images(&self) -> &[LoadedImage] {
+        &self.images
+    }
+
+    /// Re-reads the full image list from debugserver.
+    pub fn refresh_images(&mut self) {
+        if self.gdb_client.is_none() {
+            return;
+        }
+        match self.with_gdb(GdbRemoteClient::loaded_libraries, |err| {
+            format!("jGetLoadedDynamicLibrariesInfos failed: {err}")
+        }) {
+            Ok(Some(images)) => {
+                self.images.clear();
+                self.record_images(images);
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    /// Fetches the images at `addresses` (e.g. from a dyld notification) and
+    /// merges them into the registry.
+    pub fn refresh_images_at(&mut self, addresses: &[u64]) -> Result<(), String> {
+        let images = self
+            .with_gdb(
+                |client| client.loaded_libraries_at(addresses),
+                |err| format!("jGetLoadedDynamicLibrariesInfos failed: {err}"),
+            )?
+            .unwrap_or_default();
+        self.record_images(images);
+        Ok(())
+    }
+
+    /// Adds or replaces images by load address and, when the main executable
+    /// is among them, slides its symbols to the reported load address.
+    fn record_images(&mut self, images: Vec<LoadedImage>) {
+        for image in images {
+            if self.is_main_image(&image) {
+                self.update_slide_from_remote_text_base(image.load_address);
+            } else if let Some(mismatch) = self.describe_uuid_mismatch(&image) {
+                self.notices.push(mismatch);
+            }
+            match self
+                .images
+                .iter_mut()
+                .find(|known| known.load_address == image.load_address)
+            {
+                Some(known) => *known = image,
+                None => self.images.push(image),
+            }
+        }
+    }
+
+    /// Copies a file from the device with `vFile` into `cache_dir`, keeping
+    /// its remote path underneath, and returns the local copy.
+    pub fn fetch_remote_file(
+        &mut self,
+        remote_path: &str,
+        cache_dir: &Path,
+    ) -> Result<PathBuf, String> {
+        let contents = self.with_gdb(
+            |client| client.read_file(remote_path),
+            |err| format!("failed to read {remote_path} from the device: {err}"),
+        )?;
+        let local = cache_dir.join(remote_path.trim_start_matches('/'));
+        if let Some(parent) = local.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+        }
+        fs::write(&local, contents)
+            .map_err(|err| format!("failed to write {}: {err}", local.display()))?;
+        Ok(local)
+    }
+
+    /// Symbolicates against the app binary at `remote_path` on the device,
+    /// for when no local copy exists.
+    pub fn load_symbols_from_remote(&mut self, remote_path: &str) -> Result<PathBuf, String> {
+        let cache_dir = env::temp_dir().join("swiftscope-remote");
+        let local = self.fetch_remote_file(remote_path, &cache_dir)?;
+        self.load_symbols(&local)?;
+        self.notices
+            .push(format!("Loaded symbols for {remote_path} from the device"));
+        Ok(local)
+    }
+
+    /// Symbolicates the attached process's main executable, the first
+    /// image dyld reports, for a session started without a program: from
+    /// the file itself when it is here (on this Mac or in a simulator),
+    /// else fetched from the device.
+    pub fn load_symbols_for_process(&mut self) -> Result<(), String> {
+        self.refresh_images();
+        let main = self
+            .images
+            .first()
+            .map(|image| image.pathname.clone())
+            .ok_or("debugserver reported no images for the process")?;
+        if Path::new(&main).exists() {
+            self.load_symbols(Path::new(&main))
+        } else {
+            self.load_symbols_from_remote(&main).map(drop)
+        }
+    }
+
+    fn load_symbols(&mut self, program: &Path) -> Result<(), String> {
+        self.symbol_ctx =
+            Some(SymbolContext::new(program).map_err(|err| {
+                format!("failed to load symbols from {}: {err}", program.display())
+            })?);
+        self.line_index = None;
+        self.system_images_tried.clear();
+        self.refresh_images();
+        // Line breakpoints set before there were symbols resolve now.
+        for (file, lines) in self.breakpoints.clone() {
+            if let Err(err) = self.update_breakpoints(&file, &lines) {
+                eprintln!("failed to set breakpoints in {file}: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_symbols(&self) -> bool {
+        self.symbol_ctx.is_some()
+    }
+
+    /// Explains why breakpoints cannot be trusted when the device runs a
+    /// different build of the main executable than the one symbolicated
+    /// locally, or `None` when the builds match or the executable has not
+    /// been seen in the image list yet.
+    pub fn uuid_mismatch(&self) -> Option<String> {
+        self.images
+            .iter()
+            .find_map(|image| self.describe_uuid_mismatch(image))
+    }
+
+    /// An image carrying the main executable's name but another UUID is a
+    /// stale or foreign local build: its addresses do not line up.
+    fn describe_uuid_mismatch(&self, image: &LoadedImage) -> Option<String> {
+        let main = &self.symbol_ctx.as_ref()?.main;
+        let (local, remote) = (main.uuid?, image.uuid_bytes()?);
+        if local == remote || Path::new(&image.pathname).file_name() != main.path.file_name() {
+            return None;
+        }
+        Some(format!(
+            "{} (UUID {}) does not match {} on the device (UUID {}); breakpoints stay unverified until the matching build is loaded",
+            main.path.display(),
+            format_uuid(&local),
+            image.pathname,
+            format_uuid(&remote),
+        ))
+    }
+
+    fn is_main_image(&self, image: &LoadedImage) -> bool {
+        let Some(symbol_ctx) = self.symbol_ctx.as_ref() else {
+            return false;
+        };
+        let main = &symbol_ctx.main;
+        match (main.uuid, image.uuid_bytes()) {
+            (Some(local), Some(remote)) => local == remote,
+            _ => Path::new(&image.pathname).file_name() == main.path.file_name(),
+        }
+    }
+
+    /// The debuggee's pid and architecture, once a process is attached.
+    pub fn process_info(&self) -> Option<&ProcessInfo> {
+        self.process_info.as_ref()
+    }
+
+    fn refresh_process_info(&mut self) {
+        if self.gdb_client.is_none() {
+            return;
+        }
+        match self.with_gdb(GdbRemoteClient::process_info, |err| {
+            format!("qProcessInfo failed: {err}")
+        }) {
+            Ok(info) => {
+                if info.arch().is_none() {
+                    eprintln!(
+                        "Unrecognized cputype {:?}; keeping the current register layout",
+                        info.cputype
+                    );
+                }
+                self.process_info = Some(info);
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    pub fn run_state(&self) -> RunState {
+        self.run_state
+    }
+
+    /// Rejects requests that need a halted target while it is running.
+    pub fn require_stopped(&self) -> Result<(), String> {
+        match self.run_state {
+            RunState::Running => {
+                Err("target is running; wait for it to stop before issuing this request".into())
+            }
+            RunState::Stopped | RunState::Detached => Ok(()),
+        }
+    }
+
+    /// Whether a stop is waiting to be reported without another byte from
+    /// debugserver: one that raced with an interrupt, or one it queued while
+    /// answering something else.
+    pub fn has_pending_stop(&self) -> bool {
+        self.deferred_stop.is_some()
+            || self
+                .gdb_client
+                .as_ref()
+                .is_some_and(GdbRemoteClient::has_pending_stop)
+    }
+
+    /// debugserver's connection while the target runs, for watching it for
+    /// the stop to come.
+    pub fn running_connection(&self) -> Option<TcpStream> {
+        if self.run_state != RunState::Running {
+            return None;
+        }
+        self.gdb_client.as_ref()?.try_clone_stream().ok()
+    }
+
+    /// Checks for a stop reply that arrived after a resume returned without
+    /// one, moving the backend back to `Stopped` when it is found.
+    pub fn poll_stop(&mut self) -> Result<Option<BackendStopEvent>, String> {
+        if let Some(reply) = self.deferred_stop.take() {
+            return Ok(Some(self.record_stop(reply)));
+        }
+        if self.run_state != RunState::Running {
+            return Ok(None);
+        }
+        self.await_stop()
+    }
+
     pub fn update_breakpoints(&mut self, source_path: &str, lines: &[i64]) -> Result<(), String> {
         self.breakpoints
             .insert(source_path.to_string(), lines.to_vec());
+        let owned = |origin: &BreakpointOrigin| matches!(origin, BreakpointOrigin::Line { file, .. } if file == source_path);
+
+        // The local line table describes another build than the device
+        // runs; its addresses would land in the middle of unrelated code.
+        if self.uuid_mismatch().is_some() {
+            return self.replace_sites(owned, Vec::new());
+        }
 
-        self.ensure_line_index()?;
-        let Some(index) = &self.line_index else {
+        self.ensure_line_index();
+        let Some(index) = self.line_index.as_mut() else {
             return Ok(());
         };
 
         let canonical = Path::new(source_path).to_string_lossy().to_string();
+        index.index_file(&canonical);
 
+        let mut sites = Vec::new();
         for line in lines {
             if *line <= 0 {
                 continue;
@@ -96,23 +647,325 @@ impl Backend {
                 continue;
             }
             for range in ranges {
-                let remote_addr = self.symbol_ctx.local_to_remote(range.low);
-                if let Some(client) = self.gdb_client.as_mut() {
-                    client
-                        .set_software_breakpoint(remote_addr)
-                        .map_err(|err| format!("failed to plant breakpoint: {err}"))?;
-                } else {
-                    eprintln!(
-                        "No gdb-remote client for breakpoint at 0x{remote_addr:x}; call connect_debugserver first"
-                    );
-                }
+                sites.push(BreakpointSite {
+                    origin: BreakpointOrigin::Line {
+                        file: source_path.to_string(),
+                        line: *line,
+                    },
+                    address: range.low,
+                });
+            }
+        }
+
+        self.replace_sites(owned, sites)
+    }
+
+    /// Plants a breakpoint at the entry of every function whose name matches
+    /// `pattern` and returns the resolved functions.
+    pub fn set_regex_breakpoint(&mut self, pattern: &str) -> Result<Vec<FunctionSymbol>, String> {
+        let regex =
+            Regex::new(pattern).map_err(|err| format!("invalid breakpoint regex: {err}"))?;
+        let matches = self
+            .symbol_ctx
+            .as_ref()
+            .ok_or("no program symbols to search")?
+            .functions_matching(&regex)
+            .map_err(|err| format!("failed to read symbol table: {err}"))?;
+
+        let sites = matches
+            .iter()
+            .map(|function| BreakpointSite {
+                origin: BreakpointOrigin::Function {
+                    pattern: pattern.to_string(),
+                    name: function.name.clone(),
+                },
+                address: function.address,
+            })
+            .collect();
+        self.replace_sites(
+            |origin| matches!(origin, BreakpointOrigin::Function { pattern: existing, .. } if existing == pattern),
+            sites,
+        )?;
+        Ok(matches)
+    }
+
+    /// Removes the breakpoints planted for `pattern` and returns how many
+    /// functions they covered.
+    pub fn clear_regex_breakpoint(&mut self, pattern: &str) -> Result<usize, String> {
+        let owned = |origin: &BreakpointOrigin| matches!(origin, BreakpointOrigin::Function { pattern: existing, .. } if existing == pattern);
+        let count = self
+            .breakpoint_sites
+            .iter()
+            .filter(|site| owned(&site.origin))
+            .count();
+        if count == 0 {
+            return Err(format!("no breakpoints set for `{pattern}`"));
+        }
+        self.replace_sites(owned, Vec::new())?;
+        Ok(count)
+    }
+
+    /// Adds a breakpoint at a runtime (slid) address, e.g. from `b *0x1000`.
+    pub fn add_address_breakpoint(&mut self, remote_addr: u64) -> Result<String, String> {
+        let address = self.remote_to_local(remote_addr);
+        let mut sites: Vec<BreakpointSite> = self
+            .breakpoint_sites
+            .iter()
+            .filter(|site| matches!(site.origin, BreakpointOrigin::Address))
+            .cloned()
+            .collect();
+        if !sites.iter().any(|site| site.address == address) {
+            sites.push(BreakpointSite {
+                origin: BreakpointOrigin::Address,
+                address,
+            });
+        }
+        self.replace_sites(|origin| matches!(origin, BreakpointOrigin::Address), sites)?;
+        Ok(self.describe_address(remote_addr))
+    }
+
+    /// Replaces every raw-address breakpoint with `remote_addrs` and returns a
+    /// symbolized description of each.
+    pub fn set_address_breakpoints(&mut self, remote_addrs: &[u64]) -> Result<Vec<String>, String> {
+        let sites = remote_addrs
+            .iter()
+            .map(|remote| BreakpointSite {
+                origin: BreakpointOrigin::Address,
+                address: self.remote_to_local(*remote),
+            })
+            .collect();
+        self.replace_sites(|origin| matches!(origin, BreakpointOrigin::Address), sites)?;
+        Ok(remote_addrs
+            .iter()
+            .map(|remote| self.describe_address(*remote))
+            .collect())
+    }
+
+    /// Formats `0xADDR: function at file:line`, omitting what DWARF lacks.
+    pub fn describe_address(&self, remote_addr: u64) -> String {
+        let frames = self.cached_frames(remote_addr);
+        let top = frames.first();
+        let function = top
+            .and_then(|frame| frame.function.clone())
+            .or_else(|| self.symbol_name(remote_addr))
+            .or_else(|| self.module_offset_name(remote_addr));
+        let location = top
+            .and_then(|frame| Some((frame.file.as_deref()?, frame.line?)))
+            .map(|(file, line)| format!("{}:{line}", self.source_map.to_local(file)));
+
+        match (function, location) {
+            (Some(function), Some(location)) => {
+                format!("0x{remote_addr:x}: {function} at {location}")
+            }
+            (Some(function), None) => format!("0x{remote_addr:x}: {function}"),
+            (None, Some(location)) => format!("0x{remote_addr:x}: {location}"),
+            (None, None) => format!("0x{remote_addr:x}"),
+        }
+    }
+
+    /// `symbol + offset` from the symbol table, for addresses DWARF does not
+    /// cover.
+    fn symbol_name(&self, remote_addr: u64) -> Option<String> {
+        let (symbol, offset) = self.symbol_ctx.as_ref()?.symbol_for_address(remote_addr)?;
+        Some(if offset == 0 {
+            symbol
+        } else {
+            format!("{symbol} + {offset}")
+        })
+    }
+
+    /// `Module + 0xoffset` from the image's load address, the form crash
+    /// reports use, for stripped code no symbol names.
+    fn module_offset_name(&self, remote_addr: u64) -> Option<String> {
+        if let Some(image) = self
+            .symbol_ctx
+            .as_ref()
+            .and_then(|symbol_ctx| symbol_ctx.image_for_address(remote_addr))
+        {
+            let base = slide_address(image.vmaddr_text, image.slide);
+            return Some(format!("{} + 0x{:x}", image.name, remote_addr - base));
+        }
+        let image = self
+            .images
+            .iter()
+            .filter(|image| image.load_address <= remote_addr)
+            .max_by_key(|image| image.load_address)?;
+        let name = Path::new(&image.pathname).file_name()?.to_string_lossy();
+        Some(format!("{name} + 0x{:x}", remote_addr - image.load_address))
+    }
+
+    pub fn breakpoint_sites(&self) -> &[BreakpointSite] {
+        &self.breakpoint_sites
+    }
+
+    /// Swaps the sites selected by `owned` for `sites`, clearing addresses no
+    /// longer referenced and planting new ones.
+    fn replace_sites<F>(&mut self, owned: F, sites: Vec<BreakpointSite>) -> Result<(), String>
+    where
+        F: Fn(&BreakpointOrigin) -> bool,
+    {
+        let resume = self.halt_for_mutation()?;
+        let result = self.swap_sites(owned, sites);
+        if resume {
+            self.resume_after_mutation()?;
+        }
+        result
+    }
+
+    fn swap_sites<F>(&mut self, owned: F, sites: Vec<BreakpointSite>) -> Result<(), String>
+    where
+        F: Fn(&BreakpointOrigin) -> bool,
+    {
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.breakpoint_sites)
+            .into_iter()
+            .partition(|site| owned(&site.origin));
+        self.breakpoint_sites = kept;
+
+        for site in &removed {
+            let still_used = self
+                .breakpoint_sites
+                .iter()
+                .chain(sites.iter())
+                .any(|other| other.address == site.address);
+            if !still_used {
+                self.clear_site(site.address)?;
+            }
+        }
+        for site in sites {
+            let already_planted = self
+                .breakpoint_sites
+                .iter()
+                .chain(removed.iter())
+                .any(|other| other.address == site.address);
+            if !already_planted {
+                self.plant_site(site.address)?;
             }
+            self.breakpoint_sites.push(site);
+        }
+        Ok(())
+    }
+
+    /// Interrupts a running target so breakpoints can be edited. Returns
+    /// whether the caller should resume afterwards; a genuine stop that raced
+    /// with the interrupt is kept for `poll_stop` and leaves the target halted.
+    fn halt_for_mutation(&mut self) -> Result<bool, String> {
+        if self.run_state != RunState::Running {
+            return Ok(false);
+        }
+        let reply = self.interrupt()?;
+        if is_interrupt_stop(&reply) {
+            Ok(true)
+        } else {
+            self.deferred_stop = Some(reply);
+            Ok(false)
+        }
+    }
+
+    fn resume_after_mutation(&mut self) -> Result<(), String> {
+        let kind = self.last_resume.unwrap_or(ResumeKind::Continue);
+        self.send_resume(
+            kind,
+            self.last_resume_thread,
+            self.last_resume_single_thread,
+            None,
+        )
+    }
+
+    fn interrupt(&mut self) -> Result<StopReply, String> {
+        let client = self.ensure_gdb()?;
+        let reply = client
+            .interrupt(INTERRUPT_TIMEOUT)
+            .map_err(|err| format!("failed to interrupt target: {err}"))?;
+        self.run_state = RunState::Stopped;
+        Ok(reply)
+    }
+
+    /// Halts a running target. Returns the resulting stop, reported as a
+    /// pause unless a real stop raced with the interrupt, or `None` when the
+    /// target was not running.
+    pub fn pause(&mut self) -> Result<Option<BackendStopEvent>, String> {
+        if self.run_state != RunState::Running {
+            return Ok(None);
+        }
+        let reply = self.interrupt()?;
+        let pause = is_interrupt_stop(&reply);
+        let mut event = self.record_stop(reply);
+        if pause {
+            event.reason = "pause";
+            event.description = "Paused".to_string();
+        }
+        Ok(Some(event))
+    }
+
+    fn plant_site(&mut self, local_addr: u64) -> Result<(), String> {
+        let remote_addr = self.local_to_remote(local_addr);
+        if self.planted.contains(&remote_addr) {
+            return Ok(());
+        }
+        if self.gdb_client.is_some() {
+            self.with_gdb(
+                |client| client.set_software_breakpoint(remote_addr),
+                |err| format!("failed to plant breakpoint: {err}"),
+            )?;
+            self.planted.insert(remote_addr);
+            Ok(())
+        } else {
+            eprintln!(
+                "No gdb-remote client for breakpoint at 0x{remote_addr:x}; call connect_debugserver first"
+            );
+            Ok(())
         }
+    }
 
+    fn clear_site(&mut self, local_addr: u64) -> Result<(), String> {
+        let remote_addr = self.local_to_remote(local_addr);
+        if self.gdb_client.is_some() {
+            self.with_gdb(
+                |client| client.clear_software_breakpoint(remote_addr),
+                |err| format!("failed to clear breakpoint: {err}"),
+            )?;
+        }
+        self.planted.remove(&remote_addr);
         Ok(())
     }
 
-    pub fn threads(&self) -> Vec<Value> {
+    /// Sends `Z0` for every known site not planted yet, as after a fresh
+    /// debugserver connection or a slide.
+    fn replant_breakpoints(&mut self) {
+        let mut addresses: Vec<u64> = self
+            .breakpoint_sites
+            .iter()
+            .map(|site| site.address)
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        for address in addresses {
+            if let Err(err) = self.plant_site(address) {
+                eprintln!("failed to re-plant breakpoint at 0x{address:x}: {err}");
+            }
+        }
+    }
+
+    /// Lists the remote threads, falling back to a single placeholder thread
+    /// when no process is attached or the stub cannot enumerate them.
+    pub fn threads(&mut self) -> Vec<Value> {
+        let stops = self.thread_stops();
+        if !stops.is_empty() {
+            return stops
+                .iter()
+                .map(|stop| {
+                    let name = stop
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("Thread 0x{:x}", stop.tid));
+                    let reason = stop.reply.as_ref().and_then(stop_reason_label);
+                    json!({
+                        "id": stop.tid as i64,
+                        "name": thread_label(name, reason),
+                    })
+                })
+                .collect();
+        }
         vec![json!({
             "id": 1,
             "name": format!(
@@ -124,30 +977,112 @@ impl Backend {
         })]
     }
 
-    pub fn stack_trace(&self, thread_id: i64) -> Vec<Value> {
+    /// Stop events for every thread other than `primary` that has its own
+    /// stop reason, so simultaneous breakpoint hits are all reported.
+    pub fn other_thread_stops(&mut self, primary: i64) -> Vec<BackendStopEvent> {
+        self.thread_stops()
+            .into_iter()
+            .filter(|stop| stop.tid as i64 != primary)
+            .filter_map(|stop| {
+                let mut reply = stop.reply?;
+                stop_reason_label(&reply)?;
+                reply.thread_id = Some(stop.tid);
+                Some(BackendStopEvent::from_reply(&reply))
+            })
+            .collect()
+    }
+
+    /// Every thread with its stop reason, from one `jThreadsInfo` when the
+    /// stub has it and one `qThreadStopInfo` per thread otherwise. The
+    /// answer is kept until the target resumes.
+    fn thread_stops(&mut self) -> Vec<ThreadStop> {
+        if self.run_state != RunState::Stopped {
+            return Vec::new();
+        }
+        if let Some(stops) = &self.thread_stops {
+            return stops.clone();
+        }
+        if self.gdb_client.is_none() {
+            return Vec::new();
+        }
+        let expedited = self
+            .last_stop
+            .as_ref()
+            .map(|reply| reply.threads.clone())
+            .filter(|threads| !threads.is_empty());
+        let fetched = self.with_gdb(
+            |client| {
+                match client.threads_info() {
+                    Ok(Some(infos)) if !infos.is_empty() => {
+                        return Ok(infos
+                            .iter()
+                            .map(|info| ThreadStop {
+                                tid: info.tid,
+                                name: info.display_name().map(str::to_string),
+                                reply: Some(info.stop_reply()),
+                            })
+                            .collect());
+                    }
+                    Ok(_) => {}
+                    Err(err) if err.is_connection_lost() => return Err(err),
+                    Err(err) => eprintln!("jThreadsInfo failed, falling back: {err}"),
+                }
+                let ids = match &expedited {
+                    Some(threads) => threads.clone(),
+                    None => client.thread_ids()?,
+                };
+                let mut stops = Vec::new();
+                for tid in ids {
+                    let reply = match client.thread_stop_info(tid) {
+                        Ok(reply) => reply,
+                        Err(err) if err.is_connection_lost() => return Err(err),
+                        Err(err) => {
+                            eprintln!("qThreadStopInfo failed for 0x{tid:x}: {err}");
+                            None
+                        }
+                    };
+                    stops.push(ThreadStop {
+                        tid,
+                        name: None,
+                        reply,
+                    });
+                }
+                Ok(stops)
+            },
+            |err| format!("Failed to enumerate remote threads: {err}"),
+        );
+        let stops = match fetched {
+            Ok(stops) => stops,
+            Err(err) => {
+                eprintln!("{err}");
+                return Vec::new();
+            }
+        };
+        self.thread_stops = Some(stops);
+        self.thread_stops.clone().unwrap_or_default()
+    }
+
+    pub fn stack_trace(&mut self, thread_id: i64) -> Vec<Value> {
         let raw_frames = self.backend_fetch_frames(thread_id);
         let mut out = Vec::new();
 
         for (idx, (frame_id, pc)) in raw_frames.iter().enumerate() {
-            let frames = self.symbol_ctx.symbolize_frames(*pc).ok();
-            let top = frames.as_ref().and_then(|frames| frames.first());
+            self.frame_threads.insert(*frame_id, thread_id);
+            self.load_system_symbols_for(*pc);
+            let frames = self.cached_frames(*pc);
+            let top = frames.first();
             let function_name = top
-                .and_then(|frame| frame.function.as_ref())
-                .and_then(|name| {
-                    name.demangle()
-                        .ok()
-                        .map(|cow| cow.into_owned())
-                        .or_else(|| name.raw_name().ok().map(|cow| cow.into_owned()))
-                })
+                .and_then(|frame| frame.function.clone())
+                .or_else(|| self.symbol_name(*pc))
+                .or_else(|| self.module_offset_name(*pc))
                 .unwrap_or_else(|| "<unknown>".into());
 
-            let location = top.and_then(|frame| frame.location.as_ref());
-            let file_path = location
-                .and_then(|loc| loc.file)
-                .unwrap_or("<unknown>")
-                .to_string();
-            let line = location
-                .and_then(|loc| loc.line)
+            let file_path = top
+                .and_then(|frame| frame.file.as_deref())
+                .map(|file| self.source_map.to_local(file))
+                .unwrap_or_else(|| "<unknown>".to_string());
+            let line = top
+                .and_then(|frame| frame.line)
                 .map(|line| line as i64)
                 .unwrap_or(0);
             let source_name = file_path
@@ -172,34 +1107,138 @@ impl Backend {
         out
     }
 
-    fn ensure_line_index(&mut self) -> Result<(), String> {
-        if self.line_index.is_none() {
-            match LineIndex::from_binary(&self.symbol_ctx.main.path) {
-                Ok(index) => self.line_index = Some(index),
-                Err(err) => {
-                    return Err(format!(
-                        "Failed to build DWARF line index for {:?}: {err}",
-                        self.symbol_ctx.main.path
-                    ))
-                }
+    /// Adds the DeviceSupport symbol file of the loaded image containing
+    /// `remote_pc`, once per image, when no symbols cover that address yet.
+    fn load_system_symbols_for(&mut self, remote_pc: u64) {
+        let Some(symbol_ctx) = self.symbol_ctx.as_ref() else {
+            return;
+        };
+        if symbol_ctx.image_for_address(remote_pc).is_some() {
+            return;
+        }
+        let Some(support) = self.device_support.as_ref() else {
+            return;
+        };
+        let Some(host) = self
+            .gdb_client
+            .as_ref()
+            .and_then(|client| client.host_info())
+        else {
+            return;
+        };
+        let Some(os_version) = host.os_version.as_deref() else {
+            return;
+        };
+        let Some(image) = self
+            .images
+            .iter()
+            .filter(|image| image.load_address <= remote_pc)
+            .max_by_key(|image| image.load_address)
+            .filter(|image| !self.is_main_image(image))
+            .filter(|image| !self.system_images_tried.contains(&image.load_address))
+        else {
+            return;
+        };
+        let load_address = image.load_address;
+        let located = support.locate(os_version, host.os_build.as_deref(), &image.pathname);
+        self.system_images_tried.insert(load_address);
+        if let Some(path) = located {
+            let added = self.symbol_ctx.as_mut().map_or(Ok(()), |symbol_ctx| {
+                symbol_ctx.add_image_file(&path, load_address)
+            });
+            if let Err(err) = added {
+                eprintln!(
+                    "failed to load system symbols from {}: {err}",
+                    path.display()
+                );
             }
         }
-        Ok(())
     }
 
-    pub fn scopes(&self) -> Vec<Value> {
-        vec![json!({
-            "name": "Locals",
-            "variablesReference": 1,
+    /// Builds the line index on first use. A binary whose DWARF cannot be
+    /// read gets an empty index, so the session carries on with unverified
+    /// breakpoints instead of failing every `setBreakpoints`.
+    fn ensure_line_index(&mut self) {
+        if self.line_index.is_some() {
+            return;
+        }
+        let mut index = match &self.symbol_ctx {
+            Some(symbol_ctx) => symbol_ctx
+                .main_file()
+                .and_then(|file| {
+                    LineIndex::load_or_build(&file, symbol_ctx.main.uuid, symbol_ctx.cache())
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!(
+                        "Failed to build DWARF line index for {:?}: {err}",
+                        symbol_ctx.main.path
+                    );
+                    LineIndex::default()
+                }),
+            None => LineIndex::default(),
+        };
+        index.set_source_map(self.source_map.clone());
+        self.line_index = Some(index);
+        if let Some(message) = self.missing_debug_info() {
+            self.notices.push(message);
+        }
+    }
+
+    /// Explains why line breakpoints cannot resolve when the main executable
+    /// has no line tables and no dSYM was found for it, as with stripped or
+    /// App Store (bitcode-recompiled) builds. `None` until the line index has
+    /// been built.
+    pub fn missing_debug_info(&self) -> Option<String> {
+        if !self.line_index.as_ref()?.is_empty() {
+            return None;
+        }
+        let main = &self.symbol_ctx.as_ref()?.main;
+        let dsym = match main.uuid {
+            Some(uuid) => format!("no dSYM with UUID {} was found", format_uuid(&uuid)),
+            None => "no dSYM was found".to_string(),
+        };
+        Some(format!(
+            "{} has no DWARF line tables and {dsym}; line breakpoints cannot be placed. Build with debug info or put the matching .dSYM next to the app (for App Store builds, download it from App Store Connect)",
+            main.path.display()
+        ))
+    }
+
+    /// The thread a frame id from the last `stack_trace` belongs to.
+    pub fn frame_thread(&self, frame_id: i64) -> Option<i64> {
+        self.frame_threads.get(&frame_id).copied()
+    }
+
+    /// Returns the scopes of a frame reported by the last `stack_trace`, each
+    /// with a `variablesReference` that stays valid until the next resume.
+    pub fn scopes(&mut self, frame_id: i64) -> Result<Vec<Value>, String> {
+        let thread_id = *self
+            .frame_threads
+            .get(&frame_id)
+            .ok_or_else(|| format!("unknown frameId {frame_id}; request a stack trace first"))?;
+        let scope = ScopeKind::Locals;
+        let reference = self.variable_handles.allocate(ScopeHandle {
+            thread_id,
+            frame_id,
+            scope,
+        });
+        Ok(vec![json!({
+            "name": scope.label(),
+            "variablesReference": reference,
             "expensive": false
-        })]
+        })])
     }
 
-    pub fn variables(&self, variables_reference: i64) -> Vec<Value> {
-        vec![
+    pub fn variables(&self, variables_reference: i64) -> Result<Vec<Value>, String> {
+        let handle = self
+            .variable_handles
+            .resolve(variables_reference)
+            .ok_or_else(|| {
+                format!("variablesReference {variables_reference} is invalid or stale after resume")
+            })?;
+        Ok(vec![
             json!({
                 "name": "var",
-                "value": format!("value-{variables_reference}"),
+                "value": format!("value-{}", handle.frame_id),
                 "type": "string",
                 "variablesReference": 0
             }),
@@ -209,50 +1248,286 @@ impl Backend {
                 "type": "int",
                 "variablesReference": 0
             }),
-        ]
+        ])
     }
 
-    pub fn r#continue(&mut self, _thread_id: i64) -> Result<Option<BackendStopEvent>, String> {
-        let client = self.ensure_gdb()?;
-        client.continue_all().map_err(|err| err.to_string())?;
-        client
-            .wait_for_stop()
-            .map(BackendStopEvent::from_reply)
-            .map(Some)
-            .map_err(|err| err.to_string())
+    /// Resumes the target. With `single_thread` only `thread_id` runs;
+    /// otherwise the whole process does.
+    pub fn r#continue(
+        &mut self,
+        thread_id: i64,
+        single_thread: bool,
+    ) -> Result<Option<BackendStopEvent>, String> {
+        self.require_stopped()?;
+        let signal = self.pending_signal.take();
+        self.send_resume(ResumeKind::Continue, thread_id, single_thread, signal)?;
+        self.await_stop()
+    }
+
+    /// Steps `thread_id`; the other threads run alongside it unless
+    /// `single_thread` is set.
+    pub fn step_over(
+        &mut self,
+        thread_id: i64,
+        single_thread: bool,
+    ) -> Result<Option<BackendStopEvent>, String> {
+        self.require_stopped()?;
+        self.send_resume(ResumeKind::Step, thread_id, single_thread, None)?;
+        self.await_stop()
     }
 
-    pub fn step_over(&mut self, thread_id: i64) -> Result<Option<BackendStopEvent>, String> {
+    pub fn step_in(
+        &mut self,
+        thread_id: i64,
+        single_thread: bool,
+    ) -> Result<Option<BackendStopEvent>, String> {
+        self.step_over(thread_id, single_thread)
+    }
+
+    fn send_resume(
+        &mut self,
+        kind: ResumeKind,
+        thread_id: i64,
+        single_thread: bool,
+        signal: Option<u8>,
+    ) -> Result<(), String> {
         let client = self.ensure_gdb()?;
-        client
-            .step_thread(thread_id)
-            .map_err(|err| err.to_string())?;
-        client
-            .wait_for_stop()
-            .map(BackendStopEvent::from_reply)
-            .map(Some)
-            .map_err(|err| err.to_string())
+        match resume_packet(client, kind, thread_id, single_thread, signal) {
+            Ok(()) => {}
+            Err(err) if err.is_connection_lost() => {
+                self.reconnect(&err)?;
+                let client = self.ensure_gdb()?;
+                resume_packet(client, kind, thread_id, single_thread, signal)
+                    .map_err(|err| err.to_string())?;
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+        self.run_state = RunState::Running;
+        self.last_resume = Some(kind);
+        self.last_resume_single_thread = single_thread;
+        self.last_resume_thread = thread_id;
+        self.invalidate_frames();
+        Ok(())
+    }
+
+    /// Waits for the next stop reply, transparently resuming past signals the
+    /// policy passes or ignores. Returns `None` if the target is still running.
+    fn await_stop(&mut self) -> Result<Option<BackendStopEvent>, String> {
+        loop {
+            let client = self.ensure_gdb()?;
+            let reply = match client.poll_stop() {
+                Ok(Some(reply)) => reply,
+                Ok(None) => return Ok(None),
+                Err(err) if err.is_connection_lost() => match self.reconnect(&err)? {
+                    Some(reply) => reply,
+                    None => return Err("debugserver has no process after reconnecting".into()),
+                },
+                Err(err) => return Err(err.to_string()),
+            };
+            self.run_state = RunState::Stopped;
+            let thread_id = reply.thread_id.unwrap_or(1) as i64;
+            let single_thread = self.last_resume_single_thread;
+            if self.last_resume == Some(ResumeKind::Continue) && is_signal_stop(&reply) {
+                match self.signal_policy.action(reply.signal) {
+                    SignalAction::Stop => self.pending_signal = Some(reply.signal),
+                    SignalAction::Pass => {
+                        self.send_resume(
+                            ResumeKind::Continue,
+                            thread_id,
+                            single_thread,
+                            Some(reply.signal),
+                        )?;
+                        continue;
+                    }
+                    SignalAction::Ignore => {
+                        self.send_resume(ResumeKind::Continue, thread_id, single_thread, None)?;
+                        continue;
+                    }
+                }
+            }
+            return Ok(Some(self.record_stop(reply)));
+        }
+    }
+
+    fn record_stop(&mut self, reply: StopReply) -> BackendStopEvent {
+        let event = BackendStopEvent::from_reply(&reply);
+        self.last_stop = Some(reply);
+        self.thread_stops = None;
+        event
     }
 
-    pub fn step_in(&mut self, thread_id: i64) -> Result<Option<BackendStopEvent>, String> {
-        self.step_over(thread_id)
+    /// Reads a register by name (`pc`, `sp`, `x0`, ...; a leading `$` is
+    /// accepted) and returns its value as an integer.
+    pub fn read_register(&mut self, name: &str, thread_id: Option<i64>) -> Result<u64, String> {
+        self.require_stopped()?;
+        let (name, regnum) = register_number(self.ensure_gdb()?, name)?;
+        self.with_gdb(
+            |client| client.read_register_value(regnum, thread_id.map(|tid| tid as u64)),
+            |err| format!("failed to read register ${name}: {err}"),
+        )
     }
 
-    pub fn disconnect(&mut self) -> Result<(), String> {
+    /// Writes a register by name; the name accepts the same spellings as
+    /// [`Backend::read_register`].
+    pub fn write_register(
+        &mut self,
+        name: &str,
+        value: u64,
+        thread_id: Option<i64>,
+    ) -> Result<(), String> {
+        self.require_stopped()?;
+        let (name, regnum) = register_number(self.ensure_gdb()?, name)?;
+        self.with_gdb(
+            |client| client.write_register(regnum, value, thread_id.map(|tid| tid as u64)),
+            |err| format!("failed to write register ${name}: {err}"),
+        )
+    }
+
+    /// Reads target memory at a remote (slid) address.
+    pub fn read_memory(&mut self, address: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.require_stopped()?;
+        self.with_gdb(
+            |client| client.read_memory(address, len),
+            |err| format!("failed to read {len} bytes at 0x{address:x}: {err}"),
+        )
+    }
+
+    /// The memory region containing `address`, or `None` when debugserver
+    /// cannot describe regions.
+    pub fn memory_region(&mut self, address: u64) -> Result<Option<MemoryRegion>, String> {
+        self.require_stopped()?;
+        self.with_gdb(
+            |client| client.memory_region_info(address),
+            |err| format!("qMemoryRegionInfo failed at 0x{address:x}: {err}"),
+        )
+    }
+
+    /// Reads up to `len` bytes, stopping at the first unreadable region
+    /// instead of failing. The result may be shorter than `len`.
+    pub fn read_readable_memory(&mut self, address: u64, len: usize) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let cursor = address + out.len() as u64;
+            let remaining = len - out.len();
+            let chunk = match self.memory_region(cursor)? {
+                Some(region) if !region.readable || !region.contains(cursor) => break,
+                Some(region) => remaining.min((region.end() - cursor) as usize),
+                None => remaining,
+            };
+            let bytes = match self.read_memory(cursor, chunk) {
+                Ok(bytes) => bytes,
+                Err(_) if !out.is_empty() => break,
+                Err(err) => return Err(err),
+            };
+            let short = bytes.len() < chunk;
+            out.extend(bytes);
+            if short || chunk == 0 {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Writes target memory at a remote (slid) address.
+    pub fn write_memory(&mut self, address: u64, bytes: &[u8]) -> Result<(), String> {
+        self.require_stopped()?;
+        self.with_gdb(
+            |client| client.write_memory(address, bytes),
+            |err| {
+                format!(
+                    "failed to write {} bytes at 0x{address:x}: {err}",
+                    bytes.len()
+                )
+            },
+        )
+    }
+
+    /// Reads the full register file of the stopped thread.
+    pub fn registers(&mut self, thread_id: Option<i64>) -> Result<RegisterFile, String> {
+        self.require_stopped()?;
+        self.with_gdb(
+            |client| client.read_registers(thread_id.map(|tid| tid as u64)),
+            |err| format!("failed to read registers: {err}"),
+        )
+    }
+
+    /// Ends the session. A process debugserver controls is killed when
+    /// `terminate` is set and detached from otherwise; the connection is
+    /// dropped either way.
+    pub fn disconnect(&mut self, terminate: bool) -> Result<(), String> {
+        let result = self.release_process(terminate);
+        self.connected_host = None;
         self.connected_port = None;
         self.gdb_client = None;
-        Ok(())
+        self.run_state = RunState::Detached;
+        self.last_resume = None;
+        self.pending_signal = None;
+        self.process_info = None;
+        self.images.clear();
+        self.deferred_stop = None;
+        self.invalidate_frames();
+        result
+    }
+
+    /// Kills the debuggee but keeps the debugserver connection open.
+    pub fn terminate(&mut self) -> Result<(), String> {
+        let result = self.release_process(true);
+        if self.gdb_client.is_some() {
+            self.run_state = RunState::Stopped;
+        }
+        self.last_resume = None;
+        self.pending_signal = None;
+        self.process_info = None;
+        self.images.clear();
+        self.deferred_stop = None;
+        self.invalidate_frames();
+        result
+    }
+
+    fn release_process(&mut self, kill: bool) -> Result<(), String> {
+        if !self.has_process() {
+            return Ok(());
+        }
+        if !kill && self.run_state == RunState::Running {
+            self.interrupt()?;
+        }
+        let client = self.ensure_gdb()?;
+        if kill {
+            client
+                .kill()
+                .map_err(|err| format!("failed to kill process: {err}"))
+        } else {
+            client
+                .detach()
+                .map_err(|err| format!("failed to detach from process: {err}"))
+        }
+    }
+
+    /// Frame ids and variable references describe one stop; drop them once
+    /// the target moves.
+    fn invalidate_frames(&mut self) {
+        self.last_stop = None;
+        self.thread_stops = None;
+        self.frame_threads.clear();
+        self.variable_handles.invalidate();
     }
 
     fn backend_fetch_frames(&self, thread_id: i64) -> Vec<(i64, u64)> {
         if let Some(provider) = &self.frame_provider {
             return provider(thread_id);
         }
+        if let Some(pc) = self
+            .last_stop
+            .as_ref()
+            .and_then(|reply| reply.thread_pc(thread_id as u64))
+        {
+            return vec![(thread_id * 100 + 1, pc)];
+        }
 
-        vec![(
-            thread_id * 100 + 1,
-            self.symbol_ctx.main.vmaddr_text + self.symbol_ctx.main.slide as u64,
-        )]
+        let entry = self.symbol_ctx.as_ref().map_or(0, |symbol_ctx| {
+            slide_address(symbol_ctx.main.vmaddr_text, symbol_ctx.main.slide)
+        });
+        vec![(thread_id * 100 + 1, entry)]
     }
 
     fn ensure_gdb(&mut self) -> Result<&mut GdbRemoteClient, String> {
@@ -261,23 +1536,112 @@ impl Backend {
             .ok_or_else(|| "no gdb-remote connection; call connect_debugserver first".to_string())
     }
 
-    pub fn program_path(&self) -> &Path {
-        &self.symbol_ctx.main.path
+    /// Runs `request` on the connection. When the socket dropped underneath
+    /// it, reconnects and runs it once more; other failures go through
+    /// `describe`.
+    fn with_gdb<T>(
+        &mut self,
+        mut request: impl FnMut(&mut GdbRemoteClient) -> Result<T, GdbRemoteError>,
+        describe: impl Fn(GdbRemoteError) -> String,
+    ) -> Result<T, String> {
+        match request(self.ensure_gdb()?) {
+            Err(err) if err.is_connection_lost() && !self.reconnecting => {
+                self.reconnect(&err)?;
+                request(self.ensure_gdb()?).map_err(describe)
+            }
+            result => result.map_err(describe),
+        }
+    }
+
+    pub fn program_path(&self) -> Option<&Path> {
+        Some(&self.symbol_ctx.as_ref()?.main.path)
+    }
+
+    /// Frames for `remote_pc`; none while there are no symbols.
+    fn cached_frames(&self, remote_pc: u64) -> Arc<[SymbolizedFrame]> {
+        match &self.symbol_ctx {
+            Some(symbol_ctx) => symbol_ctx.cached_frames(remote_pc),
+            None => Arc::new([]),
+        }
+    }
+
+    /// Breakpoint sites are kept at unslid addresses; without symbols there
+    /// is no slide and the two agree.
+    fn remote_to_local(&self, remote_addr: u64) -> u64 {
+        match &self.symbol_ctx {
+            Some(symbol_ctx) => symbol_ctx.translate_remote_pc(remote_addr),
+            None => remote_addr,
+        }
+    }
+
+    fn local_to_remote(&self, local_addr: u64) -> u64 {
+        match &self.symbol_ctx {
+            Some(symbol_ctx) => symbol_ctx.local_to_remote(local_addr),
+            None => local_addr,
+        }
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 struct FileLine {
     file: String,
     line: u64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AddressRange {
     pub low: u64,
     pub high: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointOrigin {
+    Line { file: String, line: i64 },
+    Function { pattern: String, name: String },
+    Address,
+}
+
+/// A resolved breakpoint address (unslid) and the request that produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointSite {
+    pub origin: BreakpointOrigin,
+    pub address: u64,
+}
+
+/// One thread at the current stop; `reply` is `None` when the stub cannot
+/// say why it stopped.
+#[derive(Debug, Clone)]
+struct ThreadStop {
+    tid: u64,
+    name: Option<String>,
+    reply: Option<StopReply>,
+}
+
+/// Whether `path` names a file on an iOS device rather than this Mac: an
+/// app container or the developer disk image.
+pub fn is_device_path(path: &str) -> bool {
+    const DEVICE_PREFIXES: &[&str] = &[
+        "/private/var/containers/",
+        "/var/containers/",
+        "/private/var/mobile/",
+        "/var/mobile/",
+        "/Developer/",
+    ];
+    DEVICE_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Parses a `0x`-prefixed hex or decimal address; a leading `*` (as in
+/// `b *0x1000`) is ignored.
+pub fn parse_address(text: &str) -> Option<u64> {
+    let text = text.trim().trim_start_matches('*').trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
 pub struct BackendStopEvent {
     pub reason: &'static str,
     pub description: String,
@@ -285,13 +1649,20 @@ pub struct BackendStopEvent {
 }
 
 impl BackendStopEvent {
-    fn from_reply(reply: StopReply) -> Self {
+    fn from_reply(reply: &StopReply) -> Self {
         let thread_id = reply.thread_id.unwrap_or(1) as i64;
-        let (reason, description) = match reply.reason {
+        let (reason, description) = match &reply.reason {
             StopReason::Breakpoint => ("breakpoint", "Breakpoint hit".to_string()),
             StopReason::Step => ("step", "Step completed".to_string()),
-            StopReason::Signal => ("signal", format!("Signal {}", reply.signal)),
-            StopReason::Unknown(text) => ("stopped", text),
+            StopReason::Signal => (
+                "signal",
+                match signals::signal_name(reply.signal) {
+                    Some(name) => format!("Signal {} ({name})", reply.signal),
+                    None => format!("Signal {}", reply.signal),
+                },
+            ),
+            StopReason::NoReason => ("stopped", "Suspended".to_string()),
+            StopReason::Unknown(text) => ("stopped", text.clone()),
         };
         Self {
             reason,
@@ -301,44 +1672,285 @@ impl BackendStopEvent {
     }
 }
 
+/// Short reason shown next to a thread's name, or `None` for threads that
+/// are merely suspended.
+fn stop_reason_label(reply: &StopReply) -> Option<&'static str> {
+    match reply.reason {
+        StopReason::Breakpoint => Some("breakpoint"),
+        StopReason::Step => Some("step"),
+        StopReason::Signal => Some("signal"),
+        StopReason::Unknown(_) => Some("stopped"),
+        StopReason::NoReason => None,
+    }
+}
+
+fn thread_label(name: String, reason: Option<&str>) -> String {
+    match reason {
+        Some(reason) => format!("{name} ({reason})"),
+        None => name,
+    }
+}
+
+/// Resolves a register name against the connected target's layout.
+fn register_number(client: &GdbRemoteClient, name: &str) -> Result<(String, u32), String> {
+    let name = name.trim().trim_start_matches('$').to_ascii_lowercase();
+    let regnum = client
+        .register_layout()
+        .find(&name)
+        .map(|info| info.regnum)
+        .ok_or_else(|| format!("unknown register `${name}`"))?;
+    Ok((name, regnum))
+}
+
+fn resume_packet(
+    client: &mut GdbRemoteClient,
+    kind: ResumeKind,
+    thread_id: i64,
+    single_thread: bool,
+    signal: Option<u8>,
+) -> Result<(), GdbRemoteError> {
+    match (kind, signal) {
+        (ResumeKind::Continue, Some(signal)) => {
+            client.continue_with_signal(signal, thread_id, single_thread)
+        }
+        (ResumeKind::Continue, None) if single_thread => client.continue_thread(thread_id),
+        (ResumeKind::Continue, None) => client.continue_all(),
+        (ResumeKind::Step, _) => client.step_thread(thread_id, single_thread),
+    }
+}
+
+/// The SIGSTOP/SIGINT stop debugserver reports after an interrupt.
+fn is_interrupt_stop(reply: &StopReply) -> bool {
+    matches!(reply.reason, StopReason::Signal)
+        && matches!(reply.signal, signals::SIGSTOP | signals::SIGINT)
+}
+
+fn is_signal_stop(reply: &StopReply) -> bool {
+    matches!(reply.reason, StopReason::Signal) && reply.signal != signals::SIGTRAP
+}
+
+#[derive(Default)]
 pub struct LineIndex {
     map: HashMap<FileLine, Vec<AddressRange>>,
+    source_map: SourceMap,
+    /// Compile units not yet run through their line programs; see
+    /// [`LineIndex::index_file`].
+    pending: Option<PendingUnits>,
+}
+
+/// DWARF kept alive so compile units can be indexed on demand.
+struct PendingUnits {
+    sections: gimli::DwarfSections<SectionBytes>,
+    endian: RunTimeEndian,
+    /// Each unindexed unit with the full paths and bare file names its line
+    /// program header lists.
+    units: Vec<(gimli::DebugInfoOffset, HashSet<String>)>,
 }
 
 impl LineIndex {
-    pub fn from_binary(path: &Path) -> AnyResult<Self> {
-        let data = fs::read(path)
-            .with_context(|| format!("failed to read Mach-O for line index: {}", path.display()))?;
-        let file =
-            object::File::parse(&*data).context("failed to parse Mach-O for DWARF line index")?;
-        let endian = if file.is_little_endian() {
-            RunTimeEndian::Little
-        } else {
-            RunTimeEndian::Big
+    /// Loads the index for `file` from `cache` when it holds one for this
+    /// build, or builds the full index once and caches it. Without a cache
+    /// the index is lazy, so each session only reads what it needs.
+    pub fn load_or_build(
+        file: &MappedFile,
+        uuid: Option<[u8; 16]>,
+        cache: Option<&SymbolCache>,
+    ) -> AnyResult<Self> {
+        let Some((cache, uuid)) = cache.zip(uuid) else {
+            return Self::lazy_from_mapped(file);
         };
-        let dwarf_sections = gimli::DwarfSections::load(|id| load_section_vec(&file, id))?;
-        let dwarf = dwarf_sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+        let cached: Option<Vec<(FileLine, Vec<AddressRange>)>> =
+            cache.load(LINE_INDEX_CACHE_KIND, file.path(), &uuid);
+        if let Some(entries) = cached {
+            return Ok(LineIndex {
+                map: entries.into_iter().collect(),
+                ..LineIndex::default()
+            });
+        }
+        let index = Self::from_mapped(file)?;
+        let entries: Vec<_> = index.map.iter().collect();
+        if let Err(err) = cache.store(LINE_INDEX_CACHE_KIND, file.path(), &uuid, &entries) {
+            eprintln!(
+                "failed to cache line index of {}: {err}",
+                file.path().display()
+            );
+        }
+        Ok(index)
+    }
+
+    /// Indexes every compile unit of the binary at `path` up front.
+    pub fn from_binary(path: &Path) -> AnyResult<Self> {
+        Self::from_mapped(&MappedFile::open(path)?)
+    }
+
+    /// Like [`LineIndex::from_binary`], reusing a mapping the caller holds.
+    pub fn from_mapped(file: &MappedFile) -> AnyResult<Self> {
+        let (sections, endian) = load_dwarf_sections(file)?;
+        let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
         Self::new_from_dwarf(&dwarf)
     }
 
-    #[allow(dead_code)]
+    /// Records only which source files each compile unit covers; line
+    /// programs run when [`LineIndex::index_file`] asks for one of them.
+    pub fn lazy_from_binary(path: &Path) -> AnyResult<Self> {
+        Self::lazy_from_mapped(&MappedFile::open(path)?)
+    }
+
+    /// Like [`LineIndex::lazy_from_binary`], reusing a mapping the caller holds.
+    pub fn lazy_from_mapped(file: &MappedFile) -> AnyResult<Self> {
+        let (sections, endian) = load_dwarf_sections(file)?;
+        let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+        let mut units = Vec::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            let Some(offset) = header.offset().as_debug_info_offset() else {
+                continue;
+            };
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.as_ref() else {
+                continue;
+            };
+            let program = program.header();
+            let mut names = HashSet::new();
+            for entry in program.file_names() {
+                if let Some(path) = file_entry_path(&dwarf, &unit, program, entry) {
+                    if let Some(name) = Path::new(&path).file_name().and_then(|n| n.to_str()) {
+                        names.insert(name.to_string());
+                    }
+                    names.insert(path);
+                }
+            }
+            units.push((offset, names));
+        }
+        Ok(LineIndex {
+            pending: Some(PendingUnits {
+                sections,
+                endian,
+                units,
+            }),
+            ..LineIndex::default()
+        })
+    }
+
+    /// Runs the line programs of every pending compile unit that lists
+    /// `file` (by path or bare name), so [`LineIndex::lookup`] can see it.
+    pub fn index_file(&mut self, file: &str) {
+        let Some(pending) = self.pending.as_mut() else {
+            return;
+        };
+        let mapped = self.source_map.to_build(file);
+        let name = Path::new(&mapped)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(&mapped)
+            .to_string();
+        let endian = pending.endian;
+        let dwarf = pending
+            .sections
+            .borrow(|section| gimli::EndianSlice::new(section, endian));
+        let mut remaining = Vec::with_capacity(pending.units.len());
+        for (offset, names) in std::mem::take(&mut pending.units) {
+            if !names.contains(mapped.as_str()) && !names.contains(&name) {
+                remaining.push((offset, names));
+                continue;
+            }
+            let indexed = dwarf
+                .debug_info
+                .header_from_offset(offset)
+                .and_then(|header| Self::index_unit(&dwarf, header));
+            match indexed {
+                Ok(unit_index) => {
+                    for (key, ranges) in unit_index.map {
+                        self.map.entry(key).or_default().extend(ranges);
+                    }
+                }
+                Err(err) => eprintln!("failed to index compile unit at {offset:?}: {err}"),
+            }
+        }
+        if remaining.is_empty() {
+            self.pending = None;
+        } else {
+            pending.units = remaining;
+        }
+    }
+
+    /// Indexes every compile unit's line program. Units are handed out to
+    /// one worker per core and the per-unit maps merged in unit order, so
+    /// the result matches a serial walk.
     pub fn new_from_dwarf(
-        _dwarf: &gimli::Dwarf<gimli::EndianSlice<'_, gimli::RunTimeEndian>>,
+        dwarf: &gimli::Dwarf<gimli::EndianSlice<'_, gimli::RunTimeEndian>>,
     ) -> AnyResult<Self> {
-        let mut index = LineIndex {
-            map: HashMap::new(),
-        };
-        let mut units = _dwarf.units();
+        let mut headers = Vec::new();
+        let mut units = dwarf.units();
         while let Some(header) = units.next()? {
-            let unit = _dwarf.unit(header)?;
-            if let Some(program) = unit.line_program.clone() {
-                index.consume_line_program(_dwarf, &unit, program)?;
+            headers.push(header);
+        }
+
+        let workers = thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .clamp(1, headers.len().max(1));
+        let next = AtomicUsize::new(0);
+        let mut per_unit: Vec<(usize, gimli::Result<LineIndex>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut indexed = Vec::new();
+                        loop {
+                            let position = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(header) = headers.get(position) else {
+                                return indexed;
+                            };
+                            indexed.push((position, Self::index_unit(dwarf, *header)));
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("line index worker panicked"))
+                .collect()
+        });
+        per_unit.sort_by_key(|(position, _)| *position);
+
+        let mut index = LineIndex::default();
+        for (_, unit_index) in per_unit {
+            for (key, ranges) in unit_index?.map {
+                index.map.entry(key).or_default().extend(ranges);
             }
         }
         Ok(index)
     }
 
+    fn index_unit(
+        dwarf: &gimli::Dwarf<EndianSlice<'_, RunTimeEndian>>,
+        header: gimli::UnitHeader<EndianSlice<'_, RunTimeEndian>>,
+    ) -> gimli::Result<LineIndex> {
+        let mut index = LineIndex::default();
+        let unit = dwarf.unit(header)?;
+        if let Some(program) = unit.line_program.clone() {
+            index.consume_line_program(dwarf, &unit, program)?;
+        }
+        Ok(index)
+    }
+
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = source_map;
+    }
+
+    /// True when the binary has no line tables at all, indexed or pending.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+            && self
+                .pending
+                .as_ref()
+                .is_none_or(|pending| pending.units.is_empty())
+    }
+
+    /// Looks up `file:line`, translating local paths to DWARF build paths
+    /// through the source map first.
     pub fn lookup(&self, file: &str, line: u64) -> Vec<AddressRange> {
+        let mapped = self.source_map.to_build(file);
+        let file = mapped.as_str();
         let mut results = Vec::new();
         let key = FileLine {
             file: file.to_string(),
@@ -389,8 +2001,8 @@ impl LineIndex {
                 continue;
             }
 
-            let file_path = line_file_path(dwarf, unit, header, &row)
-                .unwrap_or_else(|| "<unknown>".to_string());
+            let file_path =
+                line_file_path(dwarf, unit, header, row).unwrap_or_else(|| "<unknown>".to_string());
             let line = row.line().map(|value| value.get()).unwrap_or(0);
             let address = row.address();
 
@@ -433,47 +2045,76 @@ impl LineIndex {
 }
 
 pub fn binary_has_dwarf_line_info(path: &Path) -> bool {
-    match fs::read(path) {
-        Ok(data) => match object::File::parse(&*data) {
-            Ok(file) => {
-                let endian = if file.is_little_endian() {
-                    RunTimeEndian::Little
-                } else {
-                    RunTimeEndian::Big
-                };
-                match gimli::DwarfSections::load(|id| load_section_vec(&file, id)) {
-                    Ok(sections) => {
-                        let dwarf =
-                            sections.borrow(|section| gimli::EndianSlice::new(section, endian));
-                        let mut units = dwarf.units();
-                        while let Ok(Some(header)) = units.next() {
-                            if let Ok(unit) = dwarf.unit(header) {
-                                if unit.line_program.is_some() {
-                                    return true;
-                                }
-                            }
-                        }
-                        false
-                    }
-                    Err(_) => false,
-                }
+    let Ok(file) = MappedFile::open(path) else {
+        return false;
+    };
+    let Ok((sections, endian)) = load_dwarf_sections(&file) else {
+        return false;
+    };
+    let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        if let Ok(unit) = dwarf.unit(header) {
+            if unit.line_program.is_some() {
+                return true;
             }
-            Err(_) => false,
-        },
-        Err(_) => false,
+        }
+    }
+    false
+}
+
+/// A DWARF section's bytes: a window into the mapped binary, or an owned
+/// buffer when the section is compressed and had to be inflated.
+enum SectionBytes {
+    Mapped(MappedFile, Range<usize>),
+    Owned(Vec<u8>),
+}
+
+impl Deref for SectionBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SectionBytes::Mapped(file, range) => &file[range.clone()],
+            SectionBytes::Owned(data) => data,
+        }
     }
 }
 
-fn load_section_vec(
+/// Locates the DWARF sections of a mapped binary without copying them.
+fn load_dwarf_sections(
+    mapped: &MappedFile,
+) -> AnyResult<(gimli::DwarfSections<SectionBytes>, RunTimeEndian)> {
+    let file =
+        object::File::parse(&**mapped).context("failed to parse Mach-O for DWARF line index")?;
+    let endian = if file.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let sections = gimli::DwarfSections::load(|id| load_section_bytes(mapped, &file, id))?;
+    Ok((sections, endian))
+}
+
+fn load_section_bytes(
+    mapped: &MappedFile,
     file: &object::File<'_>,
     id: SectionId,
-) -> Result<Vec<u8>, object::read::Error> {
-    if let Some(section) = file.section_by_name(id.name()) {
-        let data = section.uncompressed_data()?;
-        Ok(data.into_owned())
-    } else {
-        Ok(Vec::new())
+) -> Result<SectionBytes, object::read::Error> {
+    let Some(section) = file.section_by_name(id.name()) else {
+        return Ok(SectionBytes::Owned(Vec::new()));
+    };
+    let range = section.compressed_file_range()?;
+    if range.format == CompressionFormat::None {
+        let start = range.offset as usize;
+        let end = start + range.uncompressed_size as usize;
+        if end <= mapped.len() {
+            return Ok(SectionBytes::Mapped(mapped.clone(), start..end));
+        }
     }
+    Ok(SectionBytes::Owned(
+        section.uncompressed_data()?.into_owned(),
+    ))
 }
 
 fn line_file_path(
@@ -482,26 +2123,54 @@ fn line_file_path(
     header: &LineProgramHeader<EndianSlice<'_, RunTimeEndian>>,
     row: &LineRow,
 ) -> Option<String> {
-    let file_entry = row.file(header)?;
+    file_entry_path(dwarf, unit, header, row.file(header)?)
+}
+
+fn file_entry_path(
+    dwarf: &gimli::Dwarf<EndianSlice<'_, RunTimeEndian>>,
+    unit: &Unit<EndianSlice<'_, RunTimeEndian>>,
+    header: &LineProgramHeader<EndianSlice<'_, RunTimeEndian>>,
+    file_entry: &gimli::FileEntry<EndianSlice<'_, RunTimeEndian>>,
+) -> Option<String> {
     let file_name = dwarf.attr_string(unit, file_entry.path_name()).ok()?;
-    let mut path = file_name.to_string_lossy().into_owned();
+    let file_name = file_name.to_string_lossy();
+    if file_name.starts_with('/') {
+        return Some(file_name.into_owned());
+    }
 
-    if let Some(dir_attr) = file_entry.directory(header) {
-        if let Ok(dir) = dwarf.attr_string(unit, dir_attr) {
-            let dir = dir.to_string_lossy();
-            if !dir.is_empty() {
-                path = format!("{}/{}", dir.trim_end_matches('/'), path);
-            }
+    let dir = file_entry
+        .directory(header)
+        .and_then(|dir_attr| dwarf.attr_string(unit, dir_attr).ok())
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Include directories may be relative to the compilation directory, which
+    // DWARF 5 producers rely on.
+    let dir = match unit.comp_dir {
+        Some(comp_dir) if !dir.starts_with('/') => {
+            join_dwarf_path(&comp_dir.to_string_lossy(), &dir)
         }
-    }
+        _ => dir,
+    };
+    Some(join_dwarf_path(&dir, &file_name))
+}
 
-    Some(path)
+fn join_dwarf_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if name.is_empty() {
+        dir.to_string()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::symbols::{Image, SymbolContext};
+    use crate::{
+        mock_debugserver::{MockDebugserver, MockState},
+        symbols::{Image, SymbolContext},
+    };
     use addr2line::Loader;
     use object::{Object, ObjectSymbol};
 
@@ -513,7 +2182,7 @@ mod tests {
 
     #[test]
     fn threads_have_id_and_name() {
-        let backend = test_backend();
+        let mut backend = test_backend();
         let threads = backend.threads();
         assert!(!threads.is_empty(), "expected at least one thread");
         let thread = threads.first().unwrap();
@@ -521,12 +2190,148 @@ mod tests {
         assert!(thread.get("name").is_some());
     }
 
+    #[test]
+    fn thread_labels_include_stop_reason() {
+        let reply = |reason| StopReply {
+            signal: 5,
+            thread_id: Some(2),
+            reason,
+            threads: Vec::new(),
+            thread_pcs: Vec::new(),
+            registers: HashMap::new(),
+        };
+        assert_eq!(
+            stop_reason_label(&reply(StopReason::Breakpoint)),
+            Some("breakpoint")
+        );
+        assert_eq!(stop_reason_label(&reply(StopReason::NoReason)), None);
+        assert_eq!(
+            thread_label("main".into(), Some("breakpoint")),
+            "main (breakpoint)"
+        );
+        assert_eq!(thread_label("worker".into(), None), "worker");
+    }
+
+    #[test]
+    fn recording_the_main_image_updates_the_slide() {
+        let mut backend = test_backend();
+        let vmaddr = backend.symbol_ctx.as_ref().unwrap().main.vmaddr_text;
+        let main_path = backend
+            .symbol_ctx
+            .as_ref()
+            .unwrap()
+            .main
+            .path
+            .to_string_lossy()
+            .into_owned();
+        let uuid = backend
+            .symbol_ctx
+            .as_ref()
+            .unwrap()
+            .main
+            .uuid
+            .map(|bytes| bytes.iter().map(|b| format!("{b:02X}")).collect::<String>());
+        backend.record_images(vec![
+            LoadedImage {
+                load_address: 0x1_8000_0000,
+                pathname: "/usr/lib/dyld".into(),
+                uuid: Some("00000000-0000-0000-0000-000000000001".into()),
+            },
+            LoadedImage {
+                load_address: vmaddr + 0x4000,
+                pathname: main_path,
+                uuid,
+            },
+        ]);
+        assert_eq!(backend.loaded_images().len(), 2);
+        assert_eq!(backend.symbol_ctx.as_ref().unwrap().main.slide, 0x4000);
+
+        backend.record_images(vec![LoadedImage {
+            load_address: 0x1_8000_0000,
+            pathname: "/usr/lib/dyld".into(),
+            uuid: None,
+        }]);
+        assert_eq!(backend.loaded_images().len(), 2);
+        assert_eq!(backend.symbol_ctx.as_ref().unwrap().main.slide, 0x4000);
+    }
+
+    #[test]
+    fn stripped_frames_are_named_by_module_offset() {
+        let mut backend = test_backend();
+        backend.record_images(vec![LoadedImage {
+            load_address: 0x7_0000_0000,
+            pathname: "/private/var/containers/Bundle/Application/X/Stripped.app/Stripped".into(),
+            uuid: None,
+        }]);
+        backend.set_frame_provider(|_thread_id| vec![(1, 0x7_0000_1234)]);
+
+        let frames = backend.stack_trace(1);
+        assert_eq!(frames[0]["name"], "Stripped + 0x1234");
+        assert_eq!(
+            backend.describe_address(0x7_0000_1234),
+            "0x700001234: Stripped + 0x1234"
+        );
+    }
+
+    #[test]
+    fn missing_line_tables_explain_unverified_breakpoints() {
+        let mut backend = test_backend();
+        assert_eq!(backend.missing_debug_info(), None);
+
+        backend.line_index = Some(LineIndex::default());
+        let message = backend.missing_debug_info().unwrap();
+        assert!(message.contains("no DWARF line tables"), "{message}");
+
+        backend.line_index = Some(LineIndex {
+            map: HashMap::from([(
+                FileLine {
+                    file: "main.swift".into(),
+                    line: 1,
+                },
+                vec![AddressRange {
+                    low: 0x1000,
+                    high: 0x1004,
+                }],
+            )]),
+            ..LineIndex::default()
+        });
+        assert_eq!(backend.missing_debug_info(), None);
+    }
+
+    #[test]
+    fn scopes_are_scoped_to_their_frame() {
+        let mut backend = test_backend();
+        backend.set_frame_provider(|_thread_id| vec![(101, 0x10), (102, 0x20)]);
+        backend.stack_trace(1);
+
+        let inner = backend.scopes(101).unwrap()[0]["variablesReference"]
+            .as_i64()
+            .unwrap();
+        let outer = backend.scopes(102).unwrap()[0]["variablesReference"]
+            .as_i64()
+            .unwrap();
+        assert_ne!(inner, outer);
+        assert_eq!(
+            backend.variables(outer).unwrap()[0]["value"].as_str(),
+            Some("value-102")
+        );
+        assert!(backend.scopes(999).is_err());
+
+        backend.invalidate_frames();
+        assert!(backend.variables(inner).is_err());
+        assert!(backend.scopes(101).is_err());
+    }
+
     #[test]
     fn update_slide_tracks_remote_base() {
         let mut backend = test_backend_with_vmaddr(0x1000);
         backend.update_slide_from_remote_text_base(0x3000);
-        assert_eq!(backend.symbol_ctx.main.slide, 0x2000);
-        let translated = backend.symbol_ctx.translate_remote_pc(0x3000 + 0x40);
+        assert_eq!(backend.symbol_ctx.as_ref().unwrap().main.slide, 0x2000);
+        let translated = backend
+            .symbol_ctx
+            .as_ref()
+            .unwrap()
+            .translate_remote_pc(0x3000 + 0x40);
         assert_eq!(translated, 0x1000 + 0x40);
     }
 
@@ -596,7 +2401,11 @@ mod tests {
                 high: 0x20,
             }],
         );
-        let index = LineIndex { map };
+        let index = LineIndex {
+            map,
+            source_map: SourceMap::default(),
+            pending: None,
+        };
         assert_eq!(
             index.lookup("/tmp/main.rs", 10),
             vec![AddressRange {
@@ -607,12 +2416,44 @@ mod tests {
         assert!(index.lookup("/tmp/main.rs", 11).is_empty());
     }
 
+    #[test]
+    fn line_index_lookup_applies_source_map() {
+        let mut map = HashMap::new();
+        map.insert(
+            FileLine {
+                file: "/ci/build/App/main.swift".into(),
+                line: 3,
+            },
+            vec![AddressRange {
+                low: 0x40,
+                high: 0x48,
+            }],
+        );
+        let mut index = LineIndex {
+            map,
+            source_map: SourceMap::default(),
+            pending: None,
+        };
+        assert!(index.lookup("/Users/me/App/main.swift", 3).is_empty());
+        index.set_source_map(SourceMap::new(vec![(
+            "/ci/build".into(),
+            "/Users/me".into(),
+        )]));
+        assert_eq!(
+            index.lookup("/Users/me/App/main.swift", 3),
+            vec![AddressRange {
+                low: 0x40,
+                high: 0x48
+            }]
+        );
+    }
+
     #[cfg(target_os = "macos")]
     #[test]
     fn backend_from_app_uses_symbol_context() {
         let exe = std::env::current_exe().unwrap();
-        let backend = Backend::new_from_app(&exe).unwrap();
-        assert_eq!(backend.symbol_ctx.main.path, exe);
+        let backend = Backend::new_from_app(&exe, None).unwrap();
+        assert_eq!(backend.symbol_ctx.as_ref().unwrap().main.path, exe);
     }
 
     #[test]
@@ -626,14 +2467,153 @@ mod tests {
                     line: 42,
                 },
                 vec![AddressRange {
-                    low: backend.symbol_ctx.main.vmaddr_text,
-                    high: backend.symbol_ctx.main.vmaddr_text + 4,
+                    low: backend.symbol_ctx.as_ref().unwrap().main.vmaddr_text,
+                    high: backend.symbol_ctx.as_ref().unwrap().main.vmaddr_text + 4,
                 }],
             )]),
+            source_map: SourceMap::default(),
+            pending: None,
         });
         assert!(backend.update_breakpoints("/tmp/foo.rs", &[42]).is_ok());
     }
 
+    #[test]
+    fn running_target_rejects_stopped_only_requests() {
+        let mut backend = test_backend();
+        assert_eq!(backend.run_state(), RunState::Detached);
+        assert!(backend.require_stopped().is_ok());
+
+        backend.run_state = RunState::Running;
+        assert!(backend.require_stopped().is_err());
+        assert!(backend.update_breakpoints("/tmp/foo.rs", &[1]).is_err());
+        assert!(backend.r#continue(1, false).is_err());
+        assert!(backend.step_over(1, true).is_err());
+
+        backend.disconnect(false).unwrap();
+        assert_eq!(backend.run_state(), RunState::Detached);
+    }
+
+    #[test]
+    fn parse_address_accepts_hex_and_decimal() {
+        assert_eq!(parse_address("*0x10000abcd"), Some(0x10000abcd));
+        assert_eq!(parse_address("0XFF"), Some(0xff));
+        assert_eq!(parse_address("4096"), Some(4096));
+        assert_eq!(parse_address("main"), None);
+    }
+
+    #[test]
+    fn address_breakpoints_translate_and_symbolize() {
+        let mut backend = test_backend_with_vmaddr(0x1000);
+        backend.update_slide_from_remote_text_base(0x3000);
+        backend_symbol_test_function();
+        let symbol = find_symbol_address("backend_symbol_test_function");
+        let remote = symbol + 0x2000;
+
+        let description = backend.add_address_breakpoint(remote).unwrap();
+        assert!(
+            description.contains("backend_symbol_test_function"),
+            "address should symbolize back: {description}"
+        );
+        backend.add_address_breakpoint(remote).unwrap();
+        assert_eq!(backend.breakpoint_sites().len(), 1);
+        assert_eq!(backend.breakpoint_sites()[0].address, symbol);
+
+        backend.set_address_breakpoints(&[]).unwrap();
+        assert!(backend.breakpoint_sites().is_empty());
+    }
+
+    #[test]
+    fn set_regex_breakpoint_resolves_functions() {
+        let mut backend = test_backend();
+        backend_symbol_test_function();
+        let matches = backend
+            .set_regex_breakpoint("^backend_symbol_test_function$")
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].address,
+            find_symbol_address("backend_symbol_test_function")
+        );
+        assert!(backend.set_regex_breakpoint("(").is_err());
+        assert_eq!(backend.breakpoint_sites().len(), 1);
+
+        assert_eq!(
+            backend
+                .clear_regex_breakpoint("^backend_symbol_test_function$")
+                .unwrap(),
+            1
+        );
+        assert!(backend.breakpoint_sites().is_empty());
+        assert!(backend
+            .clear_regex_breakpoint("^backend_symbol_test_function$")
+            .is_err());
+    }
+
+    #[test]
+    fn update_breakpoints_replaces_sites_for_file() {
+        let mut backend = test_backend();
+        backend.line_index = Some(LineIndex {
+            map: HashMap::from([
+                (
+                    FileLine {
+                        file: "/tmp/foo.rs".into(),
+                        line: 10,
+                    },
+                    vec![AddressRange {
+                        low: 0x100,
+                        high: 0x104,
+                    }],
+                ),
+                (
+                    FileLine {
+                        file: "/tmp/foo.rs".into(),
+                        line: 20,
+                    },
+                    vec![AddressRange {
+                        low: 0x200,
+                        high: 0x204,
+                    }],
+                ),
+            ]),
+            source_map: SourceMap::default(),
+            pending: None,
+        });
+
+        backend
+            .update_breakpoints("/tmp/foo.rs", &[10, 20])
+            .unwrap();
+        let addresses: Vec<u64> = backend
+            .breakpoint_sites()
+            .iter()
+            .map(|site| site.address)
+            .collect();
+        assert_eq!(addresses, vec![0x100, 0x200]);
+
+        backend.update_breakpoints("/tmp/foo.rs", &[20]).unwrap();
+        assert_eq!(
+            backend.breakpoint_sites(),
+            &[BreakpointSite {
+                origin: BreakpointOrigin::Line {
+                    file: "/tmp/foo.rs".into(),
+                    line: 20,
+                },
+                address: 0x200,
+            }]
+        );
+
+        backend.symbol_ctx.as_mut().unwrap().main.uuid = Some([0x11; 16]);
+        let name = backend.symbol_ctx.as_ref().unwrap().main.path.clone();
+        backend.record_images(vec![LoadedImage {
+            load_address: 0x1_0000_0000,
+            pathname: name.to_string_lossy().into_owned(),
+            uuid: Some("22222222-2222-2222-2222-222222222222".into()),
+        }]);
+        backend
+            .update_breakpoints("/tmp/foo.rs", &[10, 20])
+            .unwrap();
+        assert!(backend.breakpoint_sites().is_empty());
+    }
+
     #[test]
     fn line_index_builds_from_current_binary() {
         let exe = std::env::current_exe().unwrap();
@@ -647,6 +2627,219 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parallel_line_index_matches_serial_walk() {
+        let file = MappedFile::open(&std::env::current_exe().unwrap()).unwrap();
+        let (sections, endian) = load_dwarf_sections(&file).unwrap();
+        let dwarf = sections.borrow(|section| EndianSlice::new(section, endian));
+
+        let parallel = LineIndex::new_from_dwarf(&dwarf).unwrap();
+        let mut serial = LineIndex::default();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next().unwrap() {
+            for (key, ranges) in LineIndex::index_unit(&dwarf, header).unwrap().map {
+                serial.map.entry(key).or_default().extend(ranges);
+            }
+        }
+        assert_eq!(parallel.map, serial.map);
+    }
+
+    /// Writes a DWARF 5 compile unit whose line table stores strings in
+    /// `.debug_line_str` and lists `Sources/View.swift` relative to the
+    /// compilation directory.
+    fn dwarf5_sections() -> gimli::write::Sections<gimli::write::EndianVec<gimli::LittleEndian>> {
+        use gimli::write::{
+            Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+        };
+
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 5,
+            address_size: 8,
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+        let comp_dir = LineString::new(&b"/build/App"[..], encoding, &mut dwarf.line_strings);
+        let comp_file = LineString::new(&b"main.swift"[..], encoding, &mut dwarf.line_strings);
+        let mut program = LineProgram::new(
+            encoding,
+            gimli::LineEncoding::default(),
+            comp_dir,
+            comp_file,
+            None,
+        );
+        let sources = LineString::new(&b"Sources"[..], encoding, &mut dwarf.line_strings);
+        let dir = program.add_directory(sources);
+        let view = LineString::new(&b"View.swift"[..], encoding, &mut dwarf.line_strings);
+        let file = program.add_file(view, dir, None);
+        program.begin_sequence(Some(Address::Constant(0x1000)));
+        program.row().file = file;
+        program.row().line = 7;
+        program.generate_row();
+        program.end_sequence(0x10);
+        dwarf.unit.line_program = program;
+        let root = dwarf.unit.root();
+        dwarf.unit.get_mut(root).set(
+            gimli::DW_AT_comp_dir,
+            AttributeValue::String(b"/build/App".to_vec()),
+        );
+
+        let mut sections = Sections::new(EndianVec::new(gimli::LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        sections
+    }
+
+    /// Writes an ELF object at `name` in the temp directory with one compile
+    /// unit per `(path, line, address)`, so line index tests don't have to
+    /// index the whole test binary.
+    fn line_table_fixture(name: &str, rows: &[(&str, u64, u64)]) -> PathBuf {
+        use gimli::write::{Address, Dwarf, EndianVec, LineProgram, LineString, Sections, Unit};
+
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let mut dwarf = Dwarf::new();
+        for &(path, line, address) in rows {
+            let (dir, file) = path.rsplit_once('/').unwrap();
+            let mut program = LineProgram::new(
+                encoding,
+                gimli::LineEncoding::default(),
+                LineString::String(dir.into()),
+                LineString::String(file.into()),
+                None,
+            );
+            let dir = program.default_directory();
+            let file = program.add_file(LineString::String(file.into()), dir, None);
+            program.begin_sequence(Some(Address::Constant(address)));
+            program.row().file = file;
+            program.row().line = line;
+            program.generate_row();
+            program.end_sequence(0x10);
+            dwarf.units.add(Unit::new(encoding, program));
+        }
+        let mut sections = Sections::new(EndianVec::new(gimli::LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+
+        let mut elf = object::write::Object::new(
+            object::BinaryFormat::Elf,
+            object::Architecture::X86_64,
+            object::Endianness::Little,
+        );
+        sections
+            .for_each(|id, data| {
+                if !data.slice().is_empty() {
+                    let section = elf.add_section(
+                        Vec::new(),
+                        id.name().as_bytes().to_vec(),
+                        object::SectionKind::Debug,
+                    );
+                    elf.set_section_data(section, data.slice().to_vec(), 1);
+                }
+                Ok::<_, gimli::Error>(())
+            })
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("swiftscope-{name}-{}.o", std::process::id()));
+        fs::write(&path, elf.write().unwrap()).unwrap();
+        path
+    }
+
+    const FIXTURE_ROWS: &[(&str, u64, u64)] = &[
+        ("/build/App/Sources/View.swift", 7, 0x1000),
+        ("/build/App/Sources/Model.swift", 3, 0x2000),
+        ("/build/App/main.swift", 1, 0x3000),
+    ];
+
+    #[test]
+    fn dwarf5_line_tables_resolve_directories_against_comp_dir() {
+        let sections = dwarf5_sections();
+        assert!(!sections.debug_line_str.slice().is_empty());
+        let dwarf = gimli::Dwarf::load(|id| {
+            let data = sections
+                .get(id)
+                .map(|section| section.slice())
+                .unwrap_or(&[]);
+            Ok::<_, gimli::Error>(EndianSlice::new(data, RunTimeEndian::Little))
+        })
+        .unwrap();
+
+        let index = LineIndex::new_from_dwarf(&dwarf).unwrap();
+        let ranges = index.lookup("/build/App/Sources/View.swift", 7);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].low, 0x1000);
+        assert_eq!(index.lookup("View.swift", 7).len(), 1);
+    }
+
+    #[test]
+    fn mapped_sections_match_inflated_section_data() {
+        let mapped = MappedFile::open(&std::env::current_exe().unwrap()).unwrap();
+        let file = object::File::parse(&*mapped).unwrap();
+        for id in [
+            SectionId::DebugInfo,
+            SectionId::DebugLine,
+            SectionId::DebugStr,
+        ] {
+            let bytes = load_section_bytes(&mapped, &file, id).unwrap();
+            let expected = file
+                .section_by_name(id.name())
+                .map(|section| section.uncompressed_data().unwrap().into_owned())
+                .unwrap_or_default();
+            assert_eq!(&*bytes, &expected[..], "{}", id.name());
+        }
+    }
+
+    #[test]
+    fn line_index_is_served_from_cache() {
+        let exe = line_table_fixture("line-cache", FIXTURE_ROWS);
+        let dir =
+            std::env::temp_dir().join(format!("swiftscope-line-cache-{}", std::process::id()));
+        let cache = SymbolCache::new(&dir);
+        let uuid = [0x42; 16];
+        let file = MappedFile::open(&exe).unwrap();
+
+        let built = LineIndex::load_or_build(&file, Some(uuid), Some(&cache)).unwrap();
+        assert!(built.pending.is_none(), "a cache miss should index fully");
+        assert!(cache
+            .load::<Vec<(FileLine, Vec<AddressRange>)>>(LINE_INDEX_CACHE_KIND, &exe, &uuid)
+            .is_some());
+        let lazy = LineIndex::load_or_build(&file, None, Some(&cache)).unwrap();
+        assert!(
+            lazy.pending.is_some(),
+            "without a cache key the index is lazy"
+        );
+        let cached = LineIndex::load_or_build(&file, Some(uuid), Some(&cache)).unwrap();
+        assert!(cached.pending.is_none());
+        assert_eq!(cached.lookup("Model.swift", 3)[0].low, 0x2000);
+        assert_eq!(cached.map, LineIndex::from_binary(&exe).unwrap().map);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&exe).unwrap();
+    }
+
+    #[test]
+    fn lazy_line_index_indexes_only_requested_files() {
+        let exe = line_table_fixture("lazy-lines", FIXTURE_ROWS);
+        let full = LineIndex::from_binary(&exe).unwrap();
+        let mut lazy = LineIndex::lazy_from_binary(&exe).unwrap();
+        assert_eq!(lazy.pending.as_ref().unwrap().units.len(), 3);
+
+        assert!(lazy.lookup("View.swift", 7).is_empty());
+        lazy.index_file("/elsewhere/Sources/View.swift");
+        let remaining = lazy
+            .pending
+            .as_ref()
+            .map_or(0, |pending| pending.units.len());
+        assert_eq!(
+            remaining, 2,
+            "only the unit for View.swift should be indexed"
+        );
+
+        let ranges = lazy.lookup("View.swift", 7);
+        assert_eq!(ranges[0].low, 0x1000);
+        assert_eq!(ranges, full.lookup("View.swift", 7));
+        assert!(lazy.lookup("Model.swift", 3).is_empty());
+        fs::remove_file(&exe).unwrap();
+    }
+
     #[test]
     fn detects_dwarf_in_current_binary() {
         let exe = std::env::current_exe().unwrap();
@@ -660,6 +2853,289 @@ mod tests {
         assert!(binary_has_dwarf_line_info(&exe));
     }
 
+    fn mock_backend() -> (MockDebugserver, Backend) {
+        let server = MockDebugserver::start().unwrap();
+        let mut backend = test_backend();
+        backend
+            .connect_debugserver(crate::gdb_remote::DEFAULT_HOST, server.port())
+            .unwrap();
+        (server, backend)
+    }
+
+    /// The stop a resume left pending, once the mock has sent it.
+    fn wait_for_stop(server: &MockDebugserver, backend: &mut Backend) -> BackendStopEvent {
+        server.wait_until(|state| !state.is_running());
+        backend
+            .poll_stop()
+            .unwrap()
+            .expect("the mock sent a stop reply")
+    }
+
+    #[test]
+    fn mismatched_main_image_uuid_is_reported() {
+        let mut backend = test_backend();
+        backend.symbol_ctx.as_mut().unwrap().main.uuid = Some([0x11; 16]);
+        let name = backend
+            .symbol_ctx
+            .as_ref()
+            .unwrap()
+            .main
+            .path
+            .file_name()
+            .unwrap()
+            .to_owned();
+        let image = |uuid: &str| LoadedImage {
+            load_address: 0x1_0000_0000,
+            pathname: format!("/private/var/containers/App/{}", name.to_string_lossy()),
+            uuid: Some(uuid.into()),
+        };
+
+        backend.record_images(vec![image("22222222-2222-2222-2222-222222222222")]);
+        let mismatch = backend.uuid_mismatch().expect("builds differ");
+        assert!(mismatch.contains("UUID 11111111-1111-1111-1111-111111111111"));
+        assert!(mismatch.contains("UUID 22222222-2222-2222-2222-222222222222"));
+        assert_eq!(backend.take_notices(), vec![mismatch]);
+        assert_eq!(backend.symbol_ctx.as_ref().unwrap().main.slide, 0);
+
+        backend.images.clear();
+        backend.record_images(vec![image("11111111-1111-1111-1111-111111111111")]);
+        assert_eq!(backend.uuid_mismatch(), None);
+        assert!(backend.take_notices().is_empty());
+    }
+
+    #[test]
+    fn breakpoints_follow_a_new_slide() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc).unwrap();
+        backend.add_address_breakpoint(pc).unwrap();
+        assert_eq!(
+            server.state().breakpoints,
+            std::collections::BTreeSet::from([pc])
+        );
+        let planted = |server: &MockDebugserver| {
+            let state = server.state();
+            state
+                .packets
+                .iter()
+                .filter(|packet| packet.starts_with("Z0,"))
+                .count()
+        };
+        assert_eq!(planted(&server), 1, "sites are planted once");
+
+        let vmaddr = backend.symbol_ctx.as_ref().unwrap().main.vmaddr_text;
+        backend.update_slide_from_remote_text_base(vmaddr + 0x4000);
+        assert_eq!(
+            server.state().breakpoints,
+            std::collections::BTreeSet::from([pc + 0x4000])
+        );
+        backend.update_slide_from_remote_text_base(vmaddr + 0x4000);
+        assert_eq!(planted(&server), 2);
+    }
+
+    #[test]
+    fn thread_stop_reasons_are_fetched_once_per_stop() {
+        let (server, mut backend) = mock_backend();
+        let queries = |server: &MockDebugserver| {
+            let state = server.state();
+            state
+                .packets
+                .iter()
+                .filter(|packet| packet.starts_with("qThreadStopInfo"))
+                .count()
+        };
+        let threads = backend.threads();
+        backend.threads();
+        backend.other_thread_stops(threads[0]["id"].as_i64().unwrap());
+        assert!(queries(&server) > 0);
+        assert_eq!(queries(&server), threads.len());
+
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc + 0x40).unwrap();
+        if backend.r#continue(0x303, false).unwrap().is_none() {
+            wait_for_stop(&server, &mut backend);
+        }
+        backend.threads();
+        assert_eq!(queries(&server), 2 * threads.len());
+    }
+
+    #[test]
+    fn registers_are_read_one_by_one_when_g_would_not_fit() {
+        let mut state = MockState::default();
+        state.packet_size = 0x100;
+        let server = MockDebugserver::with_state(state).unwrap();
+        let mut backend = test_backend();
+        backend
+            .connect_debugserver(crate::gdb_remote::DEFAULT_HOST, server.port())
+            .unwrap();
+        let pc = server.state().pc();
+
+        let registers = backend.registers(None).unwrap();
+        assert_eq!(registers.value("pc"), Some(pc));
+        let state = server.state();
+        assert!(!state.packets.iter().any(|packet| packet == "g"));
+        assert!(state.packets.iter().any(|packet| packet.starts_with('p')));
+    }
+
+    #[test]
+    fn launch_waits_for_a_slow_spawn() {
+        let mut state = MockState::default();
+        state.launch_delay = Duration::from_millis(600);
+        let server = MockDebugserver::with_state(state).unwrap();
+        let mut backend = test_backend();
+        backend
+            .connect_debugserver(crate::gdb_remote::DEFAULT_HOST, server.port())
+            .unwrap();
+
+        let spec = LaunchSpec {
+            program: "/private/var/containers/Bundle/Application/App.app/App".into(),
+            ..LaunchSpec::default()
+        };
+        backend.launch_process(&spec).unwrap();
+        // The late reply was consumed, so later packets stay in step.
+        let pc = server.state().pc();
+        assert_eq!(backend.read_register("pc", None).unwrap(), pc);
+    }
+
+    #[test]
+    fn relaunch_replants_breakpoints() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc).unwrap();
+        // A fresh process has none of the old sites.
+        server.state().breakpoints.clear();
+
+        let spec = LaunchSpec {
+            program: "/private/var/containers/Bundle/Application/App.app/App".into(),
+            ..LaunchSpec::default()
+        };
+        backend.launch_process(&spec).unwrap();
+        assert_eq!(
+            server.state().breakpoints,
+            std::collections::BTreeSet::from([pc])
+        );
+    }
+
+    #[test]
+    fn continue_stops_at_planted_breakpoint() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc + 0x40).unwrap();
+        assert!(server.state().breakpoints.contains(&(pc + 0x40)));
+
+        let event = match backend.r#continue(0x303, false).unwrap() {
+            Some(event) => event,
+            None => wait_for_stop(&server, &mut backend),
+        };
+        assert_eq!(event.reason, "breakpoint");
+        assert_eq!(server.state().pc(), pc + 0x40);
+        assert_eq!(backend.read_register("pc", None).unwrap(), pc + 0x40);
+
+        backend.set_address_breakpoints(&[]).unwrap();
+        assert!(server.state().breakpoints.is_empty());
+    }
+
+    #[test]
+    fn step_advances_one_instruction() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        let event = match backend.step_over(0x303, true).unwrap() {
+            Some(event) => event,
+            None => wait_for_stop(&server, &mut backend),
+        };
+        assert_eq!(event.reason, "step");
+        assert_eq!(backend.read_register("pc", Some(0x303)).unwrap(), pc + 4);
+    }
+
+    #[test]
+    fn signal_that_stopped_the_target_is_delivered_on_continue() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc + 0x40).unwrap();
+        server.state().raise = Some(11);
+
+        let event = backend.r#continue(0x303, false).unwrap().unwrap();
+        assert_eq!(event.reason, "signal");
+        let event = backend.r#continue(0x303, false).unwrap().unwrap();
+        assert_eq!(event.reason, "breakpoint");
+        let packets = &server.state().packets;
+        let resumes: Vec<&str> = packets
+            .iter()
+            .filter(|packet| packet.starts_with("vCont;"))
+            .map(String::as_str)
+            .collect();
+        assert_eq!(resumes, ["vCont;c", "vCont;C0b:303;c"]);
+    }
+
+    #[test]
+    fn registers_and_memory_round_trip_through_debugserver() {
+        let (server, mut backend) = mock_backend();
+        server.state().map_memory(0x2000, vec![0; 16]);
+
+        backend.write_memory(0x2004, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(
+            backend.read_memory(0x2000, 8).unwrap(),
+            [0, 0, 0, 0, 1, 2, 3, 4]
+        );
+        assert!(backend.read_memory(0x9000, 4).is_err());
+
+        backend.write_register("x0", 0xfeed, None).unwrap();
+        assert_eq!(backend.read_register("$x0", None).unwrap(), 0xfeed);
+        assert_eq!(server.state().registers[0], 0xfeed);
+    }
+
+    #[test]
+    fn pause_interrupts_running_target() {
+        let (server, mut backend) = mock_backend();
+        assert!(backend.r#continue(0x303, false).unwrap().is_none());
+        assert_eq!(backend.run_state(), RunState::Running);
+
+        let event = backend
+            .pause()
+            .unwrap()
+            .expect("running target should stop");
+        assert_eq!(event.reason, "pause");
+        assert_eq!(backend.run_state(), RunState::Stopped);
+        assert!(server.state().saw_packet("vCont;c"));
+    }
+
+    #[test]
+    fn dropped_connection_is_reestablished() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        backend.add_address_breakpoint(pc + 8).unwrap();
+        server.state().breakpoints.clear();
+        server.drop_connection();
+
+        // The resume is lost with the connection; the reconnect resyncs with
+        // the stop debugserver still holds and re-plants the breakpoint.
+        let resync = match backend.r#continue(0x303, false).unwrap() {
+            Some(event) => event,
+            None => wait_for_stop(&server, &mut backend),
+        };
+        assert_eq!(resync.reason, "signal");
+        assert!(server.state().breakpoints.contains(&(pc + 8)));
+        assert_eq!(backend.take_notices().len(), 2);
+
+        let event = match backend.r#continue(0x303, false).unwrap() {
+            Some(event) => event,
+            None => wait_for_stop(&server, &mut backend),
+        };
+        assert_eq!(event.reason, "breakpoint");
+    }
+
+    #[test]
+    fn requests_while_stopped_survive_a_dropped_connection() {
+        let (server, mut backend) = mock_backend();
+        let pc = server.state().pc();
+        server.state().map_memory(pc, vec![0xaa; 16]);
+        server.drop_connection();
+
+        assert_eq!(backend.read_memory(pc, 4).unwrap(), vec![0xaa; 4]);
+        assert_eq!(backend.take_notices().len(), 2);
+        assert_eq!(backend.read_register("pc", None).unwrap(), pc);
+    }
+
     fn test_backend() -> Backend {
         test_backend_with_vmaddr(0x0)
     }
@@ -669,7 +3145,7 @@ mod tests {
         let loader = Loader::new(&exe).unwrap();
         let image = Image {
             name: "test".into(),
-            path: exe.into(),
+            path: exe,
             uuid: None,
             vmaddr_text,
             slide: 0,
@@ -697,7 +3173,7 @@ mod tests {
         name == symbol_name
             || name
                 .strip_prefix('_')
-                .map_or(false, |rest| rest == symbol_name)
+                .is_some_and(|rest| rest == symbol_name)
             || name.contains(symbol_name)
     }
 }
@@ -8,23 +8,33 @@ use clap::{Parser, ValueEnum};
 #[path = "../cli.rs"]
 mod cli;
 
-use cli::{load_debug_json, save_debug_json, AdapterConfig, DebugJson};
+use cli::{load_debug_json, resolve_app_bundle, save_debug_json, AdapterConfig, DebugJson};
 
 #[derive(Debug, Parser)]
 #[command(about = "Generate or update Zed debug.json entries for ios-lldb")]
 struct Args {
-    /// Path to the debuggee binary (Mach-O).
-    #[arg(long)]
-    program: PathBuf,
+    /// Path to the debuggee binary (Mach-O) or its `.app` bundle.
+    #[arg(long, required_unless_present = "undo")]
+    program: Option<PathBuf>,
     /// Working directory for the debuggee (defaults to the parent of program).
     #[arg(long)]
     cwd: Option<PathBuf>,
+    /// Argument for the debuggee on launch (pass multiple times).
+    #[arg(long = "arg", allow_hyphen_values = true)]
+    arg: Vec<String>,
+    /// `KEY=VALUE` environment variable for the debuggee on launch (pass
+    /// multiple times).
+    #[arg(long, value_parser = cli::parse_env_pair)]
+    env: Vec<(String, String)>,
     /// Debugserver port to use.
     #[arg(long, default_value_t = 0)]
     port: u16,
-    /// Display label for the configuration.
-    #[arg(long, default_value = "ios-lldb")]
-    label: String,
+    /// Display label for the configuration; a configuration with the same
+    /// label is replaced. Defaults to `<program> (<target>, <request>)`,
+    /// numbered `#2`, `#3`, ... if a configuration for another program or
+    /// target has that label already.
+    #[arg(long)]
+    label: Option<String>,
     /// Request kind.
     #[arg(long, value_enum, default_value = "attach")]
     request: RequestKind,
@@ -34,6 +44,65 @@ struct Args {
     /// Update the output file instead of printing to stdout.
     #[arg(long)]
     write: bool,
+    /// Restore the output file to the version before the last --write.
+    #[arg(long, conflicts_with = "write")]
+    undo: bool,
+    /// Bundle identifier to attach to; defaults to the one in the `.app`'s
+    /// Info.plist when --program is a bundle.
+    #[arg(long)]
+    bundle_id: Option<String>,
+    /// dSYM bundle recorded as `dsymPath`, which the adapter prefers over
+    /// the binary's own DWARF; defaults to `<program>.dSYM` when Xcode left
+    /// one next to --program.
+    #[arg(long)]
+    dsym: Option<PathBuf>,
+    /// Where the app runs; sim and device record `simulator`/`device` for
+    /// bundle id lookups. Repeat with --matrix to pick the targets emitted.
+    #[arg(long, value_enum)]
+    target: Vec<Target>,
+    /// Simulator UDID or name for the sim target.
+    #[arg(long, default_value = "booted")]
+    simulator: String,
+    /// Device identifier for the device target.
+    #[arg(long)]
+    device: Option<String>,
+    /// Emit launch and attach configurations for every --target (default
+    /// host, sim and device) in one run, labelled `<label> (<target>,
+    /// <request>)`, with the program's name for `<label>` by default.
+    #[arg(long)]
+    matrix: bool,
+    /// Write paths under the current directory or DerivedData as
+    /// `${workspaceRoot}`/`${derivedData}`, for a debug.json that works on
+    /// any machine.
+    #[arg(long)]
+    portable: bool,
+    /// `json` prints the configurations and resolved paths as one JSON
+    /// object instead of debug.json plus a summary.
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    Host,
+    Sim,
+    Device,
+}
+
+impl Target {
+    fn as_str(self) -> &'static str {
+        match self {
+            Target::Host => "host",
+            Target::Sim => "sim",
+            Target::Device => "device",
+        }
+    }
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -53,45 +122,277 @@ impl RequestKind {
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let program = fs::canonicalize(&args.program)?;
+    if args.undo {
+        let output = output_path(&args);
+        cli::restore_debug_json(&output)?;
+        println!("Restored {}", output.display());
+        return Ok(());
+    }
+    let program_arg = args
+        .program
+        .as_ref()
+        .expect("clap requires --program without --undo");
+    let (program, bundle_id) = resolve_program(program_arg)?;
     let cwd = args
         .cwd
         .clone()
         .or_else(|| program.parent().map(Path::to_path_buf))
         .unwrap_or_else(|| std::env::current_dir().unwrap());
 
-    let entry = AdapterConfig {
-        label: args.label.clone(),
+    let dsym = match &args.dsym {
+        Some(dsym) => Some(fs::canonicalize(dsym)?),
+        None => cli::adjacent_dsym(&fs::canonicalize(program_arg)?),
+    };
+
+    let mut entry = AdapterConfig {
+        label: args
+            .label
+            .clone()
+            .unwrap_or_else(|| cli::program_name(program_arg)),
         adapter: "ios-lldb".into(),
         request: args.request.as_str().into(),
         program: program.display().to_string(),
         cwd: cwd.display().to_string(),
         debugserver_port: args.port,
+        debugserver_host: None,
+        bundle_id: args.bundle_id.clone().or(bundle_id),
+        device: None,
+        simulator: None,
+        dsym_path: dsym.map(|dsym| dsym.display().to_string()),
+        args: args.arg.clone(),
+        env: args.env.iter().cloned().collect(),
+        extra: Default::default(),
     };
+    if args.portable {
+        cli::make_portable(&mut entry, &fs::canonicalize(std::env::current_dir()?)?);
+    }
+    let mut entries = configurations(&args, entry);
 
-    if args.write {
-        let output = args
-            .output
-            .clone()
-            .unwrap_or_else(|| PathBuf::from(".zed/debug.json"));
-        let mut json = load_debug_json(&output)?;
-        cli::upsert_configuration(&mut json.configurations, entry.clone());
-        save_debug_json(&output, &json)?;
-        println!(
-            "Updated {} with configuration \"{}\"",
-            output.display(),
-            entry.label
-        );
-    } else {
-        let mut json = DebugJson::default();
-        json.configurations.push(entry.clone());
-        println!("{}", serde_json::to_string_pretty(&json)?);
+    let output = args.write.then(|| output_path(&args));
+    if let Some(output) = &output {
+        let mut json = load_debug_json(output)?;
+        for entry in &mut entries {
+            if args.label.is_none() {
+                entry.label = json.unique_label(entry);
+            }
+            json.upsert(entry);
+        }
+        save_debug_json(output, &json)?;
+    }
+    if args.format == Format::Json {
+        let report = serde_json::json!({
+            "configurations": entries,
+            "debugJson": output,
+            "program": program,
+            "cwd": cwd,
+            "port": args.port,
+            "bundleId": entries[0].bundle_id,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    match &output {
+        Some(output) => {
+            for entry in &entries {
+                println!(
+                    "Updated {} with configuration \"{}\"",
+                    output.display(),
+                    entry.label
+                );
+            }
+        }
+        None => {
+            let mut json = DebugJson::default();
+            for entry in &entries {
+                json.upsert(entry);
+            }
+            println!("{}", json.as_str());
+        }
     }
 
+    let entry = &entries[0];
     println!("program : {}", entry.program);
     println!("cwd     : {}", entry.cwd);
     println!("adapter : {}", entry.adapter);
-    println!("request : {}", entry.request);
+    if entries.len() == 1 {
+        println!("request : {}", entry.request);
+    }
     println!("port    : {}", entry.debugserver_port);
+    if let Some(bundle_id) = &entry.bundle_id {
+        println!("bundle  : {bundle_id}");
+    }
+    if let Some(dsym) = &entry.dsym_path {
+        println!("dsym    : {dsym}");
+    }
     Ok(())
 }
+
+fn output_path(args: &Args) -> PathBuf {
+    args.output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(".zed/debug.json"))
+}
+
+/// The configurations to emit: `entry` for the first --target, or with
+/// --matrix a launch and an attach variant for each one.
+fn configurations(args: &Args, mut entry: AdapterConfig) -> Vec<AdapterConfig> {
+    if !args.matrix {
+        if args.label.is_none() {
+            let target = args.target.first().map_or("host", |target| target.as_str());
+            entry.label = cli::target_label(&entry.label, target, &entry.request);
+        }
+        return match args.target.first() {
+            Some(&target) => vec![for_target(args, &entry, target)],
+            None => vec![entry],
+        };
+    }
+    let targets = if args.target.is_empty() {
+        vec![Target::Host, Target::Sim, Target::Device]
+    } else {
+        args.target.clone()
+    };
+    targets
+        .into_iter()
+        .flat_map(|target| {
+            cli::request_variants(&for_target(args, &entry, target), target.as_str())
+        })
+        .collect()
+}
+
+/// `entry` pointed at `target`: bundle id lookups need a simulator or a
+/// device, and a host process has no bundle to look up.
+fn for_target(args: &Args, entry: &AdapterConfig, target: Target) -> AdapterConfig {
+    let mut entry = entry.clone();
+    match target {
+        Target::Host => entry.bundle_id = None,
+        Target::Sim => entry.simulator = Some(args.simulator.clone()),
+        Target::Device => entry.device = args.device.clone(),
+    }
+    entry
+}
+
+/// The Mach-O to debug for `program`, looking inside `.app` bundles, and
+/// the bundle identifier when there is one.
+fn resolve_program(program: &Path) -> anyhow::Result<(PathBuf, Option<String>)> {
+    let program = fs::canonicalize(program)?;
+    match resolve_app_bundle(&program)? {
+        Some(bundle) => {
+            let executable = fs::canonicalize(&bundle.executable).map_err(|err| {
+                anyhow::anyhow!(
+                    "{} has no executable at {}: {err}",
+                    program.display(),
+                    bundle.executable.display()
+                )
+            })?;
+            Ok((executable, bundle.bundle_id))
+        }
+        None => Ok((program, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleExecutable</key>
+    <string>MyAppBinary</string>
+    <key>CFBundleIdentifier</key>
+    <string>com.example.MyApp</string>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn app_bundles_resolve_to_their_executable() {
+        let dir = std::env::temp_dir().join(format!("gendebug-bundle-{}", std::process::id()));
+        let app = dir.join("MyApp.app");
+        fs::create_dir_all(&app).unwrap();
+        fs::write(app.join("Info.plist"), INFO_PLIST).unwrap();
+        fs::write(app.join("MyAppBinary"), b"").unwrap();
+
+        let (program, bundle_id) = resolve_program(&app).unwrap();
+        assert_eq!(program, fs::canonicalize(app.join("MyAppBinary")).unwrap());
+        assert_eq!(bundle_id.as_deref(), Some("com.example.MyApp"));
+
+        assert_eq!(cli::adjacent_dsym(&app), None);
+        fs::create_dir_all(dir.join("MyApp.app.dSYM")).unwrap();
+        assert_eq!(cli::adjacent_dsym(&app), Some(dir.join("MyApp.app.dSYM")));
+
+        let (program, bundle_id) = resolve_program(&app.join("MyAppBinary")).unwrap();
+        assert!(program.ends_with("MyApp.app/MyAppBinary"));
+        assert_eq!(bundle_id, None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn debuggee_args_and_env_are_repeatable() {
+        let args = Args::parse_from([
+            "ios-lldb-gendebug",
+            "--program",
+            "MyApp",
+            "--arg",
+            "-v",
+            "--arg",
+            "input.txt",
+            "--env",
+            "LOG=debug",
+        ]);
+        assert_eq!(args.arg, ["-v", "input.txt"]);
+        assert_eq!(args.env, [("LOG".to_string(), "debug".to_string())]);
+        assert!(
+            Args::try_parse_from(["ios-lldb-gendebug", "--program", "x", "--env", "LOG"]).is_err()
+        );
+    }
+
+    #[test]
+    fn matrix_labels_every_target_and_request() {
+        let args = Args::parse_from([
+            "ios-lldb-gendebug",
+            "--program",
+            "MyApp",
+            "--matrix",
+            "--device",
+            "00008120",
+        ]);
+        let entry = AdapterConfig {
+            label: "MyApp".into(),
+            adapter: "ios-lldb".into(),
+            request: "attach".into(),
+            program: "/build/MyApp".into(),
+            cwd: "/build".into(),
+            debugserver_port: 0,
+            debugserver_host: None,
+            bundle_id: Some("com.example.MyApp".into()),
+            device: None,
+            simulator: None,
+            dsym_path: None,
+            args: Vec::new(),
+            env: Default::default(),
+            extra: Default::default(),
+        };
+        let entries = configurations(&args, entry);
+        let labels: Vec<&str> = entries.iter().map(|entry| entry.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "MyApp (host, launch)",
+                "MyApp (host, attach)",
+                "MyApp (sim, launch)",
+                "MyApp (sim, attach)",
+                "MyApp (device, launch)",
+                "MyApp (device, attach)",
+            ]
+        );
+        assert_eq!(entries[1].request, "attach");
+        assert_eq!(entries[1].bundle_id, None);
+        assert_eq!(entries[3].simulator.as_deref(), Some("booted"));
+        assert_eq!(entries[5].device.as_deref(), Some("00008120"));
+        assert_eq!(entries[5].bundle_id.as_deref(), Some("com.example.MyApp"));
+    }
+}
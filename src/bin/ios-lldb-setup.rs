@@ -1,26 +1,52 @@
 use std::{
-    net::TcpListener,
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    io::{BufRead, BufReader, IsTerminal, Read, Write},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, ChildStdin, Command, Output, Stdio},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{bail, Context};
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use serde_json::Value;
+#[cfg(unix)]
+use swiftscope::usbmux;
+use swiftscope::{
+    app_logs::{self, LogStream},
+    app_process::{self, AppHost, RunningProcess},
+    crash_report,
+    iproxy::{IproxyCommand, Supervisor},
+    jsonc,
+    ports::PortReservation,
+    symbols::{self, SymbolContext, SymbolizedFrame},
+    variables::Variables,
+};
 
 #[path = "../cli.rs"]
 mod cli;
+#[path = "../validate.rs"]
+mod validate;
+#[path = "../vscode.rs"]
+mod vscode;
 
-use cli::{load_debug_json, save_debug_json, AdapterConfig};
+use cli::{load_debug_json, resolve_app_bundle, save_debug_json, AdapterConfig, DebugJson};
 
-#[derive(Debug, Parser)]
-#[command(about = "Drive Luxmentis/xcede + iproxy flows and emit Zed configs")]
+#[derive(Debug, Clone, Parser)]
+#[command(about = "Drive simctl, Luxmentis/xcede + usbmuxd flows and emit Zed configs")]
 struct Args {
+    #[command(subcommand)]
+    action: Option<Action>,
     #[arg(long, value_enum, default_value = "host")]
     mode: Mode,
     /// Path to the Xcode project/workspace root.
     #[arg(long, default_value = ".")]
     project: PathBuf,
-    /// Scheme to build (required for sim/device).
+    /// Scheme to build (required for sim/device). Found with `xcodebuild
+    /// -list` when left out: the only shared scheme of --project's
+    /// workspace or project, or one picked at a prompt.
     #[arg(long)]
     scheme: Option<String>,
     /// Binary to use for symbolication (host mode).
@@ -29,12 +55,22 @@ struct Args {
     /// CWD for the debuggee.
     #[arg(long)]
     cwd: Option<PathBuf>,
+    /// Argument for the debuggee on launch (pass multiple times).
+    #[arg(long = "arg", allow_hyphen_values = true)]
+    arg: Vec<String>,
+    /// `KEY=VALUE` environment variable for the debuggee on launch (pass
+    /// multiple times).
+    #[arg(long, value_parser = cli::parse_env_pair)]
+    env: Vec<(String, String)>,
     /// Override debugserver port.
     #[arg(long)]
     port: Option<u16>,
-    /// Gendebug label.
-    #[arg(long, default_value = "ios-lldb")]
-    label: String,
+    /// Label for the configuration; a configuration with the same label is
+    /// replaced. Defaults to `<app> (<mode>, <request>)`, numbered `#2`,
+    /// `#3`, ... if a configuration for another program or target has that
+    /// label already.
+    #[arg(long)]
+    label: Option<String>,
     /// Request type.
     #[arg(long, value_enum, default_value = "attach")]
     request: RequestKind,
@@ -44,21 +80,198 @@ struct Args {
     /// Write config to debug.json.
     #[arg(long)]
     write: bool,
+    /// Write paths under --project or DerivedData as
+    /// `${workspaceRoot}`/`${derivedData}`, for a debug.json that works on
+    /// any machine.
+    #[arg(long)]
+    portable: bool,
+    /// `json` prints the configurations, resolved paths, port and helper
+    /// pids as one JSON object on stdout, with progress on stderr.
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+    /// Emit both a launch and an attach configuration, labelled `<label>
+    /// (<mode>, <request>)`, instead of one for --request.
+    #[arg(long)]
+    matrix: bool,
     /// Path to `xcede` binary.
     #[arg(long, default_value = "xcede")]
     xcede: String,
     /// Additional xcede arguments (pass multiple times).
     #[arg(long)]
     xcede_arg: Vec<String>,
-    /// iproxy binary path (device mode).
+    /// How device mode forwards the local port to debugserver on the device.
+    #[arg(long, value_enum, default_value = "usbmux")]
+    forwarder: Forwarder,
+    /// Device mode over Wi-Fi: forward the local port to a network-paired
+    /// device, which usbmuxd reaches over the network, instead of a USB one.
+    #[arg(long)]
+    network: bool,
+    /// iproxy binary path (`--forwarder iproxy`).
     #[arg(long, default_value = "iproxy")]
     iproxy: String,
     /// Remote device port for debugserver (device mode).
     #[arg(long, default_value_t = 2331)]
     device_port: u16,
-    /// Keep the helper process alive awaiting Enter key (useful for iproxy).
+    /// Keep the spawned debugserver running until Enter is pressed (sim mode
+    /// and device mode with --start-debugserver).
     #[arg(long)]
     wait: bool,
+    /// Print the app's logs (`simctl log stream` or `idevicesyslog`) until
+    /// Enter is pressed; implies --wait. Device mode needs the UDID.
+    #[arg(long)]
+    logs: bool,
+    /// Built .app to install and debug: on a simulator via simctl (sim mode;
+    /// without it sim mode defers to xcede) or on the device with
+    /// --start-debugserver.
+    #[arg(long)]
+    app: Option<PathBuf>,
+    /// Simulator UDID or name to boot (sim mode).
+    #[arg(long, default_value = "booted")]
+    simulator: String,
+    /// Bundle identifier to launch; read from the app's Info.plist by default.
+    #[arg(long)]
+    bundle_id: Option<String>,
+    /// debugserver binary; defaults to the one in the selected Xcode.
+    #[arg(long)]
+    debugserver: Option<PathBuf>,
+    /// Path to `xcrun`.
+    #[arg(long, default_value = "xcrun")]
+    xcrun: String,
+    /// Start debugserver on the device through devicectl, attached to the
+    /// app launched suspended, instead of expecting one to be listening
+    /// already (device mode).
+    #[arg(long)]
+    start_debugserver: bool,
+    /// Device UDID or name for devicectl (device mode with
+    /// --start-debugserver); see `list-devices`.
+    #[arg(long)]
+    device: Option<String>,
+    /// ios-llm-devicectl binary; defaults to the one next to this executable.
+    #[arg(long)]
+    devicectl_bridge: Option<PathBuf>,
+    /// Build --scheme with xcodebuild first and debug the product it reports,
+    /// instead of relying on xcede or --app/--program.
+    #[arg(long)]
+    build: bool,
+    /// xcodebuild -destination; derived from the mode, --simulator and
+    /// --device by default.
+    #[arg(long)]
+    destination: Option<String>,
+    /// xcodebuild -configuration.
+    #[arg(long, default_value = "Debug")]
+    configuration: String,
+    /// dSYM bundle recorded as `dsymPath`, which the adapter prefers over
+    /// the binary's own DWARF; defaults to the one the build produced or
+    /// the one next to --app/--program.
+    #[arg(long)]
+    dsym: Option<PathBuf>,
+    /// DerivedData to search for --scheme's last build when host mode has
+    /// no --program or sim mode no --app; defaults to Xcode's.
+    #[arg(long)]
+    derived_data: Option<PathBuf>,
+    /// Stay running and, each time the app binary is rebuilt, rewrite its
+    /// configuration and restart the helpers (port forwards, debugserver,
+    /// the simulator app) for the new build.
+    #[arg(long, conflicts_with = "build")]
+    watch: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Action {
+    /// List connected iOS devices to pick a `--mode device` target from.
+    ListDevices {
+        /// Print JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List available simulators, whose UDIDs `--simulator` accepts.
+    ListSimulators {
+        /// Only runtimes containing this text, e.g. `iOS 17` or `iOS-17-2`.
+        #[arg(long)]
+        runtime: Option<String>,
+        /// Only simulators whose name contains this text (case-insensitive).
+        #[arg(long)]
+        name: Option<String>,
+        /// Only booted simulators.
+        #[arg(long)]
+        booted: bool,
+        /// Print JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the tools a debugging session needs (Xcode, debugserver,
+    /// usbmuxd, simulator runtimes, the adapter, paired devices) and, given
+    /// an app or binary, that it is signed so debugserver can attach.
+    Doctor {
+        /// App bundle or Mach-O to check; defaults to --app, then --program.
+        target: Option<PathBuf>,
+    },
+    /// Check debug.json's ios-lldb configurations (programs, ports, request
+    /// kinds) and print each problem at its line and column.
+    Validate {
+        /// debug.json to check; defaults to --output, then .zed/debug.json.
+        path: Option<PathBuf>,
+    },
+    /// Convert the lldb entries of a VS Code launch.json into ios-lldb
+    /// configurations in debug.json (--output, default .zed/debug.json).
+    ImportVscode {
+        #[arg(default_value = ".vscode/launch.json")]
+        launch_json: PathBuf,
+    },
+    /// Write debug.json's ios-lldb configurations (--output, default
+    /// .zed/debug.json) into a VS Code launch.json as CodeLLDB entries.
+    ExportVscode {
+        #[arg(default_value = ".vscode/launch.json")]
+        launch_json: PathBuf,
+    },
+    /// Restore debug.json (--output, default .zed/debug.json) to the version
+    /// before the last write.
+    Undo,
+    /// Attach to a process already running on this Mac (host mode) or in a
+    /// simulator (sim mode): start debugserver attached to it and emit an
+    /// attach configuration, skipping builds and installs.
+    Attach {
+        #[arg(long, required_unless_present = "name", conflicts_with = "name")]
+        pid: Option<u32>,
+        /// Executable name, e.g. `MyApp`; must match one process.
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Symbolicate a crash report (`.ips` or `.crash`) with local symbols:
+    /// --program/--app when their UUID matches, --dsym (a dSYM, archive or
+    /// directory of dSYMs) and the dSYMs Spotlight knows.
+    Symbolicate { report: PathBuf },
+    /// Run a configuration from debug.json through the debug adapter
+    /// without Zed (initialize, launch or attach, threads, stackTrace,
+    /// disconnect) and report each step.
+    Smoke {
+        /// debug.json to read; defaults to --output, then .zed/debug.json.
+        path: Option<PathBuf>,
+        /// Label of the configuration to run; defaults to the first ios-lldb
+        /// one.
+        #[arg(long)]
+        config: Option<String>,
+        /// Seconds to wait for each response.
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+enum Forwarder {
+    /// Talk to usbmuxd directly from this process.
+    Usbmux,
+    /// Spawn libimobiledevice's iproxy.
+    Iproxy,
+    /// iOS 17 and later: bring up devicectl's CoreDevice tunnel, launch the
+    /// app stopped and run debugserver over it (as --start-debugserver).
+    Coredevice,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -84,11 +297,121 @@ impl RequestKind {
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    match args.mode {
-        Mode::Host => host_flow(&args),
-        Mode::Sim => sim_flow(&args),
-        Mode::Device => device_flow(&args),
+    let mut args = Args::parse();
+    match &args.action {
+        Some(Action::ListDevices { json }) => return list_devices(&args, *json),
+        Some(Action::ListSimulators {
+            runtime,
+            name,
+            booted,
+            json,
+        }) => {
+            let filter = SimulatorFilter {
+                runtime: runtime.clone(),
+                name: name.clone(),
+                booted: *booted,
+            };
+            return list_simulators(&args, &filter, *json);
+        }
+        Some(Action::Doctor { target }) => {
+            let target = target
+                .as_ref()
+                .or(args.app.as_ref())
+                .or(args.program.as_ref());
+            return doctor(&args, target.map(PathBuf::as_path));
+        }
+        Some(Action::Validate { path }) => {
+            let path = path
+                .clone()
+                .or_else(|| args.output.clone())
+                .unwrap_or_else(|| PathBuf::from(".zed/debug.json"));
+            return validate_debug_json(&args, &path);
+        }
+        Some(Action::ImportVscode { launch_json }) => return import_vscode(&args, launch_json),
+        Some(Action::ExportVscode { launch_json }) => return export_vscode(&args, launch_json),
+        Some(Action::Attach { pid, name }) => return attach_flow(&args, *pid, name.as_deref()),
+        Some(Action::Symbolicate { report }) => return symbolicate(&args, report),
+        Some(Action::Smoke {
+            path,
+            config,
+            timeout,
+        }) => {
+            let path = path.clone().unwrap_or_else(|| debug_json_path(&args));
+            return smoke(
+                &args,
+                &path,
+                config.as_deref(),
+                Duration::from_secs(*timeout),
+            );
+        }
+        Some(Action::Undo) => {
+            let path = debug_json_path(&args);
+            cli::restore_debug_json(&path)?;
+            println!("Restored {}", path.display());
+            return Ok(());
+        }
+        None => {}
+    }
+    if args.scheme.is_none() && (args.build || args.program.is_none() && args.app.is_none()) {
+        if let Some(container) = xcode_container(&args.project) {
+            args.scheme = Some(discover_scheme(&args, &container)?);
+        }
+    }
+    let base = args;
+    loop {
+        let mut args = base.clone();
+        find_product(&mut args)?;
+        match args.mode {
+            Mode::Host => host_flow(&args),
+            Mode::Sim => sim_flow(&args),
+            Mode::Device => device_flow(&args),
+        }?;
+        if !args.watch {
+            return Ok(());
+        }
+    }
+}
+
+/// Fills in --app and --program from --build or DerivedData; under
+/// --watch this runs again after every rebuild.
+fn find_product(args: &mut Args) -> anyhow::Result<()> {
+    if args.build {
+        let product = xcodebuild(args)?;
+        say(args, format_args!("Built {}", product.app.display()));
+        if let Some(dsym) = product.dsym {
+            say(args, format_args!("dSYM : {}", dsym.display()));
+            args.dsym.get_or_insert(dsym);
+        }
+        args.app.get_or_insert(product.app);
+        args.program.get_or_insert(product.executable);
+    } else if args.program.is_none() && args.app.is_none() {
+        if let Some(product) = derived_data_product(args) {
+            say(
+                args,
+                format_args!("Using {} from DerivedData", product.app.display()),
+            );
+            args.app = Some(product.app);
+            args.program = Some(product.executable);
+            args.dsym = args.dsym.take().or(product.dsym);
+        }
+    }
+    Ok(())
+}
+
+/// Progress for people: stdout, or stderr with `--format json` so that
+/// stdout carries only the report.
+fn say(args: &Args, message: std::fmt::Arguments) {
+    match args.format {
+        Format::Text => println!("{message}"),
+        Format::Json => eprintln!("{message}"),
+    }
+}
+
+/// Where spawned helpers may write: stderr with `--format json`.
+fn helper_stdout(args: &Args) -> Stdio {
+    match args.format {
+        Format::Text => Stdio::inherit(),
+        Format::Json => Stdio::from(std::io::stderr()),
     }
 }
 
@@ -96,7 +419,7 @@ fn host_flow(args: &Args) -> anyhow::Result<()> {
     let program = args
         .program
         .as_ref()
-        .context("--program is required in host mode")?;
+        .context("--program is required in host mode (or --scheme with a build in DerivedData)")?;
     let program = dunce::canonicalize(program)?;
     let cwd = args
         .cwd
@@ -104,10 +427,17 @@ fn host_flow(args: &Args) -> anyhow::Result<()> {
         .unwrap_or_else(|| program.parent().unwrap().to_path_buf());
     let port = args.port.unwrap_or(0);
 
-    emit_config(args, &program, &cwd, port)
+    emit_config(args, &program, &cwd, (None, port), &[])?;
+    if args.watch {
+        wait_for_rebuild(args, &program)?;
+    }
+    Ok(())
 }
 
 fn sim_flow(args: &Args) -> anyhow::Result<()> {
+    if let Some(app) = &args.app {
+        return simctl_flow(args, app);
+    }
     let info = run_xcede(args)?;
     let program = info
         .app_binary
@@ -115,68 +445,1652 @@ fn sim_flow(args: &Args) -> anyhow::Result<()> {
         .context("xcede output missing app_binary; pass --program manually")?;
     let cwd = args.cwd.clone().unwrap_or_else(|| args.project.clone());
     let port = args.port.or(info.debugserver_port).unwrap_or(0);
-    emit_config(args, &program, &cwd, port)
+    emit_config(args, &program, &cwd, (None, port), &[])?;
+    if args.watch {
+        wait_for_rebuild(args, &program)?;
+    }
+    Ok(())
 }
 
-fn device_flow(args: &Args) -> anyhow::Result<()> {
-    let local_port = args.port.unwrap_or(23456);
-    ensure_port_free(local_port)?;
-    let info = run_xcede(args)?;
-    let program = info
-        .app_binary
+/// Boots the simulator, installs and launches the app suspended until a
+/// debugger attaches, then leaves debugserver attached to it and listening.
+fn simctl_flow(args: &Args, app: &Path) -> anyhow::Result<()> {
+    let (app, program, bundle_id) = app_bundle(args, app)?;
+
+    boot_simulator(args)?;
+    let install = simctl(
+        args,
+        &["install", &args.simulator, &app.display().to_string()],
+    )?;
+    ensure_success("simctl install", &install)?;
+    let launch = simctl(
+        args,
+        &["launch", "--wait-for-debugger", &args.simulator, &bundle_id],
+    )?;
+    ensure_success("simctl launch", &launch)?;
+    let stdout = String::from_utf8_lossy(&launch.stdout);
+    let pid = parse_launch_pid(&stdout)
+        .with_context(|| format!("simctl launch printed no pid: {}", stdout.trim()))?;
+    say(
+        args,
+        format_args!("Launched {bundle_id} (pid {pid}) waiting for the debugger"),
+    );
+
+    let (mut server, port) = attach_debugserver(args, pid)?;
+
+    let cwd = args
+        .cwd
         .clone()
-        .context("xcede output missing app_binary; pass --program manually")?;
-    let cwd = args.cwd.clone().unwrap_or_else(|| args.project.clone());
-    let remote_port = info.debugserver_port.unwrap_or(args.device_port);
+        .unwrap_or_else(|| app.parent().unwrap().to_path_buf());
+    let logs = start_logs(args, &program, AppHost::Simulator(args.simulator.clone()))?;
+    let mut helpers = vec![("app", pid), ("debugserver", server.id())];
+    helpers.extend(logs.as_ref().map(|logs| ("logs", logs.pid())));
+    let result = emit_config(args, &program, &cwd, (None, port), &helpers);
+    if args.wait || args.logs || args.watch {
+        hold(args, &program, "Press Enter to stop debugserver.")?;
+        drop(logs);
+        let _ = server.kill();
+    }
+    result
+}
 
-    let mut iproxy = Command::new(&args.iproxy)
-        .arg(local_port.to_string())
-        .arg(remote_port.to_string())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
+/// Starts debugserver on this Mac attached to `pid` (a host or simulator
+/// process), listening on --port or a free one.
+fn attach_debugserver(args: &Args, pid: u32) -> anyhow::Result<(Child, u16)> {
+    let reservation = PortReservation::reserve(args.port.unwrap_or(0))?;
+    let debugserver = match &args.debugserver {
+        Some(path) => path.clone(),
+        None => xcode_debugserver(args)?,
+    };
+    let port = reservation.release();
+    let server = Command::new(&debugserver)
+        .arg(format!("127.0.0.1:{port}"))
+        .arg(format!("--attach={pid}"))
+        .stdin(Stdio::null())
+        .stdout(helper_stdout(args))
         .spawn()
-        .context("failed to spawn iproxy")?;
-    println!(
-        "iproxy started on port {local_port} -> device {remote_port}. Press Ctrl+C to terminate."
+        .with_context(|| format!("failed to spawn {}", debugserver.display()))?;
+    say(
+        args,
+        format_args!(
+            "debugserver (pid {}) attached and listening on port {port}",
+            server.id()
+        ),
     );
+    Ok((server, port))
+}
 
-    let result = emit_config(args, &program, &cwd, local_port);
-    if args.wait {
-        let mut input = String::new();
-        let _ = std::io::stdin().read_line(&mut input);
+/// Attaches to a process already running on this Mac (host mode) or in a
+/// simulator (sim mode), without building or installing anything.
+fn attach_flow(args: &Args, pid: Option<u32>, name: Option<&str>) -> anyhow::Result<()> {
+    if matches!(args.mode, Mode::Device) {
+        bail!("attach finds host and simulator processes; use --mode host or --mode sim");
     }
-    let _ = iproxy.kill();
+    let in_simulator = matches!(args.mode, Mode::Sim);
+    let host = in_simulator.then(|| AppHost::Simulator(args.simulator.clone()));
+    let processes = app_process::running_processes(host.as_ref()).map_err(anyhow::Error::msg)?;
+    let process = find_process(&processes, pid, name, in_simulator)?;
+    let path = Path::new(&process.path);
+    say(
+        args,
+        format_args!("Attaching to {} (pid {})", process.path, process.pid),
+    );
+
+    let mut args = args.clone();
+    args.request = RequestKind::Attach;
+    let bundle = path
+        .parent()
+        .filter(|dir| dir.extension().is_some_and(|ext| ext == "app"));
+    if let Some(bundle) = bundle {
+        args.app.get_or_insert_with(|| bundle.to_path_buf());
+        if args.bundle_id.is_none() {
+            args.bundle_id = resolve_app_bundle(bundle)?.and_then(|bundle| bundle.bundle_id);
+        }
+    }
+    let (mut server, port) = attach_debugserver(&args, process.pid)?;
+    let cwd = args
+        .cwd
+        .clone()
+        .unwrap_or_else(|| path.parent().unwrap().to_path_buf());
+    let helpers = [("debugserver", server.id())];
+    let result = emit_config(&args, path, &cwd, (None, port), &helpers);
+    hold(&args, path, "Press Enter to stop debugserver.")?;
+    let _ = server.kill();
     result
 }
 
-fn emit_config(args: &Args, program: &Path, cwd: &Path, port: u16) -> anyhow::Result<()> {
-    let entry = AdapterConfig {
-        label: args.label.clone(),
+/// The process with `pid`, or the only one whose executable is called
+/// `name`: among simulator processes when `in_simulator`, else among the
+/// Mac's own.
+fn find_process<'a>(
+    processes: &'a [RunningProcess],
+    pid: Option<u32>,
+    name: Option<&str>,
+    in_simulator: bool,
+) -> anyhow::Result<&'a RunningProcess> {
+    if let Some(pid) = pid {
+        return processes
+            .iter()
+            .find(|process| process.pid == pid)
+            .with_context(|| format!("no process with pid {pid}"));
+    }
+    let name = name.context("pass --pid or --name")?;
+    let matching: Vec<&RunningProcess> = processes
+        .iter()
+        .filter(|process| process.path.rsplit('/').next() == Some(name))
+        .filter(|process| app_process::runs_in_simulator(process, "booted") == in_simulator)
+        .collect();
+    let place = if in_simulator {
+        "in a simulator"
+    } else {
+        "on this Mac"
+    };
+    match matching.as_slice() {
+        [] => bail!("no process named {name} is running {place}"),
+        [process] => Ok(process),
+        several => bail!(
+            "several processes named {name} are running {place} (pids {}); pick one with --pid",
+            several
+                .iter()
+                .map(|process| process.pid.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Keeps a flow's helpers running: until Enter is pressed or, with
+/// --watch, until `program` is rebuilt.
+fn hold(args: &Args, program: &Path, prompt: &str) -> anyhow::Result<()> {
+    if args.watch {
+        return wait_for_rebuild(args, program);
+    }
+    say(args, format_args!("{prompt}"));
+    let mut input = String::new();
+    let _ = std::io::stdin().read_line(&mut input);
+    Ok(())
+}
+
+/// Blocks until `program` has been rewritten and then left alone for
+/// `WATCH_SETTLE`, so a binary the linker is still writing isn't picked up.
+fn wait_for_rebuild(args: &Args, program: &Path) -> anyhow::Result<()> {
+    say(
+        args,
+        format_args!(
+            "Ready to debug {}. Watching {} for rebuilds (Ctrl-C to stop).",
+            cli::program_name(args.app.as_deref().unwrap_or(program)),
+            program.display()
+        ),
+    );
+    let modified = || fs::metadata(program).and_then(|meta| meta.modified()).ok();
+    let mut watch = RebuildWatch::new(modified());
+    while !watch.poll(modified(), Instant::now()) {
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+    say(
+        args,
+        format_args!("{} was rebuilt; refreshing.", program.display()),
+    );
+    Ok(())
+}
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+const WATCH_SETTLE: Duration = Duration::from_secs(1);
+
+/// Tracks a binary's modification time across polls.
+struct RebuildWatch {
+    built: Option<SystemTime>,
+    seen: Option<SystemTime>,
+    since: Instant,
+}
+
+impl RebuildWatch {
+    fn new(built: Option<SystemTime>) -> Self {
+        Self {
+            built,
+            seen: built,
+            since: Instant::now(),
+        }
+    }
+
+    /// Whether a new build is complete, given the binary's modification
+    /// time (`None` while it is missing) at `now`.
+    fn poll(&mut self, modified: Option<SystemTime>, now: Instant) -> bool {
+        if modified != self.seen {
+            self.seen = modified;
+            self.since = now;
+            return false;
+        }
+        modified.is_some() && modified != self.built && now - self.since >= WATCH_SETTLE
+    }
+}
+
+/// Tails `program`'s logs on `host` to stdout when `--logs` is set.
+fn start_logs(args: &Args, program: &Path, host: AppHost) -> anyhow::Result<Option<LogStream>> {
+    if !args.logs {
+        return Ok(None);
+    }
+    let process = program
+        .file_name()
+        .context("--logs needs the app's executable")?
+        .to_string_lossy();
+    let command = app_logs::log_command(&process, &host);
+    let format = args.format;
+    let stream = LogStream::spawn(command, move |line| match format {
+        Format::Text => println!("{line}"),
+        Format::Json => eprintln!("{line}"),
+    })
+    .map_err(anyhow::Error::msg)?;
+    say(args, format_args!("Streaming logs from {process}."));
+    Ok(Some(stream))
+}
+
+/// The canonical bundle path, its executable and bundle identifier
+/// (`--bundle-id` overrides Info.plist).
+fn app_bundle(args: &Args, app: &Path) -> anyhow::Result<(PathBuf, PathBuf, String)> {
+    let app = dunce::canonicalize(app).with_context(|| format!("no app at {}", app.display()))?;
+    let bundle = resolve_app_bundle(&app)?
+        .with_context(|| format!("{} has no Info.plist", app.display()))?;
+    let bundle_id = args
+        .bundle_id
+        .clone()
+        .or(bundle.bundle_id)
+        .context("Info.plist has no CFBundleIdentifier; pass --bundle-id")?;
+    Ok((app, bundle.executable, bundle_id))
+}
+
+fn boot_simulator(args: &Args) -> anyhow::Result<()> {
+    if args.simulator == "booted" {
+        return Ok(());
+    }
+    let output = simctl(args, &["boot", &args.simulator])?;
+    if output.status.success() || is_already_booted(&String::from_utf8_lossy(&output.stderr)) {
+        return Ok(());
+    }
+    ensure_success("simctl boot", &output)
+}
+
+fn simctl(args: &Args, simctl_args: &[&str]) -> anyhow::Result<Output> {
+    Command::new(&args.xcrun)
+        .arg("simctl")
+        .args(simctl_args)
+        .output()
+        .with_context(|| format!("failed to run {} simctl", args.xcrun))
+}
+
+fn ensure_success(what: &str, output: &Output) -> anyhow::Result<()> {
+    if !output.status.success() {
+        bail!(
+            "{what} failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// One `doctor` finding: what was found, or what is wrong and how to fix it.
+struct Check {
+    name: &'static str,
+    outcome: Result<String, String>,
+    hint: &'static str,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: anyhow::Result<String>, hint: &'static str) -> Self {
+        Self {
+            name,
+            outcome: outcome.map_err(|err| format!("{err:#}")),
+            hint,
+        }
+    }
+}
+
+/// Where usbmuxd listens, for `doctor`.
+fn usbmuxd_socket() -> anyhow::Result<String> {
+    #[cfg(unix)]
+    {
+        let socket = usbmux::socket_path();
+        if !socket.exists() {
+            bail!("no {} socket", socket.display());
+        }
+        Ok(format!("listening at {}", socket.display()))
+    }
+    #[cfg(not(unix))]
+    bail!("usbmuxd is only reachable on macOS and Linux")
+}
+
+fn doctor(args: &Args, target: Option<&Path>) -> anyhow::Result<()> {
+    let mut checks = vec![
+        Check::new(
+            "Xcode command-line tools",
+            command_stdout("xcode-select", &["-p"]),
+            "install them with `xcode-select --install`",
+        ),
+        Check::new(
+            "debugserver",
+            match &args.debugserver {
+                Some(path) if path.exists() => Ok(path.display().to_string()),
+                Some(path) => Err(anyhow::anyhow!("{} does not exist", path.display())),
+                None => xcode_debugserver(args).map(|path| path.display().to_string()),
+            },
+            "install Xcode and select it with `sudo xcode-select -s /Applications/Xcode.app`",
+        ),
+        Check::new(
+            "debug adapter",
+            debug_adapter()
+                .map(|path| path.display().to_string())
+                .context("`ios-lldb` is not on PATH and IOS_LLDB_DAP_PATH is unset"),
+            "build it with `cargo build --features cli --bin swiftscope` and link it \
+             onto PATH as `ios-lldb`",
+        ),
+    ];
+    // Only what the chosen mode needs: a host session has no use for
+    // simulator runtimes, and only a device needs usbmuxd or a signature.
+    match args.mode {
+        Mode::Host => {}
+        Mode::Sim => checks.push(Check::new(
+            "simulator runtimes",
+            simulator_runtimes(args).and_then(|runtimes| {
+                if runtimes.is_empty() {
+                    bail!("none installed");
+                }
+                Ok(runtimes.join(", "))
+            }),
+            "install one from Xcode > Settings > Platforms",
+        )),
+        Mode::Device => {
+            checks.push(Check::new(
+                "usbmuxd",
+                usbmuxd_socket(),
+                "usbmuxd ships with macOS; on Linux install and start usbmuxd",
+            ));
+            checks.push(Check::new(
+                "paired devices",
+                devicectl_devices(args)
+                    .or_else(|_| idevice_devices())
+                    .and_then(|devices| {
+                        if devices.is_empty() {
+                            bail!("none connected");
+                        }
+                        Ok(devices
+                            .iter()
+                            .map(|device| device.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "))
+                    }),
+                "connect a device, trust this Mac and enable Developer Mode",
+            ));
+            if args.forwarder == Forwarder::Iproxy {
+                checks.push(Check::new(
+                    "iproxy",
+                    find_on_path(&args.iproxy)
+                        .map(|path| path.display().to_string())
+                        .with_context(|| format!("`{}` is not on PATH", args.iproxy)),
+                    "install libimobiledevice (`brew install libimobiledevice`)",
+                ));
+            }
+            if let Some(target) = target {
+                checks.push(Check::new(
+                    "code signature",
+                    codesign_problems(target).and_then(|problems| match problems.as_slice() {
+                        [] => Ok(format!("{} has get-task-allow", target.display())),
+                        _ => bail!("{}: {}", target.display(), problems.join("; ")),
+                    }),
+                    "debugserver cannot attach without a valid signature and get-task-allow",
+                ));
+            }
+        }
+    }
+
+    print!("{}", format_checks(&checks));
+    let failures = checks.iter().filter(|check| check.outcome.is_err()).count();
+    if failures > 0 {
+        bail!("{failures} check(s) failed");
+    }
+    Ok(())
+}
+
+fn validate_debug_json(args: &Args, path: &Path) -> anyhow::Result<()> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let findings = validate::validate(&source, &args.project);
+    for finding in &findings {
+        println!("{}:{finding}", path.display());
+    }
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == validate::Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("{errors} error(s) in {}", path.display());
+    }
+    println!("{} is valid", path.display());
+    Ok(())
+}
+
+fn symbolicate(args: &Args, path: &Path) -> anyhow::Result<()> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let report = crash_report::parse(&text)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    let program = match (&args.program, &args.app) {
+        (Some(program), _) => Some(program.clone()),
+        (None, Some(app)) => resolve_app_bundle(app)?.map(|bundle| bundle.executable),
+        (None, None) => None,
+    };
+    let mut contexts: HashMap<usize, Option<SymbolContext>> = HashMap::new();
+    let (mut symbolicated, mut total) = (0, 0);
+    let mut missing = BTreeSet::new();
+    let out = report.symbolicated(|image, frame| {
+        total += 1;
+        let index = frame
+            .image
+            .expect("symbolicated only asks about known images");
+        let ctx = contexts
+            .entry(index)
+            .or_insert_with(|| image_symbols(args, program.as_deref(), image));
+        let lines = ctx
+            .as_ref()
+            .map(|ctx| describe_frame(ctx, frame))
+            .unwrap_or_default();
+        if lines.is_empty() {
+            missing.insert(image.name.clone());
+        } else {
+            symbolicated += 1;
+        }
+        lines
+    });
+    print!("{out}");
+    eprintln!("Symbolicated {symbolicated} of {total} frames.");
+    if !missing.is_empty() {
+        eprintln!(
+            "No symbols for: {}",
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Symbols for a crashed process's image, slid to where it was loaded:
+/// the local binary or dSYM whose UUID matches the report's.
+fn image_symbols(
+    args: &Args,
+    program: Option<&Path>,
+    image: &crash_report::BinaryImage,
+) -> Option<SymbolContext> {
+    let uuid = image.uuid?;
+    let binary = program
+        .into_iter()
+        .map(Path::to_path_buf)
+        .chain(args.app.iter().flat_map(|app| {
+            let name = &image.name;
+            [
+                app.join(name),
+                app.join(format!("Frameworks/{name}.framework/{name}")),
+                app.join("Frameworks").join(name),
+            ]
+        }))
+        .find(|path| symbols::file_uuid(path) == Some(uuid));
+    let dwarf = args
+        .dsym
+        .as_deref()
+        .and_then(|dsym| symbols::find_dsym_dwarf(dsym, &uuid));
+    let file = binary
+        .or_else(|| dwarf.clone())
+        .or_else(|| symbols::spotlight_dsym_dwarf(&uuid))?;
+    match SymbolContext::with_dsym(&file, dwarf.as_deref()) {
+        Ok(mut ctx) => {
+            let slide = image.load_address.wrapping_sub(ctx.main.vmaddr_text) as i64;
+            ctx.set_slide(slide);
+            Some(ctx)
+        }
+        Err(err) => {
+            eprintln!("ignoring {}: {err:#}", file.display());
+            None
+        }
+    }
+}
+
+/// `function + offset (file:line)` for `frame`, preceded by the calls
+/// inlined into it; empty when `ctx` doesn't know the address.
+fn describe_frame(ctx: &SymbolContext, frame: &crash_report::CrashFrame) -> Vec<String> {
+    let pc = frame.address - u64::from(frame.is_return);
+    let Some((function, offset)) = ctx
+        .function_at(pc)
+        .map(|function| (function.name, frame.address - function.start))
+        .or_else(|| {
+            ctx.symbol_for_address(pc)
+                .map(|(name, offset)| (name, offset + u64::from(frame.is_return)))
+        })
+    else {
+        return Vec::new();
+    };
+    let location = |symbolized: &SymbolizedFrame| {
+        let file = symbolized.file.as_deref()?;
+        let file = Path::new(file).file_name()?.to_string_lossy();
+        Some(match symbolized.line {
+            Some(line) => format!(" ({file}:{line})"),
+            None => format!(" ({file})"),
+        })
+    };
+    let inline_chain = ctx.cached_frames(pc);
+    let Some((outermost, inlined)) = inline_chain.split_last() else {
+        return vec![format!("{function} + {offset}")];
+    };
+    inlined
+        .iter()
+        .map(|symbolized| {
+            format!(
+                "{} [inlined]{}",
+                symbolized.function.as_deref().unwrap_or("???"),
+                location(symbolized).unwrap_or_default()
+            )
+        })
+        .chain([format!(
+            "{function} + {offset}{}",
+            location(outermost).unwrap_or_default()
+        )])
+        .collect()
+}
+
+/// The adapter Zed would run: IOS_LLDB_DAP_PATH, `ios-lldb` on PATH, or
+/// swiftscope from the same build as this binary.
+fn debug_adapter() -> Option<PathBuf> {
+    env::var_os("IOS_LLDB_DAP_PATH")
+        .map(PathBuf::from)
+        .or_else(|| find_on_path("ios-lldb"))
+        .or_else(|| {
+            let exe = env::current_exe().ok()?.with_file_name("swiftscope");
+            exe.is_file().then_some(exe)
+        })
+}
+
+fn smoke(args: &Args, path: &Path, label: Option<&str>, timeout: Duration) -> anyhow::Result<()> {
+    let config = configurations(&load_debug_json(path)?)
+        .into_iter()
+        .filter(|config| swiftscope::ADAPTER_NAMES.contains(&config.adapter.as_str()))
+        .find(|config| label.is_none_or(|label| config.label == label))
+        .with_context(|| match label {
+            Some(label) => format!(
+                "no ios-lldb configuration labelled {label:?} in {}",
+                path.display()
+            ),
+            None => format!("no ios-lldb configuration in {}", path.display()),
+        })?;
+    let request = match config.request.as_str() {
+        "launch" => "launch",
+        "attach" => "attach",
+        other => bail!(
+            "{}: request must be \"launch\" or \"attach\", not {other:?}",
+            config.label
+        ),
+    };
+    let project = dunce::canonicalize(&args.project).unwrap_or_else(|_| args.project.clone());
+    let mut arguments = serde_json::to_value(&config)?;
+    Variables {
+        workspace_root: Some(project.clone()),
+        ..Variables::from_env()
+    }
+    .expand(&mut arguments)
+    .map_err(anyhow::Error::msg)?;
+
+    let adapter = debug_adapter()
+        .context("no debug adapter: set IOS_LLDB_DAP_PATH or put `ios-lldb` on PATH")?;
+    say(
+        args,
+        format_args!("Running {:?} with {}", config.label, adapter.display()),
+    );
+    let mut child = Command::new(&adapter)
+        .env(swiftscope::variables::WORKSPACE_ROOT_ENV_VAR, &project)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::from(std::io::stderr()))
+        .spawn()
+        .with_context(|| format!("failed to spawn {}", adapter.display()))?;
+    let mut client = DapClient {
+        stdin: child.stdin.take().context("adapter stdin not captured")?,
+        messages: spawn_dap_reader(child.stdout.take().context("adapter stdout not captured")?),
+        seq: 0,
+        timeout,
+    };
+    let checks = smoke_steps(&mut client, request, &arguments);
+    drop(client);
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+
+    print!("{}", format_checks(&checks));
+    let failures = checks.iter().filter(|check| check.outcome.is_err()).count();
+    if failures > 0 {
+        bail!("smoke test of {:?} failed", config.label);
+    }
+    println!("{:?} works", config.label);
+    Ok(())
+}
+
+/// The smoke test's requests, stopping at the first failure (after trying
+/// to disconnect, so a launched debuggee doesn't linger).
+fn smoke_steps(client: &mut DapClient, request: &'static str, arguments: &Value) -> Vec<Check> {
+    let mut checks = vec![Check::new(
+        "initialize",
+        client
+            .request(
+                "initialize",
+                serde_json::json!({ "adapterID": "ios-lldb", "clientID": "ios-lldb-setup" }),
+            )
+            .map(|_| "adapter responded".into()),
+        "run the adapter by hand to see why it fails to start",
+    )];
+    if checks[0].outcome.is_err() {
+        return checks;
+    }
+    let started = client.request(request, arguments.clone());
+    let started_ok = started.is_ok();
+    checks.push(Check::new(
+        request,
+        started.map(|_| {
+            format!(
+                "{} on port {}",
+                arguments["program"].as_str().unwrap_or("?"),
+                arguments["debugserverPort"]
+            )
+        }),
+        "check that debugserver is listening on debugserverPort (`ios-lldb-setup doctor`)",
+    ));
+    if started_ok {
+        let threads = client
+            .request("threads", serde_json::json!({}))
+            .and_then(|body| {
+                let threads = body["threads"].as_array().cloned().unwrap_or_default();
+                let first = threads
+                    .first()
+                    .and_then(|thread| thread["id"].as_i64())
+                    .context("no threads reported")?;
+                Ok((first, threads.len()))
+            });
+        let thread = threads.as_ref().ok().map(|(first, _)| *first);
+        checks.push(Check::new(
+            "threads",
+            threads.map(|(_, count)| format!("{count} thread(s)")),
+            "the debuggee may have exited already",
+        ));
+        if let Some(thread) = thread {
+            checks.push(Check::new(
+                "stackTrace",
+                client
+                    .request("stackTrace", serde_json::json!({ "threadId": thread }))
+                    .and_then(|body| {
+                        let frames = body["stackFrames"].as_array().cloned().unwrap_or_default();
+                        let top = frames.first().context("no stack frames reported")?;
+                        Ok(format!(
+                            "{} frame(s), top: {}",
+                            frames.len(),
+                            top["name"].as_str().unwrap_or("?")
+                        ))
+                    }),
+                "check that `program` matches the binary being debugged and has symbols",
+            ));
+        }
+    }
+    checks.push(Check::new(
+        "disconnect",
+        client
+            .request(
+                "disconnect",
+                serde_json::json!({ "terminateDebuggee": true }),
+            )
+            .map(|_| "session ended".into()),
+        "the adapter may have crashed; rerun with RUST_LOG=debug",
+    ));
+    checks
+}
+
+/// The client half of a DAP session with an adapter on stdio.
+struct DapClient {
+    stdin: ChildStdin,
+    messages: Receiver<anyhow::Result<Value>>,
+    seq: i64,
+    timeout: Duration,
+}
+
+impl DapClient {
+    /// Sends `command` and waits for its response, skipping events; the
+    /// response's body on success, its message otherwise.
+    fn request(&mut self, command: &str, arguments: Value) -> anyhow::Result<Value> {
+        self.seq += 1;
+        let body = serde_json::json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.stdin.flush()?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let message = self
+                .messages
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|_| anyhow::anyhow!("no response within {}s", self.timeout.as_secs()))??;
+            if message["type"] != "response" || message["request_seq"] != self.seq {
+                continue;
+            }
+            if message["success"] == true {
+                return Ok(message["body"].clone());
+            }
+            bail!(
+                "{}",
+                message["message"].as_str().unwrap_or("request failed")
+            );
+        }
+    }
+}
+
+/// Reads the adapter's messages on a thread of its own, so requests can
+/// time out.
+fn spawn_dap_reader(stdout: impl Read + Send + 'static) -> Receiver<anyhow::Result<Value>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let message = match read_dap_message(&mut reader) {
+                Ok(Some(message)) => Ok(message),
+                Ok(None) => Err(anyhow::anyhow!("the adapter exited")),
+                Err(err) => Err(err),
+            };
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// One `Content-Length`-framed message, or `None` at end of input.
+fn read_dap_message(reader: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = Some(length.trim().parse::<usize>()?);
+        }
+    }
+    let mut body = vec![0; content_length.context("DAP message without Content-Length")?];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn debug_json_path(args: &Args) -> PathBuf {
+    args.output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(".zed/debug.json"))
+}
+
+fn import_vscode(args: &Args, launch_json: &Path) -> anyhow::Result<()> {
+    let source = fs::read_to_string(launch_json)
+        .with_context(|| format!("failed to read {}", launch_json.display()))?;
+    let launch = jsonc::parse(&source)
+        .with_context(|| format!("failed to parse {}", launch_json.display()))?;
+    let (configs, skipped) = vscode::import_vscode_launch(&launch);
+    for note in &skipped {
+        eprintln!("skipped {note}");
+    }
+    if configs.is_empty() {
+        bail!(
+            "no lldb configurations to import from {}",
+            launch_json.display()
+        );
+    }
+    let output = debug_json_path(args);
+    let mut json = load_debug_json(&output)?;
+    for config in configs {
+        println!("Imported \"{}\"", config.label);
+        json.upsert(&config);
+    }
+    save_debug_json(&output, &json)?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+/// The configurations of `json` this tool can read; others are skipped.
+fn configurations(json: &DebugJson) -> Vec<AdapterConfig> {
+    let value = jsonc::parse(json.as_str()).expect("validated by parse");
+    value
+        .get("configurations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|config| AdapterConfig::deserialize(config).ok())
+        .collect()
+}
+
+/// Comments in an existing launch.json are not kept.
+fn export_vscode(args: &Args, launch_json: &Path) -> anyhow::Result<()> {
+    let json = load_debug_json(&debug_json_path(args))?;
+    let mut launch = match fs::read_to_string(launch_json) {
+        Ok(source) => jsonc::parse(&source)
+            .with_context(|| format!("failed to parse {}", launch_json.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Value::Null,
+        Err(err) => return Err(err).context(format!("failed to read {}", launch_json.display())),
+    };
+    vscode::export_vscode_launch(&configurations(&json), &mut launch)
+        .map_err(|err| anyhow::anyhow!("failed to parse {}: {err}", launch_json.display()))?;
+    if let Some(dir) = launch_json.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(launch_json, serde_json::to_string_pretty(&launch)? + "\n")?;
+    println!("Wrote {}", launch_json.display());
+    Ok(())
+}
+
+fn format_checks(checks: &[Check]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        match &check.outcome {
+            Ok(detail) => out.push_str(&format!("[ok]   {}: {detail}\n", check.name)),
+            Err(err) => out.push_str(&format!(
+                "[FAIL] {}: {err}\n       {}\n",
+                check.name, check.hint
+            )),
+        }
+    }
+    out
+}
+
+fn command_stdout(program: &str, command_args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new(program)
+        .args(command_args)
+        .output()
+        .with_context(|| format!("failed to run {program}"))?;
+    ensure_success(program, &output)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn find_on_path(binary: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(binary))
+        .find(|path| path.is_file())
+}
+
+/// Names of the available simulator runtimes, e.g. `iOS 17.2`.
+fn simulator_runtimes(args: &Args) -> anyhow::Result<Vec<String>> {
+    let output = Command::new(&args.xcrun)
+        .args(["simctl", "list", "-j", "runtimes"])
+        .output()
+        .with_context(|| format!("failed to run {} simctl", args.xcrun))?;
+    ensure_success("simctl list runtimes", &output)?;
+    let value: Value = serde_json::from_slice(&output.stdout).context("invalid simctl json")?;
+    Ok(parse_runtimes(&value))
+}
+
+fn parse_runtimes(value: &Value) -> Vec<String> {
+    value
+        .get("runtimes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|runtime| runtime.get("isAvailable").and_then(Value::as_bool) != Some(false))
+        .filter_map(|runtime| runtime.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Why debugserver would fail to attach to `target`: attach to a binary with
+/// a broken signature or without `get-task-allow` fails without saying why.
+fn codesign_problems(target: &Path) -> anyhow::Result<Vec<String>> {
+    let verify = Command::new("codesign")
+        .args(["--verify", "--strict"])
+        .arg(target)
+        .output()
+        .context("failed to run codesign")?;
+    if !verify.status.success() {
+        return Ok(vec![format!(
+            "signature is invalid: {}",
+            String::from_utf8_lossy(&verify.stderr).trim()
+        )]);
+    }
+    let entitlements = Command::new("codesign")
+        .args(["-d", "--entitlements", "-", "--xml"])
+        .arg(target)
+        .output()
+        .context("failed to run codesign")?;
+    ensure_success("codesign -d --entitlements", &entitlements)?;
+    Ok(entitlement_problems(&entitlements.stdout))
+}
+
+const GET_TASK_ALLOW_HINT: &str =
+    "build with the Debug configuration and a development signing identity";
+
+fn entitlement_problems(entitlements: &[u8]) -> Vec<String> {
+    if entitlements.iter().all(u8::is_ascii_whitespace) {
+        return vec![format!(
+            "no entitlements, so get-task-allow is missing; {GET_TASK_ALLOW_HINT}"
+        )];
+    }
+    let dictionary = match plist::Value::from_reader_xml(entitlements) {
+        Ok(plist::Value::Dictionary(dictionary)) => dictionary,
+        Ok(_) => return vec!["entitlements are not a dictionary".into()],
+        Err(err) => return vec![format!("unreadable entitlements: {err}")],
+    };
+    match dictionary.get("get-task-allow") {
+        Some(plist::Value::Boolean(true)) => Vec::new(),
+        Some(_) => vec![format!("get-task-allow is false; {GET_TASK_ALLOW_HINT}")],
+        None => vec![format!("get-task-allow is missing; {GET_TASK_ALLOW_HINT}")],
+    }
+}
+
+/// The debugserver shipped inside the Xcode that `xcode-select` points at.
+fn xcode_debugserver(args: &Args) -> anyhow::Result<PathBuf> {
+    let output = Command::new(&args.xcrun)
+        .args(["--find", "lldb"])
+        .output()
+        .context("failed to locate Xcode")?;
+    ensure_success("xcrun --find lldb", &output)?;
+    let lldb = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    debugserver_for_lldb(&lldb).context("no debugserver found next to lldb; pass --debugserver")
+}
+
+/// `lldb` lives in `Contents/Developer/Toolchains/*/usr/bin` or
+/// `Contents/Developer/usr/bin`; debugserver ships in LLDB.framework under
+/// `Contents/SharedFrameworks`.
+fn debugserver_for_lldb(lldb: &Path) -> Option<PathBuf> {
+    lldb.ancestors()
+        .filter(|dir| dir.file_name().is_some_and(|name| name == "Contents"))
+        .map(|contents| {
+            contents.join("SharedFrameworks/LLDB.framework/Versions/A/Resources/debugserver")
+        })
+        .find(|path| path.exists())
+}
+
+/// `simctl launch` prints `com.example.App: 12345`.
+fn parse_launch_pid(stdout: &str) -> Option<u32> {
+    stdout
+        .lines()
+        .find_map(|line| line.rsplit_once(':')?.1.trim().parse().ok())
+}
+
+fn is_already_booted(stderr: &str) -> bool {
+    stderr.contains("current state: Booted")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceInfo {
+    /// What devicectl's `--device` accepts: the UDID when known.
+    identifier: String,
+    name: String,
+    os_version: Option<String>,
+    model: Option<String>,
+    connection: Option<String>,
+}
+
+fn list_devices(args: &Args, json: bool) -> anyhow::Result<()> {
+    let devices = match devicectl_devices(args) {
+        Ok(devices) => devices,
+        Err(err) => {
+            eprintln!("devicectl unavailable ({err:#}); trying libimobiledevice");
+            idevice_devices()?
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+    } else {
+        print!("{}", format_device_table(&devices));
+    }
+    Ok(())
+}
+
+fn devicectl_devices(args: &Args) -> anyhow::Result<Vec<DeviceInfo>> {
+    let value = devicectl_json(args, "devices", &["list", "devices"])?;
+    Ok(parse_devicectl_devices(&value))
+}
+
+/// Runs `devicectl <devicectl_args>` and reads the JSON it writes with
+/// `--json-output`.
+fn devicectl_json(args: &Args, name: &str, devicectl_args: &[&str]) -> anyhow::Result<Value> {
+    let json_path =
+        env::temp_dir().join(format!("ios-lldb-setup-{name}-{}.json", std::process::id()));
+    let what = format!("devicectl {}", devicectl_args.join(" "));
+    let output = Command::new(&args.xcrun)
+        .arg("devicectl")
+        .args(devicectl_args)
+        .arg("--json-output")
+        .arg(&json_path)
+        .output()
+        .with_context(|| format!("failed to run {} devicectl", args.xcrun))?;
+    ensure_success(&what, &output)?;
+    let data = fs::read_to_string(&json_path)
+        .with_context(|| format!("failed to read json output {}", json_path.display()))?;
+    fs::remove_file(&json_path).ok();
+    serde_json::from_str(&data).context("invalid devicectl json")
+}
+
+/// Asks devicectl about `device`, which opens its CoreDevice tunnel when
+/// it isn't up yet, and returns the tunnel's address on this Mac. The
+/// address is only reported: debugserver listens on no port there, so the
+/// config points at ios-llm-devicectl's local bridge, whose devicectl
+/// session runs through the tunnel.
+fn coredevice_tunnel(args: &Args, device: &str) -> anyhow::Result<String> {
+    let value = devicectl_json(
+        args,
+        "device-info",
+        &["device", "info", "details", "--device", device],
+    )?;
+    parse_tunnel(&value).map_err(|problem| anyhow::anyhow!("{device}: {problem}"))
+}
+
+/// The tunnel address from `devicectl device info details` JSON, or why
+/// the tunnel isn't usable.
+fn parse_tunnel(value: &Value) -> Result<String, String> {
+    let properties = value
+        .pointer("/result/connectionProperties")
+        .ok_or("devicectl reported no connection properties")?;
+    let text = |key: &str| properties.get(key).and_then(Value::as_str);
+    match text("tunnelState") {
+        Some("connected") => text("tunnelIPAddress")
+            .map(str::to_string)
+            .ok_or_else(|| "the tunnel is connected but has no address".to_string()),
+        Some(state) => Err(format!(
+            "the CoreDevice tunnel is {state}; unlock the device, trust this Mac \
+             and check it shows up in `list-devices`"
+        )),
+        None => Err(
+            "no CoreDevice tunnel; the device may run iOS 16 or earlier, \
+                     so use --forwarder usbmux"
+                .to_string(),
+        ),
+    }
+}
+
+fn parse_devicectl_devices(value: &Value) -> Vec<DeviceInfo> {
+    let text = |device: &Value, pointer: &str| {
+        device
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    value
+        .pointer("/result/devices")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|device| {
+            let identifier =
+                text(device, "/hardwareProperties/udid").or_else(|| text(device, "/identifier"))?;
+            let os_version = text(device, "/deviceProperties/osVersionNumber").map(|version| {
+                match text(device, "/deviceProperties/osBuildUpdate") {
+                    Some(build) => format!("{version} ({build})"),
+                    None => version,
+                }
+            });
+            let connection = [
+                text(device, "/connectionProperties/transportType"),
+                text(device, "/connectionProperties/tunnelState"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+            Some(DeviceInfo {
+                identifier,
+                name: text(device, "/deviceProperties/name").unwrap_or_default(),
+                os_version,
+                model: text(device, "/hardwareProperties/marketingName")
+                    .or_else(|| text(device, "/hardwareProperties/productType")),
+                connection: (!connection.is_empty()).then(|| connection.join(", ")),
+            })
+        })
+        .collect()
+}
+
+/// Lists devices through libimobiledevice's `idevice_id` and `ideviceinfo`,
+/// for hosts without Xcode 15's devicectl.
+fn idevice_devices() -> anyhow::Result<Vec<DeviceInfo>> {
+    let output = Command::new("idevice_id")
+        .arg("-l")
+        .output()
+        .context("failed to run idevice_id; install Xcode 15+ or libimobiledevice")?;
+    ensure_success("idevice_id -l", &output)?;
+    let info = |udid: &str, key: &str| {
+        let output = Command::new("ideviceinfo")
+            .args(["-u", udid, "-k", key])
+            .output()
+            .ok()?;
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !value.is_empty()).then_some(value)
+    };
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|udid| !udid.is_empty())
+        .map(|udid| DeviceInfo {
+            identifier: udid.to_string(),
+            name: info(udid, "DeviceName").unwrap_or_default(),
+            os_version: info(udid, "ProductVersion"),
+            model: info(udid, "ProductType"),
+            connection: None,
+        })
+        .collect())
+}
+
+fn format_device_table(devices: &[DeviceInfo]) -> String {
+    if devices.is_empty() {
+        return "No devices found.\n".into();
+    }
+    let rows: Vec<[&str; 5]> = devices
+        .iter()
+        .map(|device| {
+            [
+                device.name.as_str(),
+                device.os_version.as_deref().unwrap_or("-"),
+                device.model.as_deref().unwrap_or("-"),
+                device.connection.as_deref().unwrap_or("-"),
+                device.identifier.as_str(),
+            ]
+        })
+        .collect();
+    format_table(["NAME", "OS", "MODEL", "CONNECTION", "IDENTIFIER"], &rows)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatorInfo {
+    udid: String,
+    name: String,
+    /// Display form of the runtime, e.g. `iOS 17.2`.
+    runtime: String,
+    state: String,
+}
+
+#[derive(Debug, Default)]
+struct SimulatorFilter {
+    runtime: Option<String>,
+    name: Option<String>,
+    booted: bool,
+}
+
+impl SimulatorFilter {
+    fn matches(&self, simulator: &SimulatorInfo, runtime_id: &str) -> bool {
+        let contains =
+            |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+        self.runtime.as_deref().is_none_or(|runtime| {
+            contains(&simulator.runtime, runtime) || contains(runtime_id, runtime)
+        }) && self
+            .name
+            .as_deref()
+            .is_none_or(|name| contains(&simulator.name, name))
+            && (!self.booted || simulator.state == "Booted")
+    }
+}
+
+fn list_simulators(args: &Args, filter: &SimulatorFilter, json: bool) -> anyhow::Result<()> {
+    let output = Command::new(&args.xcrun)
+        .args(["simctl", "list", "-j", "devices"])
+        .output()
+        .with_context(|| format!("failed to run {} simctl", args.xcrun))?;
+    ensure_success("simctl list", &output)?;
+    let value: Value = serde_json::from_slice(&output.stdout).context("invalid simctl json")?;
+    let simulators = parse_simulators(&value, filter);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&simulators)?);
+    } else if simulators.is_empty() {
+        println!("No simulators found.");
+    } else {
+        let rows: Vec<[&str; 4]> = simulators
+            .iter()
+            .map(|sim| [&*sim.name, &*sim.runtime, &*sim.state, &*sim.udid])
+            .collect();
+        print!(
+            "{}",
+            format_table(["NAME", "RUNTIME", "STATE", "UDID"], &rows)
+        );
+    }
+    Ok(())
+}
+
+/// The available simulators in `simctl list -j devices` output that pass
+/// `filter`, ordered by runtime then name.
+fn parse_simulators(value: &Value, filter: &SimulatorFilter) -> Vec<SimulatorInfo> {
+    let mut simulators = Vec::new();
+    let Some(runtimes) = value.get("devices").and_then(Value::as_object) else {
+        return simulators;
+    };
+    for (runtime_id, devices) in runtimes {
+        let runtime = runtime_display_name(runtime_id);
+        for device in devices.as_array().into_iter().flatten() {
+            if device.get("isAvailable").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+            let text = |key: &str| device.get(key).and_then(Value::as_str).map(str::to_string);
+            let Some(udid) = text("udid") else {
+                continue;
+            };
+            let simulator = SimulatorInfo {
+                udid,
+                name: text("name").unwrap_or_default(),
+                runtime: runtime.clone(),
+                state: text("state").unwrap_or_default(),
+            };
+            if filter.matches(&simulator, runtime_id) {
+                simulators.push(simulator);
+            }
+        }
+    }
+    simulators.sort_by(|a, b| (&a.runtime, &a.name).cmp(&(&b.runtime, &b.name)));
+    simulators
+}
+
+/// `com.apple.CoreSimulator.SimRuntime.iOS-17-2` becomes `iOS 17.2`.
+fn runtime_display_name(runtime_id: &str) -> String {
+    let short = runtime_id.rsplit('.').next().unwrap_or(runtime_id);
+    match short.split_once('-') {
+        Some((platform, version)) => format!("{platform} {}", version.replace('-', ".")),
+        None => short.to_string(),
+    }
+}
+
+/// Left-aligned columns separated by two spaces, header first.
+fn format_table<const N: usize>(header: [&str; N], rows: &[[&str; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn device_flow(args: &Args) -> anyhow::Result<()> {
+    if args.start_debugserver || args.forwarder == Forwarder::Coredevice {
+        return devicectl_device_flow(args);
+    }
+    if args.network {
+        if args.logs {
+            bail!("--logs needs the device on USB; drop --network");
+        }
+        if args.forwarder == Forwarder::Iproxy {
+            bail!("--network forwards through usbmuxd; drop --forwarder iproxy");
+        }
+    }
+    let reservation = PortReservation::reserve(args.port.unwrap_or(0))?;
+    let (program, remote_port) = device_program(args)?;
+    let cwd = args.cwd.clone().unwrap_or_else(|| args.project.clone());
+
+    let mut iproxy = None;
+    #[cfg(unix)]
+    let mut usb_forward = None;
+    let mut udid = args.device.clone();
+    let local_port = match args.forwarder {
+        #[cfg(not(unix))]
+        Forwarder::Usbmux => bail!("usbmuxd is only reachable on macOS and Linux; use iproxy"),
+        #[cfg(unix)]
+        Forwarder::Usbmux => {
+            let socket = usbmux::socket_path();
+            let connection = if args.network { "Network" } else { "USB" };
+            let device = usbmux_device(&socket, args.device.as_deref(), connection)?;
+            let over = match device.network_address {
+                Some(address) if args.network => format!("usbmuxd (Wi-Fi, {address})"),
+                _ => "usbmuxd".to_string(),
+            };
+            let forward = usbmux::forward(socket, reservation, device.device_id, remote_port);
+            let port = forward.port();
+            usb_forward = Some(forward);
+            say(
+                args,
+                format_args!(
+                    "Forwarding port {port} -> {} port {remote_port} over {over}.",
+                    device.udid
+                ),
+            );
+            udid = Some(device.udid);
+            port
+        }
+        Forwarder::Iproxy => {
+            let port = reservation.release();
+            let command = IproxyCommand {
+                program: args.iproxy.clone(),
+                local_port: port,
+                remote_port,
+                udid: args.device.clone(),
+            };
+            iproxy = Some(
+                Supervisor::start(command, |status| eprintln!("{status}"))
+                    .context("failed to spawn iproxy")?,
+            );
+            say(
+                args,
+                format_args!("iproxy forwarding port {port} -> device {remote_port}."),
+            );
+            port
+        }
+        Forwarder::Coredevice => unreachable!("CoreDevice sessions start debugserver"),
+    };
+
+    let logs = match udid {
+        Some(udid) => start_logs(args, &program, AppHost::Device(udid))?,
+        None if args.logs => bail!("--logs with --forwarder iproxy needs --device <udid>"),
+        None => None,
+    };
+    let mut helpers = Vec::new();
+    helpers.extend(
+        iproxy
+            .as_ref()
+            .and_then(Supervisor::pid)
+            .map(|pid| ("iproxy", pid)),
+    );
+    helpers.extend(logs.as_ref().map(|logs| ("logs", logs.pid())));
+    let result = emit_config(args, &program, &cwd, (None, local_port), &helpers);
+    // Both forwarders only run while this process does, so keep it up for
+    // the session.
+    hold(args, &program, "Press Enter to stop forwarding.")?;
+    drop(logs);
+    drop(iproxy);
+    #[cfg(unix)]
+    drop(usb_forward);
+    result
+}
+
+/// The program to debug and debugserver's port on the device: --program
+/// and --device-port, or what xcede reports.
+fn device_program(args: &Args) -> anyhow::Result<(PathBuf, u16)> {
+    match &args.program {
+        Some(program) => Ok((program.clone(), args.device_port)),
+        None => {
+            let info = run_xcede(args)?;
+            let program = info
+                .app_binary
+                .context("xcede output missing app_binary; pass --program manually")?;
+            Ok((program, info.debugserver_port.unwrap_or(args.device_port)))
+        }
+    }
+}
+
+/// The device usbmuxd lists over `connection` (`USB` or `Network`): the
+/// one named by `udid`, or the only one.
+#[cfg(unix)]
+fn usbmux_device(
+    socket: &Path,
+    udid: Option<&str>,
+    connection: &str,
+) -> anyhow::Result<usbmux::UsbDevice> {
+    let devices = usbmux::list_devices(socket)
+        .with_context(|| format!("failed to query usbmuxd at {}", socket.display()))?;
+    let mut matching = devices
+        .into_iter()
+        .filter(|device| device.connection_type == connection);
+    let how = if connection == "Network" {
+        "paired over Wi-Fi"
+    } else {
+        "attached over USB"
+    };
+    match udid {
+        Some(udid) => matching
+            .find(|device| device.udid.eq_ignore_ascii_case(udid))
+            .with_context(|| format!("device {udid} is not {how}")),
+        None => {
+            let device = matching
+                .next()
+                .with_context(|| format!("no device {how}"))?;
+            if matching.next().is_some() {
+                bail!("several devices are {how}; pick one with --device");
+            }
+            Ok(device)
+        }
+    }
+}
+
+/// Runs ios-llm-devicectl, which installs `--app` if given, launches the app
+/// suspended, starts debugserver on the device attached to it and bridges it
+/// to the local port; the config is emitted once the bridge is listening.
+fn devicectl_device_flow(args: &Args) -> anyhow::Result<()> {
+    let device = args
+        .device
+        .as_deref()
+        .context("--device is required to start debugserver; see `list-devices`")?;
+    if args.forwarder == Forwarder::Coredevice {
+        let address = coredevice_tunnel(args, device)?;
+        say(
+            args,
+            format_args!(
+                "CoreDevice tunnel to {device} is up at {address}; \
+                 bridging debugserver through it"
+            ),
+        );
+    }
+    let (program, bundle_id) = match &args.app {
+        Some(app) => {
+            let (_, program, bundle_id) = app_bundle(args, app)?;
+            (program, bundle_id)
+        }
+        None => (
+            args.program
+                .clone()
+                .context("--app or --program is required with --start-debugserver")?,
+            args.bundle_id
+                .clone()
+                .context("--bundle-id is required without --app")?,
+        ),
+    };
+    for problem in codesign_problems(args.app.as_ref().unwrap_or(&program))? {
+        eprintln!("warning: {problem}");
+    }
+    let bridge = args
+        .devicectl_bridge
+        .clone()
+        .unwrap_or_else(default_devicectl_bridge);
+    let mut command = Command::new(&bridge);
+    command
+        .args(["--device", device, "--bundle-id", &bundle_id])
+        .args(["--listen-port", &args.port.unwrap_or(0).to_string()])
+        .args(["--devicectl", &args.xcrun])
+        .stdout(Stdio::piped());
+    if let Some(app) = &args.app {
+        command.arg("--install-app").arg(app);
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("failed to spawn {}", bridge.display()))?;
+    let stdout = child.stdout.take().context("bridge stdout not captured")?;
+    let local_port = match wait_for_bridge(BufReader::new(stdout), args.format) {
+        Ok(port) => port,
+        Err(err) => {
+            let _ = child.kill();
+            return Err(err);
+        }
+    };
+
+    let cwd = args.cwd.clone().unwrap_or_else(|| args.project.clone());
+    let logs = start_logs(args, &program, AppHost::Device(device.to_string()))?;
+    let mut helpers = vec![("ios-llm-devicectl", child.id())];
+    helpers.extend(logs.as_ref().map(|logs| ("logs", logs.pid())));
+    let result = emit_config(args, &program, &cwd, (None, local_port), &helpers);
+    if args.wait || args.logs || args.watch {
+        hold(args, &program, "Press Enter to stop debugserver.")?;
+        drop(logs);
+        let _ = child.kill();
+    }
+    result
+}
+
+/// ios-llm-devicectl from the same build as this binary, else from `PATH`.
+fn default_devicectl_bridge() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name("ios-llm-devicectl"))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("ios-llm-devicectl"))
+}
+
+/// Echoes the bridge's output until it reports the port it listens on,
+/// then keeps echoing from a background thread. Probing the port instead
+/// would use up the bridge's single connection.
+fn wait_for_bridge<R: BufRead + Send + 'static>(
+    mut output: R,
+    format: Format,
+) -> anyhow::Result<u16> {
+    let echo = move |line: &str| match format {
+        Format::Text => println!("{line}"),
+        Format::Json => eprintln!("{line}"),
+    };
+    let mut line = String::new();
+    let port = loop {
+        line.clear();
+        if output.read_line(&mut line)? == 0 {
+            bail!("ios-llm-devicectl exited before debugserver was ready");
+        }
+        echo(line.trim_end());
+        let port = line
+            .split_once("listening on 127.0.0.1:")
+            .and_then(|(_, port)| port.trim().parse().ok());
+        if let Some(port) = port {
+            break port;
+        }
+    };
+    std::thread::spawn(move || {
+        for line in output.lines().map_while(Result::ok) {
+            echo(&line);
+        }
+    });
+    Ok(port)
+}
+
+/// --dsym, or the dSYM Xcode left next to the app or the program.
+fn dsym_path(args: &Args, program: &Path) -> Option<PathBuf> {
+    match &args.dsym {
+        Some(dsym) => Some(dunce::canonicalize(dsym).unwrap_or_else(|_| dsym.clone())),
+        None => args
+            .app
+            .as_deref()
+            .and_then(cli::adjacent_dsym)
+            .or_else(|| cli::adjacent_dsym(program)),
+    }
+}
+
+/// Writes or prints the configuration for `program`; `helpers` are the
+/// processes spawned for the session, by name, for `--format json`.
+/// `debugserver` is the host (localhost when `None`) and port the session
+/// connects to.
+fn emit_config(
+    args: &Args,
+    program: &Path,
+    cwd: &Path,
+    debugserver: (Option<String>, u16),
+    helpers: &[(&str, u32)],
+) -> anyhow::Result<()> {
+    let (host, port) = debugserver;
+    let (target, device, simulator) = match args.mode {
+        Mode::Host => ("host", None, None),
+        Mode::Sim => ("sim", None, Some(args.simulator.clone())),
+        Mode::Device => ("device", args.device.clone(), None),
+    };
+    let label = match &args.label {
+        Some(label) => label.clone(),
+        None => {
+            let name = cli::program_name(args.app.as_deref().unwrap_or(program));
+            if args.matrix {
+                name
+            } else {
+                cli::target_label(&name, target, args.request.as_str())
+            }
+        }
+    };
+    let mut entry = AdapterConfig {
+        label,
         adapter: "ios-lldb".into(),
         request: args.request.as_str().into(),
         program: program.display().to_string(),
         cwd: cwd.display().to_string(),
         debugserver_port: port,
+        debugserver_host: host,
+        bundle_id: args.bundle_id.clone(),
+        device,
+        simulator,
+        dsym_path: dsym_path(args, program).map(|dsym| dsym.display().to_string()),
+        args: args.arg.clone(),
+        env: args.env.iter().cloned().collect(),
+        extra: Default::default(),
     };
-    if args.write {
-        let output = args
-            .output
-            .clone()
-            .unwrap_or_else(|| PathBuf::from(".zed/debug.json"));
-        let mut json = load_debug_json(&output)?;
-        cli::upsert_configuration(&mut json.configurations, entry.clone());
-        save_debug_json(&output, &json)?;
-        println!(
-            "Wrote configuration \"{}\" to {}",
-            entry.label,
-            output.display()
-        );
+    if args.portable {
+        let root = dunce::canonicalize(&args.project).unwrap_or_else(|_| args.project.clone());
+        cli::make_portable(&mut entry, &root);
+    }
+    let mut entries = if args.matrix {
+        cli::request_variants(&entry, target)
     } else {
-        println!("{}", serde_json::to_string_pretty(&entry)?);
+        vec![entry]
+    };
+    let output = args.write.then(|| debug_json_path(args));
+    if let Some(output) = &output {
+        let mut json = load_debug_json(output)?;
+        for entry in &mut entries {
+            if args.label.is_none() {
+                entry.label = json.unique_label(entry);
+            }
+            json.upsert(entry);
+            say(
+                args,
+                format_args!(
+                    "Wrote configuration \"{}\" to {}",
+                    entry.label,
+                    output.display()
+                ),
+            );
+        }
+        save_debug_json(output, &json)?;
+    }
+    if args.format == Format::Json {
+        let report = serde_json::json!({
+            "configurations": entries,
+            "debugJson": output,
+            "program": program,
+            "cwd": cwd,
+            "port": port,
+            "helpers": helpers
+                .iter()
+                .map(|(name, pid)| serde_json::json!({ "name": name, "pid": pid }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if output.is_none() {
+        for entry in &entries {
+            println!("{}", serde_json::to_string_pretty(entry)?);
+        }
+    }
+    println!("program: {}", entries[0].program);
+    println!("cwd    : {}", entries[0].cwd);
+    if let Some(host) = &entries[0].debugserver_host {
+        println!("host   : {host}");
+    }
+    println!("port   : {}", entries[0].debugserver_port);
+    if let Some(dsym) = &entries[0].dsym_path {
+        println!("dsym   : {dsym}");
     }
-    println!("program: {}", entry.program);
-    println!("cwd    : {}", entry.cwd);
-    println!("port   : {}", entry.debugserver_port);
     Ok(())
 }
 
@@ -220,9 +2134,670 @@ fn run_xcede(args: &Args) -> anyhow::Result<XcedeInfo> {
     Ok(info)
 }
 
-fn ensure_port_free(port: u16) -> anyhow::Result<()> {
-    let listener = TcpListener::bind(("127.0.0.1", port))
-        .with_context(|| format!("port {port} is already in use"))?;
-    drop(listener);
-    Ok(())
+/// What an xcodebuild build produced, from its build settings.
+#[derive(Debug, PartialEq, Eq)]
+struct BuiltProduct {
+    app: PathBuf,
+    executable: PathBuf,
+    dsym: Option<PathBuf>,
+}
+
+fn xcodebuild(args: &Args) -> anyhow::Result<BuiltProduct> {
+    let scheme = args
+        .scheme
+        .as_deref()
+        .context("--scheme is required with --build")?;
+    let destination = args
+        .destination
+        .clone()
+        .unwrap_or_else(|| default_destination(args));
+    let command = |extra: &[&str]| {
+        let mut command = Command::new("xcodebuild");
+        match args.project.extension().and_then(|ext| ext.to_str()) {
+            Some("xcworkspace") => {
+                command.arg("-workspace").arg(&args.project);
+            }
+            Some("xcodeproj") => {
+                command.arg("-project").arg(&args.project);
+            }
+            _ => {
+                command.current_dir(&args.project);
+            }
+        }
+        command
+            .args(["-scheme", scheme, "-destination", &destination])
+            .args(["-configuration", &args.configuration])
+            .args(extra);
+        command
+    };
+
+    say(args, format_args!("Building {scheme} for {destination}"));
+    let status = command(&["build"])
+        .stdout(helper_stdout(args))
+        .status()
+        .context("failed to run xcodebuild")?;
+    if !status.success() {
+        bail!("xcodebuild build failed with status {status}");
+    }
+    let output = command(&["-showBuildSettings", "-json"])
+        .output()
+        .context("failed to run xcodebuild -showBuildSettings")?;
+    ensure_success("xcodebuild -showBuildSettings", &output)?;
+    let settings: Value =
+        serde_json::from_slice(&output.stdout).context("invalid xcodebuild build settings")?;
+    parse_built_product(&settings).context("xcodebuild reported no application target")
+}
+
+/// The freshest build of --scheme under DerivedData, for host and sim
+/// modes given a scheme but neither --program nor --app.
+fn derived_data_product(args: &Args) -> Option<BuiltProduct> {
+    let scheme = args.scheme.as_ref()?;
+    let products = match args.mode {
+        Mode::Host => args.configuration.clone(),
+        Mode::Sim => format!("{}-iphonesimulator", args.configuration),
+        Mode::Device => return None,
+    };
+    let derived_data = args
+        .derived_data
+        .clone()
+        .or(Variables::from_env().derived_data)?;
+    let project = dunce::canonicalize(&args.project).unwrap_or_else(|_| args.project.clone());
+    find_derived_product(&derived_data, &project_name(&project), scheme, &products)
+}
+
+/// The name Xcode gives `project`'s DerivedData folder (before the
+/// `-<hash>` suffix): the workspace or project it is, or the one inside it.
+fn project_name(project: &Path) -> String {
+    xcode_container(project)
+        .as_deref()
+        .unwrap_or(project)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// `project` if it is an .xcworkspace or .xcodeproj, else the one inside
+/// it (workspaces first, as Xcode opens them).
+fn xcode_container(project: &Path) -> Option<PathBuf> {
+    if matches!(
+        project.extension().and_then(|ext| ext.to_str()),
+        Some("xcworkspace" | "xcodeproj")
+    ) {
+        return Some(project.to_path_buf());
+    }
+    let mut found: Vec<PathBuf> = fs::read_dir(project)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("xcworkspace" | "xcodeproj")
+            )
+        })
+        .collect();
+    found.sort_by_key(|path| {
+        (
+            path.extension().is_some_and(|ext| ext != "xcworkspace"),
+            path.clone(),
+        )
+    });
+    found.into_iter().next()
+}
+
+/// --scheme when it was left out: the only scheme `xcodebuild -list`
+/// reports for `container`, or the user's pick when there are several and
+/// stdin is a terminal.
+fn discover_scheme(args: &Args, container: &Path) -> anyhow::Result<String> {
+    let flag = match container.extension().and_then(|ext| ext.to_str()) {
+        Some("xcworkspace") => "-workspace",
+        _ => "-project",
+    };
+    let output = Command::new("xcodebuild")
+        .args(["-list", "-json", flag])
+        .arg(container)
+        .output()
+        .context("failed to run xcodebuild -list (or pass --scheme)")?;
+    ensure_success("xcodebuild -list", &output)?;
+    let list: Value =
+        serde_json::from_slice(&output.stdout).context("invalid xcodebuild -list output")?;
+    let schemes = parse_schemes(&list);
+    match schemes.as_slice() {
+        [] => bail!(
+            "{} has no shared schemes; pass --scheme",
+            container.display()
+        ),
+        [only] => {
+            say(args, format_args!("Using scheme {only}"));
+            Ok(only.clone())
+        }
+        _ if std::io::stdin().is_terminal() => prompt_scheme(&schemes),
+        _ => bail!(
+            "{} has several schemes ({}); pass --scheme",
+            container.display(),
+            schemes.join(", ")
+        ),
+    }
+}
+
+/// The schemes in `xcodebuild -list -json` output, for a workspace or a
+/// project.
+fn parse_schemes(list: &Value) -> Vec<String> {
+    ["workspace", "project"]
+        .iter()
+        .filter_map(|kind| list.get(kind)?.get("schemes")?.as_array())
+        .flatten()
+        .filter_map(|scheme| Some(scheme.as_str()?.to_string()))
+        .collect()
+}
+
+/// Asks on stderr, so stdout stays clean for `--format json`.
+fn prompt_scheme(schemes: &[String]) -> anyhow::Result<String> {
+    eprintln!("Several schemes found:");
+    for (index, scheme) in schemes.iter().enumerate() {
+        eprintln!("  {}) {scheme}", index + 1);
+    }
+    loop {
+        eprint!("Scheme to use [1-{}]: ", schemes.len());
+        std::io::stderr().flush()?;
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            bail!("no scheme chosen; pass --scheme");
+        }
+        let input = input.trim();
+        let picked = match input.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|index| schemes.get(index)),
+            Err(_) => schemes.iter().find(|scheme| *scheme == input),
+        };
+        if let Some(scheme) = picked {
+            return Ok(scheme.clone());
+        }
+    }
+}
+
+/// Scans `<derived_data>/<project>-*/Build/Products/<products>` for app
+/// bundles (and, for macOS tools, a bare `<scheme>` executable), preferring
+/// those named after `scheme`, then the most recently built.
+fn find_derived_product(
+    derived_data: &Path,
+    project: &str,
+    scheme: &str,
+    products: &str,
+) -> Option<BuiltProduct> {
+    let prefix = format!("{project}-");
+    let mut best: Option<((bool, std::time::SystemTime), BuiltProduct)> = None;
+    for folder in fs::read_dir(derived_data).ok()?.flatten() {
+        if !folder.file_name().to_string_lossy().starts_with(&prefix) {
+            continue;
+        }
+        let dir = folder.path().join("Build/Products").join(products);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let (named_after_scheme, executable) = if let Some(stem) = name.strip_suffix(".app") {
+                match resolve_app_bundle(&path) {
+                    Ok(Some(bundle)) => (stem == scheme, bundle.executable),
+                    _ => continue,
+                }
+            } else if name == scheme && path.is_file() {
+                (true, path.clone())
+            } else {
+                continue;
+            };
+            let Ok(modified) = fs::metadata(&executable).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+            let rank = (named_after_scheme, modified);
+            if best.as_ref().is_some_and(|(best, _)| *best >= rank) {
+                continue;
+            }
+            let dsym = dir.join(format!("{name}.dSYM"));
+            let product = BuiltProduct {
+                app: path,
+                executable,
+                dsym: dsym.is_dir().then_some(dsym),
+            };
+            best = Some((rank, product));
+        }
+    }
+    best.map(|(_, product)| product)
+}
+
+fn default_destination(args: &Args) -> String {
+    match args.mode {
+        Mode::Host => "platform=macOS".into(),
+        Mode::Sim if args.simulator != "booted" => format!("id={}", args.simulator),
+        Mode::Sim => "generic/platform=iOS Simulator".into(),
+        Mode::Device => match &args.device {
+            Some(device) => format!("id={device}"),
+            None => "generic/platform=iOS".into(),
+        },
+    }
+}
+
+/// The first application target in `xcodebuild -showBuildSettings -json`.
+fn parse_built_product(settings: &Value) -> Option<BuiltProduct> {
+    settings.as_array()?.iter().find_map(|target| {
+        let settings = target.get("buildSettings")?;
+        let get = |key: &str| settings.get(key).and_then(Value::as_str);
+        if get("WRAPPER_EXTENSION") != Some("app") {
+            return None;
+        }
+        let build_dir = PathBuf::from(get("TARGET_BUILD_DIR")?);
+        let dsym = get("DWARF_DSYM_FOLDER_PATH")
+            .zip(get("DWARF_DSYM_FILE_NAME"))
+            .map(|(dir, name)| PathBuf::from(dir).join(name));
+        Some(BuiltProduct {
+            app: build_dir.join(get("WRAPPER_NAME")?),
+            executable: build_dir.join(get("EXECUTABLE_PATH")?),
+            dsym,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devicectl_devices_are_listed_with_udid_and_os() {
+        let value = serde_json::json!({
+            "result": {
+                "devices": [
+                    {
+                        "identifier": "4D3C1B2A-0000-0000-0000-000000000000",
+                        "deviceProperties": {
+                            "name": "Ada's iPhone",
+                            "osVersionNumber": "17.2",
+                            "osBuildUpdate": "21C62"
+                        },
+                        "hardwareProperties": {
+                            "udid": "00008120-001A2B3C4D5E6F70",
+                            "marketingName": "iPhone 15 Pro",
+                            "productType": "iPhone16,1"
+                        },
+                        "connectionProperties": {
+                            "transportType": "wired",
+                            "tunnelState": "connected"
+                        }
+                    },
+                    { "identifier": "5E4D3C2B-0000-0000-0000-000000000000" }
+                ]
+            }
+        });
+        let devices = parse_devicectl_devices(&value);
+        assert_eq!(
+            devices[0],
+            DeviceInfo {
+                identifier: "00008120-001A2B3C4D5E6F70".into(),
+                name: "Ada's iPhone".into(),
+                os_version: Some("17.2 (21C62)".into()),
+                model: Some("iPhone 15 Pro".into()),
+                connection: Some("wired, connected".into()),
+            }
+        );
+        assert_eq!(
+            devices[1].identifier,
+            "5E4D3C2B-0000-0000-0000-000000000000"
+        );
+        assert_eq!(devices[1].os_version, None);
+
+        let table = format_device_table(&devices);
+        let lines: Vec<_> = table.lines().collect();
+        assert!(lines[0].starts_with("NAME"), "{table}");
+        assert!(lines[1].contains("17.2 (21C62)"), "{table}");
+        assert!(lines[1].ends_with("00008120-001A2B3C4D5E6F70"), "{table}");
+        assert_eq!(format_device_table(&[]), "No devices found.\n");
+    }
+
+    #[test]
+    fn simulators_are_filtered_by_runtime_name_and_state() {
+        let value = serde_json::json!({
+            "devices": {
+                "com.apple.CoreSimulator.SimRuntime.iOS-17-2": [
+                    { "udid": "A1", "name": "iPhone 15", "state": "Booted", "isAvailable": true },
+                    { "udid": "A2", "name": "iPad Air", "state": "Shutdown", "isAvailable": true },
+                    { "udid": "A3", "name": "iPhone 14", "state": "Shutdown", "isAvailable": false }
+                ],
+                "com.apple.CoreSimulator.SimRuntime.iOS-16-4": [
+                    { "udid": "B1", "name": "iPhone 14", "state": "Shutdown", "isAvailable": true }
+                ]
+            }
+        });
+        let udids = |filter: SimulatorFilter| {
+            parse_simulators(&value, &filter)
+                .into_iter()
+                .map(|sim| sim.udid)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(udids(SimulatorFilter::default()), ["B1", "A2", "A1"]);
+        assert_eq!(
+            udids(SimulatorFilter {
+                runtime: Some("ios 17".into()),
+                ..SimulatorFilter::default()
+            }),
+            ["A2", "A1"]
+        );
+        assert_eq!(
+            udids(SimulatorFilter {
+                runtime: Some("iOS-16-4".into()),
+                ..SimulatorFilter::default()
+            }),
+            ["B1"]
+        );
+        assert_eq!(
+            udids(SimulatorFilter {
+                name: Some("iphone".into()),
+                booted: true,
+                ..SimulatorFilter::default()
+            }),
+            ["A1"]
+        );
+        assert_eq!(
+            parse_simulators(&value, &SimulatorFilter::default())[0].runtime,
+            "iOS 16.4"
+        );
+    }
+
+    #[test]
+    fn coredevice_tunnel_address_needs_a_connected_tunnel() {
+        let details = |properties: Value| serde_json::json!({ "result": { "connectionProperties": properties } });
+        assert_eq!(
+            parse_tunnel(&details(serde_json::json!({
+                "tunnelState": "connected",
+                "tunnelIPAddress": "fd35:d15b:8d3f::1",
+                "transportType": "wired"
+            }))),
+            Ok("fd35:d15b:8d3f::1".to_string())
+        );
+        let err = parse_tunnel(&details(
+            serde_json::json!({ "tunnelState": "disconnected" }),
+        ))
+        .unwrap_err();
+        assert!(err.contains("is disconnected"), "{err}");
+        let err =
+            parse_tunnel(&details(serde_json::json!({ "transportType": "wired" }))).unwrap_err();
+        assert!(err.contains("--forwarder usbmux"), "{err}");
+        assert!(parse_tunnel(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn bridge_is_ready_once_it_reports_listening() {
+        let output = "Installing MyApp.app\ngdb-remote bridge listening on 127.0.0.1:23456\n";
+        assert_eq!(
+            wait_for_bridge(std::io::Cursor::new(output), Format::Text).unwrap(),
+            23456
+        );
+
+        let err = wait_for_bridge(
+            std::io::Cursor::new("devicectl launch failed\n"),
+            Format::Text,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("exited before"), "{err}");
+    }
+
+    #[test]
+    fn built_app_is_read_from_build_settings() {
+        let settings = serde_json::json!([
+            {
+                "target": "MyAppTests",
+                "buildSettings": { "WRAPPER_EXTENSION": "xctest" }
+            },
+            {
+                "target": "MyApp",
+                "buildSettings": {
+                    "WRAPPER_EXTENSION": "app",
+                    "TARGET_BUILD_DIR": "/DerivedData/Build/Products/Debug-iphonesimulator",
+                    "WRAPPER_NAME": "MyApp.app",
+                    "EXECUTABLE_PATH": "MyApp.app/MyApp",
+                    "DWARF_DSYM_FOLDER_PATH": "/DerivedData/Build/Products/Debug-iphonesimulator",
+                    "DWARF_DSYM_FILE_NAME": "MyApp.app.dSYM"
+                }
+            }
+        ]);
+        let products = Path::new("/DerivedData/Build/Products/Debug-iphonesimulator");
+        assert_eq!(
+            parse_built_product(&settings),
+            Some(BuiltProduct {
+                app: products.join("MyApp.app"),
+                executable: products.join("MyApp.app/MyApp"),
+                dsym: Some(products.join("MyApp.app.dSYM")),
+            })
+        );
+        assert_eq!(parse_built_product(&serde_json::json!([])), None);
+    }
+
+    #[test]
+    fn freshest_scheme_build_is_found_in_derived_data() {
+        let derived = std::env::temp_dir().join(format!("setup-derived-{}", std::process::id()));
+        let built = |age_secs: u64| SystemTime::now() - Duration::from_secs(age_secs);
+        let app = |folder: &str, products: &str, name: &str, modified: SystemTime| {
+            let app = derived
+                .join(folder)
+                .join("Build/Products")
+                .join(products)
+                .join(format!("{name}.app"));
+            fs::create_dir_all(&app).unwrap();
+            fs::write(
+                app.join("Info.plist"),
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict><key>CFBundleExecutable</key><string>MyApp</string></dict></plist>"#,
+            )
+            .unwrap();
+            fs::write(app.join("MyApp"), b"").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(app.join("MyApp"))
+                .and_then(|file| file.set_modified(modified))
+                .unwrap();
+            app
+        };
+        app("MyApp-aaaa", "Debug-iphonesimulator", "MyApp", built(60));
+        app("MyApp-bbbb", "Debug-iphonesimulator", "Widget", built(0));
+        app("Other-cccc", "Debug-iphonesimulator", "MyApp", built(0));
+        let fresh = app("MyApp-dddd", "Debug-iphonesimulator", "MyApp", built(30));
+        fs::create_dir_all(fresh.with_extension("app.dSYM")).unwrap();
+        app("MyApp-eeee", "Release-iphonesimulator", "MyApp", built(0));
+
+        let product =
+            find_derived_product(&derived, "MyApp", "MyApp", "Debug-iphonesimulator").unwrap();
+        assert_eq!(product.app, fresh);
+        assert_eq!(product.executable, fresh.join("MyApp"));
+        assert_eq!(product.dsym, Some(fresh.with_extension("app.dSYM")));
+        assert_eq!(
+            find_derived_product(&derived, "MyApp", "MyApp", "Debug").map(|p| p.app),
+            None
+        );
+
+        let project = derived.join("Source");
+        fs::create_dir_all(project.join("MyApp.xcodeproj")).unwrap();
+        assert_eq!(project_name(&project), "MyApp");
+        assert_eq!(project_name(Path::new("/src/Big.xcworkspace")), "Big");
+        let _ = fs::remove_dir_all(&derived);
+    }
+
+    #[test]
+    fn schemes_come_from_the_workspace_before_the_project() {
+        let project = std::env::temp_dir().join(format!("setup-schemes-{}", std::process::id()));
+        fs::create_dir_all(project.join("MyApp.xcodeproj")).unwrap();
+        assert_eq!(
+            xcode_container(&project),
+            Some(project.join("MyApp.xcodeproj"))
+        );
+        fs::create_dir_all(project.join("MyApp.xcworkspace")).unwrap();
+        assert_eq!(
+            xcode_container(&project),
+            Some(project.join("MyApp.xcworkspace"))
+        );
+        assert_eq!(
+            xcode_container(&project.join("MyApp.xcodeproj")).unwrap(),
+            project.join("MyApp.xcodeproj")
+        );
+        let _ = fs::remove_dir_all(&project);
+
+        let workspace = serde_json::json!({
+            "workspace": { "name": "MyApp", "schemes": ["MyApp", "MyAppTests"] }
+        });
+        assert_eq!(parse_schemes(&workspace), ["MyApp", "MyAppTests"]);
+        let project = serde_json::json!({
+            "project": {
+                "name": "Tool",
+                "configurations": ["Debug", "Release"],
+                "schemes": ["Tool"],
+                "targets": ["Tool"]
+            }
+        });
+        assert_eq!(parse_schemes(&project), ["Tool"]);
+    }
+
+    #[test]
+    fn dap_messages_are_read_by_content_length() {
+        let stream = "Content-Length: 15\r\n\r\n{\"seq\":1,\"a\":1}\
+                      Content-Length: 2\r\nContent-Type: application/json\r\n\r\n{}";
+        let mut reader = stream.as_bytes();
+        let first = read_dap_message(&mut reader).unwrap().unwrap();
+        assert_eq!(first, serde_json::json!({ "seq": 1, "a": 1 }));
+        let second = read_dap_message(&mut reader).unwrap().unwrap();
+        assert_eq!(second, serde_json::json!({}));
+        assert!(read_dap_message(&mut reader).unwrap().is_none());
+        assert!(read_dap_message(&mut "\r\n{}".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rebuilds_count_once_the_binary_settles() {
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let new = old + Duration::from_secs(60);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut watch = RebuildWatch::new(Some(old));
+        assert!(!watch.poll(Some(old), at(5000)));
+        // The linker removes and rewrites the binary.
+        assert!(!watch.poll(None, at(5500)));
+        assert!(!watch.poll(None, at(7000)));
+        assert!(!watch.poll(Some(new), at(7500)));
+        assert!(!watch.poll(Some(new), at(8000)));
+        assert!(watch.poll(Some(new), at(8500)));
+    }
+
+    #[test]
+    fn running_processes_are_found_by_pid_or_name() {
+        let processes = [
+            (1, "/sbin/launchd"),
+            (4120, "/Applications/MyApp.app/Contents/MacOS/MyApp"),
+            (5210, "/Users/me/Library/Developer/CoreSimulator/Devices/AAAA-1111/data/Containers/Bundle/Application/X/MyApp.app/MyApp"),
+            (5300, "/Users/me/Library/Developer/CoreSimulator/Devices/BBBB-2222/data/Containers/Bundle/Application/Y/MyApp.app/MyApp"),
+        ]
+        .map(|(pid, path)| RunningProcess {
+            pid,
+            path: path.into(),
+        });
+
+        let pid_of = |name, in_simulator| {
+            find_process(&processes, None, Some(name), in_simulator).map(|process| process.pid)
+        };
+        assert_eq!(pid_of("MyApp", false).unwrap(), 4120);
+        let several = pid_of("MyApp", true).unwrap_err().to_string();
+        assert!(several.contains("pids 5210, 5300"), "{several}");
+        assert!(pid_of("Other", false).is_err());
+        assert!(find_process(&processes, Some(5210), None, false)
+            .unwrap()
+            .path
+            .ends_with("/MyApp"));
+        assert!(find_process(&processes, Some(9), None, false).is_err());
+    }
+
+    #[test]
+    fn doctor_reports_failures_with_hints() {
+        let checks = [
+            Check::new("iproxy", Ok("/opt/homebrew/bin/iproxy".into()), "unused"),
+            Check::new(
+                "paired devices",
+                Err(anyhow::anyhow!("none connected")),
+                "connect a device",
+            ),
+        ];
+        assert_eq!(
+            format_checks(&checks),
+            "[ok]   iproxy: /opt/homebrew/bin/iproxy\n\
+             [FAIL] paired devices: none connected\n       connect a device\n"
+        );
+    }
+
+    #[test]
+    fn only_available_runtimes_are_listed() {
+        let value = serde_json::json!({ "runtimes": [
+            { "name": "iOS 17.2", "isAvailable": true },
+            { "name": "iOS 16.4", "isAvailable": false },
+            { "name": "watchOS 10.2", "isAvailable": true }
+        ]});
+        assert_eq!(parse_runtimes(&value), ["iOS 17.2", "watchOS 10.2"]);
+    }
+
+    #[test]
+    fn entitlements_need_get_task_allow() {
+        let plist = |body: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict>{body}</dict></plist>"#
+            )
+        };
+        let allowed = plist("<key>get-task-allow</key><true/>");
+        assert!(entitlement_problems(allowed.as_bytes()).is_empty());
+
+        let denied = plist("<key>get-task-allow</key><false/>");
+        assert!(entitlement_problems(denied.as_bytes())[0].contains("is false"));
+
+        let missing = plist("<key>application-identifier</key><string>T.app</string>");
+        assert!(entitlement_problems(missing.as_bytes())[0].contains("is missing"));
+
+        assert!(entitlement_problems(b"\n")[0].starts_with("no entitlements"));
+    }
+
+    #[test]
+    fn destination_follows_the_mode() {
+        let mut args = Args::parse_from(["ios-lldb-setup", "--mode", "sim"]);
+        assert_eq!(default_destination(&args), "generic/platform=iOS Simulator");
+        args.simulator = "ABCD".into();
+        assert_eq!(default_destination(&args), "id=ABCD");
+        args.mode = Mode::Device;
+        assert_eq!(default_destination(&args), "generic/platform=iOS");
+        args.device = Some("00008120".into());
+        assert_eq!(default_destination(&args), "id=00008120");
+    }
+
+    #[test]
+    fn launch_pid_is_parsed_from_simctl_output() {
+        assert_eq!(parse_launch_pid("com.example.App: 48213\n"), Some(48213));
+        assert_eq!(parse_launch_pid("An error was encountered"), None);
+    }
+
+    #[test]
+    fn already_booted_simulators_are_not_an_error() {
+        assert!(is_already_booted(
+            "An error was encountered processing the command (domain=com.apple.CoreSimulator.SimError, code=405):\nUnable to boot device in current state: Booted"
+        ));
+        assert!(!is_already_booted("Invalid device: Nope"));
+    }
+
+    #[test]
+    fn debugserver_is_found_in_the_lldb_framework() {
+        let root = std::env::temp_dir().join(format!("swiftscope-xcode-{}", std::process::id()));
+        let contents = root.join("Xcode.app/Contents");
+        let resources = contents.join("SharedFrameworks/LLDB.framework/Versions/A/Resources");
+        std::fs::create_dir_all(&resources).unwrap();
+        std::fs::write(resources.join("debugserver"), b"").unwrap();
+
+        let lldb = contents.join("Developer/Toolchains/XcodeDefault.xctoolchain/usr/bin/lldb");
+        assert_eq!(
+            debugserver_for_lldb(&lldb),
+            Some(resources.join("debugserver"))
+        );
+        assert_eq!(debugserver_for_lldb(Path::new("/usr/bin/lldb")), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
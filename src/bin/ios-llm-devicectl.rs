@@ -1,7 +1,6 @@
 use std::{
     fs,
     io::{self, Read, Write},
-    net::TcpListener,
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
     thread,
@@ -11,6 +10,7 @@ use std::{
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use serde_json::{json, Value};
+use swiftscope::ports::PortReservation;
 
 #[derive(Debug, Parser)]
 #[command(about = "Launch debugserver over devicectl and bridge it to a local port")]
@@ -24,7 +24,8 @@ struct Args {
     /// Optional .app path to install before launching.
     #[arg(long)]
     install_app: Option<PathBuf>,
-    /// Local TCP port to expose the gdb-remote connection on.
+    /// Local TCP port to expose the gdb-remote connection on; 0 picks a
+    /// free one, reported in the "listening on" line and the state file.
     #[arg(long, default_value_t = 2331)]
     listen_port: u16,
     /// Path to debugserver on the device.
@@ -53,6 +54,9 @@ struct LaunchResult {
 fn main() -> Result<()> {
     env_logger::builder().format_timestamp(None).init();
     let args = Args::parse();
+    // Held through install and launch so the port is still free for the
+    // adapter once debugserver is up.
+    let reservation = PortReservation::reserve(args.listen_port)?;
     if let Some(app) = &args.install_app {
         install_app(&args, app)?;
     }
@@ -66,15 +70,15 @@ fn main() -> Result<()> {
     } else {
         println!("devicectl output did not reveal an app binary path");
     }
-    if let Err(err) = write_state_file(&args, &launch) {
+    if let Err(err) = write_state_file(&args, &launch, reservation.port()) {
         eprintln!("failed to record session state: {err}");
     }
     let child = spawn_debugserver(&args, launch.pid)?;
-    bridge_stdio(child, args.listen_port)?;
+    bridge_stdio(child, reservation)?;
     Ok(())
 }
 
-fn write_state_file(args: &Args, launch: &LaunchResult) -> Result<()> {
+fn write_state_file(args: &Args, launch: &LaunchResult, listen_port: u16) -> Result<()> {
     let path = args
         .state_file
         .clone()
@@ -90,7 +94,7 @@ fn write_state_file(args: &Args, launch: &LaunchResult) -> Result<()> {
     let state = json!({
         "device": args.device,
         "bundle_id": args.bundle_id,
-        "listen_port": args.listen_port,
+        "listen_port": listen_port,
         "app_binary": canonical_app.as_ref().map(|p| p.display().to_string()),
     });
     fs::write(&path, serde_json::to_string_pretty(&state)?)
@@ -174,7 +178,7 @@ fn spawn_debugserver(args: &Args, pid: i64) -> Result<Child> {
         .context("failed to launch debugserver via devicectl")
 }
 
-fn bridge_stdio(mut child: Child, port: u16) -> Result<()> {
+fn bridge_stdio(mut child: Child, reservation: PortReservation) -> Result<()> {
     let mut child_stdout = child
         .stdout
         .take()
@@ -195,13 +199,14 @@ fn bridge_stdio(mut child: Child, port: u16) -> Result<()> {
         });
     }
 
-    let listener = TcpListener::bind(("127.0.0.1", port))
-        .with_context(|| format!("failed to bind port {port}"))?;
+    let port = reservation.port();
     println!("gdb-remote bridge listening on 127.0.0.1:{port}");
-    let (mut stream, addr) = listener
+    let mut stream = reservation
         .accept()
         .context("failed to accept adapter connection")?;
-    println!("Adapter connected from {addr}");
+    if let Ok(addr) = stream.peer_addr() {
+        println!("Adapter connected from {addr}");
+    }
 
     let mut stream_for_stdin = stream.try_clone().context("failed to clone tcp stream")?;
     let writer = thread::spawn(move || {
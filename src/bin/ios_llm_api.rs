@@ -25,12 +25,13 @@ use axum::{
 };
 use clap::Parser;
 use futures_core::Stream;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use swiftscope::{
     backend,
     debug_session::{self, DebugSession, DebugSessionError},
+    gdb_remote,
 };
-use serde::Deserialize;
-use serde_json::{json, Value};
 use tokio::{
     io::{AsyncBufReadExt, AsyncRead, BufReader},
     net::{TcpListener, TcpStream},
@@ -43,10 +44,13 @@ use tokio::{
 #[derive(Debug, Parser)]
 #[command(about = "Start the iOS LLM Debug API server")]
 struct Args {
+    /// Host where debugserver is listening.
+    #[arg(long, default_value = gdb_remote::DEFAULT_HOST)]
+    debugserver_host: String,
     /// Port where debugserver is listening.
     #[arg(long)]
     debugserver_port: u16,
-    /// Optional Mach-O path to use for symbolication (falls back to IOS_LLDB_DAP_CONFIG env).
+    /// Mach-O path to use for symbolication (defaults to this executable).
     #[arg(long)]
     program: Option<PathBuf>,
     /// Host interface for the HTTP server.
@@ -97,6 +101,7 @@ struct Args {
 struct Config {
     host: String,
     port: u16,
+    debugserver_host: String,
     debugserver_port: u16,
     program: PathBuf,
     device: Option<String>,
@@ -118,6 +123,7 @@ impl Config {
         Self {
             host: args.host.clone(),
             port: args.port,
+            debugserver_host: args.debugserver_host.clone(),
             debugserver_port: args.debugserver_port,
             program,
             device: args.device.clone(),
@@ -201,14 +207,12 @@ async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
     let listen_host = args.host.clone();
     let listen_port = args.port;
-    let backend = if let Some(program) = args.program.as_deref() {
-        debug_session::backend_from_program(program)?
-    } else {
-        debug_session::init_backend()?
+    let program = match &args.program {
+        Some(program) => program.clone(),
+        None => std::env::current_exe()?,
     };
-
-    let mut session = DebugSession::new(backend);
-    let config = Config::from_args(&args, session.program_path().to_path_buf());
+    let mut session = DebugSession::new(debug_session::backend_from_program(&program)?);
+    let config = Config::from_args(&args, program);
     let (log_tx, _log_rx) = broadcast::channel(1024);
     let bridge = if config.manage_bridge {
         Some(Arc::new(Mutex::new(BridgeController::new(
@@ -229,7 +233,7 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    session.connect_debugserver(config.debugserver_port)?;
+    session.connect_debugserver(&config.debugserver_host, config.debugserver_port)?;
     warn_if_missing_dwarf(&config)?;
 
     let build_runner = config
@@ -293,6 +297,7 @@ async fn health_check(State(state): State<AppState>) -> Json<serde_json::Value>
     Json(json!({
         "ok": true,
         "program": state.config.program.display().to_string(),
+        "debugserverHost": state.config.debugserver_host.clone(),
         "debugserverPort": state.config.debugserver_port,
         "device": state.config.device,
         "bundleId": state.config.bundle_id,
@@ -332,7 +337,10 @@ async fn handle_launch_request(state: &AppState, restart: bool) -> anyhow::Resul
     drop(controller);
     let mut session = state.session.lock().await;
     session
-        .connect_debugserver(state.config.debugserver_port)
+        .connect_debugserver(
+            &state.config.debugserver_host,
+            state.config.debugserver_port,
+        )
         .map_err(|err: DebugSessionError| anyhow::anyhow!(err))?;
     Ok(json!({ "ok": true }))
 }
@@ -625,12 +633,17 @@ fn execute_command(
         LlmCommand::SetBreakpoint { file, line } => session
             .set_breakpoint(&file, line)
             .map(|bp| json!({ "ok": true, "breakpoint_id": bp.id })),
-        LlmCommand::Locals => Ok(json!({ "ok": true, "locals": session.locals() })),
-        LlmCommand::Scopes => Ok(json!({ "ok": true, "scopes": session.scopes() })),
-        LlmCommand::Variables { reference } => {
-            let reference = reference.unwrap_or(1);
-            Ok(json!({ "ok": true, "variables": session.variables_for_reference(reference) }))
+        LlmCommand::Locals => session
+            .locals()
+            .map(|locals| json!({ "ok": true, "locals": locals })),
+        LlmCommand::Scopes => session
+            .scopes()
+            .map(|scopes| json!({ "ok": true, "scopes": scopes })),
+        LlmCommand::Variables { reference } => match reference {
+            Some(reference) => session.variables_for_reference(reference),
+            None => session.locals(),
         }
+        .map(|variables| json!({ "ok": true, "variables": variables })),
         LlmCommand::Evaluate { expression } => session
             .evaluate(&expression)
             .map(|result| json!({ "ok": true, "result": result.result, "type": result.ty })),
@@ -1,17 +1,20 @@
 use std::{
+    collections::BTreeMap,
     fs,
     io::{self, Write},
-    path::Path,
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use swiftscope::{jsonc, variables::Variables};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A debug.json kept as its text, so writing configurations back leaves
+/// comments, other adapters' configurations and unknown fields as they were.
+#[derive(Debug, Clone)]
 pub struct DebugJson {
-    #[serde(default = "default_version")]
-    pub version: String,
-    #[serde(default)]
-    pub configurations: Vec<AdapterConfig>,
+    source: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,17 +26,247 @@ pub struct AdapterConfig {
     pub cwd: String,
     #[serde(rename = "debugserverPort")]
     pub debugserver_port: u16,
+    /// Where debugserver listens when it isn't forwarded to localhost, such
+    /// as a device on the network.
+    #[serde(
+        rename = "debugserverHost",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub debugserver_host: Option<String>,
+    #[serde(rename = "bundleId", default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub simulator: Option<String>,
+    /// The build's `.dSYM`, which the adapter reads DWARF from in
+    /// preference to the binary.
+    #[serde(rename = "dsymPath", default, skip_serializing_if = "Option::is_none")]
+    pub dsym_path: Option<String>,
+    /// Arguments and environment for the debuggee on launch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Keys this tool doesn't know, kept when the entry is rewritten.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-fn default_version() -> String {
-    "0.2.0".into()
+impl AdapterConfig {
+    /// Whether `other` debugs the same program the same way on the same
+    /// simulator or device, whatever its label, port or other settings.
+    pub fn same_target(&self, other: &AdapterConfig) -> bool {
+        self.adapter == other.adapter
+            && self.request == other.request
+            && self.program == other.program
+            && self.device == other.device
+            && self.simulator == other.simulator
+    }
+}
+
+/// The Mach-O inside an `.app` bundle and the bundle's identifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppBundle {
+    pub executable: PathBuf,
+    pub bundle_id: Option<String>,
+}
+
+/// Reads `path`'s `Info.plist` when it is an app bundle: flat (iOS) or with
+/// `Contents/MacOS` (macOS). Returns `None` for anything else, such as a
+/// bare executable.
+pub fn resolve_app_bundle(path: &Path) -> io::Result<Option<AppBundle>> {
+    if !path.is_dir() {
+        return Ok(None);
+    }
+    let (info_plist, executable_dir) = if path.join("Info.plist").is_file() {
+        (path.join("Info.plist"), path.to_path_buf())
+    } else if path.join("Contents/Info.plist").is_file() {
+        (
+            path.join("Contents/Info.plist"),
+            path.join("Contents/MacOS"),
+        )
+    } else {
+        return Ok(None);
+    };
+    let info = plist::Value::from_file(&info_plist)
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse {}: {err}", info_plist.display()),
+            )
+        })?
+        .into_dictionary()
+        .unwrap_or_default();
+    let string = |key: &str| info.get(key).and_then(plist::Value::as_string);
+    let executable = match string("CFBundleExecutable") {
+        Some(name) => name.to_string(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    Ok(Some(AppBundle {
+        executable: executable_dir.join(executable),
+        bundle_id: string("CFBundleIdentifier").map(str::to_string),
+    }))
+}
+
+/// The `<name>.dSYM` Xcode writes next to `path`, an app bundle or a bare
+/// executable, if there is one.
+pub fn adjacent_dsym(path: &Path) -> Option<PathBuf> {
+    let mut name = path.file_name()?.to_os_string();
+    name.push(".dSYM");
+    let dsym = path.with_file_name(name);
+    dsym.is_dir().then_some(dsym)
+}
+
+/// Parses a `--env KEY=VALUE` flag.
+pub fn parse_env_pair(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {pair:?}")),
+    }
 }
 
 impl Default for DebugJson {
     fn default() -> Self {
         Self {
-            version: default_version(),
-            configurations: Vec::new(),
+            source: "{\n  \"version\": \"0.2.0\",\n  \"configurations\": []\n}".into(),
+        }
+    }
+}
+
+impl DebugJson {
+    /// Accepts JSON with comments and trailing commas.
+    pub fn parse(source: String) -> Result<Self, String> {
+        let value = jsonc::parse(&source).map_err(|err| err.to_string())?;
+        if !value.is_object() {
+            return Err("expected an object".into());
+        }
+        match value.get("configurations") {
+            None | Some(Value::Array(_)) => Ok(Self { source }),
+            Some(_) => Err("`configurations` is not an array".into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// `entry.label`, numbered ` #2`, ` #3`, ... when a configuration for
+    /// another program or target already has it, so that a generated label
+    /// replaces only the configuration an earlier run generated for the
+    /// same target.
+    pub fn unique_label(&self, entry: &AdapterConfig) -> String {
+        let value = jsonc::parse(&self.source).expect("validated by parse");
+        let configs = value
+            .get("configurations")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let taken = |label: &str| {
+            configs.iter().any(|config| {
+                config.get("label").and_then(Value::as_str) == Some(label)
+                    && !AdapterConfig::deserialize(config)
+                        .is_ok_and(|config| config.same_target(entry))
+            })
+        };
+        (1..)
+            .map(|n| match n {
+                1 => entry.label.clone(),
+                n => format!("{} #{n}", entry.label),
+            })
+            .find(|label| !taken(label))
+            .expect("some number is free")
+    }
+
+    /// Updates the configuration labelled like `entry` key by key, so the
+    /// keys `entry` leaves out and the comments around them stay, or
+    /// appends `entry`. Only that configuration's text changes.
+    pub fn upsert(&mut self, entry: &AdapterConfig) {
+        let blanked = jsonc::blank(&self.source);
+        let value: Value = serde_json::from_str(&blanked).expect("validated by parse");
+        let spans = jsonc::spans(&blanked);
+        let existing = value
+            .get("configurations")
+            .and_then(Value::as_array)
+            .and_then(|configs| {
+                configs
+                    .iter()
+                    .position(|config| config.get("label") == Some(&json!(entry.label)))
+                    .map(|index| (index, &configs[index]))
+            });
+        if let Some((index, old)) = existing {
+            let pointer = format!("/configurations/{index}");
+            let fields: Map<String, Value> =
+                serde_json::from_value(json!(entry)).expect("configurations are objects");
+            for (key, value) in fields {
+                if old.get(&key) == Some(&value) {
+                    continue;
+                }
+                let blanked = jsonc::blank(&self.source);
+                let spans = jsonc::spans(&blanked);
+                let member = key.replace('~', "~0").replace('/', "~1");
+                match spans.get(&format!("{pointer}/{member}")) {
+                    Some(span) => {
+                        let indent = jsonc::indent_at(&self.source, span.start);
+                        let text = jsonc::to_string_indented(&value, indent);
+                        self.source.replace_range(span.clone(), &text);
+                    }
+                    None => {
+                        let text = |indent: &str| {
+                            format!(
+                                "{}: {}",
+                                json!(key),
+                                jsonc::to_string_indented(&value, indent)
+                            )
+                        };
+                        self.append(&blanked, spans[&pointer].clone(), text);
+                    }
+                }
+            }
+        } else if value.get("configurations").is_some() {
+            let text = |indent: &str| jsonc::to_string_indented(entry, indent);
+            self.append(&blanked, spans["/configurations"].clone(), text);
+        } else {
+            let text = |indent: &str| {
+                format!(
+                    "\"configurations\": [\n{indent}  {}\n{indent}]",
+                    jsonc::to_string_indented(entry, &format!("{indent}  "))
+                )
+            };
+            self.append(&blanked, spans[""].clone(), text);
+        }
+    }
+
+    /// Adds an item to the end of the array or object at `span`, on a line
+    /// of its own indented like its siblings and below any comment trailing
+    /// the last one; `text` renders it given the indent.
+    fn append(&mut self, blanked: &str, span: Range<usize>, text: impl Fn(&str) -> String) {
+        let close = span.end - 1;
+        let last = blanked[..close].trim_end().len() - 1;
+        if last == span.start {
+            let outer = jsonc::indent_at(&self.source, span.start);
+            let inner = format!("{outer}  ");
+            let item = format!("\n{inner}{}\n{outer}", text(&inner));
+            self.source.replace_range(last + 1..close, &item);
+            return;
+        }
+        let first = blanked[span.start + 1..].len() - blanked[span.start + 1..].trim_start().len();
+        let indent = jsonc::indent_at(&self.source, span.start + 1 + first).to_string();
+        let after = last + 1;
+        // Below the rest of the last item's line, which can only hold a
+        // (blanked) comment that stays with it.
+        let line_end = blanked[after..close]
+            .find('\n')
+            .map_or(after, |newline| after + newline);
+        self.source
+            .insert_str(line_end, &format!("\n{indent}{}", text(&indent)));
+        // A trailing comma, where the file has one, now separates the items.
+        if !self.source[after..].trim_start().starts_with(',') {
+            self.source.insert(after, ',');
         }
     }
 }
@@ -43,29 +276,325 @@ pub fn load_debug_json(path: &Path) -> io::Result<DebugJson> {
         return Ok(DebugJson::default());
     }
     let contents = fs::read_to_string(path)?;
-    let parsed = serde_json::from_str(&contents).map_err(|err| {
+    DebugJson::parse(contents).map_err(|err| {
         io::Error::new(
             io::ErrorKind::InvalidData,
             format!("failed to parse {}: {err}", path.display()),
         )
-    })?;
-    Ok(parsed)
+    })
 }
 
+/// Replaces `path` atomically (a temp file renamed over it), first keeping
+/// the previous version at [`backup_path`] for [`restore_debug_json`].
 pub fn save_debug_json(path: &Path, json: &DebugJson) -> io::Result<()> {
     if let Some(dir) = path.parent() {
         fs::create_dir_all(dir)?;
     }
-    let mut file = fs::File::create(path)?;
-    let body = serde_json::to_string_pretty(json).expect("serialize debug.json");
-    file.write_all(body.as_bytes())?;
-    Ok(())
+    let temp = sibling(path, ".tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(json.as_str().as_bytes())?;
+    file.sync_all()?;
+    if path.exists() {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(&temp, path)
 }
 
-pub fn upsert_configuration(configs: &mut Vec<AdapterConfig>, entry: AdapterConfig) {
-    if let Some(existing) = configs.iter_mut().find(|cfg| cfg.label == entry.label) {
-        *existing = entry;
-    } else {
-        configs.push(entry);
+/// Where [`save_debug_json`] keeps the version it replaced.
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+/// Puts the backup of `path` back in its place.
+pub fn restore_debug_json(path: &Path) -> io::Result<()> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no backup of {} at {}", path.display(), backup.display()),
+        ));
+    }
+    fs::rename(backup, path)
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `<base> (<target>, <request>)`, as in `MyApp (sim, attach)`.
+pub fn target_label(base: &str, target: &str, request: &str) -> String {
+    format!("{base} ({target}, {request})")
+}
+
+/// What configurations for `program` are named after when no label is
+/// given: an app bundle's name, or the executable's.
+pub fn program_name(program: &Path) -> String {
+    let name = match program.extension().and_then(|ext| ext.to_str()) {
+        Some("app") => program.file_stem(),
+        _ => program.file_name(),
+    };
+    name.map_or_else(
+        || "ios-lldb".into(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// `entry` as a launch and an attach configuration for `target`, labelled
+/// `<label> (<target>, <request>)` so one debug.json can hold every variant.
+pub fn request_variants(entry: &AdapterConfig, target: &str) -> Vec<AdapterConfig> {
+    ["launch", "attach"]
+        .into_iter()
+        .map(|request| AdapterConfig {
+            label: target_label(&entry.label, target, request),
+            request: request.into(),
+            ..entry.clone()
+        })
+        .collect()
+}
+
+/// Rewrites `entry`'s paths under `workspace_root` or Xcode's DerivedData
+/// as `${workspaceRoot}`/`${derivedData}`, which the adapter expands at
+/// launch, so the configuration can be checked in.
+pub fn make_portable(entry: &mut AdapterConfig, workspace_root: &Path) {
+    let prefixes = [
+        (Variables::from_env().derived_data, "${derivedData}"),
+        (Some(workspace_root.to_path_buf()), "${workspaceRoot}"),
+    ];
+    let paths = [&mut entry.program, &mut entry.cwd]
+        .into_iter()
+        .chain(entry.dsym_path.as_mut());
+    for path in paths {
+        for (prefix, variable) in &prefixes {
+            let Some(rest) = prefix
+                .as_ref()
+                .and_then(|prefix| Path::new(path.as_str()).strip_prefix(prefix).ok())
+            else {
+                continue;
+            };
+            *path = if rest.as_os_str().is_empty() {
+                variable.to_string()
+            } else {
+                format!("{variable}/{}", rest.display())
+            };
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configurations(json: &DebugJson) -> Vec<AdapterConfig> {
+        let value = jsonc::parse(json.as_str()).unwrap();
+        value["configurations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|config| AdapterConfig::deserialize(config).ok())
+            .collect()
+    }
+
+    #[test]
+    fn env_flags_split_at_the_first_equals_sign() {
+        assert_eq!(
+            parse_env_pair("OPTS=a=b").unwrap(),
+            ("OPTS".to_string(), "a=b".to_string())
+        );
+        assert_eq!(parse_env_pair("EMPTY=").unwrap().1, "");
+        assert!(parse_env_pair("NOVALUE").is_err());
+        assert!(parse_env_pair("=x").is_err());
+    }
+
+    #[test]
+    fn portable_paths_use_placeholders() {
+        let mut entry = AdapterConfig {
+            label: "MyApp".into(),
+            adapter: "ios-lldb".into(),
+            request: "attach".into(),
+            program: "/work/MyApp/build/MyApp".into(),
+            cwd: "/work/MyApp".into(),
+            debugserver_port: 2331,
+            debugserver_host: None,
+            bundle_id: None,
+            device: None,
+            simulator: None,
+            dsym_path: Some("/work/MyApp/build/MyApp.app.dSYM".into()),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            extra: Map::new(),
+        };
+        make_portable(&mut entry, Path::new("/work/MyApp"));
+        assert_eq!(entry.program, "${workspaceRoot}/build/MyApp");
+        assert_eq!(entry.cwd, "${workspaceRoot}");
+        assert_eq!(
+            entry.dsym_path.as_deref(),
+            Some("${workspaceRoot}/build/MyApp.app.dSYM")
+        );
+
+        entry.program = "/opt/MyApp".into();
+        make_portable(&mut entry, Path::new("/work/MyApp"));
+        assert_eq!(entry.program, "/opt/MyApp");
+    }
+
+    #[test]
+    fn saving_keeps_a_backup_that_can_be_restored() {
+        let dir = std::env::temp_dir().join(format!("cli-backup-{}", std::process::id()));
+        let path = dir.join("debug.json");
+        let _ = fs::remove_dir_all(&dir);
+        assert!(restore_debug_json(&path).is_err());
+
+        save_debug_json(&path, &DebugJson::default()).unwrap();
+        assert!(!backup_path(&path).exists());
+        fs::write(&path, "// hand-edited\n{}").unwrap();
+        let json = DebugJson::parse("{ \"version\": \"0.3.0\" }".into()).unwrap();
+        save_debug_json(&path, &json).unwrap();
+        assert_eq!(load_debug_json(&path).unwrap().as_str(), json.as_str());
+        assert_eq!(
+            fs::read_to_string(backup_path(&path)).unwrap(),
+            "// hand-edited\n{}"
+        );
+        assert!(!dir.join("debug.json.tmp").exists());
+
+        restore_debug_json(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "// hand-edited\n{}");
+        assert!(!backup_path(&path).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn upserts_leave_the_rest_of_the_file_alone() {
+        let source = r#"{
+  // Shared with the team; keep the comments.
+  "version": "0.2.0",
+  "configurations": [
+    { "label": "Node", "adapter": "JavaScript", "request": "launch" },
+    {
+      "label": "MyApp",
+      "adapter": "ios-lldb",
+      "request": "attach",
+      "program": "/old/MyApp",
+      "cwd": "/old",
+      "debugserverPort": 2331,
+      // Set by hand.
+      "args": ["--verbose"],
+      "dsymPath": "/old/MyApp.dSYM",
+      "initCommands": ["settings set target.x86-disassembly-flavor intel"], // mine
+    },
+  ],
+}"#;
+        let mut json = DebugJson::parse(source.into()).unwrap();
+        assert_eq!(configurations(&json).len(), 1);
+
+        let mut entry = configurations(&json).remove(0);
+        entry.program = "/new/MyApp".into();
+        entry.debugserver_host = Some("10.0.0.2".into());
+        entry.args.clear();
+        entry.dsym_path = None;
+        entry.extra.clear();
+        json.upsert(&entry);
+        entry.label = "Second".into();
+        json.upsert(&entry);
+
+        let text = json.as_str();
+        assert!(text.starts_with("{\n  // Shared with the team; keep the comments.\n"));
+        assert!(
+            text.contains(r#"{ "label": "Node", "adapter": "JavaScript", "request": "launch" },"#)
+        );
+        let value = jsonc::parse(text).unwrap();
+        let configs = value["configurations"].as_array().unwrap();
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs[1]["program"], "/new/MyApp");
+        assert_eq!(configs[1]["debugserverHost"], "10.0.0.2");
+        assert_eq!(configs[1]["args"], json!(["--verbose"]));
+        assert_eq!(configs[1]["dsymPath"], "/old/MyApp.dSYM");
+        assert_eq!(
+            configs[1]["initCommands"][0],
+            "settings set target.x86-disassembly-flavor intel"
+        );
+        assert!(text.contains("      // Set by hand.\n"), "{text}");
+        assert!(text.contains(r#"intel"], // mine"#), "{text}");
+        assert!(
+            text.contains("\n      \"debugserverHost\": \"10.0.0.2\"\n    },"),
+            "{text}"
+        );
+        assert_eq!(configs[2]["label"], "Second");
+        assert!(
+            text.contains("\n    {\n      \"label\": \"Second\","),
+            "{text}"
+        );
+
+        let mut empty = DebugJson::parse("{}".into()).unwrap();
+        empty.upsert(&entry);
+        assert_eq!(configurations(&empty)[0].label, "Second");
+        assert!(DebugJson::parse("{ \"configurations\": {} }".into()).is_err());
+    }
+
+    #[test]
+    fn generated_labels_only_replace_the_same_target() {
+        assert_eq!(program_name(Path::new("/build/MyApp.app")), "MyApp");
+        assert_eq!(program_name(Path::new("/build/my.tool")), "my.tool");
+        let entry = |program: &str| {
+            AdapterConfig::deserialize(json!({
+                "label": target_label("MyApp", "sim", "attach"),
+                "adapter": "ios-lldb",
+                "request": "attach",
+                "program": program,
+                "cwd": "/build",
+                "debugserverPort": 2331,
+                "simulator": "booted",
+            }))
+            .unwrap()
+        };
+        let mut json = DebugJson::parse(
+            r#"{ "configurations": [{ "label": "MyApp (sim, attach) #2", "adapter": "CodeLLDB" }] }"#
+                .into(),
+        )
+        .unwrap();
+        let first = entry("/build/Debug-iphonesimulator/MyApp.app/MyApp");
+        assert_eq!(json.unique_label(&first), "MyApp (sim, attach)");
+        json.upsert(&first);
+
+        let mut moved = entry("/other/MyApp.app/MyApp");
+        moved.debugserver_port = 4000;
+        assert_eq!(json.unique_label(&moved), "MyApp (sim, attach) #3");
+        moved.label = json.unique_label(&moved);
+        json.upsert(&moved);
+
+        let mut rerun = first.clone();
+        rerun.debugserver_port = 5000;
+        assert_eq!(json.unique_label(&rerun), "MyApp (sim, attach)");
+        moved.debugserver_port = 6000;
+        assert_eq!(json.unique_label(&moved), "MyApp (sim, attach) #3");
+    }
+
+    #[test]
+    fn default_debug_json_matches_pretty_printing() {
+        let mut json = DebugJson::default();
+        let entry = AdapterConfig::deserialize(json!({
+            "label": "MyApp",
+            "adapter": "ios-lldb",
+            "request": "attach",
+            "program": "/build/MyApp",
+            "cwd": "/build",
+            "debugserverPort": 2331,
+        }))
+        .unwrap();
+        json.upsert(&entry);
+        let entry = jsonc::to_string_indented(&entry, "    ");
+        assert_eq!(
+            json.as_str(),
+            format!("{{\n  \"version\": \"0.2.0\",\n  \"configurations\": [\n    {entry}\n  ]\n}}")
+        );
+    }
+
+    #[test]
+    fn jsonc_keeps_comment_markers_inside_strings() {
+        let value = jsonc::parse(r#"{ "url": "http://host/*x*/", "s": "a,]" , }"#).unwrap();
+        assert_eq!(value["url"], "http://host/*x*/");
+        assert_eq!(value["s"], "a,]");
     }
 }
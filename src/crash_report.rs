@@ -0,0 +1,385 @@
+//! Crash reports as iOS writes them, `.ips` (JSON, iOS 15 on) or the older
+//! `.crash` text, parsed far enough to symbolicate their frames offline: the
+//! binary images with their load addresses and UUIDs, and each backtrace
+//! frame's address.
+
+use regex::Regex;
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// The report as text; `.ips` reports are rendered in the `.crash`
+    /// layout.
+    lines: Vec<String>,
+    pub images: Vec<BinaryImage>,
+    pub frames: Vec<CrashFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryImage {
+    pub name: String,
+    /// Where the image lived on the device.
+    pub path: String,
+    pub uuid: Option<[u8; 16]>,
+    pub load_address: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashFrame {
+    /// Index into [`CrashReport::images`], when the report names the image.
+    pub image: Option<usize>,
+    pub address: u64,
+    /// Every frame but a backtrace's first is a return address, just past
+    /// the call; look up `address - 1` to land on the calling line.
+    pub is_return: bool,
+    line: usize,
+    /// How much of the line, through the address, symbolication keeps.
+    keep: usize,
+}
+
+impl CrashReport {
+    /// The report with every frame `describe` names rewritten to
+    /// `<frame> <image> <address> <description>`, one line per description
+    /// (for inlined calls). Other lines are kept as they are.
+    pub fn symbolicated(
+        &self,
+        mut describe: impl FnMut(&BinaryImage, &CrashFrame) -> Vec<String>,
+    ) -> String {
+        let mut lines: Vec<Vec<String>> =
+            self.lines.iter().map(|line| vec![line.clone()]).collect();
+        for frame in &self.frames {
+            let Some(image) = frame.image.map(|index| &self.images[index]) else {
+                continue;
+            };
+            let descriptions = describe(image, frame);
+            if descriptions.is_empty() {
+                continue;
+            }
+            let kept = &self.lines[frame.line][..frame.keep];
+            lines[frame.line] = descriptions
+                .iter()
+                .map(|description| format!("{kept} {description}"))
+                .collect();
+        }
+        let mut out = lines.concat().join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+pub fn parse(text: &str) -> Result<CrashReport, String> {
+    if text.trim_start().starts_with('{') {
+        parse_ips(text)
+    } else {
+        parse_text(text)
+    }
+}
+
+/// An `.ips` report: a one-line JSON header, then the JSON body.
+fn parse_ips(text: &str) -> Result<CrashReport, String> {
+    let values = serde_json::Deserializer::from_str(text)
+        .into_iter::<Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid .ips report: {err}"))?;
+    let (header, body) = match values.as_slice() {
+        [header, body] => (header, body),
+        [body] => (&Value::Null, body),
+        _ => return Err("invalid .ips report: expected a header and a body".into()),
+    };
+    let used_images = body
+        .get("usedImages")
+        .and_then(Value::as_array)
+        .ok_or("invalid .ips report: no usedImages")?;
+    let images: Vec<BinaryImage> = used_images
+        .iter()
+        .map(|image| {
+            let path = image["path"].as_str().unwrap_or("???").to_string();
+            BinaryImage {
+                name: image["name"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| path.rsplit('/').next().unwrap_or("???").to_string()),
+                path,
+                uuid: image["uuid"].as_str().and_then(parse_uuid),
+                load_address: image["base"].as_u64().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let mut report = CrashReport {
+        lines: Vec::new(),
+        images,
+        frames: Vec::new(),
+    };
+    let field =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let process = field(body, "procName").or_else(|| field(header, "app_name"));
+    let fields = [
+        ("Process", process),
+        ("Identifier", field(header, "bundleID")),
+        ("Version", field(header, "app_version")),
+        ("OS Version", field(header, "os_version")),
+        (
+            "Exception Type",
+            field(&body["exception"], "type").map(|kind| {
+                match field(&body["exception"], "signal") {
+                    Some(signal) => format!("{kind} ({signal})"),
+                    None => kind,
+                }
+            }),
+        ),
+        ("Exception Codes", field(&body["exception"], "codes")),
+        (
+            "Triggered by Thread",
+            body["faultingThread"]
+                .as_u64()
+                .map(|thread| thread.to_string()),
+        ),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            report
+                .lines
+                .push(format!("{:<20}{value}", format!("{name}:")));
+        }
+    }
+
+    let backtraces = body
+        .get("lastExceptionBacktrace")
+        .and_then(Value::as_array)
+        .map(|frames| ("Last Exception Backtrace:".to_string(), frames));
+    let threads = body
+        .get("threads")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, thread)| {
+            let mut title = format!("Thread {index}");
+            if thread["triggered"] == true {
+                title.push_str(" Crashed");
+            }
+            title.push(':');
+            if let Some(name) = field(thread, "name") {
+                title.push_str(&format!(": {name}"));
+            }
+            if let Some(queue) = field(thread, "queue") {
+                title.push_str(&format!(": Dispatch queue: {queue}"));
+            }
+            Some((title, thread.get("frames")?.as_array()?))
+        });
+    for (title, frames) in backtraces.into_iter().chain(threads) {
+        report.lines.push(String::new());
+        report.lines.push(title);
+        for (number, frame) in frames.iter().enumerate() {
+            let index = frame["imageIndex"].as_u64().map(|index| index as usize);
+            let image = index.and_then(|index| report.images.get(index));
+            let offset = frame["imageOffset"].as_u64().unwrap_or(0);
+            let base = image.map_or(0, |image| image.load_address);
+            let address = base + offset;
+            let kept = format!(
+                "{number:<4}{:<30}\t0x{address:016x}",
+                image.map_or("???", |image| image.name.as_str())
+            );
+            let rest = match (field(frame, "symbol"), frame["symbolLocation"].as_u64()) {
+                (Some(symbol), Some(location)) => format!("{symbol} + {location}"),
+                (Some(symbol), None) => symbol,
+                _ => format!("0x{base:x} + {offset}"),
+            };
+            report.frames.push(CrashFrame {
+                image: index.filter(|index| *index < report.images.len()),
+                address,
+                is_return: number > 0,
+                line: report.lines.len(),
+                keep: kept.len(),
+            });
+            report.lines.push(format!("{kept} {rest}"));
+        }
+    }
+
+    report.lines.push(String::new());
+    report.lines.push("Binary Images:".into());
+    for image in &report.images {
+        let uuid = image.uuid.as_ref().map_or(String::new(), |uuid| {
+            uuid.iter().map(|byte| format!("{byte:02x}")).collect()
+        });
+        report.lines.push(format!(
+            "0x{:x} {} <{uuid}> {}",
+            image.load_address, image.name, image.path
+        ));
+    }
+    Ok(report)
+}
+
+/// A `.crash` report: backtraces under `Thread N:` (or `Last Exception
+/// Backtrace:`) headings, with the images listed under `Binary Images:`.
+fn parse_text(text: &str) -> Result<CrashReport, String> {
+    let heading = Regex::new(r"^(Thread \d+( Crashed)?:|Last Exception Backtrace:)").unwrap();
+    let frame_line = Regex::new(r"^(\d+)\s+(.+?)\s+(0x[0-9a-fA-F]+)(\s|$)").unwrap();
+    let image_line = Regex::new(
+        r"^\s*(0x[0-9a-fA-F]+)\s*-\s*0x[0-9a-fA-F]+\s+\+?(.+?)\s+\S+\s+<([0-9a-fA-F-]+)>\s+(.+?)\s*$",
+    )
+    .unwrap();
+
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut images = Vec::new();
+    let mut frames = Vec::new();
+    let mut in_backtrace = false;
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            in_backtrace = false;
+        } else if heading.is_match(line) {
+            in_backtrace = true;
+        } else if let Some(captures) = frame_line.captures(line).filter(|_| in_backtrace) {
+            frames.push((
+                captures[2].to_string(),
+                CrashFrame {
+                    image: None,
+                    address: parse_hex(&captures[3]).unwrap_or(0),
+                    is_return: &captures[1] != "0",
+                    line: index,
+                    keep: captures.get(3).unwrap().end(),
+                },
+            ));
+        } else if let Some(captures) = image_line.captures(line) {
+            images.push(BinaryImage {
+                name: captures[2].to_string(),
+                path: captures[4].to_string(),
+                uuid: parse_uuid(&captures[3]),
+                load_address: parse_hex(&captures[1]).unwrap_or(0),
+            });
+        }
+    }
+    if frames.is_empty() {
+        return Err("no backtraces found; expected a .crash or .ips report".into());
+    }
+    let frames = frames
+        .into_iter()
+        .map(|(name, frame)| CrashFrame {
+            image: images.iter().position(|image| image.name == name),
+            ..frame
+        })
+        .collect();
+    Ok(CrashReport {
+        lines,
+        images,
+        frames,
+    })
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+/// A UUID written with or without dashes, in either case.
+pub fn parse_uuid(text: &str) -> Option<[u8; 16]> {
+    let hex: String = text.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut uuid = [0; 16];
+    for (index, byte) in uuid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(uuid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; 16] = [
+        0xa1, 0xb2, 0xc3, 0xd4, 0xe5, 0xf6, 0x07, 0x18, 0x29, 0x3a, 0x4b, 0x5c, 0x6d, 0x7e, 0x8f,
+        0x90,
+    ];
+
+    fn describe(image: &BinaryImage, frame: &CrashFrame) -> Vec<String> {
+        if image.name != "MyApp" {
+            return Vec::new();
+        }
+        let offset = frame.address - image.load_address;
+        vec![format!("f{offset:x} (return: {})", frame.is_return)]
+    }
+
+    #[test]
+    fn text_reports_keep_their_layout() {
+        let text = "\
+Process:               MyApp [1234]
+Exception Type:  EXC_BREAKPOINT (SIGTRAP)
+
+Thread 0 Crashed:
+0   MyApp                         \t0x0000000100f2c3d4 0x100f24000 + 33748
+1   libswiftCore.dylib            \t0x00000001a0000010 swift_once + 16
+2   MyApp                         \t0x0000000100f2c500 0x100f24000 + 34048
+
+Thread 0 crashed with ARM Thread State (64-bit):
+    x0: 0x0000000000000001
+
+Binary Images:
+       0x100f24000 -        0x100f2ffff MyApp arm64  <a1b2c3d4e5f60718293a4b5c6d7e8f90> /private/var/containers/Bundle/Application/X/MyApp.app/MyApp
+       0x1a0000000 -        0x1a0ffffff libswiftCore.dylib arm64e  <00112233445566778899aabbccddeeff> /usr/lib/swift/libswiftCore.dylib
+";
+        let report = parse(text).unwrap();
+        assert_eq!(report.images.len(), 2);
+        assert_eq!(report.images[0].uuid, Some(UUID));
+        assert_eq!(report.images[0].load_address, 0x100f24000);
+        assert_eq!(
+            report.images[0].path,
+            "/private/var/containers/Bundle/Application/X/MyApp.app/MyApp"
+        );
+        assert_eq!(report.frames.len(), 3);
+        assert_eq!(report.frames[1].image, Some(1));
+
+        let symbolicated = report.symbolicated(describe);
+        let lines: Vec<&str> = symbolicated.lines().collect();
+        assert_eq!(lines.len(), text.lines().count());
+        assert_eq!(
+            lines[4],
+            "0   MyApp                         \t0x0000000100f2c3d4 f83d4 (return: false)"
+        );
+        assert_eq!(
+            lines[5],
+            "1   libswiftCore.dylib            \t0x00000001a0000010 swift_once + 16"
+        );
+        assert!(lines[6].ends_with("f8500 (return: true)"));
+        assert!(parse("Incident Identifier: X\n").is_err());
+    }
+
+    #[test]
+    fn ips_reports_are_rendered_as_text() {
+        let text = r#"{"app_name":"MyApp","bundleID":"com.example.MyApp","os_version":"iPhone OS 17.2 (21C62)","bug_type":"309"}
+{
+  "procName" : "MyApp",
+  "exception" : {"codes":"0x0000000000000001, 0x0000000100f2c3d4","type":"EXC_BREAKPOINT","signal":"SIGTRAP"},
+  "faultingThread" : 0,
+  "threads" : [
+    {"triggered":true,"queue":"com.apple.main-thread","frames":[
+      {"imageOffset":33748,"imageIndex":0},
+      {"imageOffset":16,"symbol":"swift_once","symbolLocation":16,"imageIndex":1}
+    ]},
+    {"frames":[{"imageOffset":34048,"imageIndex":0}]}
+  ],
+  "usedImages" : [
+    {"base":4310843392,"size":49152,"uuid":"a1b2c3d4-e5f6-0718-293a-4b5c6d7e8f90","path":"/private/var/containers/Bundle/Application/X/MyApp.app/MyApp","name":"MyApp","arch":"arm64"},
+    {"base":6979321856,"uuid":"00112233-4455-6677-8899-aabbccddeeff","path":"/usr/lib/swift/libswiftCore.dylib","name":"libswiftCore.dylib"}
+  ]
+}"#;
+        let report = parse(text).unwrap();
+        assert_eq!(report.images[0].uuid, Some(UUID));
+        assert_eq!(report.frames[0].address, 0x100f2c3d4);
+        assert!(!report.frames[0].is_return);
+        assert!(report.frames[1].is_return);
+        assert!(!report.frames[2].is_return);
+
+        let symbolicated = report.symbolicated(describe);
+        assert!(symbolicated.contains("Exception Type:     EXC_BREAKPOINT (SIGTRAP)\n"));
+        assert!(symbolicated
+            .contains("\nThread 0 Crashed:: Dispatch queue: com.apple.main-thread\n0   MyApp"));
+        assert!(symbolicated.contains("\t0x0000000100f2c3d4 f83d4 (return: false)\n"));
+        assert!(symbolicated.contains("\t0x00000001a0000010 swift_once + 16\n"));
+        assert!(symbolicated.contains("\nThread 1:\n0   MyApp"));
+        assert!(symbolicated.contains(
+            "\n0x100f24000 MyApp <a1b2c3d4e5f60718293a4b5c6d7e8f90> /private/var/containers"
+        ));
+    }
+}
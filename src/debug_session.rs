@@ -1,6 +1,6 @@
 use std::{
     collections::{BTreeSet, HashMap},
-    env, io,
+    io,
     path::{Path, PathBuf},
 };
 
@@ -8,13 +8,9 @@ use serde::Serialize;
 use serde_json::Value;
 use thiserror::Error;
 
-use crate::{
-    backend::{Backend, BackendStopEvent},
-    CONFIG_ENV_VAR,
-};
+use crate::backend::{Backend, BackendStopEvent};
 
 const DEFAULT_THREAD_ID: i64 = 1;
-const LOCALS_REFERENCE: i64 = 1;
 
 pub type BreakpointId = u32;
 
@@ -37,13 +33,13 @@ impl DebugSession {
         }
     }
 
-    pub fn connect_debugserver(&mut self, port: u16) -> Result<(), DebugSessionError> {
+    pub fn connect_debugserver(&mut self, host: &str, port: u16) -> Result<(), DebugSessionError> {
         self.backend
-            .connect_debugserver(port)
+            .connect_debugserver(host, port)
             .map_err(DebugSessionError::Backend)
     }
 
-    pub fn stacktrace(&self) -> Vec<Frame> {
+    pub fn stacktrace(&mut self) -> Vec<Frame> {
         self.backend
             .stack_trace(self.thread_id)
             .into_iter()
@@ -52,38 +48,51 @@ impl DebugSession {
             .collect()
     }
 
-    pub fn threads(&self) -> Vec<Value> {
+    pub fn threads(&mut self) -> Vec<Value> {
         self.backend.threads()
     }
 
-    pub fn scopes(&self) -> Vec<Value> {
-        self.backend.scopes()
+    /// Scopes of the innermost frame of the selected thread.
+    pub fn scopes(&mut self) -> Result<Vec<Value>, DebugSessionError> {
+        let frames = self.backend.stack_trace(self.thread_id);
+        let Some(frame_id) = frames
+            .first()
+            .and_then(|frame| frame.get("id"))
+            .and_then(Value::as_i64)
+        else {
+            return Ok(Vec::new());
+        };
+        self.backend
+            .scopes(frame_id)
+            .map_err(DebugSessionError::Backend)
     }
 
     pub fn continue_execution(&mut self) -> Result<Option<SessionStop>, DebugSessionError> {
         self.backend
-            .r#continue(self.thread_id)
+            .r#continue(self.thread_id, false)
             .map(|maybe_event| maybe_event.map(SessionStop::from))
             .map_err(DebugSessionError::Backend)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<SessionStop>, DebugSessionError> {
         self.backend
-            .step_over(self.thread_id)
+            .step_over(self.thread_id, false)
             .map(|maybe_event| maybe_event.map(SessionStop::from))
             .map_err(DebugSessionError::Backend)
     }
 
     pub fn step_in(&mut self) -> Result<Option<SessionStop>, DebugSessionError> {
         self.backend
-            .step_in(self.thread_id)
+            .step_in(self.thread_id, false)
             .map(|maybe_event| maybe_event.map(SessionStop::from))
             .map_err(DebugSessionError::Backend)
     }
 
+    /// Detaches from the app, leaving it running.
     pub fn disconnect(&mut self) -> Result<(), DebugSessionError> {
         self.backend
-            .disconnect()
+            .disconnect(false)
             .map_err(DebugSessionError::Backend)
     }
 
@@ -92,10 +101,7 @@ impl DebugSession {
         file: &str,
         line: u32,
     ) -> Result<Breakpoint, DebugSessionError> {
-        let entry = self
-            .file_breakpoints
-            .entry(file.to_string())
-            .or_insert_with(BTreeSet::new);
+        let entry = self.file_breakpoints.entry(file.to_string()).or_default();
         entry.insert(line as i64);
         let current_lines: Vec<i64> = entry.iter().copied().collect();
         self.backend
@@ -111,26 +117,51 @@ impl DebugSession {
         })
     }
 
-    pub fn locals(&self) -> Vec<Variable> {
-        self.variables_for_reference(LOCALS_REFERENCE)
+    pub fn locals(&mut self) -> Result<Vec<Variable>, DebugSessionError> {
+        let scopes = self.scopes()?;
+        let Some(reference) = scopes
+            .first()
+            .and_then(|scope| scope.get("variablesReference"))
+            .and_then(Value::as_i64)
+        else {
+            return Ok(Vec::new());
+        };
+        self.variables_for_reference(reference)
     }
 
-    pub fn variables_for_reference(&self, reference: i64) -> Vec<Variable> {
+    pub fn variables_for_reference(
+        &self,
+        reference: i64,
+    ) -> Result<Vec<Variable>, DebugSessionError> {
         self.backend
             .variables(reference)
-            .into_iter()
-            .map(Variable::from_backend_value)
-            .collect()
+            .map(|values| {
+                values
+                    .into_iter()
+                    .map(Variable::from_backend_value)
+                    .collect()
+            })
+            .map_err(DebugSessionError::Backend)
     }
 
-    pub fn evaluate(&self, expression: &str) -> Result<EvalResult, DebugSessionError> {
+    pub fn evaluate(&mut self, expression: &str) -> Result<EvalResult, DebugSessionError> {
         let trimmed = expression.trim();
         if trimmed.is_empty() {
             return Err(DebugSessionError::UnsupportedExpression(
                 expression.to_string(),
             ));
         }
-        let locals = self.locals();
+        if trimmed.starts_with('$') {
+            let value = self
+                .backend
+                .read_register(trimmed, Some(self.thread_id))
+                .map_err(DebugSessionError::Backend)?;
+            return Ok(EvalResult {
+                result: format!("0x{value:016x}"),
+                ty: "register".into(),
+            });
+        }
+        let locals = self.locals()?;
         if let Some(variable) = locals.iter().find(|var| var.name == trimmed) {
             return Ok(EvalResult {
                 result: variable.value.clone(),
@@ -142,7 +173,7 @@ impl DebugSession {
         ))
     }
 
-    pub fn evaluate_swift(&self, expression: &str) -> Result<EvalResult, DebugSessionError> {
+    pub fn evaluate_swift(&mut self, expression: &str) -> Result<EvalResult, DebugSessionError> {
         self.evaluate(expression)
     }
 
@@ -166,12 +197,13 @@ impl DebugSession {
         self.evaluate_watch_expressions()
     }
 
-    pub fn evaluate_watch_expressions(&self) -> Result<Vec<WatchValue>, DebugSessionError> {
+    pub fn evaluate_watch_expressions(&mut self) -> Result<Vec<WatchValue>, DebugSessionError> {
         self.watch_expressions
-            .iter()
+            .clone()
+            .into_iter()
             .map(|expr| {
-                self.evaluate(expr).map(|result| WatchValue {
-                    expression: expr.clone(),
+                self.evaluate(&expr).map(|result| WatchValue {
+                    expression: expr,
                     result,
                 })
             })
@@ -181,10 +213,6 @@ impl DebugSession {
     pub fn select_thread(&mut self, thread_id: i64) {
         self.thread_id = thread_id.max(1);
     }
-
-    pub fn program_path(&self) -> &Path {
-        self.backend.program_path()
-    }
 }
 
 #[derive(Debug, Error)]
@@ -300,25 +328,28 @@ impl From<BackendStopEvent> for SessionStop {
     }
 }
 
-pub fn init_backend() -> io::Result<Backend> {
-    if let Ok(raw) = env::var(CONFIG_ENV_VAR) {
-        if let Some(program) = parse_program_from_config(&raw)? {
-            return backend_from_program(&program);
+/// The backend for the `launch` or `attach` request carrying `arguments`,
+/// symbolicating their `program` (with `dsymPath` preferred for DWARF).
+/// When there is no such program here (an attach by pid, or a program that
+/// only exists on the device) it starts without symbols, which the session
+/// loads from the process once attached.
+pub fn init_backend(arguments: &Value) -> io::Result<Backend> {
+    match argument_path(arguments, "program").filter(|program| program.exists()) {
+        Some(program) => {
+            let dsym = argument_path(arguments, "dsymPath");
+            Backend::new_from_app(&program, dsym.as_deref()).map_err(io::Error::other)
         }
+        None => Ok(Backend::without_symbols()),
     }
-    let exe = env::current_exe()?;
-    backend_from_program(&exe)
 }
 
 pub fn backend_from_program(program: &Path) -> io::Result<Backend> {
-    Backend::new_from_app(program).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    Backend::new_from_app(program, None).map_err(io::Error::other)
 }
 
-pub fn parse_program_from_config(raw: &str) -> io::Result<Option<PathBuf>> {
-    let value: Value =
-        serde_json::from_str(raw).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-    Ok(value
-        .get("program")
+fn argument_path(arguments: &Value, key: &str) -> Option<PathBuf> {
+    arguments
+        .get(key)
         .and_then(Value::as_str)
-        .map(PathBuf::from))
+        .map(PathBuf::from)
 }
@@ -1,10 +1,12 @@
 use std::{
+    collections::{HashMap, VecDeque},
     fmt,
     io::{self, Read, Write},
     net::TcpStream,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use serde::Deserialize;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -17,13 +19,92 @@ pub enum GdbRemoteError {
     BadChecksum,
     #[error("unexpected reply: {0}")]
     UnexpectedReply(String),
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+    #[error("packet still corrupt after {0} retransmissions")]
+    RetriesExhausted(usize),
 }
 
+impl GdbRemoteError {
+    /// True when the socket itself failed (EOF, reset, broken pipe), as
+    /// opposed to debugserver rejecting a packet. Such errors are worth a
+    /// reconnect.
+    pub fn is_connection_lost(&self) -> bool {
+        matches!(
+            self,
+            GdbRemoteError::Io(err) if matches!(
+                err.kind(),
+                io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+            )
+        )
+    }
+}
+
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+const CPU_TYPE_ARM64_32: u32 = 0x0200_000c;
+const CPU_SUBTYPE_ARM64E: u32 = 2;
+
+/// Host used when launch/attach arguments do not name one.
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// How long `vAttach` may take to suspend the target and report a stop.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long debugserver may take to spawn the process for `A`/`vRun`.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Memory transfer size used when the stub does not advertise `PacketSize`.
+const DEFAULT_MEMORY_CHUNK_SIZE: usize = 1024;
+
+/// Upper bound on one memory transfer, however large a packet the stub takes.
+const MAX_MEMORY_CHUNK_SIZE: usize = 0x10000;
+
+/// Upper bound on `qRegisterInfo` queries, in case a stub never answers
+/// with the terminating error.
+const MAX_REGISTER_INFOS: u32 = 1024;
+
+/// How many times a packet is resent after a NAK, or requested again after a
+/// checksum mismatch, before giving up.
+const MAX_RETRANSMITS: usize = 3;
+
+/// Room in a packet for framing and the `Maddr,len:` header.
+const PACKET_OVERHEAD: usize = 64;
+
+/// The largest packet we take, advertised in `qSupported` so the stub can
+/// send bulk replies whole.
+const PACKET_SIZE: usize = 0x20000;
+
 #[derive(Debug, Clone)]
 pub struct StopReply {
     pub signal: u8,
     pub thread_id: Option<u64>,
     pub reason: StopReason,
+    /// Every thread in the process, from lldb's `threads:` key.
+    pub threads: Vec<u64>,
+    /// Program counters parallel to `threads`, from `thread-pcs:`.
+    pub thread_pcs: Vec<u64>,
+    /// Expedited `NN:value` registers of the stopped thread, as target bytes.
+    pub registers: HashMap<u32, Vec<u8>>,
+}
+
+impl StopReply {
+    /// The program counter reported for `tid` in `thread-pcs:`.
+    pub fn thread_pc(&self, tid: u64) -> Option<u64> {
+        self.threads
+            .iter()
+            .position(|&id| id == tid)
+            .and_then(|idx| self.thread_pcs.get(idx).copied())
+    }
+
+    /// An expedited register of the stopped thread.
+    pub fn register(&self, regnum: u32, little_endian: bool) -> Option<u64> {
+        register_value_from_bytes(self.registers.get(&regnum)?, little_endian)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,18 +112,380 @@ pub enum StopReason {
     Breakpoint,
     Step,
     Signal,
+    /// The thread is suspended but did not cause the stop (`T00`).
+    NoReason,
     Unknown(String),
 }
 
+/// A mapped (or unmapped) range of the debuggee's address space from
+/// `qMemoryRegionInfo`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub size: u64,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    pub name: Option<String>,
+}
+
+impl MemoryRegion {
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.size)
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        (self.start..self.end()).contains(&address)
+    }
+}
+
+/// One image from `jGetLoadedDynamicLibrariesInfos`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LoadedImage {
+    /// Runtime address of the image's Mach-O header.
+    pub load_address: u64,
+    pub pathname: String,
+    #[serde(default)]
+    pub uuid: Option<String>,
+}
+
+impl LoadedImage {
+    /// The image UUID as raw bytes, parsed from debugserver's dashed hex form.
+    pub fn uuid_bytes(&self) -> Option<[u8; 16]> {
+        let hex: String = self
+            .uuid
+            .as_deref()?
+            .chars()
+            .filter(|c| *c != '-')
+            .collect();
+        hex_decode(&hex)?.try_into().ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct LoadedImagesReply {
+    images: Vec<LoadedImage>,
+}
+
+/// The debuggee as described by `qProcessInfo`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u64,
+    pub cputype: Option<u32>,
+    pub cpusubtype: Option<u32>,
+    pub ostype: Option<String>,
+    pub os_version: Option<String>,
+    pub ptr_size: Option<u32>,
+    pub little_endian: bool,
+}
+
+impl ProcessInfo {
+    /// Architecture name derived from the Mach-O cputype/cpusubtype pair.
+    pub fn arch(&self) -> Option<&'static str> {
+        arch_name(self.cputype?, self.cpusubtype)
+    }
+}
+
+/// The machine debugserver runs on, as described by `qHostInfo`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostInfo {
+    pub cputype: Option<u32>,
+    pub cpusubtype: Option<u32>,
+    pub ostype: Option<String>,
+    pub os_version: Option<String>,
+    /// The OS build, e.g. `21C62`.
+    pub os_build: Option<String>,
+    pub ptr_size: Option<u32>,
+    pub little_endian: bool,
+}
+
+impl HostInfo {
+    pub fn arch(&self) -> Option<&'static str> {
+        arch_name(self.cputype?, self.cpusubtype)
+    }
+}
+
+/// Where one register lives in the `g` packet, as described by
+/// `qRegisterInfo` or a built-in layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterInfo {
+    pub name: String,
+    pub alt_name: Option<String>,
+    /// lldb's generic role (`pc`, `sp`, `fp`, `ra`, `flags`, ...).
+    pub generic: Option<String>,
+    pub regnum: u32,
+    /// Byte offset into the `g` reply.
+    pub offset: usize,
+    /// Size in bytes.
+    pub size: usize,
+}
+
+/// The ordered set of registers making up a `g` reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterLayout {
+    registers: Vec<RegisterInfo>,
+}
+
+impl RegisterLayout {
+    pub fn new(registers: Vec<RegisterInfo>) -> Self {
+        Self { registers }
+    }
+
+    /// debugserver's arm64 general-purpose registers: `x0`–`x28`, `fp`,
+    /// `lr`, `sp`, `pc` (8 bytes each) followed by the 4-byte `cpsr`.
+    pub fn arm64() -> Self {
+        let mut registers = Vec::new();
+        let mut offset = 0;
+        for regnum in 0..=33u32 {
+            let (name, alt_name, generic) = match regnum {
+                29 => ("fp".to_string(), Some("x29"), Some("fp")),
+                30 => ("lr".to_string(), Some("x30"), Some("ra")),
+                31 => ("sp".to_string(), Some("x31"), Some("sp")),
+                32 => ("pc".to_string(), None, Some("pc")),
+                33 => ("cpsr".to_string(), None, Some("flags")),
+                n => (format!("x{n}"), None, None),
+            };
+            let size = if regnum == 33 { 4 } else { 8 };
+            registers.push(RegisterInfo {
+                name,
+                alt_name: alt_name.map(str::to_string),
+                generic: generic.map(str::to_string),
+                regnum,
+                offset,
+                size,
+            });
+            offset += size;
+        }
+        Self { registers }
+    }
+
+    /// debugserver's x86_64 general-purpose registers (simulator targets):
+    /// `rax`–`r15`, `rip`, `rflags`, `cs`, `fs`, `gs`, 8 bytes each.
+    pub fn x86_64() -> Self {
+        const NAMES: [&str; 21] = [
+            "rax", "rbx", "rcx", "rdx", "rdi", "rsi", "rbp", "rsp", "r8", "r9", "r10", "r11",
+            "r12", "r13", "r14", "r15", "rip", "rflags", "cs", "fs", "gs",
+        ];
+        let registers = NAMES
+            .iter()
+            .enumerate()
+            .map(|(index, name)| RegisterInfo {
+                name: name.to_string(),
+                alt_name: None,
+                generic: match *name {
+                    "rbp" => Some("fp"),
+                    "rsp" => Some("sp"),
+                    "rip" => Some("pc"),
+                    "rflags" => Some("flags"),
+                    _ => None,
+                }
+                .map(str::to_string),
+                regnum: index as u32,
+                offset: index * 8,
+                size: 8,
+            })
+            .collect();
+        Self { registers }
+    }
+
+    /// The built-in layout for a Mach-O cputype, if there is one.
+    pub fn for_cputype(cputype: u32) -> Option<Self> {
+        match cputype {
+            CPU_TYPE_ARM64 => Some(Self::arm64()),
+            CPU_TYPE_X86_64 => Some(Self::x86_64()),
+            _ => None,
+        }
+    }
+
+    pub fn registers(&self) -> &[RegisterInfo] {
+        &self.registers
+    }
+
+    /// Finds a register by name, alternate name, or generic role.
+    pub fn find(&self, name: &str) -> Option<&RegisterInfo> {
+        self.registers.iter().find(|info| {
+            info.name == name
+                || info.alt_name.as_deref() == Some(name)
+                || info.generic.as_deref() == Some(name)
+        })
+    }
+}
+
+impl Default for RegisterLayout {
+    fn default() -> Self {
+        Self::arm64()
+    }
+}
+
+/// A snapshot of every register from one `g` reply.
+#[derive(Debug, Clone)]
+pub struct RegisterFile {
+    layout: RegisterLayout,
+    little_endian: bool,
+    /// Decoded reply bytes; `None` where the stub sent `xx` (unavailable).
+    bytes: Vec<Option<u8>>,
+}
+
+impl RegisterFile {
+    pub fn parse(
+        layout: &RegisterLayout,
+        reply: &str,
+        little_endian: bool,
+    ) -> Result<Self, GdbRemoteError> {
+        if !reply.len().is_multiple_of(2) {
+            return Err(GdbRemoteError::UnexpectedReply(reply.to_string()));
+        }
+        let bytes = (0..reply.len())
+            .step_by(2)
+            .map(|idx| match &reply[idx..idx + 2] {
+                "xx" => Ok(None),
+                pair => u8::from_str_radix(pair, 16)
+                    .map(Some)
+                    .map_err(|_| GdbRemoteError::UnexpectedReply(reply.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            layout: layout.clone(),
+            little_endian,
+            bytes,
+        })
+    }
+
+    /// Raw target-order bytes of a register, or `None` when the reply was
+    /// too short or marked it unavailable.
+    pub fn raw(&self, name: &str) -> Option<Vec<u8>> {
+        let info = self.layout.find(name)?;
+        self.bytes
+            .get(info.offset..info.offset + info.size)?
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// A register of up to 8 bytes as an integer.
+    pub fn value(&self, name: &str) -> Option<u64> {
+        register_value_from_bytes(&self.raw(name)?, self.little_endian)
+    }
+
+    pub fn pc(&self) -> Option<u64> {
+        self.value("pc")
+    }
+
+    pub fn sp(&self) -> Option<u64> {
+        self.value("sp")
+    }
+
+    pub fn fp(&self) -> Option<u64> {
+        self.value("fp")
+    }
+
+    pub fn lr(&self) -> Option<u64> {
+        self.value("ra")
+    }
+
+    pub fn layout(&self) -> &RegisterLayout {
+        &self.layout
+    }
+}
+
+/// One entry of debugserver's `jThreadsInfo` reply.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThreadInfo {
+    pub tid: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub signal: Option<u8>,
+    #[serde(default)]
+    pub qname: Option<String>,
+    /// Expedited registers keyed by decimal register number, as hex bytes
+    /// in target order.
+    #[serde(default)]
+    pub registers: HashMap<String, String>,
+}
+
+impl ThreadInfo {
+    /// Raw bytes of an expedited register, if debugserver sent it.
+    pub fn register(&self, regnum: u32) -> Option<Vec<u8>> {
+        hex_decode(self.registers.get(&regnum.to_string())?)
+    }
+
+    /// The thread's stop reason in stop-reply form.
+    pub fn stop_reply(&self) -> StopReply {
+        let signal = self.signal.unwrap_or(0);
+        let reason = match self.reason.as_deref() {
+            Some(reason) => parse_stop_reason(reason),
+            None if signal == 0 => StopReason::NoReason,
+            None => StopReason::Signal,
+        };
+        StopReply {
+            signal,
+            thread_id: Some(self.tid),
+            reason,
+            threads: Vec::new(),
+            thread_pcs: Vec::new(),
+            registers: self
+                .registers
+                .iter()
+                .filter_map(|(regnum, value)| Some((regnum.parse().ok()?, hex_decode(value)?)))
+                .collect(),
+        }
+    }
+
+    /// The thread name, falling back to its dispatch queue name.
+    pub fn display_name(&self) -> Option<&str> {
+        self.name
+            .as_deref()
+            .filter(|name| !name.is_empty())
+            .or(self.qname.as_deref())
+    }
+}
+
 pub struct GdbRemoteClient {
     stream: TcpStream,
+    pub host: String,
     pub port: u16,
     pub no_ack_mode: bool,
+    has_process: bool,
+    host_info: Option<HostInfo>,
+    little_endian: bool,
+    register_layout: RegisterLayout,
+    /// Whether the stub accepts lldb's binary `x`/`X` memory packets;
+    /// probed on first use.
+    binary_memory: Option<bool>,
+    /// Bytes moved per memory packet, sized from the stub's `PacketSize`.
+    memory_chunk_size: usize,
+    /// The stub's `PacketSize`, when it advertised one.
+    packet_size: Option<usize>,
+    /// Whether the stub answers `jThreadsInfo`; probed on first use.
+    json_threads_info: Option<bool>,
+    /// Stop replies delivered as `%Stop` notifications, waiting to be
+    /// consumed by `poll_stop`/`wait_for_stop`.
+    pending_stops: VecDeque<StopReply>,
+    /// Set after a `%Stop` until `vStopped` has drained the stub's queue.
+    needs_vstopped: bool,
+    /// Whether register packets accept a `;thread:<tid>;` suffix
+    /// (`QThreadSuffixSupported`); otherwise `Hg` selects the thread.
+    thread_suffix: bool,
+    /// Set once `qRegisterInfo` has described the target's registers, which
+    /// then take precedence over the built-in layouts.
+    registers_discovered: bool,
+}
+
+/// One framed unit read off the wire.
+enum Frame {
+    /// `$...#xx`: a reply to our last packet, or a stop reply.
+    Packet(Vec<u8>),
+    /// `%...#xx`: an asynchronous notification, never acknowledged.
+    Notification(Vec<u8>),
 }
 
 impl fmt::Debug for GdbRemoteClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("GdbRemoteClient")
+            .field("host", &self.host)
             .field("port", &self.port)
             .field("no_ack_mode", &self.no_ack_mode)
             .finish()
@@ -50,14 +493,30 @@ impl fmt::Debug for GdbRemoteClient {
 }
 
 impl GdbRemoteClient {
-    pub fn connect(port: u16) -> Result<Self, GdbRemoteError> {
-        let stream = TcpStream::connect(("127.0.0.1", port))?;
+    /// Connects to debugserver at `host:port`. `host` may be a name or an
+    /// IPv4/IPv6 address, so a networked device or a VM works as well as a
+    /// local forward.
+    pub fn connect(host: &str, port: u16) -> Result<Self, GdbRemoteError> {
+        let stream = TcpStream::connect((host, port))?;
         stream.set_read_timeout(Some(Duration::from_millis(200)))?;
         stream.set_write_timeout(Some(Duration::from_millis(200)))?;
         let mut client = Self {
             stream,
+            host: host.to_string(),
             port,
             no_ack_mode: false,
+            has_process: false,
+            host_info: None,
+            little_endian: true,
+            register_layout: RegisterLayout::default(),
+            binary_memory: None,
+            memory_chunk_size: DEFAULT_MEMORY_CHUNK_SIZE,
+            packet_size: None,
+            json_threads_info: None,
+            pending_stops: VecDeque::new(),
+            needs_vstopped: false,
+            thread_suffix: false,
+            registers_discovered: false,
         };
         client.handshake()?;
         Ok(client)
@@ -78,8 +537,12 @@ impl GdbRemoteClient {
         }
 
         // Announce capabilities.
-        let _ = self.send_packet("qSupported:multiprocess+;qRelocInsn+");
-        if let Ok(reply) = self.read_packet() {
+        let features = format!("qSupported:multiprocess+;qRelocInsn+;PacketSize={PACKET_SIZE:x}");
+        if let Ok(Some(reply)) = self.send_packet(&features) {
+            if let Some(size) = parse_packet_size(&reply) {
+                self.packet_size = Some(size);
+                self.memory_chunk_size = memory_chunk_size(size.min(PACKET_SIZE));
+            }
             if reply.contains("QStartNoAckMode+") {
                 if let Ok(resp) = self.send_packet("QStartNoAckMode") {
                     if resp.as_deref() == Some("OK") {
@@ -89,9 +552,263 @@ impl GdbRemoteClient {
             }
         }
 
+        // lldb extensions: thread-scoped register packets, and stop replies
+        // that list every thread with its pc.
+        if let Ok(Some(reply)) = self.send_packet("QThreadSuffixSupported") {
+            self.thread_suffix = reply == "OK";
+        }
+        let _ = self.send_packet("QListThreadsInStopReply");
+
+        // Learn the architecture before anything decodes registers.
+        if let Ok(Some(reply)) = self.send_packet("qHostInfo") {
+            if let Some(info) = parse_host_info(&reply) {
+                self.little_endian = info.little_endian;
+                if let Some(layout) = info.cputype.and_then(RegisterLayout::for_cputype) {
+                    self.register_layout = layout;
+                }
+                self.host_info = Some(info);
+            }
+        }
+
         // Query current stop reason to synchronize state.
         let _ = self.send_packet("?")?;
-        let _ = self.read_packet();
+        if let Ok(reply) = self.read_packet() {
+            self.has_process = parse_stop_reply(&reply).is_some();
+        }
+        if self.has_process {
+            self.discover_registers();
+        }
+        Ok(())
+    }
+
+    fn process_started(&mut self) {
+        self.has_process = true;
+        self.discover_registers();
+    }
+
+    /// Replaces the built-in register layout with the one debugserver
+    /// describes, when it implements `qRegisterInfo`. Registers are only
+    /// known once a process exists.
+    fn discover_registers(&mut self) {
+        match self.query_register_infos() {
+            Ok(registers) if !registers.is_empty() => {
+                self.register_layout = RegisterLayout::new(registers);
+                self.registers_discovered = true;
+            }
+            Ok(_) => {}
+            Err(err) => eprintln!("qRegisterInfo failed, using the built-in layout: {err}"),
+        }
+    }
+
+    /// Iterates `qRegisterInfo<N>` until the stub answers with an error.
+    /// Returns no registers when the packet is unsupported.
+    pub fn query_register_infos(&mut self) -> Result<Vec<RegisterInfo>, GdbRemoteError> {
+        let mut registers: Vec<RegisterInfo> = Vec::new();
+        for regnum in 0..MAX_REGISTER_INFOS {
+            let reply = self
+                .send_packet(&format!("qRegisterInfo{regnum:x}"))?
+                .unwrap_or_default();
+            if is_error_reply(&reply) {
+                break;
+            }
+            let next_offset = registers.last().map_or(0, |info| info.offset + info.size);
+            let info = parse_register_info(regnum, &reply, next_offset)
+                .ok_or(GdbRemoteError::UnexpectedReply(reply))?;
+            registers.push(info);
+        }
+        Ok(registers)
+    }
+
+    /// The `qHostInfo` reply from the handshake, if the stub sent one.
+    pub fn host_info(&self) -> Option<&HostInfo> {
+        self.host_info.as_ref()
+    }
+
+    /// Target byte order from `qHostInfo`/`qProcessInfo`; little-endian until
+    /// the stub says otherwise.
+    pub fn is_little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    /// Pointer size in bytes, defaulting to 8.
+    pub fn pointer_size(&self) -> u32 {
+        self.host_info
+            .as_ref()
+            .and_then(|info| info.ptr_size)
+            .unwrap_or(8)
+    }
+
+    /// Whether debugserver reported a live process during the handshake.
+    pub fn has_process(&self) -> bool {
+        self.has_process
+    }
+
+    pub fn set_disable_aslr(&mut self, disable: bool) -> Result<(), GdbRemoteError> {
+        self.expect_ok(&format!("QSetDisableASLR:{}", u8::from(disable)))
+    }
+
+    pub fn set_working_dir(&mut self, dir: &str) -> Result<(), GdbRemoteError> {
+        self.expect_ok(&format!("QSetWorkingDir:{}", hex_encode(dir.as_bytes())))
+    }
+
+    /// Redirects one of the next launch's standard streams (0 = stdin,
+    /// 1 = stdout, 2 = stderr) to `path` on the device.
+    pub fn set_stdio_path(&mut self, stream: u8, path: &str) -> Result<(), GdbRemoteError> {
+        let packet = match stream {
+            0 => "QSetSTDIN",
+            1 => "QSetSTDOUT",
+            2 => "QSetSTDERR",
+            other => {
+                return Err(GdbRemoteError::UnexpectedReply(format!(
+                    "no standard stream {other}"
+                )))
+            }
+        };
+        self.expect_ok(&format!("{packet}:{}", hex_encode(path.as_bytes())))
+    }
+
+    pub fn set_environment_variable(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> Result<(), GdbRemoteError> {
+        let entry = format!("{key}={value}");
+        if needs_hex_encoding(&entry) {
+            self.expect_ok(&format!(
+                "QEnvironmentHexEncoded:{}",
+                hex_encode(entry.as_bytes())
+            ))
+        } else {
+            self.expect_ok(&format!("QEnvironment:{entry}"))
+        }
+    }
+
+    /// Starts `program` with `args` using the `A` packet, falling back to
+    /// `vRun` when the stub does not implement `A`. The new process is left
+    /// stopped at its entry point.
+    pub fn launch(&mut self, program: &str, args: &[String]) -> Result<(), GdbRemoteError> {
+        let argv: Vec<&str> = std::iter::once(program)
+            .chain(args.iter().map(String::as_str))
+            .collect();
+
+        let mut packet = String::from("A");
+        for (index, arg) in argv.iter().enumerate() {
+            let encoded = hex_encode(arg.as_bytes());
+            if index > 0 {
+                packet.push(',');
+            }
+            packet.push_str(&format!("{},{index},{encoded}", encoded.len()));
+        }
+
+        // debugserver answers only once the process is spawned, which can
+        // take far longer than the socket read timeout on a device.
+        self.write_packet(packet.as_bytes())?;
+        match self.await_reply(LAUNCH_TIMEOUT, "the launch")?.as_str() {
+            "OK" => {
+                self.write_packet(b"qLaunchSuccess")?;
+                match self.await_reply(LAUNCH_TIMEOUT, "qLaunchSuccess")?.as_str() {
+                    "OK" | "" => {}
+                    resp => return Err(GdbRemoteError::Remote(resp.to_string())),
+                }
+                self.process_started();
+                Ok(())
+            }
+            "" => {
+                let mut packet = String::from("vRun");
+                for arg in &argv {
+                    packet.push(';');
+                    packet.push_str(&hex_encode(arg.as_bytes()));
+                }
+                self.write_packet(packet.as_bytes())?;
+                let reply = self.await_reply(LAUNCH_TIMEOUT, "the launch")?;
+                if parse_stop_reply(&reply).is_none() {
+                    return Err(GdbRemoteError::Remote(reply));
+                }
+                self.process_started();
+                Ok(())
+            }
+            resp => Err(GdbRemoteError::Remote(resp.to_string())),
+        }
+    }
+
+    /// Attaches to a running process by pid with `vAttach`. The process is
+    /// left stopped and its stop reply returned.
+    pub fn attach(&mut self, pid: u32) -> Result<StopReply, GdbRemoteError> {
+        self.write_packet(format!("vAttach;{pid:x}").as_bytes())?;
+        self.await_attach(ATTACH_TIMEOUT, &format!("attach to pid {pid}"))
+    }
+
+    /// Waits for a process whose executable is named `name` to launch and
+    /// attaches to it with `vAttachWait`. Gives up after `timeout`, asking
+    /// debugserver to stop waiting.
+    pub fn attach_wait(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<StopReply, GdbRemoteError> {
+        let packet = format!("vAttachWait;{}", hex_encode(name.as_bytes()));
+        self.write_packet(packet.as_bytes())?;
+        let result = self.await_attach(timeout, &format!("a process named {name}"));
+        if matches!(result, Err(GdbRemoteError::Timeout(_))) {
+            self.stream.write_all(&[0x03])?;
+            self.stream.flush()?;
+            // Swallow the error reply for the abandoned wait.
+            let _ = self.await_reply(Duration::from_secs(1), "cancelled attach");
+        }
+        result
+    }
+
+    fn await_attach(&mut self, timeout: Duration, what: &str) -> Result<StopReply, GdbRemoteError> {
+        let reply = self.await_reply(timeout, what)?;
+        let stop = parse_stop_reply(&reply).ok_or(GdbRemoteError::Remote(reply))?;
+        self.process_started();
+        Ok(stop)
+    }
+
+    /// Reads the next packet, tolerating replies that take longer than the
+    /// socket read timeout to arrive.
+    fn await_reply(&mut self, timeout: Duration, what: &str) -> Result<String, GdbRemoteError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut probe = [0u8; 1];
+            match self.stream.peek(&mut probe) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(_) => return self.read_packet(),
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    if Instant::now() >= deadline {
+                        return Err(GdbRemoteError::Timeout(what.to_string()));
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Detaches from the process with `D`, leaving it running and
+    /// debugserver free to exit cleanly.
+    pub fn detach(&mut self) -> Result<(), GdbRemoteError> {
+        self.expect_ok("D")?;
+        self.has_process = false;
+        Ok(())
+    }
+
+    /// Kills the process with `k`. debugserver answers with an `X` exit
+    /// reply, or not at all once the process is already gone.
+    pub fn kill(&mut self) -> Result<(), GdbRemoteError> {
+        self.write_packet(b"k")?;
+        match self.read_packet() {
+            Ok(reply) if reply.starts_with('X') || reply.starts_with('W') || reply == "OK" => {}
+            Ok(reply) => return Err(GdbRemoteError::UnexpectedReply(reply)),
+            Err(GdbRemoteError::Io(err))
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut
+                    || err.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(err) => return Err(err),
+        }
+        self.has_process = false;
         Ok(())
     }
 
@@ -99,25 +816,511 @@ impl GdbRemoteClient {
         self.expect_ok(&format!("Z0,{address:x},1"))
     }
 
-    #[allow(dead_code)]
     pub fn clear_software_breakpoint(&mut self, address: u64) -> Result<(), GdbRemoteError> {
         self.expect_ok(&format!("z0,{address:x},1"))
     }
 
+    /// Reads `len` bytes at `address`, using binary `x` packets when the
+    /// stub supports them and hex `m` packets otherwise. A read that runs
+    /// into unmapped memory part-way returns the bytes read so far; a read
+    /// that fails on its first chunk is an error.
+    pub fn read_memory(&mut self, address: u64, len: usize) -> Result<Vec<u8>, GdbRemoteError> {
+        let binary = self.supports_binary_memory()?;
+        let mut out = Vec::with_capacity(len);
+        for (chunk_address, chunk_len) in memory_chunks(address, len, self.memory_chunk_size) {
+            let bytes = if binary {
+                let reply =
+                    self.request_bytes(format!("x{chunk_address:x},{chunk_len:x}").as_bytes())?;
+                // A 3-byte `Exx` is only an error when we did not ask for 3 bytes.
+                let is_error =
+                    reply.is_empty() || (chunk_len != 3 && reply.len() == 3 && reply[0] == b'E');
+                if is_error {
+                    Err(String::from_utf8_lossy(&reply).into_owned())
+                } else {
+                    Ok(reply)
+                }
+            } else {
+                let reply = self
+                    .send_packet(&format!("m{chunk_address:x},{chunk_len:x}"))?
+                    .unwrap_or_default();
+                if is_error_reply(&reply) {
+                    Err(reply)
+                } else {
+                    Ok(hex_decode(&reply).ok_or(GdbRemoteError::UnexpectedReply(reply))?)
+                }
+            };
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(reply) if out.is_empty() => return Err(GdbRemoteError::Remote(reply)),
+                Err(_) => break,
+            };
+            let short = bytes.len() < chunk_len;
+            out.extend(bytes);
+            if short {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Writes `bytes` at `address` with binary `X` packets, or hex `M`
+    /// packets when the stub lacks binary support.
+    pub fn write_memory(&mut self, address: u64, bytes: &[u8]) -> Result<(), GdbRemoteError> {
+        let binary = self.supports_binary_memory()?;
+        for (chunk_address, chunk_len) in
+            memory_chunks(address, bytes.len(), self.memory_chunk_size)
+        {
+            let start = (chunk_address - address) as usize;
+            let data = &bytes[start..start + chunk_len];
+            let reply = if binary {
+                let mut payload = format!("X{chunk_address:x},{chunk_len:x}:").into_bytes();
+                payload.extend(escape_binary(data));
+                String::from_utf8_lossy(&self.request_bytes(&payload)?).into_owned()
+            } else {
+                self.send_packet(&format!(
+                    "M{chunk_address:x},{chunk_len:x}:{}",
+                    hex_encode(data)
+                ))?
+                .unwrap_or_default()
+            };
+            if reply != "OK" {
+                return Err(GdbRemoteError::Remote(reply));
+            }
+        }
+        Ok(())
+    }
+
+    fn supports_binary_memory(&mut self) -> Result<bool, GdbRemoteError> {
+        if let Some(supported) = self.binary_memory {
+            return Ok(supported);
+        }
+        let supported = self.send_packet("x0,0")?.as_deref() == Some("OK");
+        self.binary_memory = Some(supported);
+        Ok(supported)
+    }
+
+    /// Opens a file on the remote host with `vFile:open`, returning its
+    /// descriptor. `flags` and `mode` use the gdb protocol's values
+    /// (`0` is read-only).
+    pub fn file_open(&mut self, path: &str, flags: u32, mode: u32) -> Result<i64, GdbRemoteError> {
+        let packet = format!(
+            "vFile:open:{},{flags:x},{mode:x}",
+            hex_encode(path.as_bytes())
+        );
+        let reply = self.request_bytes(packet.as_bytes())?;
+        parse_file_reply(&reply).map(|(fd, _)| fd)
+    }
+
+    /// Reads up to `count` bytes at `offset` from a remote descriptor. An
+    /// empty result means end of file.
+    pub fn file_pread(
+        &mut self,
+        fd: i64,
+        count: usize,
+        offset: u64,
+    ) -> Result<Vec<u8>, GdbRemoteError> {
+        let packet = format!("vFile:pread:{fd:x},{count:x},{offset:x}");
+        let reply = self.request_bytes(packet.as_bytes())?;
+        let (len, data) = parse_file_reply(&reply)?;
+        Ok(data[..(len.max(0) as usize).min(data.len())].to_vec())
+    }
+
+    pub fn file_close(&mut self, fd: i64) -> Result<(), GdbRemoteError> {
+        let reply = self.request_bytes(format!("vFile:close:{fd:x}").as_bytes())?;
+        parse_file_reply(&reply).map(|_| ())
+    }
+
+    /// Reads a whole file from the remote host, e.g. an app binary or dylib
+    /// with no local copy.
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, GdbRemoteError> {
+        let fd = self.file_open(path, 0, 0)?;
+        let mut contents = Vec::new();
+        let result = loop {
+            match self.file_pread(fd, self.memory_chunk_size, contents.len() as u64) {
+                Ok(chunk) if chunk.is_empty() => break Ok(()),
+                Ok(chunk) => contents.extend(chunk),
+                Err(err) => break Err(err),
+            }
+        };
+        let closed = self.file_close(fd);
+        result.and(closed).map(|()| contents)
+    }
+
+    /// Describes the region containing `address`. Returns `None` when the
+    /// stub does not implement `qMemoryRegionInfo`.
+    pub fn memory_region_info(
+        &mut self,
+        address: u64,
+    ) -> Result<Option<MemoryRegion>, GdbRemoteError> {
+        let reply = self
+            .send_packet(&format!("qMemoryRegionInfo:{address:x}"))?
+            .unwrap_or_default();
+        if reply.is_empty() {
+            return Ok(None);
+        }
+        if is_error_reply(&reply) {
+            return Err(GdbRemoteError::Remote(reply));
+        }
+        parse_memory_region(&reply)
+            .map(Some)
+            .ok_or(GdbRemoteError::UnexpectedReply(reply))
+    }
+
+    /// Reads one register with the `p` packet, returning its raw target-order bytes.
+    pub fn read_register(
+        &mut self,
+        regnum: u32,
+        tid: Option<u64>,
+    ) -> Result<Vec<u8>, GdbRemoteError> {
+        let packet = self.thread_scoped(format!("p{regnum:x}"), tid)?;
+        let reply = self.send_packet(&packet)?.unwrap_or_default();
+        if is_error_reply(&reply) {
+            return Err(GdbRemoteError::Remote(reply));
+        }
+        hex_decode(&reply).ok_or(GdbRemoteError::UnexpectedReply(reply))
+    }
+
+    /// Reads one register of up to 8 bytes as an integer.
+    pub fn read_register_value(
+        &mut self,
+        regnum: u32,
+        tid: Option<u64>,
+    ) -> Result<u64, GdbRemoteError> {
+        let bytes = self.read_register(regnum, tid)?;
+        register_value_from_bytes(&bytes, self.little_endian).ok_or_else(|| {
+            GdbRemoteError::UnexpectedReply(format!(
+                "register {regnum} is {} bytes wide",
+                bytes.len()
+            ))
+        })
+    }
+
+    /// Writes one register with the `P` packet, encoding `value` in target
+    /// byte order at the width the register layout gives it.
+    pub fn write_register(
+        &mut self,
+        regnum: u32,
+        value: u64,
+        tid: Option<u64>,
+    ) -> Result<(), GdbRemoteError> {
+        let size = self
+            .register_layout
+            .registers()
+            .iter()
+            .find(|info| info.regnum == regnum)
+            .map_or(8, |info| info.size);
+        let bytes = register_bytes_from_value(value, size, self.little_endian);
+        let packet = self.thread_scoped(format!("P{regnum:x}={}", hex_encode(&bytes)), tid)?;
+        self.expect_ok(&packet)
+    }
+
+    /// Replaces the layout used to decode `g` replies, e.g. with one built
+    /// from `qRegisterInfo`.
+    pub fn set_register_layout(&mut self, layout: RegisterLayout) {
+        self.register_layout = layout;
+    }
+
+    pub fn register_layout(&self) -> &RegisterLayout {
+        &self.register_layout
+    }
+
+    /// Reads every register of `tid` (or the current thread) with the `g`
+    /// packet, or one `p` packet per register when the whole file would not
+    /// fit in a packet the stub accepts.
+    pub fn read_registers(&mut self, tid: Option<u64>) -> Result<RegisterFile, GdbRemoteError> {
+        let file_size = self
+            .register_layout
+            .registers()
+            .iter()
+            .map(|info| info.offset + info.size)
+            .max()
+            .unwrap_or(0);
+        if self
+            .packet_size
+            .is_some_and(|size| file_size * 2 + PACKET_OVERHEAD > size)
+        {
+            let mut bytes = vec![None; file_size];
+            for info in self.register_layout.registers().to_vec() {
+                match self.read_register(info.regnum, tid) {
+                    Ok(value) => {
+                        for (slot, byte) in
+                            bytes[info.offset..].iter_mut().zip(value).take(info.size)
+                        {
+                            *slot = Some(byte);
+                        }
+                    }
+                    Err(GdbRemoteError::Remote(_)) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            return Ok(RegisterFile {
+                layout: self.register_layout.clone(),
+                little_endian: self.little_endian,
+                bytes,
+            });
+        }
+        let packet = self.thread_scoped("g".to_string(), tid)?;
+        let reply = self.send_packet(&packet)?.unwrap_or_default();
+        if is_error_reply(&reply) {
+            return Err(GdbRemoteError::Remote(reply));
+        }
+        RegisterFile::parse(&self.register_layout, &reply, self.little_endian)
+    }
+
+    /// Aims a register packet at `tid`: with a `;thread:` suffix when the
+    /// stub supports one, otherwise by selecting the thread with `Hg` first.
+    fn thread_scoped(
+        &mut self,
+        packet: String,
+        tid: Option<u64>,
+    ) -> Result<String, GdbRemoteError> {
+        let Some(tid) = tid else {
+            return Ok(packet);
+        };
+        if self.thread_suffix {
+            return Ok(format!("{packet};thread:{tid:x};"));
+        }
+        self.expect_ok(&format!("Hg{tid:x}"))?;
+        Ok(packet)
+    }
+
     pub fn continue_all(&mut self) -> Result<(), GdbRemoteError> {
         self.expect_ok("vCont;c")
     }
 
-    pub fn step_thread(&mut self, _thread_id: i64) -> Result<(), GdbRemoteError> {
-        self.expect_ok("vCont;s")
+    /// Resumes only `thread_id`, leaving every other thread suspended.
+    pub fn continue_thread(&mut self, thread_id: i64) -> Result<(), GdbRemoteError> {
+        self.expect_ok(&vcont_packet("c", thread_id, true))
+    }
+
+    /// Delivers `signal` to `thread_id` as it resumes; the other threads keep
+    /// running unless `single_thread` is set.
+    pub fn continue_with_signal(
+        &mut self,
+        signal: u8,
+        thread_id: i64,
+        single_thread: bool,
+    ) -> Result<(), GdbRemoteError> {
+        self.expect_ok(&vcont_packet(
+            &format!("C{signal:02x}"),
+            thread_id,
+            single_thread,
+        ))
+    }
+
+    /// Single-steps `thread_id`. Other threads continue while it steps
+    /// unless `single_thread` is set.
+    pub fn step_thread(
+        &mut self,
+        thread_id: i64,
+        single_thread: bool,
+    ) -> Result<(), GdbRemoteError> {
+        self.expect_ok(&vcont_packet("s", thread_id, single_thread))
+    }
+
+    /// Queries `qProcessInfo` and, when the architecture has a built-in
+    /// register layout, switches to it.
+    pub fn process_info(&mut self) -> Result<ProcessInfo, GdbRemoteError> {
+        let reply = self.send_packet("qProcessInfo")?.unwrap_or_default();
+        if is_error_reply(&reply) {
+            return Err(GdbRemoteError::Remote(reply));
+        }
+        let info = parse_process_info(&reply).ok_or(GdbRemoteError::UnexpectedReply(reply))?;
+        self.little_endian = info.little_endian;
+        if !self.registers_discovered {
+            if let Some(layout) = info.cputype.and_then(RegisterLayout::for_cputype) {
+                self.register_layout = layout;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Fetches every loaded image. Returns `None` when the stub does not
+    /// implement `jGetLoadedDynamicLibrariesInfos`.
+    pub fn loaded_libraries(&mut self) -> Result<Option<Vec<LoadedImage>>, GdbRemoteError> {
+        self.query_loaded_libraries(&serde_json::json!({ "fetch_all_solibs": true }))
+    }
+
+    /// Fetches only the images whose Mach-O headers are at `addresses`, as
+    /// reported by a dyld load notification.
+    pub fn loaded_libraries_at(
+        &mut self,
+        addresses: &[u64],
+    ) -> Result<Option<Vec<LoadedImage>>, GdbRemoteError> {
+        self.query_loaded_libraries(&serde_json::json!({ "solib_addresses": addresses }))
+    }
+
+    fn query_loaded_libraries(
+        &mut self,
+        arguments: &serde_json::Value,
+    ) -> Result<Option<Vec<LoadedImage>>, GdbRemoteError> {
+        // The JSON argument contains `}`, which must be escaped on the wire.
+        let mut payload = b"jGetLoadedDynamicLibrariesInfos:".to_vec();
+        payload.extend(escape_binary(arguments.to_string().as_bytes()));
+        let reply = String::from_utf8_lossy(&self.request_bytes(&payload)?).into_owned();
+        if reply.is_empty() {
+            return Ok(None);
+        }
+        if is_error_reply(&reply) {
+            return Err(GdbRemoteError::Remote(reply));
+        }
+        parse_loaded_images(&reply).map(Some)
+    }
+
+    /// Lists every thread of the process, following `qsThreadInfo`
+    /// continuations until the stub reports the end of the list.
+    pub fn thread_ids(&mut self) -> Result<Vec<u64>, GdbRemoteError> {
+        let mut ids = Vec::new();
+        let mut query = "qfThreadInfo";
+        loop {
+            let reply = self.send_packet(query)?.unwrap_or_default();
+            match parse_thread_info_reply(&reply) {
+                Some(ThreadInfoReply::More(batch)) => ids.extend(batch),
+                Some(ThreadInfoReply::End) => return Ok(ids),
+                None => return Err(GdbRemoteError::UnexpectedReply(reply)),
+            }
+            query = "qsThreadInfo";
+        }
+    }
+
+    /// Fetches every thread with its stop reason, name, and expedited
+    /// registers in one `jThreadsInfo` round trip. Returns `None` when the
+    /// stub does not implement the packet.
+    pub fn threads_info(&mut self) -> Result<Option<Vec<ThreadInfo>>, GdbRemoteError> {
+        if self.json_threads_info == Some(false) {
+            return Ok(None);
+        }
+        let reply = self.send_packet("jThreadsInfo")?.unwrap_or_default();
+        if is_error_reply(&reply) {
+            self.json_threads_info = Some(false);
+            return Ok(None);
+        }
+        self.json_threads_info = Some(true);
+        parse_threads_info(&reply).map(Some)
+    }
+
+    /// Asks why one thread is stopped. Returns `None` when the stub does not
+    /// implement `qThreadStopInfo`.
+    pub fn thread_stop_info(
+        &mut self,
+        thread_id: u64,
+    ) -> Result<Option<StopReply>, GdbRemoteError> {
+        let reply = self
+            .send_packet(&format!("qThreadStopInfo{thread_id:x}"))?
+            .unwrap_or_default();
+        if reply.is_empty() {
+            return Ok(None);
+        }
+        parse_stop_reply(&reply)
+            .map(Some)
+            .ok_or(GdbRemoteError::UnexpectedReply(reply))
+    }
+
+    /// Asks why the process is stopped with `?`. Returns `None` when
+    /// debugserver has no stopped process to report.
+    pub fn stop_status(&mut self) -> Result<Option<StopReply>, GdbRemoteError> {
+        self.send_packet("?")?;
+        let reply = self.read_packet()?;
+        Ok(parse_stop_reply(&reply))
     }
 
     pub fn wait_for_stop(&mut self) -> Result<StopReply, GdbRemoteError> {
         loop {
-            let packet = self.read_packet()?;
-            if let Some(reply) = parse_stop_reply(&packet) {
+            if let Some(reply) = self.next_pending_stop()? {
+                return Ok(reply);
+            }
+            if let Frame::Packet(data) = self.read_frame()? {
+                if let Some(reply) = parse_stop_reply(&String::from_utf8_lossy(&data)) {
+                    return Ok(reply);
+                }
+            }
+        }
+    }
+
+    /// Returns the next stop reply if one arrives within the read timeout, or
+    /// `None` while the target is still running.
+    pub fn poll_stop(&mut self) -> Result<Option<StopReply>, GdbRemoteError> {
+        loop {
+            if let Some(reply) = self.next_pending_stop()? {
+                return Ok(Some(reply));
+            }
+            let mut probe = [0u8; 1];
+            match self.stream.peek(&mut probe) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(_) => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err.into()),
+            }
+            if let Frame::Packet(data) = self.read_frame()? {
+                if let Some(reply) = parse_stop_reply(&String::from_utf8_lossy(&data)) {
+                    return Ok(Some(reply));
+                }
+            }
+        }
+    }
+
+    /// Halts the running process by writing the out-of-band `\x03` byte and
+    /// waits up to `timeout` for the stop reply it provokes. The reply may
+    /// instead report a stop that raced with the interrupt.
+    pub fn interrupt(&mut self, timeout: Duration) -> Result<StopReply, GdbRemoteError> {
+        self.stream.write_all(&[0x03])?;
+        self.stream.flush()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(reply) = self.poll_stop()? {
                 return Ok(reply);
             }
+            if Instant::now() >= deadline {
+                return Err(GdbRemoteError::Timeout(
+                    "stop reply after interrupt".to_string(),
+                ));
+            }
+        }
+    }
+
+    /// Whether a stop delivered by notification is queued, waiting for
+    /// [`GdbRemoteClient::poll_stop`].
+    pub fn has_pending_stop(&self) -> bool {
+        !self.pending_stops.is_empty()
+    }
+
+    /// A second handle on the connection, for another thread to wait on
+    /// with `peek` without taking any bytes from this client.
+    pub fn try_clone_stream(&self) -> io::Result<TcpStream> {
+        self.stream.try_clone()
+    }
+
+    /// Pops a stop delivered by notification. The first time one is taken,
+    /// `vStopped` is sent until the stub answers `OK`, queueing any further
+    /// stops it was holding back.
+    fn next_pending_stop(&mut self) -> Result<Option<StopReply>, GdbRemoteError> {
+        let Some(reply) = self.pending_stops.pop_front() else {
+            return Ok(None);
+        };
+        if self.needs_vstopped {
+            self.needs_vstopped = false;
+            loop {
+                self.write_packet(b"vStopped")?;
+                let next = self.read_packet()?;
+                match parse_stop_reply(&next) {
+                    Some(stop) => self.pending_stops.push_back(stop),
+                    None => break,
+                }
+            }
+        }
+        Ok(Some(reply))
+    }
+
+    fn handle_notification(&mut self, data: &[u8]) {
+        let text = String::from_utf8_lossy(data);
+        if let Some(reply) = parse_stop_notification(&text) {
+            self.pending_stops.push_back(reply);
+            self.needs_vstopped = true;
         }
     }
 
@@ -131,26 +1334,7 @@ impl GdbRemoteClient {
     }
 
     fn send_packet(&mut self, payload: &str) -> Result<Option<String>, GdbRemoteError> {
-        let mut packet = String::with_capacity(payload.len() + 4);
-        packet.push('$');
-        packet.push_str(payload);
-        packet.push('#');
-        let checksum = payload.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
-        packet.push_str(&format!("{:02x}", checksum));
-        self.stream.write_all(packet.as_bytes())?;
-        self.stream.flush()?;
-
-        if !self.no_ack_mode {
-            let mut ack = [0u8; 1];
-            self.stream.read_exact(&mut ack)?;
-            if ack[0] != b'+' {
-                return Err(GdbRemoteError::UnexpectedReply(format!(
-                    "expected ack '+', got {:?}",
-                    ack[0] as char
-                )));
-            }
-        }
-
+        self.write_packet(payload.as_bytes())?;
         if payload.starts_with('v')
             || payload.starts_with('c')
             || payload.starts_with('s')
@@ -162,37 +1346,471 @@ impl GdbRemoteClient {
         }
     }
 
+    /// Sends a packet whose payload may carry escaped binary data and returns
+    /// the decoded reply bytes.
+    fn request_bytes(&mut self, payload: &[u8]) -> Result<Vec<u8>, GdbRemoteError> {
+        self.write_packet(payload)?;
+        self.read_packet_bytes()
+    }
+
+    fn write_packet(&mut self, payload: &[u8]) -> Result<(), GdbRemoteError> {
+        let mut packet = Vec::with_capacity(payload.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(payload);
+        packet.push(b'#');
+        let checksum = payload.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        packet.extend_from_slice(format!("{:02x}", checksum).as_bytes());
+
+        for _ in 0..=MAX_RETRANSMITS {
+            self.stream.write_all(&packet)?;
+            self.stream.flush()?;
+            if self.no_ack_mode {
+                return Ok(());
+            }
+            let mut ack = [0u8; 1];
+            self.stream.read_exact(&mut ack)?;
+            match ack[0] {
+                b'+' => return Ok(()),
+                // NAK: the stub saw a corrupt packet; send it again.
+                b'-' => continue,
+                other => {
+                    return Err(GdbRemoteError::UnexpectedReply(format!(
+                        "expected ack '+', got {:?}",
+                        other as char
+                    )))
+                }
+            }
+        }
+        Err(GdbRemoteError::RetriesExhausted(MAX_RETRANSMITS))
+    }
+
     fn read_packet(&mut self) -> Result<String, GdbRemoteError> {
-        let mut start = [0u8; 1];
+        self.read_packet_bytes()
+            .map(|data| String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// Reads one packet, verifies its checksum over the wire bytes, and
+    /// undoes run-length encoding and `}` escaping. Notifications that arrive
+    /// first are set aside for the stop queue.
+    fn read_packet_bytes(&mut self) -> Result<Vec<u8>, GdbRemoteError> {
         loop {
-            self.stream.read_exact(&mut start)?;
-            if start[0] == b'$' {
-                break;
-            } else if start[0] == b'+' && self.no_ack_mode {
-                continue;
+            match self.read_frame()? {
+                Frame::Packet(data) => return Ok(data),
+                Frame::Notification(data) => self.handle_notification(&data),
             }
         }
-        let mut data = Vec::new();
+    }
+
+    /// Reads the next frame. In ack mode a corrupt packet is NAKed so the
+    /// stub resends it, up to `MAX_RETRANSMITS` times; without acks there is
+    /// no way to ask again and the checksum error is returned.
+    fn read_frame(&mut self) -> Result<Frame, GdbRemoteError> {
+        let mut retries = 0;
         loop {
-            let mut byte = [0u8; 1];
-            self.stream.read_exact(&mut byte)?;
-            if byte[0] == b'#' {
-                break;
+            let raw = read_raw_frame(&mut self.stream)?;
+            if raw.notification {
+                if !raw.checksum_ok {
+                    return Err(GdbRemoteError::BadChecksum);
+                }
+                return decode_packet_data(&raw.data).map(Frame::Notification);
+            }
+            if raw.checksum_ok {
+                if !self.no_ack_mode {
+                    self.stream.write_all(b"+")?;
+                }
+                return decode_packet_data(&raw.data).map(Frame::Packet);
+            }
+            if self.no_ack_mode {
+                return Err(GdbRemoteError::BadChecksum);
+            }
+            if retries == MAX_RETRANSMITS {
+                return Err(GdbRemoteError::RetriesExhausted(MAX_RETRANSMITS));
+            }
+            retries += 1;
+            self.stream.write_all(b"-")?;
+            self.stream.flush()?;
+        }
+    }
+}
+
+/// One `$`/`%` frame as read off the wire, before decoding.
+struct RawFrame {
+    notification: bool,
+    data: Vec<u8>,
+    checksum_ok: bool,
+}
+
+/// Scans to the next `$` or `%` and reads a frame up to its checksum. Bytes
+/// outside a frame (acks, line noise) are skipped, and a `$` inside one means
+/// the previous frame was truncated, so reading restarts from there.
+fn read_raw_frame<R: Read>(reader: &mut R) -> io::Result<RawFrame> {
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b'$' || byte[0] == b'%' {
+            break;
+        }
+    }
+    let mut notification = byte[0] == b'%';
+    let mut data = Vec::new();
+    loop {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' => break,
+            b'$' => {
+                notification = false;
+                data.clear();
             }
-            data.push(byte[0]);
+            other => data.push(other),
         }
-        let mut checksum_bytes = [0u8; 2];
-        self.stream.read_exact(&mut checksum_bytes)?;
-        let sent = u8::from_str_radix(std::str::from_utf8(&checksum_bytes).unwrap_or("00"), 16)
-            .map_err(|_| GdbRemoteError::BadChecksum)?;
-        let computed = data.iter().copied().fold(0u8, |acc, b| acc.wrapping_add(b));
-        if sent != computed {
-            return Err(GdbRemoteError::BadChecksum);
+    }
+    let mut checksum_bytes = [0u8; 2];
+    reader.read_exact(&mut checksum_bytes)?;
+    let sent = std::str::from_utf8(&checksum_bytes)
+        .ok()
+        .and_then(|text| u8::from_str_radix(text, 16).ok());
+    let computed = data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    Ok(RawFrame {
+        notification,
+        data,
+        checksum_ok: sent == Some(computed),
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ThreadInfoReply {
+    More(Vec<u64>),
+    End,
+}
+
+/// Parses a `qfThreadInfo`/`qsThreadInfo` reply: `m<id>,<id>...` or `l`.
+fn parse_thread_info_reply(reply: &str) -> Option<ThreadInfoReply> {
+    if reply == "l" {
+        return Some(ThreadInfoReply::End);
+    }
+    let ids = reply
+        .strip_prefix('m')?
+        .split(',')
+        .map(parse_thread_id)
+        .collect::<Option<Vec<_>>>()?;
+    Some(ThreadInfoReply::More(ids))
+}
+
+/// Parses the `key:value;` pairs of a `qProcessInfo` reply. Numeric fields
+/// are hex except `ptrsize`, which debugserver sends in decimal.
+fn parse_process_info(reply: &str) -> Option<ProcessInfo> {
+    let mut info = ProcessInfo {
+        little_endian: true,
+        ..ProcessInfo::default()
+    };
+    let mut saw_pid = false;
+    for (key, value) in reply.split(';').filter_map(|pair| pair.split_once(':')) {
+        let hex = || u32::from_str_radix(value, 16).ok();
+        match key {
+            "pid" => {
+                info.pid = u64::from_str_radix(value, 16).ok()?;
+                saw_pid = true;
+            }
+            "cputype" => info.cputype = hex(),
+            "cpusubtype" => info.cpusubtype = hex(),
+            "ostype" => info.ostype = Some(value.to_string()),
+            "os_version" => info.os_version = Some(value.to_string()),
+            "ptrsize" => info.ptr_size = value.parse().ok(),
+            "endian" => info.little_endian = value != "big",
+            _ => {}
         }
-        if !self.no_ack_mode {
-            self.stream.write_all(b"+")?;
+    }
+    saw_pid.then_some(info)
+}
+
+/// Parses a `qHostInfo` reply. Unlike `qProcessInfo`, debugserver sends the
+/// cputype fields here in decimal.
+fn parse_host_info(reply: &str) -> Option<HostInfo> {
+    let mut info = HostInfo {
+        little_endian: true,
+        ..HostInfo::default()
+    };
+    let mut saw_field = false;
+    for (key, value) in reply.split(';').filter_map(|pair| pair.split_once(':')) {
+        saw_field = true;
+        match key {
+            "cputype" => info.cputype = value.parse().ok(),
+            "cpusubtype" => info.cpusubtype = value.parse().ok(),
+            "ostype" => info.ostype = Some(value.to_string()),
+            "os_version" => info.os_version = Some(value.to_string()),
+            // Hex-encoded ASCII, unlike `os_version`.
+            "os_build" => {
+                info.os_build =
+                    hex_decode(value).map(|bytes| String::from_utf8_lossy(&bytes).into())
+            }
+            "ptrsize" => info.ptr_size = value.parse().ok(),
+            "endian" => info.little_endian = value != "big",
+            _ => {}
         }
-        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+    saw_field.then_some(info)
+}
+
+/// Splits a `vFile` reply, `F<result>[,<errno>][;<data>]`, into its result
+/// and attachment. A result of -1 is an error carrying the errno.
+fn parse_file_reply(reply: &[u8]) -> Result<(i64, &[u8]), GdbRemoteError> {
+    let unexpected =
+        || GdbRemoteError::UnexpectedReply(String::from_utf8_lossy(reply).into_owned());
+    let body = reply.strip_prefix(b"F").ok_or_else(unexpected)?;
+    let (header, data) = match body.iter().position(|&b| b == b';') {
+        Some(split) => (&body[..split], &body[split + 1..]),
+        None => (body, &body[body.len()..]),
+    };
+    let header = std::str::from_utf8(header).map_err(|_| unexpected())?;
+    let (result, errno) = match header.split_once(',') {
+        Some((result, errno)) => (result, Some(errno)),
+        None => (header, None),
+    };
+    let result = match result.strip_prefix('-') {
+        Some(magnitude) => -i64::from_str_radix(magnitude, 16).map_err(|_| unexpected())?,
+        None => i64::from_str_radix(result, 16).map_err(|_| unexpected())?,
+    };
+    if result < 0 {
+        return Err(GdbRemoteError::Remote(format!(
+            "vFile failed with errno {}",
+            errno.unwrap_or("?")
+        )));
+    }
+    Ok((result, data))
+}
+
+/// Parses one `qRegisterInfo` reply, e.g.
+/// `name:fp;alt-name:x29;bitsize:64;offset:232;generic:fp;`. Registers
+/// without an `offset` follow the previous one in the `g` packet.
+fn parse_register_info(regnum: u32, reply: &str, next_offset: usize) -> Option<RegisterInfo> {
+    let mut name = None;
+    let mut alt_name = None;
+    let mut generic = None;
+    let mut bitsize = None;
+    let mut offset = None;
+    for (key, value) in reply.split(';').filter_map(|pair| pair.split_once(':')) {
+        match key {
+            "name" => name = Some(value.to_string()),
+            "alt-name" => alt_name = Some(value.to_string()),
+            "generic" => generic = Some(value.to_string()),
+            "bitsize" => bitsize = value.parse::<usize>().ok(),
+            "offset" => offset = value.parse::<usize>().ok(),
+            _ => {}
+        }
+    }
+    Some(RegisterInfo {
+        name: name?,
+        alt_name,
+        generic,
+        regnum,
+        offset: offset.unwrap_or(next_offset),
+        size: bitsize? / 8,
+    })
+}
+
+/// Parses `start:<hex>;size:<hex>;permissions:rwx;name:<hex>;`. Unmapped
+/// gaps come back with no permissions.
+fn parse_memory_region(reply: &str) -> Option<MemoryRegion> {
+    let mut region = MemoryRegion::default();
+    let (mut saw_start, mut saw_size) = (false, false);
+    for (key, value) in reply.split(';').filter_map(|pair| pair.split_once(':')) {
+        match key {
+            "start" => {
+                region.start = u64::from_str_radix(value, 16).ok()?;
+                saw_start = true;
+            }
+            "size" => {
+                region.size = u64::from_str_radix(value, 16).ok()?;
+                saw_size = true;
+            }
+            "permissions" => {
+                region.readable = value.contains('r');
+                region.writable = value.contains('w');
+                region.executable = value.contains('x');
+            }
+            "name" => {
+                region.name = hex_decode(value).map(|bytes| String::from_utf8_lossy(&bytes).into())
+            }
+            _ => {}
+        }
+    }
+    (saw_start && saw_size).then_some(region)
+}
+
+fn parse_loaded_images(reply: &str) -> Result<Vec<LoadedImage>, GdbRemoteError> {
+    serde_json::from_str::<LoadedImagesReply>(reply)
+        .map(|parsed| parsed.images)
+        .map_err(|err| {
+            GdbRemoteError::UnexpectedReply(format!("jGetLoadedDynamicLibrariesInfos: {err}"))
+        })
+}
+
+fn parse_threads_info(reply: &str) -> Result<Vec<ThreadInfo>, GdbRemoteError> {
+    serde_json::from_str(reply)
+        .map_err(|err| GdbRemoteError::UnexpectedReply(format!("jThreadsInfo: {err}")))
+}
+
+/// Parses a hex thread id, accepting the multiprocess `p<pid>.<tid>` form.
+fn parse_thread_id(text: &str) -> Option<u64> {
+    let tid = match text.strip_prefix('p') {
+        Some(rest) => rest.split_once('.')?.1,
+        None => text,
+    };
+    u64::from_str_radix(tid, 16).ok()
+}
+
+/// Escapes `$`, `#`, `}` and `*` in a binary payload as `}` followed by the
+/// byte XOR 0x20.
+fn escape_binary(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for &byte in data {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            out.push(b'}');
+            out.push(byte ^ 0x20);
+        } else {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// Expands `*` run-length encoding (repeat the previous byte `count - 29`
+/// more times) and `}` escapes in a received packet body.
+fn decode_packet_data(data: &[u8]) -> Result<Vec<u8>, GdbRemoteError> {
+    let malformed = || GdbRemoteError::UnexpectedReply(String::from_utf8_lossy(data).into_owned());
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().copied();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => out.push(bytes.next().ok_or_else(malformed)? ^ 0x20),
+            b'*' => {
+                let count = bytes.next().ok_or_else(malformed)?;
+                let repeat = count.checked_sub(29).ok_or_else(malformed)?;
+                let previous = *out.last().ok_or_else(malformed)?;
+                out.extend(std::iter::repeat_n(previous, repeat as usize));
+            }
+            _ => out.push(byte),
+        }
+    }
+    Ok(out)
+}
+
+/// Empty replies mean "unsupported"; `Exx` replies carry an errno.
+fn is_error_reply(reply: &str) -> bool {
+    reply.is_empty() || (reply.starts_with('E') && reply.len() == 3)
+}
+
+/// The stub's maximum packet size from its `qSupported` reply (hex).
+fn parse_packet_size(reply: &str) -> Option<usize> {
+    reply
+        .split(';')
+        .find_map(|feature| feature.strip_prefix("PacketSize="))
+        .and_then(|size| usize::from_str_radix(size, 16).ok())
+}
+
+/// The largest memory transfer whose packet fits in `packet_size` bytes.
+/// Hex encoding and binary escaping can both double the payload.
+fn memory_chunk_size(packet_size: usize) -> usize {
+    (packet_size.saturating_sub(PACKET_OVERHEAD) / 2).clamp(1, MAX_MEMORY_CHUNK_SIZE)
+}
+
+/// Splits `[address, address + len)` into `(address, len)` transfers of at
+/// most `chunk` bytes.
+fn memory_chunks(address: u64, len: usize, chunk: usize) -> Vec<(u64, usize)> {
+    (0..len)
+        .step_by(chunk)
+        .map(|offset| (address + offset as u64, chunk.min(len - offset)))
+        .collect()
+}
+
+/// Decodes a target-order register of at most 8 bytes.
+fn register_value_from_bytes(bytes: &[u8], little_endian: bool) -> Option<u64> {
+    if bytes.len() > 8 {
+        return None;
+    }
+    let mut buf = [0u8; 8];
+    if little_endian {
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(buf))
+    } else {
+        buf[8 - bytes.len()..].copy_from_slice(bytes);
+        Some(u64::from_be_bytes(buf))
+    }
+}
+
+/// Encodes `value` as a target-order register of `size` bytes, zero-filling
+/// registers wider than 8 bytes.
+fn register_bytes_from_value(value: u64, size: usize, little_endian: bool) -> Vec<u8> {
+    if little_endian {
+        let mut bytes = value.to_le_bytes().to_vec();
+        bytes.resize(size, 0);
+        bytes
+    } else {
+        let be = value.to_be_bytes();
+        let mut bytes = vec![0; size.saturating_sub(8)];
+        bytes.extend_from_slice(&be[8usize.saturating_sub(size)..]);
+        bytes
+    }
+}
+
+fn arch_name(cputype: u32, cpusubtype: Option<u32>) -> Option<&'static str> {
+    match (cputype, cpusubtype) {
+        (CPU_TYPE_ARM64, Some(CPU_SUBTYPE_ARM64E)) => Some("arm64e"),
+        (CPU_TYPE_ARM64, _) => Some("arm64"),
+        (CPU_TYPE_ARM64_32, _) => Some("arm64_32"),
+        (CPU_TYPE_X86_64, _) => Some("x86_64"),
+        _ => None,
+    }
+}
+
+/// Builds a `vCont` packet applying `action` to `thread_id` and, unless
+/// `single_thread` is set, `c` to every other thread. Non-positive ids are
+/// not real threads, so the action applies to the whole process instead.
+fn vcont_packet(action: &str, thread_id: i64, single_thread: bool) -> String {
+    if thread_id <= 0 {
+        return format!("vCont;{action}");
+    }
+    if single_thread {
+        format!("vCont;{action}:{thread_id:x}")
+    } else if action == "c" {
+        "vCont;c".to_string()
+    } else {
+        format!("vCont;{action}:{thread_id:x};c")
+    }
+}
+
+/// `QEnvironment` cannot carry packet metacharacters or non-printable bytes.
+fn needs_hex_encoding(text: &str) -> bool {
+    text.bytes()
+        .any(|b| matches!(b, b'$' | b'#' | b'*' | b'}') || !(b.is_ascii_graphic() || b == b' '))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses a `Stop:<stop reply>` notification body; other notification
+/// kinds are ignored.
+fn parse_stop_notification(body: &str) -> Option<StopReply> {
+    parse_stop_reply(body.strip_prefix("Stop:")?)
+}
+
+fn parse_stop_reason(reason: &str) -> StopReason {
+    match reason {
+        "breakpoint" => StopReason::Breakpoint,
+        "single-step" | "trace" => StopReason::Step,
+        "signal" => StopReason::Signal,
+        other => StopReason::Unknown(other.to_string()),
     }
 }
 
@@ -206,29 +1824,54 @@ fn parse_stop_reply(reply: &str) -> Option<StopReply> {
             signal: sig,
             thread_id: None,
             reason: StopReason::Signal,
+            threads: Vec::new(),
+            thread_pcs: Vec::new(),
+            registers: HashMap::new(),
         });
     }
     if reply.starts_with('T') {
         let sig = u8::from_str_radix(&reply[1..3], 16).ok()?;
-        let mut reason = StopReason::Unknown("signal".into());
+        let mut reason = StopReason::Signal;
+        let mut saw_reason = false;
         let mut thread_id = None;
+        let mut threads = Vec::new();
+        let mut thread_pcs = Vec::new();
+        let mut registers = HashMap::new();
         for part in reply[3..].split(';') {
             if let Some(rest) = part.strip_prefix("thread:") {
-                if let Ok(id) = u64::from_str_radix(rest, 16) {
+                if let Some(id) = parse_thread_id(rest) {
                     thread_id = Some(id);
                 }
+            } else if let Some(rest) = part.strip_prefix("threads:") {
+                threads = rest.split(',').filter_map(parse_thread_id).collect();
+            } else if let Some(rest) = part.strip_prefix("thread-pcs:") {
+                thread_pcs = rest
+                    .split(',')
+                    .filter_map(|pc| u64::from_str_radix(pc, 16).ok())
+                    .collect();
             } else if let Some(rest) = part.strip_prefix("reason:") {
-                reason = match rest {
-                    "breakpoint" => StopReason::Breakpoint,
-                    "single-step" => StopReason::Step,
-                    other => StopReason::Unknown(other.to_string()),
-                };
+                reason = parse_stop_reason(rest);
+                saw_reason = true;
+            } else if let Some((key, value)) = part.split_once(':') {
+                if !key.is_empty() && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    if let (Ok(regnum), Some(bytes)) =
+                        (u32::from_str_radix(key, 16), hex_decode(value))
+                    {
+                        registers.insert(regnum, bytes);
+                    }
+                }
             }
         }
+        if sig == 0 && !saw_reason {
+            reason = StopReason::NoReason;
+        }
         return Some(StopReply {
             signal: sig,
             thread_id,
             reason,
+            threads,
+            thread_pcs,
+            registers,
         });
     }
     None
@@ -250,6 +1893,320 @@ mod tests {
         assert_eq!(String::from_utf8(buf.clone()).unwrap(), "$Z0,1000,1#d4");
     }
 
+    #[test]
+    fn hex_encode_matches_protocol_encoding() {
+        assert_eq!(hex_encode(b"/tmp/a"), "2f746d702f61");
+        assert_eq!(hex_encode(b""), "");
+    }
+
+    #[test]
+    fn hex_decode_round_trips() {
+        assert_eq!(hex_decode("2f746d70"), Some(b"/tmp".to_vec()));
+        assert_eq!(hex_decode("abc"), None);
+        assert_eq!(hex_decode("zz"), None);
+    }
+
+    #[test]
+    fn register_file_parses_arm64_g_reply() {
+        let layout = RegisterLayout::arm64();
+        let mut reply = String::new();
+        for regnum in 0..33u64 {
+            reply.push_str(&hex_encode(&(0x1000 + regnum).to_le_bytes()));
+        }
+        reply.push_str("00000060");
+        let file = RegisterFile::parse(&layout, &reply, true).unwrap();
+        assert_eq!(file.value("x0"), Some(0x1000));
+        assert_eq!(file.fp(), Some(0x1000 + 29));
+        assert_eq!(file.lr(), Some(0x1000 + 30));
+        assert_eq!(file.sp(), Some(0x1000 + 31));
+        assert_eq!(file.pc(), Some(0x1000 + 32));
+        assert_eq!(file.value("x29"), file.fp());
+        assert_eq!(file.value("cpsr"), Some(0x6000_0000));
+    }
+
+    #[test]
+    fn register_file_handles_short_and_unavailable_bytes() {
+        let layout = RegisterLayout::arm64();
+        let reply = format!("{}xxxxxxxxxxxxxxxx", "11".repeat(8));
+        let file = RegisterFile::parse(&layout, &reply, true).unwrap();
+        assert_eq!(file.value("x0"), Some(0x1111_1111_1111_1111));
+        assert_eq!(file.value("x1"), None);
+        assert_eq!(file.pc(), None);
+        assert!(RegisterFile::parse(&layout, "abc", true).is_err());
+    }
+
+    #[test]
+    fn register_values_round_trip_little_endian() {
+        let bytes = register_bytes_from_value(0x1122_3344, 4, true);
+        assert_eq!(bytes, vec![0x44, 0x33, 0x22, 0x11]);
+        assert_eq!(register_value_from_bytes(&bytes, true), Some(0x1122_3344));
+        assert_eq!(register_bytes_from_value(1, 16, true).len(), 16);
+        assert_eq!(register_value_from_bytes(&[0; 16], true), None);
+    }
+
+    #[test]
+    fn register_values_round_trip_big_endian() {
+        let bytes = register_bytes_from_value(0x1122_3344, 4, false);
+        assert_eq!(bytes, vec![0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(register_value_from_bytes(&bytes, false), Some(0x1122_3344));
+        assert_eq!(register_bytes_from_value(1, 16, false)[15], 1);
+    }
+
+    #[test]
+    fn host_info_parses_decimal_cputype() {
+        let info = parse_host_info(
+            "cputype:16777228;cpusubtype:2;ostype:ios;vendor:apple;os_version:17.4.0;os_build:323145323139;endian:little;ptrsize:8;",
+        )
+        .unwrap();
+        assert_eq!(info.arch(), Some("arm64e"));
+        assert_eq!(info.os_version.as_deref(), Some("17.4.0"));
+        assert_eq!(info.os_build.as_deref(), Some("21E219"));
+        assert_eq!(info.ptr_size, Some(8));
+
+        let sim =
+            parse_host_info("cputype:16777223;cpusubtype:3;ostype:macosx;endian:little;ptrsize:8;")
+                .unwrap();
+        assert_eq!(sim.arch(), Some("x86_64"));
+        let layout = RegisterLayout::for_cputype(sim.cputype.unwrap()).unwrap();
+        assert_eq!(
+            layout.find("pc").map(|info| info.name.as_str()),
+            Some("rip")
+        );
+        assert_eq!(parse_host_info(""), None);
+    }
+
+    #[test]
+    fn decode_expands_run_length_encoding() {
+        // `0* ` is "0" followed by 3 more repeats (' ' is 32 = 29 + 3).
+        assert_eq!(decode_packet_data(b"0* ").unwrap(), b"0000");
+        assert_eq!(decode_packet_data(b"ab*\"").unwrap(), b"abbbbbb");
+        assert!(decode_packet_data(b"*!").is_err());
+    }
+
+    #[test]
+    fn binary_escapes_round_trip() {
+        let data = [0x01, b'$', b'#', b'}', b'*', 0xff];
+        let escaped = escape_binary(&data);
+        assert_eq!(
+            escaped,
+            vec![0x01, b'}', 0x04, b'}', 0x03, b'}', 0x5d, b'}', 0x0a, 0xff]
+        );
+        assert_eq!(decode_packet_data(&escaped).unwrap(), data);
+        assert!(decode_packet_data(b"abc}").is_err());
+    }
+
+    #[test]
+    fn thread_info_replies_parse() {
+        assert_eq!(
+            parse_thread_info_reply("m1a03,1a04"),
+            Some(ThreadInfoReply::More(vec![0x1a03, 0x1a04]))
+        );
+        assert_eq!(
+            parse_thread_info_reply("mp2f.1a03"),
+            Some(ThreadInfoReply::More(vec![0x1a03]))
+        );
+        assert_eq!(parse_thread_info_reply("l"), Some(ThreadInfoReply::End));
+        assert_eq!(parse_thread_info_reply("E01"), None);
+        assert_eq!(parse_thread_info_reply("mzz"), None);
+    }
+
+    #[test]
+    fn process_info_parses_arch_and_pid() {
+        let info = parse_process_info(
+            "pid:4d2;parent-pid:1;cputype:100000c;cpusubtype:2;ostype:ios;vendor:apple;endian:little;ptrsize:8;",
+        )
+        .unwrap();
+        assert_eq!(info.pid, 1234);
+        assert_eq!(info.arch(), Some("arm64e"));
+        assert_eq!(info.ostype.as_deref(), Some("ios"));
+        assert_eq!(info.ptr_size, Some(8));
+        assert!(info.little_endian);
+        assert!(RegisterLayout::for_cputype(info.cputype.unwrap()).is_some());
+        assert!(RegisterLayout::for_cputype(CPU_TYPE_ARM64_32).is_none());
+        assert_eq!(parse_process_info("cputype:100000c;"), None);
+    }
+
+    #[test]
+    fn memory_region_replies_parse() {
+        let region =
+            parse_memory_region("start:100000000;size:4000;permissions:rx;name:5f5f54455854;")
+                .unwrap();
+        assert_eq!(region.start, 0x1_0000_0000);
+        assert_eq!(region.end(), 0x1_0000_4000);
+        assert!(region.readable && region.executable && !region.writable);
+        assert_eq!(region.name.as_deref(), Some("__TEXT"));
+        assert!(region.contains(0x1_0000_3fff));
+        assert!(!region.contains(0x1_0000_4000));
+
+        let gap = parse_memory_region("start:0;size:100000000;").unwrap();
+        assert!(!gap.readable);
+        assert_eq!(parse_memory_region("permissions:rw;"), None);
+    }
+
+    #[test]
+    fn loaded_images_parse_with_uuid() {
+        let reply = r#"{"images":[{"load_address":4294967296,"mod_date":0,"pathname":"/private/var/containers/Bundle/Application/X/Demo.app/Demo","uuid":"0A1B2C3D-4E5F-6071-8293-A4B5C6D7E8F9","mach_header":{"magic":4277009103}},{"load_address":6442450944,"pathname":"/usr/lib/dyld"}]}"#;
+        let images = parse_loaded_images(reply).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].load_address, 0x1_0000_0000);
+        assert_eq!(
+            images[0].uuid_bytes(),
+            Some([
+                0x0a, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x60, 0x71, 0x82, 0x93, 0xa4, 0xb5, 0xc6, 0xd7,
+                0xe8, 0xf9
+            ])
+        );
+        assert_eq!(images[1].uuid_bytes(), None);
+        assert!(parse_loaded_images("[]").is_err());
+    }
+
+    #[test]
+    fn threads_info_json_parses() {
+        let reply = r#"[{"tid":6659,"name":"main","qname":"com.apple.main-thread","reason":"breakpoint","signal":5,"registers":{"32":"0080000001000000"}},{"tid":6660,"qname":"com.apple.root.default-qos"}]"#;
+        let threads = parse_threads_info(reply).unwrap();
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].tid, 6659);
+        assert_eq!(threads[0].reason.as_deref(), Some("breakpoint"));
+        assert_eq!(threads[0].signal, Some(5));
+        assert_eq!(
+            threads[0].register(32),
+            Some(vec![0x00, 0x80, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(threads[0].display_name(), Some("main"));
+        assert_eq!(
+            threads[1].display_name(),
+            Some("com.apple.root.default-qos")
+        );
+        let reply = threads[0].stop_reply();
+        assert!(matches!(reply.reason, StopReason::Breakpoint));
+        assert_eq!(reply.thread_id, Some(6659));
+        assert_eq!(reply.register(32, true), Some(0x1_0000_8000));
+        assert!(matches!(
+            threads[1].stop_reply().reason,
+            StopReason::NoReason
+        ));
+        assert!(parse_threads_info("not json").is_err());
+    }
+
+    #[test]
+    fn memory_transfers_are_chunked() {
+        assert_eq!(
+            memory_chunks(0x1000, 2500, 1024),
+            vec![(0x1000, 1024), (0x1400, 1024), (0x1800, 452)]
+        );
+        assert_eq!(memory_chunks(0x1000, 1024, 1024), vec![(0x1000, 1024)]);
+        assert!(memory_chunks(0x1000, 0, 1024).is_empty());
+    }
+
+    #[test]
+    fn file_replies_carry_results_and_data() {
+        assert_eq!(parse_file_reply(b"F5").unwrap(), (5, &b""[..]));
+        assert_eq!(parse_file_reply(b"F3;abc").unwrap(), (3, &b"abc"[..]));
+        assert_eq!(parse_file_reply(b"F0;").unwrap(), (0, &b""[..]));
+        let err = parse_file_reply(b"F-1,2").unwrap_err();
+        assert!(err.to_string().contains("errno 2"), "{err}");
+        assert!(parse_file_reply(b"E01").is_err());
+    }
+
+    #[test]
+    fn register_info_replies_build_a_layout() {
+        let fp = parse_register_info(
+            29,
+            "name:fp;alt-name:x29;bitsize:64;offset:232;encoding:uint;format:hex;\
+             set:General Purpose Registers;gcc:29;dwarf:29;generic:fp;",
+            0,
+        )
+        .unwrap();
+        assert_eq!(fp.name, "fp");
+        assert_eq!(fp.alt_name.as_deref(), Some("x29"));
+        assert_eq!(fp.generic.as_deref(), Some("fp"));
+        assert_eq!((fp.regnum, fp.offset, fp.size), (29, 232, 8));
+
+        let cpsr = parse_register_info(33, "name:cpsr;bitsize:32;generic:flags;", 264).unwrap();
+        assert_eq!((cpsr.offset, cpsr.size), (264, 4));
+        assert!(parse_register_info(0, "bitsize:64;", 0).is_none());
+
+        let layout = RegisterLayout::new(vec![fp, cpsr]);
+        assert_eq!(layout.find("x29").map(|info| info.regnum), Some(29));
+        assert_eq!(layout.find("flags").map(|info| info.regnum), Some(33));
+    }
+
+    #[test]
+    fn raw_frames_skip_noise_and_resync_on_dollar() {
+        let mut wire: &[u8] = b"+-junk$trunc$OK#9a%Stop:T05#99";
+        let frame = read_raw_frame(&mut wire).unwrap();
+        assert!(!frame.notification);
+        assert_eq!(frame.data, b"OK");
+        assert!(frame.checksum_ok);
+        let frame = read_raw_frame(&mut wire).unwrap();
+        assert!(frame.notification);
+        assert_eq!(frame.data, b"Stop:T05");
+        assert!(frame.checksum_ok);
+    }
+
+    #[test]
+    fn raw_frames_flag_bad_checksums() {
+        let mut wire: &[u8] = b"$OK#00$OK#zz";
+        assert!(!read_raw_frame(&mut wire).unwrap().checksum_ok);
+        assert!(!read_raw_frame(&mut wire).unwrap().checksum_ok);
+        let mut wire: &[u8] = b"$O";
+        assert!(read_raw_frame(&mut wire).is_err());
+    }
+
+    #[test]
+    fn only_socket_failures_count_as_connection_loss() {
+        let io = |kind| GdbRemoteError::Io(io::Error::from(kind));
+        assert!(io(io::ErrorKind::UnexpectedEof).is_connection_lost());
+        assert!(io(io::ErrorKind::BrokenPipe).is_connection_lost());
+        assert!(io(io::ErrorKind::ConnectionReset).is_connection_lost());
+        assert!(!io(io::ErrorKind::WouldBlock).is_connection_lost());
+        assert!(!GdbRemoteError::Remote("E01".into()).is_connection_lost());
+    }
+
+    #[test]
+    fn packet_size_sizes_memory_chunks() {
+        let reply = "qXfer:features:read+;PacketSize=20000;qEcho+";
+        assert_eq!(parse_packet_size(reply), Some(0x20000));
+        assert_eq!(parse_packet_size("QStartNoAckMode+"), None);
+        assert_eq!(memory_chunk_size(0x40000), MAX_MEMORY_CHUNK_SIZE);
+        assert_eq!(memory_chunk_size(0x1000), (0x1000 - PACKET_OVERHEAD) / 2);
+        assert_eq!(memory_chunk_size(16), 1);
+    }
+
+    #[test]
+    fn error_replies_are_recognized() {
+        assert!(is_error_reply(""));
+        assert!(is_error_reply("E08"));
+        assert!(!is_error_reply("e0ff"));
+        assert!(!is_error_reply("OK"));
+    }
+
+    #[test]
+    fn vcont_packets_address_the_selected_thread() {
+        assert_eq!(vcont_packet("s", 0x1a, false), "vCont;s:1a;c");
+        assert_eq!(vcont_packet("s", 0x1a, true), "vCont;s:1a");
+        assert_eq!(vcont_packet("c", 3, false), "vCont;c");
+        assert_eq!(vcont_packet("c", 3, true), "vCont;c:3");
+        assert_eq!(vcont_packet("C0b", 3, false), "vCont;C0b:3;c");
+        assert_eq!(vcont_packet("s", 0, true), "vCont;s");
+    }
+
+    #[test]
+    fn environment_entries_with_metacharacters_need_hex() {
+        assert!(!needs_hex_encoding("RUST_LOG=info"));
+        assert!(needs_hex_encoding("PS1=$ "));
+        assert!(needs_hex_encoding("MULTI=a\nb"));
+    }
+
+    #[test]
+    fn stop_notifications_parse() {
+        let reply = parse_stop_notification("Stop:T05thread:1a03;reason:breakpoint;").unwrap();
+        assert_eq!(reply.thread_id, Some(0x1a03));
+        assert!(matches!(reply.reason, StopReason::Breakpoint));
+        assert!(parse_stop_notification("Other:T05").is_none());
+        assert!(parse_stop_notification("T05").is_none());
+    }
+
     #[test]
     fn parse_stop_reply_signal() {
         let reply = parse_stop_reply("S05").unwrap();
@@ -263,4 +2220,34 @@ mod tests {
         assert!(matches!(reply.reason, StopReason::Breakpoint));
         assert_eq!(reply.thread_id, Some(1));
     }
+
+    #[test]
+    fn parse_stop_reply_without_signal_has_no_reason() {
+        let reply = parse_stop_reply("T00thread:1a04;").unwrap();
+        assert_eq!(reply.thread_id, Some(0x1a04));
+        assert!(matches!(reply.reason, StopReason::NoReason));
+    }
+
+    #[test]
+    fn parse_stop_reply_expedited_fields() {
+        let reply = parse_stop_reply(
+            "T05thread:303;threads:303,304;thread-pcs:100003f40,1a2b3c4d;\
+             20:403f000001000000;1d:70f5bf6f01000000;reason:breakpoint;",
+        )
+        .unwrap();
+        assert_eq!(reply.threads, vec![0x303, 0x304]);
+        assert_eq!(reply.thread_pc(0x303), Some(0x1_0000_3f40));
+        assert_eq!(reply.thread_pc(0x304), Some(0x1a2b_3c4d));
+        assert_eq!(reply.thread_pc(0x305), None);
+        assert_eq!(reply.register(0x20, true), Some(0x1_0000_3f40));
+        assert_eq!(reply.register(0x1d, true), Some(0x1_6fbf_f570));
+        assert!(matches!(reply.reason, StopReason::Breakpoint));
+    }
+
+    #[test]
+    fn parse_stop_reply_without_reason_is_signal() {
+        let reply = parse_stop_reply("T0dthread:2;").unwrap();
+        assert_eq!(reply.signal, 0x0d);
+        assert!(matches!(reply.reason, StopReason::Signal));
+    }
 }
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeKind {
+    Locals,
+}
+
+impl ScopeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ScopeKind::Locals => "Locals",
+        }
+    }
+}
+
+/// What a DAP `variablesReference` points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeHandle {
+    pub thread_id: i64,
+    pub frame_id: i64,
+    pub scope: ScopeKind,
+}
+
+/// Hands out `variablesReference` values for scopes. References are only
+/// valid while the target stays stopped; `invalidate` drops them all without
+/// reusing numbers, so a stale reference can never alias a new scope.
+#[derive(Debug)]
+pub struct HandleAllocator {
+    next: i64,
+    by_reference: HashMap<i64, ScopeHandle>,
+    by_handle: HashMap<ScopeHandle, i64>,
+}
+
+impl Default for HandleAllocator {
+    fn default() -> Self {
+        Self {
+            next: 1,
+            by_reference: HashMap::new(),
+            by_handle: HashMap::new(),
+        }
+    }
+}
+
+impl HandleAllocator {
+    pub fn allocate(&mut self, handle: ScopeHandle) -> i64 {
+        if let Some(reference) = self.by_handle.get(&handle) {
+            return *reference;
+        }
+        let reference = self.next;
+        self.next += 1;
+        self.by_reference.insert(reference, handle);
+        self.by_handle.insert(handle, reference);
+        reference
+    }
+
+    pub fn resolve(&self, reference: i64) -> Option<ScopeHandle> {
+        self.by_reference.get(&reference).copied()
+    }
+
+    pub fn invalidate(&mut self) {
+        self.by_reference.clear();
+        self.by_handle.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locals(thread_id: i64, frame_id: i64) -> ScopeHandle {
+        ScopeHandle {
+            thread_id,
+            frame_id,
+            scope: ScopeKind::Locals,
+        }
+    }
+
+    #[test]
+    fn allocate_is_stable_per_scope() {
+        let mut handles = HandleAllocator::default();
+        let inner = handles.allocate(locals(1, 101));
+        let outer = handles.allocate(locals(1, 102));
+        assert_ne!(inner, outer);
+        assert_eq!(handles.allocate(locals(1, 101)), inner);
+        assert_eq!(handles.resolve(outer), Some(locals(1, 102)));
+    }
+
+    #[test]
+    fn invalidate_never_reuses_references() {
+        let mut handles = HandleAllocator::default();
+        let before = handles.allocate(locals(1, 101));
+        handles.invalidate();
+        assert_eq!(handles.resolve(before), None);
+        let after = handles.allocate(locals(1, 101));
+        assert_ne!(before, after);
+    }
+}
@@ -0,0 +1,232 @@
+//! Supervision for an external `iproxy`: a single spawn silently stops
+//! forwarding when iproxy dies or the device reconnects, so the supervisor
+//! polls the child and its local port and restarts it, reporting each step.
+//!
+//! Health is judged by whether the port is still bound rather than by
+//! connecting to it: every connection iproxy accepts opens one to
+//! debugserver, which serves a single client.
+
+use std::{
+    io,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::ports;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a fresh iproxy gets to bind its port before it counts as dead.
+const STARTUP_GRACE: Duration = Duration::from_secs(3);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(10);
+
+/// What the supervisor observed, in the order it happened.
+#[derive(Debug)]
+pub enum Status {
+    Started { pid: u32 },
+    Exited(ExitStatus),
+    NotListening,
+    SpawnFailed(io::Error),
+    Restarting { attempt: u32, delay: Duration },
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Started { pid } => write!(f, "iproxy running (pid {pid})"),
+            Status::Exited(status) => write!(f, "iproxy exited ({status})"),
+            Status::NotListening => write!(f, "iproxy stopped listening on its port"),
+            Status::SpawnFailed(err) => write!(f, "failed to spawn iproxy: {err}"),
+            Status::Restarting { attempt, delay } => write!(
+                f,
+                "restarting iproxy in {}s (attempt {attempt})",
+                delay.as_secs_f32()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IproxyCommand {
+    pub program: String,
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// Device UDID, passed as `-u`.
+    pub udid: Option<String>,
+}
+
+impl IproxyCommand {
+    fn spawn(&self) -> io::Result<Child> {
+        let mut command = Command::new(&self.program);
+        command
+            .arg(self.local_port.to_string())
+            .arg(self.remote_port.to_string());
+        if let Some(udid) = &self.udid {
+            command.args(["-u", udid]);
+        }
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    }
+}
+
+/// Keeps iproxy running until dropped, which kills it.
+pub struct Supervisor {
+    stop: Arc<AtomicBool>,
+    /// The running iproxy's pid, 0 between restarts.
+    pid: Arc<AtomicU32>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    /// Spawns iproxy, failing if the first spawn does; later failures are
+    /// retried with backoff and passed to `report`.
+    pub fn start(
+        command: IproxyCommand,
+        report: impl Fn(Status) + Send + 'static,
+    ) -> io::Result<Self> {
+        let child = command.spawn()?;
+        report(Status::Started { pid: child.id() });
+        let stop = Arc::new(AtomicBool::new(false));
+        let pid = Arc::new(AtomicU32::new(child.id()));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            let pid = Arc::clone(&pid);
+            thread::spawn(move || supervise(command, child, &stop, &pid, report))
+        };
+        Ok(Self {
+            stop,
+            pid,
+            thread: Some(thread),
+        })
+    }
+
+    /// The pid of the iproxy currently running, if one is.
+    pub fn pid(&self) -> Option<u32> {
+        Some(self.pid.load(Ordering::SeqCst)).filter(|pid| *pid != 0)
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn supervise(
+    command: IproxyCommand,
+    child: Child,
+    stop: &AtomicBool,
+    pid: &AtomicU32,
+    report: impl Fn(Status),
+) {
+    let mut child = Some(child);
+    let mut started = Instant::now();
+    let mut attempt = 0;
+    while !stop.load(Ordering::SeqCst) {
+        thread::sleep(POLL_INTERVAL);
+        let failure = match child.as_mut().map(Child::try_wait) {
+            Some(Ok(Some(status))) => Some(Status::Exited(status)),
+            Some(Ok(None)) => (started.elapsed() >= STARTUP_GRACE
+                && !ports::is_listening(command.local_port))
+            .then_some(Status::NotListening),
+            Some(Err(err)) => Some(Status::SpawnFailed(err)),
+            None => None,
+        };
+        match failure {
+            Some(status) => report(status),
+            None if child.is_some() => {
+                if started.elapsed() >= STARTUP_GRACE {
+                    attempt = 0;
+                }
+                continue;
+            }
+            None => {}
+        }
+        if let Some(mut dead) = child.take() {
+            pid.store(0, Ordering::SeqCst);
+            let _ = dead.kill();
+            let _ = dead.wait();
+        }
+
+        attempt += 1;
+        let delay = restart_delay(attempt);
+        report(Status::Restarting { attempt, delay });
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if stop.load(Ordering::SeqCst) {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+        }
+        match command.spawn() {
+            Ok(spawned) => {
+                report(Status::Started { pid: spawned.id() });
+                pid.store(spawned.id(), Ordering::SeqCst);
+                started = Instant::now();
+                child = Some(spawned);
+            }
+            Err(err) => report(Status::SpawnFailed(err)),
+        }
+    }
+    if let Some(mut child) = child {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// 1s, 2s, 4s, ... capped at `MAX_RESTART_DELAY`.
+fn restart_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.saturating_sub(1).min(4)).min(MAX_RESTART_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn restart_delay_backs_off_to_a_cap() {
+        let delays: Vec<u64> = (1..=6).map(|n| restart_delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn a_child_that_exits_is_restarted() {
+        let (tx, rx) = mpsc::channel();
+        let command = IproxyCommand {
+            program: "true".into(),
+            local_port: 0,
+            remote_port: 0,
+            udid: None,
+        };
+        let supervisor =
+            Supervisor::start(command, move |status| tx.send(status.to_string()).unwrap()).unwrap();
+        let seen: Vec<String> = rx.iter().take(4).collect();
+        drop(supervisor);
+        assert!(seen[0].starts_with("iproxy running"), "{seen:?}");
+        assert!(seen[1].starts_with("iproxy exited"), "{seen:?}");
+        assert_eq!(seen[2], "restarting iproxy in 1s (attempt 1)");
+        assert!(seen[3].starts_with("iproxy running"), "{seen:?}");
+    }
+
+    #[test]
+    fn a_missing_binary_fails_the_first_spawn() {
+        let command = IproxyCommand {
+            program: "/nonexistent/iproxy".into(),
+            local_port: 0,
+            remote_port: 0,
+            udid: None,
+        };
+        assert!(Supervisor::start(command, |_| {}).is_err());
+    }
+}
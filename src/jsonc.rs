@@ -0,0 +1,238 @@
+//! JSON with the `//` and `/* */` comments and trailing commas that Zed and
+//! VS Code allow in their settings files, plus what the CLI tools need to
+//! edit such a file in place without losing them.
+
+use std::{collections::HashMap, ops::Range};
+
+use serde::Serialize;
+use serde_json::Value;
+
+pub fn parse(source: &str) -> serde_json::Result<Value> {
+    serde_json::from_str(&blank(source))
+}
+
+/// `source` with comments and trailing commas overwritten by spaces, so it
+/// is plain JSON whose byte offsets (and parse error positions) still match
+/// the original.
+pub fn blank(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', _) if in_string => {
+                out.push(c);
+                out.extend(chars.next());
+            }
+            ('"', _) => {
+                in_string = !in_string;
+                out.push(c);
+            }
+            ('/', Some('/')) if !in_string => {
+                out.push(' ');
+                while let Some(c) = chars.next_if(|&c| c != '\n') {
+                    push_spaces(&mut out, c);
+                }
+            }
+            ('/', Some('*')) if !in_string => {
+                chars.next();
+                out.push_str("  ");
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    push_spaces(&mut out, c);
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    blank_trailing_commas(&mut out);
+    out
+}
+
+/// As many spaces as `c` takes bytes; newlines are kept.
+fn push_spaces(out: &mut String, c: char) {
+    if c == '\n' {
+        out.push('\n');
+    } else {
+        out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+    }
+}
+
+fn blank_trailing_commas(source: &mut String) {
+    let mut commas = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in source.char_indices() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = source[index + 1..].trim_start();
+            if next.starts_with('}') || next.starts_with(']') {
+                commas.push(index);
+            }
+        }
+    }
+    for index in commas {
+        source.replace_range(index..index + 1, " ");
+    }
+}
+
+/// Byte ranges of every value in `json` (plain JSON, e.g. from [`blank`]),
+/// keyed by JSON pointer. Only meaningful for input serde_json accepts.
+pub fn spans(json: &str) -> HashMap<String, Range<usize>> {
+    let mut scanner = Scanner {
+        bytes: json.as_bytes(),
+        at: 0,
+        spans: HashMap::new(),
+    };
+    scanner.value(String::new());
+    scanner.spans
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    spans: HashMap<String, Range<usize>>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn value(&mut self, pointer: String) {
+        self.skip_whitespace();
+        let start = self.at;
+        match self.peek() {
+            Some(b'{') => {
+                self.at += 1;
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        self.at += 1;
+                        break;
+                    }
+                    let key = self.string();
+                    self.skip_whitespace();
+                    self.at += 1;
+                    let key = key.replace('~', "~0").replace('/', "~1");
+                    self.value(format!("{pointer}/{key}"));
+                    self.skip_whitespace();
+                    self.at += 1;
+                    if self.bytes.get(self.at - 1) != Some(&b',') {
+                        break;
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.at += 1;
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.at += 1;
+                } else {
+                    for index in 0.. {
+                        self.value(format!("{pointer}/{index}"));
+                        self.skip_whitespace();
+                        self.at += 1;
+                        if self.bytes.get(self.at - 1) != Some(&b',') {
+                            break;
+                        }
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            _ => {
+                while self
+                    .peek()
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.at += 1;
+                }
+            }
+        }
+        self.spans
+            .insert(pointer, start..self.at.min(self.bytes.len()));
+    }
+
+    /// Consumes a string literal, returning its contents with simple
+    /// escapes resolved.
+    fn string(&mut self) -> String {
+        self.at += 1;
+        let start = self.at;
+        while let Some(b) = self.peek() {
+            self.at += 1;
+            match b {
+                b'"' => break,
+                b'\\' => self.at += 1,
+                _ => {}
+            }
+        }
+        let raw = &self.bytes[start..self.at.saturating_sub(1).max(start)];
+        serde_json::from_slice(&[b"\"", raw, b"\""].concat())
+            .unwrap_or_else(|_| String::from_utf8_lossy(raw).into_owned())
+    }
+}
+
+/// The whitespace `source` has before `offset` on its line.
+pub fn indent_at(source: &str, offset: usize) -> &str {
+    let line_start = source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line = &source[line_start..offset];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// `value` pretty-printed for insertion at a position indented by
+/// `indent`: every line after the first carries the indent too.
+pub fn to_string_indented(value: &impl Serialize, indent: &str) -> String {
+    serde_json::to_string_pretty(value)
+        .expect("serialize JSON")
+        .replace('\n', &format!("\n{indent}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn comments_and_trailing_commas_keep_offsets() {
+        let source = "{ // héllo\n  \"a\": [1, 2,], /* x */\n  \"s\": \"// not a comment\",\n}";
+        let blanked = blank(source);
+        assert_eq!(blanked.len(), source.len());
+        assert_eq!(
+            parse(source).unwrap(),
+            json!({ "a": [1, 2], "s": "// not a comment" })
+        );
+        let spans = spans(&blanked);
+        assert_eq!(&source[spans["/a"].clone()], "[1, 2,]");
+        assert_eq!(&source[spans["/a/1"].clone()], "2");
+        assert_eq!(&source[spans["/s"].clone()], "\"// not a comment\"");
+    }
+
+    #[test]
+    fn values_are_indented_for_insertion() {
+        let source = "{\n    \"x\": 1\n}";
+        let offset = source.find('"').unwrap();
+        assert_eq!(indent_at(source, offset), "    ");
+        assert_eq!(
+            to_string_indented(&json!({ "a": 1 }), "    "),
+            "{\n      \"a\": 1\n    }"
+        );
+    }
+}
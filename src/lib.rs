@@ -1,23 +1,69 @@
+pub mod app_logs;
+pub mod app_process;
 pub mod backend;
+pub mod crash_report;
 pub mod debug_session;
 pub mod gdb_remote;
+pub mod handles;
+#[cfg(feature = "cli")]
+pub mod iproxy;
+pub mod jsonc;
+mod locator;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock_debugserver;
+pub mod ports;
+pub mod signals;
+pub mod source_map;
+pub mod symbol_cache;
+pub mod symbol_server;
 pub mod symbols;
+#[cfg(all(unix, feature = "cli"))]
+pub mod usbmux;
+pub mod variables;
 
-use serde_json::{json, Value};
+use std::{
+    fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
 use zed_extension_api::{
-    register_extension, DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario,
-    DebugTaskDefinition, EnvVars, Extension, StartDebuggingRequestArguments,
-    StartDebuggingRequestArgumentsRequest, Worktree,
+    current_platform, download_file, latest_github_release, make_file_executable, process::Command,
+    register_extension, resolve_tcp_template, settings::LspSettings, Architecture,
+    BuildTaskDefinition, BuildTaskDefinitionTemplatePayload, BuildTaskTemplate, DebugAdapterBinary,
+    DebugConfig, DebugRequest, DebugScenario, DebugTaskDefinition, DownloadedFileType, EnvVars,
+    Extension, GithubReleaseOptions, Os, StartDebuggingRequestArguments,
+    StartDebuggingRequestArgumentsRequest, TaskTemplate, TcpArguments, TcpArgumentsTemplate,
+    Worktree,
 };
 
 const ADAPTER_NAME: &str = "ios-lldb";
-pub const CONFIG_ENV_VAR: &str = "IOS_LLDB_DAP_CONFIG";
+/// Flavors of the same adapter whose scenarios come set up for a
+/// simulator or a USB device, so picking one in Zed is all it takes.
+const SIMULATOR_ADAPTER: &str = "ios-lldb-sim";
+const DEVICE_ADAPTER: &str = "ios-lldb-device";
+/// Every adapter name debug.json entries for this adapter may use.
+pub const ADAPTER_NAMES: [&str; 3] = [ADAPTER_NAME, SIMULATOR_ADAPTER, DEVICE_ADAPTER];
+/// The repository whose releases carry prebuilt adapters.
+const GITHUB_REPO: &str = "mdaiter/iOS-llm-zed-debug";
+/// The adapter binary inside a release archive.
+const RELEASE_BINARY: &str = "swiftscope";
+/// The oldest adapter that speaks the configuration this extension sends.
+const MIN_ADAPTER_VERSION: &str = "0.1.0";
 
-pub struct IosLldbExtension;
+pub struct IosLldbExtension {
+    /// As of the last session started, for the hooks Zed calls without a
+    /// worktree to read them from.
+    settings: ExtensionSettings,
+}
 
 impl Extension for IosLldbExtension {
     fn new() -> Self {
-        Self
+        Self {
+            settings: ExtensionSettings::default(),
+        }
     }
 
     fn get_dap_binary(
@@ -28,6 +74,7 @@ impl Extension for IosLldbExtension {
         worktree: &Worktree,
     ) -> Result<DebugAdapterBinary, String> {
         ensure_adapter(&adapter_name)?;
+        self.settings = WorktreeLike::settings(worktree);
         build_debug_adapter_binary(&config, user_provided_debug_adapter_path, worktree)
     }
 
@@ -40,18 +87,51 @@ impl Extension for IosLldbExtension {
     }
 
     fn dap_config_to_scenario(&mut self, config: DebugConfig) -> Result<DebugScenario, String> {
-        convert_debug_config(config)
+        convert_debug_config(config, &self.settings)
+    }
+
+    fn dap_locator_create_scenario(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+        resolved_label: String,
+        debug_adapter_name: String,
+    ) -> Option<DebugScenario> {
+        if locator_name != locator::LOCATOR_NAME {
+            return None;
+        }
+        locator::create_scenario(build_task, resolved_label, debug_adapter_name)
+    }
+
+    fn run_dap_locator(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+    ) -> Result<DebugRequest, String> {
+        if locator_name != locator::LOCATOR_NAME {
+            return Err(format!("unknown locator: {locator_name}"));
+        }
+        locator::locate(build_task)
     }
 }
 
 register_extension!(IosLldbExtension);
 
 fn ensure_adapter(adapter: &str) -> Result<(), String> {
-    if adapter == ADAPTER_NAME {
-        Ok(())
-    } else {
-        Err(format!("unsupported adapter: {adapter}"))
-    }
+    flavor_defaults(adapter).map(drop)
+}
+
+/// What scenarios for `adapter` get unless they say otherwise: the
+/// simulator flavor boots `simulator`, the device flavor forwards
+/// debugserver's port over USB itself.
+fn flavor_defaults(adapter: &str) -> Result<Map<String, Value>, String> {
+    let flag = match adapter {
+        ADAPTER_NAME => return Ok(Map::new()),
+        SIMULATOR_ADAPTER => "bootSimulator",
+        DEVICE_ADAPTER => "usbForward",
+        _ => return Err(format!("unsupported adapter: {adapter}")),
+    };
+    Ok(Map::from_iter([(flag.to_string(), Value::Bool(true))]))
 }
 
 fn build_debug_adapter_binary<W: WorktreeLike>(
@@ -59,37 +139,258 @@ fn build_debug_adapter_binary<W: WorktreeLike>(
     user_path: Option<String>,
     worktree: &W,
 ) -> Result<DebugAdapterBinary, String> {
-    let config_json: Value =
+    let mut config_json: Value =
         serde_json::from_str(&task.config).map_err(|err| format!("invalid config: {err}"))?;
     let request_kind = request_kind_from_config(&config_json)?;
-    let command = resolve_binary_path(worktree, user_path)?;
-    let mut env = worktree.shell_env();
-    upsert_env(&mut env, CONFIG_ENV_VAR, task.config.clone());
+    let settings = worktree.settings();
+    settings.apply(&mut config_json);
+    fill_in(&mut config_json, flavor_defaults(&task.adapter)?);
+    check_config(&task.adapter, &config_json, false)?;
+    resolve_paths(&mut config_json, worktree)?;
+    let adapter_env = config_json
+        .as_object_mut()
+        .and_then(|config| config.remove("adapterEnv"));
+    let config = config_json.to_string();
+    let command = resolve_binary_path(worktree, user_path.or(settings.adapter_path))?;
+    let connection = task.tcp_connection.map(resolve_tcp).transpose()?;
+    let arguments = match &connection {
+        // Zed connects to `host`, so the adapter listens there.
+        Some(tcp) if Ipv4Addr::from(tcp.host) != Ipv4Addr::LOCALHOST => vec![
+            "--port".into(),
+            tcp.port.to_string(),
+            "--host".into(),
+            Ipv4Addr::from(tcp.host).to_string(),
+        ],
+        Some(tcp) => vec!["--port".into(), tcp.port.to_string()],
+        None => Vec::new(),
+    };
+    let mut env = adapter_env_from(worktree.shell_env(), adapter_env)?;
+    upsert_env(
+        &mut env,
+        variables::WORKSPACE_ROOT_ENV_VAR,
+        worktree.root_path(),
+    );
 
     Ok(DebugAdapterBinary {
         command: Some(command),
-        arguments: Vec::new(),
+        arguments,
         envs: env,
         cwd: None,
-        connection: None,
+        connection,
         request_args: StartDebuggingRequestArguments {
-            configuration: task.config.clone(),
+            configuration: config,
             request: request_kind,
         },
     })
 }
 
+/// Makes a relative `program` and `cwd` absolute against the worktree root,
+/// and checks a launch's program is there, so a wrong path is reported when
+/// the session starts rather than by the adapter partway through it. Paths
+/// with `${...}` placeholders are left for the adapter to expand, and an
+/// attach's program may legitimately exist only on the device.
+fn resolve_paths<W: WorktreeLike>(config: &mut Value, worktree: &W) -> Result<(), String> {
+    let root = worktree.root_path();
+    for key in ["program", "cwd"] {
+        if let Some(Value::String(path)) = config.get_mut(key) {
+            if !path.is_empty() && !path.starts_with(['/', '~', '$']) {
+                *path = Path::new(&root).join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+    if config["request"] != "launch" {
+        return Ok(());
+    }
+    match config["program"].as_str() {
+        Some(program) if program.contains("${") || backend::is_device_path(program) => Ok(()),
+        Some(program) if worktree.path_exists(program) == Some(false) => Err(format!(
+            "program `{program}` does not exist; build it first or fix the path"
+        )),
+        Some(_) => Ok(()),
+        None => Err("missing `program` to launch".into()),
+    }
+}
+
+/// Where Zed reaches an adapter started with `--port`, for scenarios with a
+/// `tcp_connection`. Zed picks a free port when none is given.
+fn resolve_tcp(template: TcpArgumentsTemplate) -> Result<TcpArguments, String> {
+    match template.port {
+        Some(port) => Ok(TcpArguments {
+            port,
+            host: template.host.unwrap_or_else(|| Ipv4Addr::LOCALHOST.into()),
+            timeout: template.timeout,
+        }),
+        None => resolve_tcp_template(template),
+    }
+}
+
 fn resolve_binary_path<W: WorktreeLike>(
     worktree: &W,
     user_path: Option<String>,
 ) -> Result<String, String> {
     if let Some(path) = user_path {
+        check_adapter_version(worktree, &path)?;
         return Ok(path);
     }
 
-    worktree
-        .which(ADAPTER_NAME)
-        .ok_or_else(|| format!("unable to find `{ADAPTER_NAME}` on PATH"))
+    let outdated = match worktree.which(ADAPTER_NAME) {
+        Some(path) => match check_adapter_version(worktree, &path) {
+            Ok(()) => return Ok(path),
+            Err(err) => Some(err),
+        },
+        None => None,
+    };
+    let downloaded = download_adapter().and_then(|path| {
+        check_adapter_version(worktree, &path)?;
+        Ok(path)
+    });
+    downloaded.map_err(|err| match outdated {
+        Some(outdated) => format!("{outdated}, and downloading a newer one failed: {err}"),
+        None => {
+            format!("unable to find `{ADAPTER_NAME}` on PATH, and downloading it failed: {err}")
+        }
+    })
+}
+
+/// Refuses adapters older than [`MIN_ADAPTER_VERSION`], including ones from
+/// before `--version` existed, so a stale binary fails up front rather than
+/// misreading its configuration mid-session. An adapter the extension isn't
+/// allowed to run is let through unchecked.
+fn check_adapter_version<W: WorktreeLike>(worktree: &W, path: &str) -> Result<(), String> {
+    let Some(reported) = worktree.adapter_version(path) else {
+        return Ok(());
+    };
+    let minimum = parse_version(MIN_ADAPTER_VERSION).expect("valid minimum version");
+    match parse_version(&reported) {
+        Some(version) if version >= minimum => Ok(()),
+        Some(_) => Err(format!(
+            "`{path}` is {}, but this extension needs {MIN_ADAPTER_VERSION} or newer",
+            reported.trim()
+        )),
+        None => Err(format!(
+            "`{path}` doesn't report its version, so it predates {MIN_ADAPTER_VERSION}"
+        )),
+    }
+}
+
+/// The `major.minor.patch` in `--version` output such as
+/// `swiftscope 0.2.1`, ignoring any pre-release suffix.
+fn parse_version(output: &str) -> Option<(u64, u64, u64)> {
+    let version = output.split_whitespace().last()?;
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+/// Fetches this platform's prebuilt adapter from the latest GitHub release
+/// into `swiftscope-<version>/` in the extension's work directory, where
+/// later sessions find it. When GitHub can't be reached, the newest version
+/// downloaded before is used.
+fn download_adapter() -> Result<String, String> {
+    let (os, arch) = current_platform();
+    let asset_name = release_asset_name(os, arch)?;
+    let options = GithubReleaseOptions {
+        require_assets: true,
+        pre_release: false,
+    };
+    let release = match latest_github_release(GITHUB_REPO, options) {
+        Ok(release) => release,
+        Err(err) => {
+            return downloaded_versions()
+                .pop()
+                .map(|dir| absolute(&format!("{dir}/{RELEASE_BINARY}")))
+                .unwrap_or(Err(err))
+        }
+    };
+    let dir = format!("{RELEASE_BINARY}-{}", release.version);
+    let binary = format!("{dir}/{RELEASE_BINARY}");
+    if !Path::new(&binary).is_file() {
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == asset_name)
+            .ok_or_else(|| format!("release {} has no {asset_name}", release.version))?;
+        download_file(&asset.download_url, &dir, DownloadedFileType::GzipTar)?;
+        make_file_executable(&binary)?;
+        for old in downloaded_versions() {
+            if old != dir {
+                let _ = fs::remove_dir_all(old);
+            }
+        }
+    }
+    absolute(&binary)
+}
+
+/// The release asset holding the adapter for a platform, named after its
+/// Rust target triple.
+fn release_asset_name(os: Os, arch: Architecture) -> Result<String, String> {
+    let arch = match arch {
+        Architecture::Aarch64 => "aarch64",
+        Architecture::X8664 => "x86_64",
+        Architecture::X86 => return Err("no prebuilt adapter for 32-bit x86".into()),
+    };
+    let os = match os {
+        Os::Mac => "apple-darwin",
+        Os::Linux => "unknown-linux-gnu",
+        Os::Windows => return Err("no prebuilt adapter for Windows".into()),
+    };
+    Ok(format!("{RELEASE_BINARY}-{arch}-{os}.tar.gz"))
+}
+
+/// Directories of earlier downloads, oldest first.
+fn downloaded_versions() -> Vec<String> {
+    let mut dirs: Vec<_> = fs::read_dir(".")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let modified = entry.metadata().and_then(|meta| meta.modified()).ok();
+            name.starts_with(&format!("{RELEASE_BINARY}-"))
+                .then_some((modified, name))
+        })
+        .collect();
+    dirs.sort();
+    dirs.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Zed runs the adapter from elsewhere, so it needs the full path.
+fn absolute(path: &str) -> Result<String, String> {
+    let dir = std::env::current_dir().map_err(|err| err.to_string())?;
+    Ok(dir.join(path).to_string_lossy().into_owned())
+}
+
+/// The part of the shell environment the adapter runs with, per the
+/// scenario's `adapterEnv`: all of it (`"inherit"`, the default), none of it
+/// (`"clean"`), or only the listed variables. Dropping it keeps the shell's
+/// `DYLD_*` and similar variables away from debugserver.
+fn adapter_env_from(shell_env: EnvVars, policy: Option<Value>) -> Result<EnvVars, String> {
+    let invalid = || {
+        "`adapterEnv` must be \"inherit\", \"clean\" or a list of variable names to keep, \
+         such as [\"PATH\", \"HOME\"]"
+            .to_string()
+    };
+    match policy {
+        None | Some(Value::Null) => Ok(shell_env),
+        Some(Value::String(policy)) => match policy.as_str() {
+            "inherit" => Ok(shell_env),
+            "clean" => Ok(Vec::new()),
+            _ => Err(invalid()),
+        },
+        Some(Value::Array(names)) => {
+            let names = names
+                .iter()
+                .map(|name| name.as_str().ok_or_else(invalid))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(shell_env
+                .into_iter()
+                .filter(|(key, _)| names.contains(&key.as_str()))
+                .collect())
+        }
+        Some(_) => Err(invalid()),
+    }
 }
 
 fn upsert_env(env: &mut EnvVars, key: &str, value: String) {
@@ -114,34 +415,258 @@ fn request_kind_from_config(
     }
 }
 
-fn convert_debug_config(config: DebugConfig) -> Result<DebugScenario, String> {
-    let body = match config.request {
-        DebugRequest::Launch(launch) => json!({
-            "request": "launch",
-            "program": launch.program,
-            "args": launch.args,
-            "cwd": launch.cwd,
-            "env": env_list_to_value(launch.envs),
-            "debugserverPort": 0,
-            "stopOnEntry": config.stop_on_entry.unwrap_or(false),
-        }),
-        DebugRequest::Attach(attach) => json!({
-            "request": "attach",
-            "processId": attach.process_id,
-            "debugserverPort": 0,
-            "stopOnEntry": config.stop_on_entry.unwrap_or(false),
-        }),
+/// A mistake in an adapter configuration: the key at fault and how to fix
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub key: &'static str,
+    pub message: String,
+}
+
+/// Catches configurations the adapter would only reject once the session
+/// is under way, naming each key at fault and how to fix it. A
+/// `debugserverPort` of 0 passes while `unset_port` allows it, as in the
+/// scenarios Zed builds before settings or the user fill it in. Shared with
+/// `ios-lldb-setup validate`.
+pub fn config_problems(config: &Value, unset_port: bool) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    let mut problem = |key: &'static str, message: String| {
+        problems.push(ConfigProblem { key, message });
     };
+    match config.get("request").and_then(Value::as_str) {
+        None | Some("launch") => {
+            if config["program"].as_str().is_none_or(str::is_empty) {
+                problem(
+                    "program",
+                    "`program` is missing; set it to the executable to launch, such as the \
+                     binary inside the built .app"
+                        .into(),
+                );
+            }
+        }
+        Some("attach") => {}
+        Some(other) => problem(
+            "request",
+            format!("`request` is \"{other}\"; use \"launch\" or \"attach\""),
+        ),
+    }
+    match config.get("debugserverPort") {
+        Some(port) if port.as_u64() == Some(0) && !unset_port => problem(
+            "debugserverPort",
+            "`debugserverPort` is 0; set it to the port debugserver listens on, here or as \
+             `debugserverPort` in the extension settings"
+                .into(),
+        ),
+        Some(port) if port.as_u64().is_some_and(|port| port <= u16::MAX.into()) => {}
+        Some(port) => problem(
+            "debugserverPort",
+            format!("`debugserverPort` is {port}; use a port number from 1 to 65535"),
+        ),
+        None => problem(
+            "debugserverPort",
+            "`debugserverPort` is missing; set it to the port debugserver listens on".into(),
+        ),
+    }
+    if config
+        .get("debugserverHost")
+        .is_some_and(|host| !host.is_string())
+    {
+        problem(
+            "debugserverHost",
+            "`debugserverHost` must be a string such as \"127.0.0.1\"".into(),
+        );
+    }
+    for key in ["pid", "processId"] {
+        if let Some(pid) = config.get(key) {
+            if !pid
+                .as_u64()
+                .is_some_and(|pid| pid > 0 && pid <= u32::MAX.into())
+            {
+                problem(
+                    key,
+                    format!("`{key}` is {pid}; use the id of a running process"),
+                );
+            }
+        }
+    }
+    let pair = |pair: &Value| {
+        pair.as_array()
+            .is_some_and(|pair| pair.len() == 2 && pair.iter().all(Value::is_string))
+    };
+    if let Some(source_map) = config.get("sourceMap") {
+        if !source_map
+            .as_array()
+            .is_some_and(|pairs| pairs.iter().all(pair))
+        {
+            problem(
+                "sourceMap",
+                "`sourceMap` must be a list of [build path, local path] pairs, such as \
+                 [[\"/ci/src\", \"${workspaceRoot}\"]]"
+                    .into(),
+            );
+        }
+    }
+    problems
+}
+
+/// [`config_problems`] as one error naming the `adapter` flavor at fault.
+fn check_config(adapter: &str, config: &Value, unset_port: bool) -> Result<(), String> {
+    let problems = config_problems(config, unset_port);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        let messages: Vec<String> = problems
+            .into_iter()
+            .map(|problem| problem.message)
+            .collect();
+        Err(format!(
+            "invalid {adapter} configuration:\n  {}",
+            messages.join("\n  ")
+        ))
+    }
+}
+
+fn convert_debug_config(
+    config: DebugConfig,
+    settings: &ExtensionSettings,
+) -> Result<DebugScenario, String> {
+    let build = match &config.request {
+        DebugRequest::Launch(launch) => build_task(
+            &launch.program,
+            launch.cwd.as_deref(),
+            settings.xcodebuild_path.as_deref().unwrap_or("xcodebuild"),
+        ),
+        DebugRequest::Attach(_) => None,
+    };
+    let attach_without_pid =
+        matches!(&config.request, DebugRequest::Attach(attach) if attach.process_id.is_none());
+    // Zed's empty arguments and environment mean none were given, so they
+    // are left out for the settings to fill in.
+    let mut body = match config.request {
+        DebugRequest::Launch(launch) => {
+            let mut body = json!({
+                "request": "launch",
+                "program": launch.program,
+                "cwd": launch.cwd,
+                "debugserverPort": 0,
+            });
+            if !launch.args.is_empty() {
+                body["args"] = launch.args.into();
+            }
+            if !launch.envs.is_empty() {
+                body["env"] = env_list_to_value(launch.envs);
+            }
+            body
+        }
+        DebugRequest::Attach(attach) => {
+            let mut body = json!({
+                "request": "attach",
+                "debugserverPort": 0,
+            });
+            if let Some(pid) = attach.process_id {
+                body["processId"] = pid.into();
+            }
+            body
+        }
+    };
+    // Left out otherwise, so the `stopOnEntry` setting applies.
+    if let Some(stop_on_entry) = config.stop_on_entry {
+        body["stopOnEntry"] = stop_on_entry.into();
+    }
+    settings.apply(&mut body);
+    fill_in(&mut body, flavor_defaults(&config.adapter)?);
+    // Advertised in the scenario so there is a place to map sources.
+    fill_in(
+        &mut body,
+        Map::from_iter([("sourceMap".to_string(), json!([]))]),
+    );
+    // Without a pid the adapter lists what is running and picks the process
+    // named like `program`, which the settings may supply. With nothing to
+    // match (or a `bundleId`, which names the app itself), the attach goes
+    // to the process debugserver was started with.
+    if attach_without_pid
+        && body.get("bundleId").is_none()
+        && body["program"]
+            .as_str()
+            .is_some_and(|program| !program.is_empty())
+    {
+        body["pickProcess"] = true.into();
+    }
+    check_config(&config.adapter, &body, true)?;
 
     Ok(DebugScenario {
         label: config.label,
-        adapter: ADAPTER_NAME.to_string(),
-        build: None,
+        adapter: config.adapter,
+        build: build.map(|template| {
+            BuildTaskDefinition::Template(BuildTaskDefinitionTemplatePayload {
+                locator_name: None,
+                template,
+            })
+        }),
         config: serde_json::to_string(&body).map_err(|err| err.to_string())?,
         tcp_connection: None,
     })
 }
 
+/// The build that produces `program`, recognised from where SwiftPM
+/// (`.build/`) and xcodebuild (`Build/Products/<configuration>-<sdk>/`) put
+/// their products, so Zed rebuilds it before every session.
+fn build_task(program: &str, cwd: Option<&str>, xcodebuild: &str) -> Option<BuildTaskTemplate> {
+    let path = Path::new(program);
+    let components: Vec<&str> = path.iter().filter_map(|part| part.to_str()).collect();
+    let name = components.last()?.to_string();
+
+    if let Some(index) = components.iter().position(|part| *part == ".build") {
+        let root: PathBuf = path.iter().take(index).collect();
+        let mut args = vec!["build".to_string(), "--product".into(), name.clone()];
+        if components[index..].contains(&"release") {
+            args.extend(["-c".into(), "release".into()]);
+        }
+        return Some(BuildTaskTemplate {
+            label: format!("swift build {name}"),
+            command: "swift".into(),
+            args,
+            env: Vec::new(),
+            cwd: Some(root.to_string_lossy().into_owned()),
+        });
+    }
+
+    let index = components
+        .windows(2)
+        .position(|pair| pair == ["Build", "Products"])?;
+    let derived_data: PathBuf = path.iter().take(index).collect();
+    let products = *components.get(index + 2)?;
+    let (configuration, destination) = match products.split_once('-') {
+        Some((configuration, "iphonesimulator")) => {
+            (configuration, "generic/platform=iOS Simulator")
+        }
+        Some((configuration, "iphoneos")) => (configuration, "generic/platform=iOS"),
+        Some(_) => return None,
+        None => (products, "platform=macOS"),
+    };
+    let scheme = components
+        .iter()
+        .find_map(|part| part.strip_suffix(".app"))
+        .map_or(name, str::to_string);
+    Some(BuildTaskTemplate {
+        label: format!("xcodebuild {scheme}"),
+        command: xcodebuild.into(),
+        args: vec![
+            "build".into(),
+            "-scheme".into(),
+            scheme,
+            "-configuration".into(),
+            configuration.into(),
+            "-destination".into(),
+            destination.into(),
+            "-derivedDataPath".into(),
+            derived_data.to_string_lossy().into_owned(),
+        ],
+        env: Vec::new(),
+        cwd: Some(cwd.unwrap_or("$ZED_WORKTREE_ROOT").into()),
+    })
+}
+
 fn env_list_to_value(envs: EnvVars) -> Value {
     let map = envs
         .into_iter()
@@ -150,9 +675,70 @@ fn env_list_to_value(envs: EnvVars) -> Value {
     Value::Object(map)
 }
 
+/// Defaults for every ios-lldb scenario, from `lsp.ios-lldb` in Zed's
+/// settings.json (the only per-extension settings Zed shares with
+/// extensions): `binary.path` for the adapter, the rest under `settings`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ExtensionSettings {
+    #[serde(skip)]
+    adapter_path: Option<String>,
+    debugserver_host: Option<String>,
+    debugserver_port: Option<u16>,
+    xcodebuild_path: Option<String>,
+    stop_on_entry: Option<bool>,
+    /// Any other adapter key (`dsymPath`, `sourceMap`, `signals`, ...),
+    /// which Zed's own debug configurations have no room for.
+    #[serde(flatten)]
+    adapter: Map<String, Value>,
+}
+
+impl ExtensionSettings {
+    /// Fills in what `config` leaves out.
+    fn apply(&self, config: &mut Value) {
+        let mut defaults = self.adapter.clone();
+        if let Some(host) = &self.debugserver_host {
+            defaults.insert("debugserverHost".into(), host.clone().into());
+        }
+        if let Some(port) = self.debugserver_port {
+            defaults.insert("debugserverPort".into(), port.into());
+        }
+        if let Some(stop_on_entry) = self.stop_on_entry {
+            defaults.insert("stopOnEntry".into(), stop_on_entry.into());
+        }
+        fill_in(config, defaults);
+    }
+}
+
+/// Sets each of `defaults` that `config` leaves out. Nulls, and a
+/// `debugserverPort` of 0 as in the scenarios Zed builds itself, count as
+/// left out; an empty list or object was set on purpose.
+fn fill_in(config: &mut Value, defaults: Map<String, Value>) {
+    let Some(config) = config.as_object_mut() else {
+        return;
+    };
+    for (key, value) in defaults {
+        let left_out = match config.get(&key) {
+            None | Some(Value::Null) => true,
+            Some(port) => key == "debugserverPort" && port.as_u64() == Some(0),
+        };
+        if left_out {
+            config.insert(key, value);
+        }
+    }
+}
+
 trait WorktreeLike {
     fn which(&self, binary_name: &str) -> Option<String>;
     fn shell_env(&self) -> EnvVars;
+    fn root_path(&self) -> String;
+    /// What the adapter at `path` prints for `--version`, or `None` when
+    /// Zed won't let the extension run it.
+    fn adapter_version(&self, path: &str) -> Option<String>;
+    fn settings(&self) -> ExtensionSettings;
+    /// Whether `path` exists on the machine the adapter runs on, if that
+    /// can be found out.
+    fn path_exists(&self, path: &str) -> Option<bool>;
 }
 
 impl WorktreeLike for Worktree {
@@ -173,16 +759,48 @@ impl WorktreeLike for Worktree {
     fn shell_env(&self) -> EnvVars {
         Worktree::shell_env(self)
     }
+
+    fn root_path(&self) -> String {
+        Worktree::root_path(self)
+    }
+
+    fn adapter_version(&self, path: &str) -> Option<String> {
+        let output = Command::new(path).arg("--version").output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn settings(&self) -> ExtensionSettings {
+        let Ok(lsp) = LspSettings::for_worktree(ADAPTER_NAME, self) else {
+            return ExtensionSettings::default();
+        };
+        let mut settings: ExtensionSettings = lsp
+            .settings
+            .and_then(|settings| serde_json::from_value(settings).ok())
+            .unwrap_or_default();
+        settings.adapter_path = lsp.binary.and_then(|binary| binary.path);
+        settings
+    }
+
+    fn path_exists(&self, path: &str) -> Option<bool> {
+        // The extension itself can only see its own directory; a denied
+        // exec leaves the check to the adapter's `load_program`.
+        let output = Command::new("/bin/test").args(["-e", path]).output().ok()?;
+        match output.status {
+            Some(0) => Some(true),
+            Some(1) => Some(false),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use zed_extension_api::{AttachRequest, LaunchRequest, TcpArgumentsTemplate};
+    use zed_extension_api::{AttachRequest, LaunchRequest};
 
     #[test]
     fn dap_request_kind_uses_request_field() {
-        let mut extension = IosLldbExtension;
+        let mut extension = IosLldbExtension::new();
         let launch = json!({ "request": "launch" });
         assert_eq!(
             extension
@@ -209,9 +827,53 @@ mod tests {
             build_debug_adapter_binary(&config, Some("/custom/dap".into()), &worktree).unwrap();
         assert_eq!(binary.command.as_deref(), Some("/custom/dap"));
         assert!(binary
-            .envs
-            .iter()
-            .any(|(key, value)| key == CONFIG_ENV_VAR && value.contains("debugserverPort")));
+            .request_args
+            .configuration
+            .contains("debugserverPort"));
+        assert!(binary.envs.iter().any(|(key, value)| {
+            key == variables::WORKSPACE_ROOT_ENV_VAR && value == "/work/demo"
+        }));
+    }
+
+    #[test]
+    fn adapter_env_can_be_dropped_or_narrowed() {
+        let mut worktree = FakeWorktree::new();
+        worktree.set_binary_path("/usr/local/bin/swiftscope");
+        worktree.env = vec![
+            ("PATH".into(), "/usr/bin".into()),
+            ("DYLD_INSERT_LIBRARIES".into(), "/tmp/hook.dylib".into()),
+        ];
+        let keys = |adapter_env: Value| {
+            let mut task = sample_task_definition();
+            let mut config: Value = serde_json::from_str(&task.config).unwrap();
+            config["adapterEnv"] = adapter_env;
+            task.config = config.to_string();
+            let binary = build_debug_adapter_binary(&task, None, &worktree).unwrap();
+            assert!(!binary.request_args.configuration.contains("adapterEnv"));
+            binary
+                .envs
+                .into_iter()
+                .map(|(key, _)| key)
+                .filter(|key| key != variables::WORKSPACE_ROOT_ENV_VAR)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(json!("inherit")), ["PATH", "DYLD_INSERT_LIBRARIES"]);
+        assert!(keys(json!("clean")).is_empty());
+        assert_eq!(keys(json!(["PATH", "HOME"])), ["PATH"]);
+        assert!(adapter_env_from(Vec::new(), Some(json!("scrub"))).is_err());
+    }
+
+    #[test]
+    fn release_assets_are_named_after_target_triples() {
+        assert_eq!(
+            release_asset_name(Os::Mac, Architecture::Aarch64).unwrap(),
+            "swiftscope-aarch64-apple-darwin.tar.gz"
+        );
+        assert_eq!(
+            release_asset_name(Os::Linux, Architecture::X8664).unwrap(),
+            "swiftscope-x86_64-unknown-linux-gnu.tar.gz"
+        );
+        assert!(release_asset_name(Os::Windows, Architecture::X8664).is_err());
     }
 
     #[test]
@@ -220,10 +882,7 @@ mod tests {
         let mut worktree = FakeWorktree::new();
         worktree.set_binary_path("/usr/local/bin/SwiftScope");
         let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
-        assert_eq!(
-            binary.command.as_deref(),
-            Some("/usr/local/bin/SwiftScope")
-        );
+        assert_eq!(binary.command.as_deref(), Some("/usr/local/bin/SwiftScope"));
     }
 
     fn sample_task_definition() -> DebugTaskDefinition {
@@ -233,17 +892,46 @@ mod tests {
             config:
                 r#"{"request":"launch","debugserverPort":12345,"program":"/tmp/a","cwd":"/tmp"}"#
                     .into(),
-            tcp_connection: Some(TcpArgumentsTemplate {
-                port: None,
-                host: None,
-                timeout: None,
-            }),
+            tcp_connection: None,
         }
     }
 
+    #[test]
+    fn tcp_connections_start_the_adapter_listening() {
+        let mut config = sample_task_definition();
+        let mut worktree = FakeWorktree::new();
+        worktree.set_binary_path("/usr/local/bin/SwiftScope");
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        assert!(binary.arguments.is_empty() && binary.connection.is_none());
+
+        config.tcp_connection = Some(TcpArgumentsTemplate {
+            port: Some(4711),
+            host: None,
+            timeout: Some(5000),
+        });
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        assert_eq!(binary.arguments, ["--port", "4711"]);
+        let tcp = binary.connection.unwrap();
+        assert_eq!(
+            (Ipv4Addr::from(tcp.host), tcp.port, tcp.timeout),
+            (Ipv4Addr::LOCALHOST, 4711, Some(5000))
+        );
+
+        config.tcp_connection = Some(TcpArgumentsTemplate {
+            port: Some(4711),
+            host: Some(Ipv4Addr::new(10, 0, 0, 2).into()),
+            timeout: None,
+        });
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        assert_eq!(binary.arguments, ["--port", "4711", "--host", "10.0.0.2"]);
+    }
+
     struct FakeWorktree {
         binary_path: Option<String>,
         env: EnvVars,
+        version: Option<String>,
+        settings: ExtensionSettings,
+        missing: Vec<String>,
     }
 
     impl FakeWorktree {
@@ -251,6 +939,9 @@ mod tests {
             Self {
                 binary_path: None,
                 env: vec![("PATH".into(), "/tmp".into())],
+                version: Some(format!("swiftscope {}\n", env!("CARGO_PKG_VERSION"))),
+                settings: ExtensionSettings::default(),
+                missing: Vec::new(),
             }
         }
 
@@ -271,6 +962,131 @@ mod tests {
         fn shell_env(&self) -> EnvVars {
             self.env.clone()
         }
+
+        fn root_path(&self) -> String {
+            "/work/demo".into()
+        }
+
+        fn adapter_version(&self, _path: &str) -> Option<String> {
+            self.version.clone()
+        }
+
+        fn settings(&self) -> ExtensionSettings {
+            self.settings.clone()
+        }
+
+        fn path_exists(&self, path: &str) -> Option<bool> {
+            Some(!self.missing.iter().any(|missing| missing == path))
+        }
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_worktree() {
+        let mut config = sample_task_definition();
+        config.config =
+            r#"{"request":"launch","debugserverPort":1,"program":"build/a","cwd":"."}"#.into();
+        let mut worktree = FakeWorktree::new();
+        worktree.set_binary_path("/usr/local/bin/SwiftScope");
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        let resolved: Value = serde_json::from_str(&binary.request_args.configuration).unwrap();
+        assert_eq!(resolved["program"], "/work/demo/build/a");
+        assert_eq!(resolved["cwd"], "/work/demo/.");
+
+        worktree.missing.push("/work/demo/build/a".into());
+        let err = build_debug_adapter_binary(&config, None, &worktree).unwrap_err();
+        assert!(err.contains("/work/demo/build/a` does not exist"), "{err}");
+
+        config.config = r#"{"request":"attach","debugserverPort":1,"program":"build/a"}"#.into();
+        assert!(build_debug_adapter_binary(&config, None, &worktree).is_ok());
+    }
+
+    #[test]
+    fn settings_fill_in_what_the_scenario_leaves_out() {
+        let mut config = sample_task_definition();
+        config.config = r#"{"request":"launch","debugserverPort":0,"program":"/tmp/a"}"#.into();
+        let mut worktree = FakeWorktree::new();
+        worktree.settings = ExtensionSettings {
+            adapter_path: Some("/opt/swiftscope".into()),
+            debugserver_host: Some("10.0.0.2".into()),
+            debugserver_port: Some(2331),
+            xcodebuild_path: None,
+            stop_on_entry: Some(true),
+            adapter: Map::new(),
+        };
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        assert_eq!(binary.command.as_deref(), Some("/opt/swiftscope"));
+        let config: Value = serde_json::from_str(&binary.request_args.configuration).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "request": "launch",
+                "program": "/tmp/a",
+                "debugserverHost": "10.0.0.2",
+                "debugserverPort": 2331,
+                "stopOnEntry": true,
+            })
+        );
+
+        let mut config = json!({ "debugserverPort": 4000, "stopOnEntry": false });
+        worktree.settings.apply(&mut config);
+        assert_eq!(config["debugserverPort"], 4000);
+        assert_eq!(config["stopOnEntry"], false);
+
+        worktree.settings.adapter = Map::from_iter([
+            ("args".to_string(), json!(["--verbose"])),
+            ("env".to_string(), json!({ "TRACE": "1" })),
+            ("cwd".to_string(), json!("/srv")),
+        ]);
+        let mut config = json!({ "args": [], "env": {}, "cwd": null });
+        worktree.settings.apply(&mut config);
+        assert_eq!(config["args"], json!([]));
+        assert_eq!(config["env"], json!({}));
+        assert_eq!(config["cwd"], "/srv");
+    }
+
+    #[test]
+    fn manifest_grants_the_release_download() {
+        let manifest = include_str!("../extension.toml");
+        let repo_path = GITHUB_REPO.replace('/', r#"", ""#);
+        let download = format!(
+            "kind = \"download_file\"\nhost = \"github.com\"\npath = [\"{repo_path}\", \"**\"]"
+        );
+        assert!(
+            manifest
+                .split("[[capabilities]]")
+                .any(|entry| entry.trim() == download),
+            "{manifest}"
+        );
+    }
+
+    #[test]
+    fn outdated_adapters_are_refused() {
+        let config = sample_task_definition();
+        let mut worktree = FakeWorktree::new();
+        worktree.version = Some("swiftscope 0.0.9\n".into());
+        let err =
+            build_debug_adapter_binary(&config, Some("/custom/dap".into()), &worktree).unwrap_err();
+        assert!(err.contains("is swiftscope 0.0.9"), "{err}");
+        worktree.version = Some(String::new());
+        let err =
+            build_debug_adapter_binary(&config, Some("/custom/dap".into()), &worktree).unwrap_err();
+        assert!(err.contains("doesn't report its version"), "{err}");
+        worktree.version = None;
+        assert!(build_debug_adapter_binary(&config, Some("/custom/dap".into()), &worktree).is_ok());
+        assert_eq!(parse_version("swiftscope 1.2.3-beta.1"), Some((1, 2, 3)));
+    }
+
+    #[test]
+    fn schema_matches_what_the_adapter_requires() {
+        let schema: Value =
+            serde_json::from_str(include_str!("../debug_adapter_schemas/ios-lldb.json")).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let config: Value = serde_json::from_str(&sample_task_definition().config).unwrap();
+        for key in config.as_object().unwrap().keys() {
+            assert!(properties.contains_key(key), "{key} is not in the schema");
+        }
+        assert_eq!(schema["required"], json!(["request", "debugserverPort"]));
+        assert_eq!(schema["then"]["required"], json!(["program"]));
     }
 
     #[test]
@@ -287,12 +1103,186 @@ mod tests {
             stop_on_entry: Some(true),
         };
 
-        let scenario = convert_debug_config(config).unwrap();
+        let scenario = convert_debug_config(config, &ExtensionSettings::default()).unwrap();
         assert_eq!(scenario.adapter, ADAPTER_NAME);
         assert!(
             scenario.config.contains(r#""request":"launch""#),
             "config should serialize launch request"
         );
+        assert!(
+            scenario.config.contains(r#""sourceMap":[]"#),
+            "config should advertise sourceMap"
+        );
+    }
+
+    #[test]
+    fn adapter_keys_from_settings_reach_zeds_own_scenarios() {
+        let settings: ExtensionSettings = serde_json::from_value(json!({
+            "xcodebuildPath": "/opt/xcodebuild",
+            "dsymPath": "${workspaceRoot}/build/MyApp.app.dSYM",
+            "sourceMap": [["/ci/src", "${workspaceRoot}"]],
+            "signals": { "SIGPIPE": "pass" },
+        }))
+        .unwrap();
+        assert_eq!(settings.xcodebuild_path.as_deref(), Some("/opt/xcodebuild"));
+        let config = DebugConfig {
+            label: "Demo".into(),
+            adapter: ADAPTER_NAME.into(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: "/bin/app".into(),
+                cwd: None,
+                args: Vec::new(),
+                envs: Vec::new(),
+            }),
+            stop_on_entry: None,
+        };
+        let scenario = convert_debug_config(config, &settings).unwrap();
+        let config: Value = serde_json::from_str(&scenario.config).unwrap();
+        assert_eq!(config["dsymPath"], "${workspaceRoot}/build/MyApp.app.dSYM");
+        assert_eq!(
+            config["sourceMap"],
+            json!([["/ci/src", "${workspaceRoot}"]])
+        );
+        assert_eq!(config["signals"], json!({ "SIGPIPE": "pass" }));
+        assert!(config.get("xcodebuildPath").is_none());
+    }
+
+    #[test]
+    fn flavors_preconfigure_their_target() {
+        let mut config = sample_task_definition();
+        config.adapter = DEVICE_ADAPTER.into();
+        let mut worktree = FakeWorktree::new();
+        worktree.set_binary_path("/usr/local/bin/SwiftScope");
+        let binary = build_debug_adapter_binary(&config, None, &worktree).unwrap();
+        let resolved: Value = serde_json::from_str(&binary.request_args.configuration).unwrap();
+        assert_eq!(resolved["usbForward"], true);
+
+        let config = DebugConfig {
+            label: "Demo".into(),
+            adapter: SIMULATOR_ADAPTER.into(),
+            request: DebugRequest::Attach(AttachRequest {
+                process_id: Some(7),
+            }),
+            stop_on_entry: None,
+        };
+        let scenario = convert_debug_config(config, &ExtensionSettings::default()).unwrap();
+        assert_eq!(scenario.adapter, SIMULATOR_ADAPTER);
+        assert!(scenario.config.contains(r#""bootSimulator":true"#));
+        assert!(ensure_adapter("ios-lldb-watch").is_err());
+    }
+
+    #[test]
+    fn malformed_configurations_name_the_key_and_the_fix() {
+        assert!(check_config(
+            ADAPTER_NAME,
+            &json!({ "request": "attach", "debugserverPort": 0 }),
+            true
+        )
+        .is_ok());
+        let err = check_config(
+            SIMULATOR_ADAPTER,
+            &json!({
+                "request": "launch",
+                "program": "",
+                "debugserverPort": 70000,
+                "sourceMap": [["/ci/src"]],
+            }),
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.starts_with("invalid ios-lldb-sim configuration:"),
+            "{err}"
+        );
+        assert_eq!(
+            err.lines().skip(1).map(str::trim).collect::<Vec<_>>(),
+            [
+                "`program` is missing; set it to the executable to launch, such as the binary \
+                 inside the built .app",
+                "`debugserverPort` is 70000; use a port number from 1 to 65535",
+                "`sourceMap` must be a list of [build path, local path] pairs, such as \
+                 [[\"/ci/src\", \"${workspaceRoot}\"]]",
+            ]
+        );
+        let err = check_config(
+            ADAPTER_NAME,
+            &json!({ "request": "attach", "debugserverPort": 0 }),
+            false,
+        )
+        .unwrap_err();
+        assert!(err.contains("`debugserverPort` is 0"), "{err}");
+        let err = check_config(
+            ADAPTER_NAME,
+            &json!({ "request": "run", "debugserverPort": 1 }),
+            true,
+        )
+        .unwrap_err();
+        assert!(err.contains("`request` is \"run\""), "{err}");
+    }
+
+    #[test]
+    fn attach_scenarios_without_a_pid_pick_a_process() {
+        let config = DebugConfig {
+            label: "Demo".into(),
+            adapter: ADAPTER_NAME.into(),
+            request: DebugRequest::Attach(AttachRequest { process_id: None }),
+            stop_on_entry: None,
+        };
+        let scenario = convert_debug_config(config.clone(), &ExtensionSettings::default()).unwrap();
+        let body: Value = serde_json::from_str(&scenario.config).unwrap();
+        assert!(
+            body.get("pickProcess").is_none(),
+            "nothing to match: {body}"
+        );
+        assert!(body.get("processId").is_none());
+
+        let settings: ExtensionSettings =
+            serde_json::from_value(json!({ "program": "/Apps/MyApp.app/MyApp" })).unwrap();
+        let scenario = convert_debug_config(config, &settings).unwrap();
+        let body: Value = serde_json::from_str(&scenario.config).unwrap();
+        assert_eq!(body["pickProcess"], true);
+        assert!(body.get("processId").is_none());
+    }
+
+    #[test]
+    fn launch_scenarios_rebuild_their_program_first() {
+        let task = build_task(
+            "/Users/me/Library/Developer/Xcode/DerivedData/MyApp-abc/Build/Products/\
+             Debug-iphonesimulator/MyApp.app/MyApp",
+            Some("/work/MyApp"),
+            "xcodebuild",
+        )
+        .unwrap();
+        assert_eq!(task.command, "xcodebuild");
+        assert_eq!(
+            task.args,
+            [
+                "build",
+                "-scheme",
+                "MyApp",
+                "-configuration",
+                "Debug",
+                "-destination",
+                "generic/platform=iOS Simulator",
+                "-derivedDataPath",
+                "/Users/me/Library/Developer/Xcode/DerivedData/MyApp-abc",
+            ]
+        );
+        assert_eq!(task.cwd.as_deref(), Some("/work/MyApp"));
+
+        let task = build_task(
+            "/work/Tool/.build/arm64-apple-macosx/release/tool",
+            None,
+            "xcodebuild",
+        )
+        .unwrap();
+        assert_eq!(
+            (task.command.as_str(), task.cwd.as_deref()),
+            ("swift", Some("/work/Tool"))
+        );
+        assert_eq!(task.args, ["build", "--product", "tool", "-c", "release"]);
+
+        assert!(build_task("/bin/app", None, "xcodebuild").is_none());
     }
 
     #[test]
@@ -306,7 +1296,7 @@ mod tests {
             stop_on_entry: None,
         };
 
-        let scenario = convert_debug_config(config).unwrap();
+        let scenario = convert_debug_config(config, &ExtensionSettings::default()).unwrap();
         assert!(
             scenario.config.contains(r#""processId":42"#),
             "attach scenario should include process id"
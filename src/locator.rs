@@ -0,0 +1,264 @@
+//! The extension's debug locator: "Debug" on a `swift build`/`swift run` or
+//! `xcodebuild` task builds it and then debugs the program it produced, so
+//! simple cases need no hand-written debug.json.
+
+use std::path::Path;
+
+use serde_json::Value;
+use zed_extension_api::{
+    process::Command, BuildTaskDefinition, BuildTaskDefinitionTemplatePayload, DebugRequest,
+    DebugScenario, LaunchRequest, TaskTemplate,
+};
+
+pub const LOCATOR_NAME: &str = "ios-lldb";
+
+/// A scenario that runs `task` (as a build, where it was a `swift run`) and
+/// leaves the program for [`locate`], or `None` for tasks that build
+/// nothing this locator knows how to find.
+pub fn create_scenario(
+    task: TaskTemplate,
+    label: String,
+    adapter: String,
+) -> Option<DebugScenario> {
+    let template = build_task(task)?;
+    Some(DebugScenario {
+        label,
+        adapter,
+        build: Some(BuildTaskDefinition::Template(
+            BuildTaskDefinitionTemplatePayload {
+                locator_name: Some(LOCATOR_NAME.into()),
+                template,
+            },
+        )),
+        config: "{}".into(),
+        tcp_connection: None,
+    })
+}
+
+/// The program `task` built, asked of the build tool itself.
+pub fn locate(task: TaskTemplate) -> Result<DebugRequest, String> {
+    let cwd = task.cwd.as_deref();
+    let program = match tool(&task.command) {
+        Some("swift") => {
+            let mut args = task.args.clone();
+            let product = match flag_value(&args, "--product") {
+                Some(product) => product.to_string(),
+                None => {
+                    let description =
+                        run_in(cwd, "swift", &["package", "describe", "--type", "json"])?;
+                    executable_product(&parse(&description)?)
+                        .ok_or("the package has no executable product")?
+                }
+            };
+            args.push("--show-bin-path".into());
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let bin = run_in(cwd, "swift", &args)?;
+            Path::new(bin.trim())
+                .join(product)
+                .to_string_lossy()
+                .into_owned()
+        }
+        Some("xcodebuild") => {
+            let mut args: Vec<&str> = task
+                .args
+                .iter()
+                .map(String::as_str)
+                .filter(|arg| *arg != "build")
+                .collect();
+            args.extend(["-showBuildSettings", "-json"]);
+            let settings = run_in(cwd, "xcodebuild", &args)?;
+            built_program(&parse(&settings)?).ok_or("xcodebuild reported no executable target")?
+        }
+        _ => return Err(format!("{} isn't a build this locator knows", task.command)),
+    };
+    Ok(DebugRequest::Launch(LaunchRequest {
+        program,
+        cwd: task.cwd,
+        args: Vec::new(),
+        envs: Vec::new(),
+    }))
+}
+
+/// `task` as the build it implies: `swift build` (with `swift run`'s
+/// product as `--product`) or a plain `xcodebuild [build]`.
+fn build_task(mut task: TaskTemplate) -> Option<TaskTemplate> {
+    match tool(&task.command)? {
+        "swift" => {
+            match task.args.first().map(String::as_str) {
+                Some("build") => {}
+                Some("run") => {
+                    let run = std::mem::replace(&mut task.args, vec!["build".into()]);
+                    // What follows `--` is for the program, not the build.
+                    let mut options = run[1..].iter().take_while(|arg| *arg != "--");
+                    while let Some(arg) = options.next() {
+                        if arg.starts_with('-') {
+                            task.args.push(arg.clone());
+                            if takes_value(arg) {
+                                task.args.extend(options.next().cloned());
+                            }
+                        } else {
+                            task.args.extend(["--product".into(), arg.clone()]);
+                        }
+                    }
+                }
+                _ => return None,
+            }
+            Some(task)
+        }
+        "xcodebuild" => {
+            let other_action = task
+                .args
+                .iter()
+                .any(|arg| XCODEBUILD_ACTIONS.contains(&arg.as_str()));
+            (!other_action).then_some(task)
+        }
+        _ => None,
+    }
+}
+
+/// xcodebuild actions other than `build`, which is also the default.
+const XCODEBUILD_ACTIONS: &[&str] = &[
+    "build-for-testing",
+    "analyze",
+    "archive",
+    "test",
+    "test-without-building",
+    "docbuild",
+    "installsrc",
+    "install",
+    "clean",
+];
+
+/// The tool `command` runs, when it is `swift` or `xcodebuild` (by name or
+/// by path).
+fn tool(command: &str) -> Option<&str> {
+    let name = Path::new(command).file_name()?.to_str()?;
+    matches!(name, "swift" | "xcodebuild").then_some(name)
+}
+
+/// `swift build` options followed by a separate value.
+fn takes_value(option: &str) -> bool {
+    matches!(
+        option,
+        "-c" | "--configuration"
+            | "--package-path"
+            | "--scratch-path"
+            | "--build-path"
+            | "--triple"
+            | "--sdk"
+            | "--arch"
+            | "-Xswiftc"
+            | "-Xcc"
+            | "-Xlinker"
+    )
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.windows(2)
+        .find(|pair| pair[0] == flag)
+        .map(|pair| pair[1].as_str())
+}
+
+/// The first executable product in `swift package describe --type json`.
+fn executable_product(description: &Value) -> Option<String> {
+    description["products"]
+        .as_array()?
+        .iter()
+        .find(|product| product["type"].get("executable").is_some())
+        .and_then(|product| product["name"].as_str())
+        .map(str::to_string)
+}
+
+/// The executable xcodebuild's `-showBuildSettings -json` reports,
+/// preferring an application target to tools and frameworks.
+fn built_program(settings: &Value) -> Option<String> {
+    let targets = settings.as_array()?;
+    let executable = |want_app: bool| {
+        targets.iter().find_map(|target| {
+            let settings = target.get("buildSettings")?;
+            let get = |key: &str| settings.get(key).and_then(Value::as_str);
+            let is_app = get("WRAPPER_EXTENSION") == Some("app");
+            if want_app != is_app || (!is_app && get("MACH_O_TYPE") != Some("mh_execute")) {
+                return None;
+            }
+            Some(format!(
+                "{}/{}",
+                get("TARGET_BUILD_DIR")?,
+                get("EXECUTABLE_PATH")?
+            ))
+        })
+    };
+    executable(true).or_else(|| executable(false))
+}
+
+fn parse(output: &str) -> Result<Value, String> {
+    serde_json::from_str(output).map_err(|err| format!("unexpected build tool output: {err}"))
+}
+
+/// Runs `command` in `cwd` (Zed's process API has no working directory of
+/// its own, so a shell changes into it) and returns its stdout.
+fn run_in(cwd: Option<&str>, command: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("/bin/sh")
+        .args(["-c", "cd \"$1\" && shift && exec \"$@\"", "sh"])
+        .arg(cwd.unwrap_or("."))
+        .arg(command)
+        .args(args.iter().copied())
+        .output()?;
+    if output.status != Some(0) {
+        return Err(format!(
+            "{command} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task(command: &str, args: &[&str]) -> TaskTemplate {
+        TaskTemplate {
+            label: "build".into(),
+            command: command.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: Vec::new(),
+            cwd: Some("/work/MyApp".into()),
+        }
+    }
+
+    #[test]
+    fn runs_become_builds_and_other_tasks_are_left_alone() {
+        let build = build_task(task("swift", &["run", "-c", "release", "tool", "--", "-v"]));
+        assert_eq!(
+            build.unwrap().args,
+            ["build", "-c", "release", "--product", "tool"]
+        );
+        let build = build_task(task("/usr/bin/xcodebuild", &["-scheme", "MyApp", "build"]));
+        assert!(build.is_some());
+        assert!(build_task(task("xcodebuild", &["-scheme", "MyApp", "test"])).is_none());
+        assert!(build_task(task("swift", &["test"])).is_none());
+        assert!(build_task(task("cargo", &["build"])).is_none());
+    }
+
+    #[test]
+    fn programs_come_from_the_build_tools_reports() {
+        let settings = json!([
+            { "target": "Kit", "buildSettings": {
+                "MACH_O_TYPE": "mh_dylib", "TARGET_BUILD_DIR": "/dd", "EXECUTABLE_PATH": "Kit" } },
+            { "target": "MyApp", "buildSettings": {
+                "WRAPPER_EXTENSION": "app", "TARGET_BUILD_DIR": "/dd/Debug-iphonesimulator",
+                "EXECUTABLE_PATH": "MyApp.app/MyApp" } },
+        ]);
+        assert_eq!(
+            built_program(&settings).as_deref(),
+            Some("/dd/Debug-iphonesimulator/MyApp.app/MyApp")
+        );
+        let description = json!({ "products": [
+            { "name": "Lib", "type": { "library": ["automatic"] } },
+            { "name": "tool", "type": { "executable": null } },
+        ]});
+        assert_eq!(executable_product(&description).as_deref(), Some("tool"));
+    }
+}
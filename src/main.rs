@@ -1,37 +1,290 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
 use swiftscope::{
-    backend::{Backend, BackendStopEvent},
+    app_logs::{self, LogStream},
+    app_process::{self, AppHost},
+    backend::{self, AttachTarget, Backend, BackendStopEvent, LaunchSpec, RunState},
     debug_session::init_backend,
+    gdb_remote,
+    signals::{SignalAction, SignalPolicy},
+    source_map::SourceMap,
+    variables::Variables,
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // The Zed extension checks this before starting a session.
+    if args.first().map(String::as_str) == Some("--version") {
+        println!("swiftscope {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
     let _ = env_logger::builder().format_timestamp(None).try_init();
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut reader = BufReader::new(stdin.lock());
-    let writer = BufWriter::new(stdout.lock());
-    let backend = init_backend()?;
-    let mut session = Session::new(backend, writer);
-
-    while let Some(message) = read_dap_message(&mut reader)? {
-        let envelope: DapEnvelope = match serde_json::from_str(&message) {
-            Ok(payload) => payload,
-            Err(err) => {
-                eprintln!("Failed to parse DAP message: {err}");
-                continue;
+    let listen = parse_listen_args(&args).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{err}\nusage: swiftscope [--version | --port PORT [--host HOST] [--multi]]"),
+        )
+    })?;
+    match listen {
+        None => serve(io::stdin(), io::stdout()),
+        Some(listen) => {
+            let listener = TcpListener::bind((listen.host.as_str(), listen.port))?;
+            // Port 0 picks a free port; clients other than Zed learn it here.
+            eprintln!("swiftscope: listening on {}", listener.local_addr()?);
+            accept_clients(listener, listen.multi)
+        }
+    }
+}
+
+/// Where and how to accept DAP clients over TCP.
+#[derive(Debug, PartialEq)]
+struct Listen {
+    host: String,
+    port: u16,
+    /// Keep accepting clients, each a session of its own, instead of
+    /// serving the first and exiting as with stdio.
+    multi: bool,
+}
+
+/// `--port PORT`, `--host HOST` (default 127.0.0.1) and `--multi`, or
+/// `None` for stdio.
+fn parse_listen_args(args: &[String]) -> Result<Option<Listen>, String> {
+    let mut host = None;
+    let mut port = None;
+    let mut multi = false;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--multi" {
+            multi = true;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{flag} needs a value"))?;
+        match flag.as_str() {
+            "--port" => {
+                port = Some(value.parse().map_err(|_| format!("invalid port {value}"))?);
             }
-        };
+            "--host" => host = Some(value.clone()),
+            _ => return Err(format!("unknown argument {flag}")),
+        }
+    }
+    match (port, host, multi) {
+        (None, None, false) => Ok(None),
+        (None, _, _) => Err("--host and --multi need --port".into()),
+        (Some(port), host, multi) => Ok(Some(Listen {
+            host: host.unwrap_or_else(|| Ipv4Addr::LOCALHOST.to_string()),
+            port,
+            multi,
+        })),
+    }
+}
+
+/// Serves DAP clients connecting to `listener`: just the first, as Zed
+/// does when it starts the adapter for one session, or with `multi` every
+/// one, concurrently, each with its own backend and debugserver connection.
+fn accept_clients(listener: TcpListener, multi: bool) -> io::Result<()> {
+    if !multi {
+        let (stream, peer) = listener.accept()?;
+        eprintln!("swiftscope: DAP client connected from {peer}");
+        return serve(stream.try_clone()?, stream);
+    }
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        eprintln!("swiftscope: DAP client connected from {peer}");
+        thread::spawn(
+            move || match stream.try_clone().and_then(|input| serve(input, stream)) {
+                Ok(()) => eprintln!("swiftscope: session with {peer} ended"),
+                Err(err) => eprintln!("swiftscope: session with {peer} failed: {err}"),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// What the session loop waits on: Zed's messages, and whatever else
+/// (app logs so far) has something to report.
+enum Input {
+    Message(io::Result<String>),
+    AppLog(String),
+    /// debugserver has something to say while the target runs, most likely
+    /// the stop reply.
+    Stop,
+    /// Zed closed the connection.
+    Closed,
+}
+
+/// Runs one DAP session over `input` and `output`. Messages are read and
+/// written on threads of their own, and a watcher reports the target
+/// stopping, so the session only ever waits on its inputs.
+fn serve(input: impl Read + Send + 'static, output: impl Write + Send + 'static) -> io::Result<()> {
+    let (writer, written) = spawn_dap_writer(output);
+    let (inputs, received) = mpsc::channel();
+    spawn_dap_reader(input, inputs.clone());
+    let mut session = Session::new(writer, inputs);
+    let result = run_session(&mut session, &received);
+
+    // Everything the session wrote goes out before the adapter exits, even
+    // when it ended on an error.
+    drop(session);
+    let written = written
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("DAP writer panicked")));
+    result.and(written)
+}
+
+fn run_session<W: Write>(session: &mut Session<W>, received: &Receiver<Input>) -> io::Result<()> {
+    // The session holds a sender of its own, so this only ends on `Closed`.
+    while let Ok(input) = received.recv() {
+        match input {
+            Input::Message(message) => {
+                let envelope: DapEnvelope = match serde_json::from_str(&message?) {
+                    Ok(payload) => payload,
+                    Err(err) => {
+                        eprintln!("Failed to parse DAP message: {err}");
+                        continue;
+                    }
+                };
+                if let DapEnvelope::Request(request) = envelope {
+                    if !session.handle_request(request)? {
+                        break;
+                    }
+                }
+            }
+            Input::AppLog(line) => session.emit_app_log(&line)?,
+            Input::Stop => session.poll_target()?,
+            Input::Closed => break,
+        }
+        session.watch_target()?;
+    }
+    Ok(())
+}
+
+/// Waits on debugserver's connection while the target runs and sends
+/// [`Input::Stop`] once there is something to read. It only peeks, leaving
+/// the bytes for the session to read on its own thread.
+struct StopWatch {
+    /// Set by the watcher once it has fired, or by the session to call it
+    /// off.
+    over: Arc<AtomicBool>,
+}
+
+impl StopWatch {
+    fn spawn(connection: TcpStream, inputs: Sender<Input>) -> Self {
+        let over = Arc::new(AtomicBool::new(false));
+        let watching = Arc::clone(&over);
+        thread::spawn(move || {
+            let mut probe = [0u8; 1];
+            // The connection's read timeout wakes the peek now and then to
+            // see whether the watch was called off.
+            while !watching.load(Ordering::SeqCst) {
+                match connection.peek(&mut probe) {
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) => {}
+                    // Bytes, end of file or an error: the session's next
+                    // read tells which.
+                    _ => {
+                        if !watching.swap(true, Ordering::SeqCst) {
+                            let _ = inputs.send(Input::Stop);
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+        Self { over }
+    }
+
+    fn is_live(&self) -> bool {
+        !self.over.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for StopWatch {
+    fn drop(&mut self) {
+        self.over.store(true, Ordering::SeqCst);
+    }
+}
 
-        if let DapEnvelope::Request(request) = envelope {
-            if !session.handle_request(request)? {
+/// Reads DAP messages from `input` on a thread of its own, so the session
+/// loop can poll the target and forward app logs while Zed is quiet.
+fn spawn_dap_reader(input: impl Read + Send + 'static, inputs: Sender<Input>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(input);
+        loop {
+            let message = match read_dap_message(&mut reader) {
+                Ok(Some(message)) => Ok(message),
+                Ok(None) => {
+                    let _ = inputs.send(Input::Closed);
+                    break;
+                }
+                Err(err) => Err(err),
+            };
+            let failed = message.is_err();
+            if inputs.send(Input::Message(message)).is_err() || failed {
                 break;
             }
         }
+    });
+}
+
+/// Writes the messages sent to the returned [`DapWriter`] to `output` on a
+/// thread of its own, so a slow client never stalls the session. The
+/// thread ends, with the first write error if any, once the writer drops.
+fn spawn_dap_writer(
+    output: impl Write + Send + 'static,
+) -> (DapWriter, thread::JoinHandle<io::Result<()>>) {
+    let (sender, messages) = mpsc::channel::<Vec<u8>>();
+    let written = thread::spawn(move || {
+        let mut output = BufWriter::new(output);
+        for message in messages {
+            output.write_all(&message)?;
+            output.flush()?;
+        }
+        Ok(())
+    });
+    let writer = DapWriter {
+        pending: Vec::new(),
+        sender,
+    };
+    (writer, written)
+}
+
+/// Collects a message's bytes and hands them to the writer thread on
+/// `flush`, which [`write_dap_message`] calls once per message.
+struct DapWriter {
+    pending: Vec<u8>,
+    sender: Sender<Vec<u8>>,
+}
+
+impl Write for DapWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(bytes);
+        Ok(bytes.len())
     }
 
-    Ok(())
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .send(std::mem::take(&mut self.pending))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "DAP writer stopped"))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -53,18 +306,76 @@ struct RawRequest {
 
 #[derive(Deserialize)]
 struct LaunchArguments {
+    #[serde(rename = "debugserverHost", default = "default_debugserver_host")]
+    debugserver_host: String,
     #[serde(rename = "debugserverPort")]
     debugserver_port: u16,
     program: String,
     cwd: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default = "default_disable_aslr", rename = "disableASLR")]
+    disable_aslr: bool,
+    #[serde(default)]
+    stdio: Vec<Option<String>>,
+    #[serde(default)]
+    signals: HashMap<String, SignalAction>,
+    #[serde(default, rename = "regexBreakpoints")]
+    regex_breakpoints: Vec<String>,
+    #[serde(default, rename = "sourceMap")]
+    source_map: Vec<(String, String)>,
+    /// Stream the app's logs from `device` or `simulator` as output events.
+    #[serde(default)]
+    logs: bool,
+    device: Option<String>,
+    simulator: Option<String>,
+    #[serde(default, rename = "stopOnEntry")]
+    stop_on_entry: bool,
+    #[serde(default, rename = "bootSimulator")]
+    boot_simulator: bool,
+    #[serde(default, rename = "usbForward")]
+    usb_forward: bool,
 }
 
 #[derive(Deserialize)]
 struct AttachArguments {
+    #[serde(rename = "debugserverHost", default = "default_debugserver_host")]
+    debugserver_host: String,
     #[serde(rename = "debugserverPort")]
     debugserver_port: u16,
     program: Option<String>,
     cwd: Option<String>,
+    #[serde(alias = "processId")]
+    pid: Option<u32>,
+    /// Attach to the running app with this bundle identifier, looked up on
+    /// `device` or, without one, on `simulator` (default the booted one).
+    #[serde(rename = "bundleId")]
+    bundle_id: Option<String>,
+    device: Option<String>,
+    simulator: Option<String>,
+    #[serde(default, rename = "waitFor")]
+    wait_for: bool,
+    /// Find the process among those running where `device` or `simulator`
+    /// say (else on this Mac), by the file name of `program` if given.
+    #[serde(default, rename = "pickProcess")]
+    pick_process: bool,
+    /// Boot `simulator` first, for the `ios-lldb-sim` adapter.
+    #[serde(default, rename = "bootSimulator")]
+    boot_simulator: bool,
+    /// Reach `debugserverPort` on `device` over usbmuxd, for the
+    /// `ios-lldb-device` adapter.
+    #[serde(default, rename = "usbForward")]
+    usb_forward: bool,
+    #[serde(default)]
+    signals: HashMap<String, SignalAction>,
+    #[serde(default, rename = "regexBreakpoints")]
+    regex_breakpoints: Vec<String>,
+    #[serde(default, rename = "sourceMap")]
+    source_map: Vec<(String, String)>,
+    #[serde(default)]
+    logs: bool,
 }
 
 #[derive(Deserialize)]
@@ -100,28 +411,86 @@ struct VariablesArguments {
 struct ThreadArguments {
     #[serde(rename = "threadId")]
     thread_id: i64,
+    #[serde(rename = "singleThread", default)]
+    single_thread: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct DisconnectArguments {
+    #[serde(rename = "terminateDebuggee")]
+    terminate_debuggee: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct SetAddressBreakpointsArguments {
+    #[serde(default)]
+    breakpoints: Vec<AddressBreakpoint>,
+}
+
+#[derive(Deserialize)]
+struct AddressBreakpoint {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct ReadMemoryArguments {
+    #[serde(rename = "memoryReference")]
+    memory_reference: String,
+    #[serde(default)]
+    offset: i64,
+    count: usize,
+}
+
+#[derive(Deserialize)]
+struct EvaluateArguments {
+    expression: String,
+    #[serde(rename = "frameId")]
+    frame_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
 struct ScopesArguments {
     #[serde(rename = "frameId")]
-    _frame_id: i64,
+    frame_id: i64,
 }
 
 struct Session<W: Write> {
     next_seq: i64,
     initialized: bool,
-    backend: Backend,
+    /// Whether the debuggee was launched rather than attached to, which
+    /// decides if `disconnect` kills it by default.
+    launched: bool,
+    /// Report the launched program's stop at its entry point once Zed is
+    /// done configuring, so the session shows it paused there.
+    stop_on_entry: bool,
+    /// Created by `launch` or `attach` for the program they name.
+    backend: Option<Backend>,
     writer: W,
+    /// The session loop's inputs, for sources the session starts itself.
+    inputs: Sender<Input>,
+    /// The app's log tail while `logs` is on; its lines arrive on `inputs`.
+    app_logs: Option<LogStream>,
+    /// Watches debugserver while the target runs; its stop arrives on
+    /// `inputs`.
+    stop_watch: Option<StopWatch>,
+    /// What `reach_debugserver` set up to get to debugserver, kept until the
+    /// session ends.
+    route: Option<Route>,
 }
 
 impl<W: Write> Session<W> {
-    fn new(backend: Backend, writer: W) -> Self {
+    fn new(writer: W, inputs: Sender<Input>) -> Self {
         Self {
             next_seq: 1,
             initialized: false,
-            backend,
+            launched: false,
+            stop_on_entry: false,
+            backend: None,
             writer,
+            inputs,
+            app_logs: None,
+            stop_watch: None,
+            route: None,
         }
     }
 
@@ -132,12 +501,32 @@ impl<W: Write> Session<W> {
             arguments,
         } = request;
         let command_str = command.as_str();
-        match command_str {
+        if self.backend.is_none() && !works_before_launch(command_str) {
+            self.send_error_response(
+                seq,
+                command_str,
+                "no program to debug yet: the session starts with `launch` or `attach`".into(),
+            )?;
+            return Ok(true);
+        }
+
+        self.poll_target()?;
+        if requires_stopped_target(command_str) {
+            if let Err(err) = self.backend().require_stopped() {
+                self.send_error_response(seq, command_str, err)?;
+                return Ok(true);
+            }
+        }
+
+        let keep_running = match command_str {
             "initialize" => self.handle_initialize(seq, command_str),
             "launch" => self.handle_launch(seq, command_str, arguments),
             "attach" => self.handle_attach(seq, command_str, arguments),
             "setBreakpoints" => self.handle_set_breakpoints(seq, command_str, arguments),
-            "configurationDone" => self.handle_simple_ok(seq, command_str, Value::Null),
+            "setAddressBreakpoints" => {
+                self.handle_set_address_breakpoints(seq, command_str, arguments)
+            }
+            "configurationDone" => self.handle_configuration_done(seq, command_str),
             "threads" => self.handle_threads(seq, command_str),
             "stackTrace" => self.handle_stack_trace(seq, command_str, arguments),
             "scopes" => self.handle_scopes(seq, command_str, arguments),
@@ -145,14 +534,111 @@ impl<W: Write> Session<W> {
             "continue" => self.handle_continue(seq, command_str, arguments),
             "next" => self.handle_next(seq, command_str, arguments),
             "stepIn" => self.handle_step_in(seq, command_str, arguments),
-            "disconnect" => self.handle_disconnect(seq, command_str),
+            "pause" => self.handle_pause(seq, command_str),
+            "evaluate" => self.handle_evaluate(seq, command_str, arguments),
+            "readMemory" => self.handle_read_memory(seq, command_str, arguments),
+            "terminate" => self.handle_terminate(seq, command_str),
+            "disconnect" => self.handle_disconnect(seq, command_str, arguments),
             _ => {
                 self.send_error_response(seq, command_str, format!("Unknown command: {command}"))?;
                 Ok(true)
             }
+        }?;
+        self.emit_backend_notices()?;
+        Ok(keep_running)
+    }
+
+    /// Reports a stop the target reached since it was resumed, such as a
+    /// breakpoint hit after `continue` answered, and any backend notices.
+    fn poll_target(&mut self) -> io::Result<()> {
+        let stop = self.backend.as_mut().map_or(Ok(None), Backend::poll_stop);
+        match stop {
+            Ok(Some(event)) => self.emit_stop_event(event)?,
+            Ok(None) => {}
+            Err(err) => eprintln!("failed to poll debugserver for a stop: {err}"),
+        }
+        self.emit_backend_notices()
+    }
+
+    /// Reports a stop already waiting in the backend, then keeps a
+    /// [`StopWatch`] on debugserver for as long as the target runs.
+    fn watch_target(&mut self) -> io::Result<()> {
+        if self.backend.as_ref().is_some_and(Backend::has_pending_stop) {
+            self.poll_target()?;
+        }
+        let running = self
+            .backend
+            .as_ref()
+            .is_some_and(|backend| backend.run_state() == RunState::Running);
+        if !running {
+            self.stop_watch = None;
+        } else if !self.stop_watch.as_ref().is_some_and(StopWatch::is_live) {
+            self.stop_watch = self
+                .backend
+                .as_ref()
+                .and_then(Backend::running_connection)
+                .map(|connection| StopWatch::spawn(connection, self.inputs.clone()));
+        }
+        Ok(())
+    }
+
+    /// The backend `launch` or `attach` created; `handle_request` turns
+    /// away everything else that comes before them.
+    fn backend(&mut self) -> &mut Backend {
+        self.backend
+            .as_mut()
+            .expect("requests before launch or attach are refused")
+    }
+
+    /// Reports connection recovery and similar backend notices on the debug
+    /// console.
+    fn emit_backend_notices(&mut self) -> io::Result<()> {
+        let notices = match self.backend.as_mut() {
+            Some(backend) => backend.take_notices(),
+            None => Vec::new(),
+        };
+        for notice in notices {
+            self.emit_event(
+                "output",
+                json!({ "category": "console", "output": format!("{notice}\n") }),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Tails the app's logs on `host` for the rest of the session; failing
+    /// to is reported on the console rather than failing the request.
+    fn start_app_logs(&mut self, program: Option<&str>, host: &AppHost) -> io::Result<()> {
+        let Some(process) = program.and_then(|program| program.rsplit('/').next()) else {
+            return self.emit_event(
+                "output",
+                json!({ "category": "console", "output": "logs needs `program` to name the app's process\n" }),
+            );
+        };
+        let inputs = self.inputs.clone();
+        let command = app_logs::log_command(process, host);
+        match LogStream::spawn(command, move |line| {
+            let _ = inputs.send(Input::AppLog(line));
+        }) {
+            Ok(stream) => {
+                self.app_logs = Some(stream);
+                Ok(())
+            }
+            Err(err) => self.emit_event(
+                "output",
+                json!({ "category": "console", "output": format!("{err}\n") }),
+            ),
         }
     }
 
+    /// Forwards a line of the app's logs as `stdout` output.
+    fn emit_app_log(&mut self, line: &str) -> io::Result<()> {
+        self.emit_event(
+            "output",
+            json!({ "category": "stdout", "output": format!("{line}\n") }),
+        )
+    }
+
     fn handle_initialize(&mut self, seq: i64, command: &str) -> io::Result<bool> {
         self.initialized = true;
         self.respond(
@@ -161,15 +647,21 @@ impl<W: Write> Session<W> {
             true,
             Some(json!({
                 "supportsConfigurationDoneRequest": true,
+                "supportsReadMemoryRequest": true,
+                "supportsTerminateRequest": true,
             })),
             None,
         )?;
-        self.emit_event("initialized", Value::Null)?;
+        // `initialized` waits for `launch` or `attach`: breakpoints Zed sends
+        // in reply need the backend those create.
         Ok(true)
     }
 
     fn handle_launch(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
-        let args: LaunchArguments = match parse_arguments(arguments) {
+        let args: LaunchArguments = match expand_variables(arguments)
+            .and_then(|arguments| self.load_program(arguments, true))
+            .and_then(parse_arguments)
+        {
             Ok(args) => args,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
@@ -177,7 +669,59 @@ impl<W: Write> Session<W> {
             }
         };
 
-        if let Err(err) = self.backend.connect_debugserver(args.debugserver_port) {
+        if let Err(err) = self.apply_signal_policy(&args.signals) {
+            self.send_error_response(seq, command, err)?;
+            return Ok(true);
+        }
+        self.backend()
+            .set_source_map(SourceMap::new(args.source_map.clone()));
+
+        let reached = reach_debugserver(
+            &args.debugserver_host,
+            args.debugserver_port,
+            args.device.as_deref(),
+            args.simulator.as_deref(),
+            (args.boot_simulator, args.usb_forward),
+        );
+        let connected = reached.and_then(|(host, port, route)| {
+            self.backend().connect_debugserver(&host, port)?;
+            Ok(route)
+        });
+        match connected {
+            Ok(route) => self.route = route,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        }
+
+        self.launched = true;
+        self.stop_on_entry = args.stop_on_entry;
+
+        // A debugserver started without a program waits for us to spawn it.
+        if !self.backend().has_process() {
+            let spec = LaunchSpec {
+                program: args.program.clone(),
+                args: args.args.clone(),
+                env: args.env.clone().into_iter().collect(),
+                cwd: args.cwd.clone(),
+                disable_aslr: args.disable_aslr,
+                stdio: args.stdio.clone(),
+            };
+            if let Err(err) = self.backend().launch_process(&spec) {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        } else if !args.args.is_empty() || !args.env.is_empty() {
+            eprintln!(
+                "debugserver already started {}; launch args/env cannot be applied",
+                args.program
+            );
+        }
+
+        self.load_remote_program(Some(&args.program));
+
+        if let Err(err) = self.apply_regex_breakpoints(&args.regex_breakpoints) {
             self.send_error_response(seq, command, err)?;
             return Ok(true);
         }
@@ -188,13 +732,25 @@ impl<W: Write> Session<W> {
             json!({
                 "program": args.program,
                 "cwd": args.cwd,
+                "args": args.args,
+                "debugserverHost": args.debugserver_host,
                 "debugserverPort": args.debugserver_port,
             }),
-        )
+        )?;
+        self.emit_event("initialized", Value::Null)?;
+        self.emit_process_event(&args.program, "launch")?;
+        if args.logs {
+            let host = app_host(args.device.as_ref(), args.simulator.as_ref());
+            self.start_app_logs(Some(&args.program), &host)?;
+        }
+        Ok(true)
     }
 
     fn handle_attach(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
-        let args: AttachArguments = match parse_arguments(arguments) {
+        let args: AttachArguments = match expand_variables(arguments)
+            .and_then(|arguments| self.load_program(arguments, false))
+            .and_then(parse_arguments)
+        {
             Ok(args) => args,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
@@ -202,20 +758,151 @@ impl<W: Write> Session<W> {
             }
         };
 
-        if let Err(err) = self.backend.connect_debugserver(args.debugserver_port) {
+        if let Err(err) = self.apply_signal_policy(&args.signals) {
+            self.send_error_response(seq, command, err)?;
+            return Ok(true);
+        }
+        self.backend()
+            .set_source_map(SourceMap::new(args.source_map.clone()));
+
+        let reached = reach_debugserver(
+            &args.debugserver_host,
+            args.debugserver_port,
+            args.device.as_deref(),
+            args.simulator.as_deref(),
+            (args.boot_simulator, args.usb_forward),
+        );
+        let connected = reached.and_then(|(host, port, route)| {
+            self.backend().connect_debugserver(&host, port)?;
+            Ok(route)
+        });
+        match connected {
+            Ok(route) => self.route = route,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        }
+
+        // A debugserver started with `--listen` has no process until we
+        // attach it to one.
+        if !self.backend().has_process() {
+            let target = match attach_target(&args) {
+                Ok(target) => target,
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    return Ok(true);
+                }
+            };
+            if let Some(target) = target {
+                if let Err(err) = self.backend().attach_process(&target) {
+                    self.send_error_response(seq, command, err)?;
+                    return Ok(true);
+                }
+            }
+        }
+
+        self.load_remote_program(args.program.as_deref());
+
+        if let Err(err) = self.apply_regex_breakpoints(&args.regex_breakpoints) {
             self.send_error_response(seq, command, err)?;
             return Ok(true);
         }
 
+        self.launched = false;
+        let name = args
+            .program
+            .clone()
+            .or_else(|| args.bundle_id.clone())
+            .unwrap_or_default();
         self.handle_simple_ok(
             seq,
             command,
             json!({
                 "program": args.program,
                 "cwd": args.cwd,
+                "debugserverHost": args.debugserver_host,
                 "debugserverPort": args.debugserver_port,
             }),
-        )
+        )?;
+        self.emit_event("initialized", Value::Null)?;
+        self.emit_process_event(&name, "attach")?;
+        if args.logs {
+            let host = app_host(args.device.as_ref(), args.simulator.as_ref());
+            self.start_app_logs(args.program.as_deref(), &host)?;
+        }
+        Ok(true)
+    }
+
+    /// Creates the backend for the program `arguments` name (which a launch
+    /// `requires`, either here or on the device) and passes them on for
+    /// parsing.
+    fn load_program(&mut self, arguments: Value, requires: bool) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .filter(|program| !program.is_empty());
+        if requires {
+            let Some(program) = program else {
+                return Err("`program` is missing: name the executable to launch".into());
+            };
+            if !Path::new(program).exists() && !backend::is_device_path(program) {
+                return Err(format!(
+                    "`program` {program} does not exist; build it first or fix the path"
+                ));
+            }
+        }
+        let backend =
+            init_backend(&arguments).map_err(|err| format!("failed to load symbols: {err}"))?;
+        self.backend = Some(backend);
+        Ok(arguments)
+    }
+
+    /// When the backend started without symbols, loads them once there is a
+    /// process: pulled over `vFile` when `program` names a path on the
+    /// device, else from the executable the process reports.
+    fn load_remote_program(&mut self, program: Option<&str>) {
+        if self.backend().has_symbols() || !self.backend().has_process() {
+            return;
+        }
+        let loaded = match program.filter(|program| backend::is_device_path(program)) {
+            Some(program) => self.backend().load_symbols_from_remote(program).map(drop),
+            None => self.backend().load_symbols_for_process(),
+        };
+        if let Err(err) = loaded {
+            eprintln!("{err}");
+        }
+    }
+
+    /// Sends the DAP `process` event once `qProcessInfo` has identified the
+    /// debuggee.
+    fn emit_process_event(&mut self, name: &str, start_method: &str) -> io::Result<()> {
+        let Some(info) = self.backend().process_info() else {
+            return Ok(());
+        };
+        let body = json!({
+            "name": name,
+            "systemProcessId": info.pid,
+            "isLocalProcess": false,
+            "startMethod": start_method,
+            "pointerSize": info.ptr_size.map(|size| size * 8),
+        });
+        self.emit_event("process", body)
+    }
+
+    fn apply_signal_policy(
+        &mut self,
+        signals: &HashMap<String, SignalAction>,
+    ) -> Result<(), String> {
+        let policy = SignalPolicy::from_config(signals)?;
+        self.backend().set_signal_policy(policy);
+        Ok(())
+    }
+
+    fn apply_regex_breakpoints(&mut self, patterns: &[String]) -> Result<(), String> {
+        for pattern in patterns {
+            self.backend().set_regex_breakpoint(pattern)?;
+        }
+        Ok(())
     }
 
     fn handle_set_breakpoints(
@@ -238,27 +925,131 @@ impl<W: Write> Session<W> {
         };
 
         let lines: Vec<i64> = args.breakpoints.iter().map(|bp| bp.line).collect();
-        if let Err(err) = self.backend.update_breakpoints(&path, &lines) {
+        if let Err(err) = self.backend().update_breakpoints(&path, &lines) {
             self.send_error_response(seq, command, err)?;
             return Ok(true);
         }
 
+        let problem = self
+            .backend()
+            .uuid_mismatch()
+            .or_else(|| self.backend().missing_debug_info());
         let breakpoints: Vec<_> = args
             .breakpoints
             .into_iter()
             .map(|bp| {
-                json!({
-                    "verified": true,
+                let mut breakpoint = json!({
+                    "verified": problem.is_none(),
                     "line": bp.line,
-                })
+                });
+                if let Some(problem) = &problem {
+                    breakpoint["message"] = json!(problem);
+                }
+                breakpoint
             })
             .collect();
 
         self.handle_simple_ok(seq, command, json!({ "breakpoints": breakpoints }))
     }
 
+    fn handle_set_address_breakpoints(
+        &mut self,
+        seq: i64,
+        command: &str,
+        arguments: Value,
+    ) -> io::Result<bool> {
+        let args: SetAddressBreakpointsArguments = match parse_arguments(arguments) {
+            Ok(args) => args,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        };
+
+        let mut addresses = Vec::with_capacity(args.breakpoints.len());
+        for breakpoint in &args.breakpoints {
+            let Some(address) = backend::parse_address(&breakpoint.address) else {
+                self.send_error_response(
+                    seq,
+                    command,
+                    format!("invalid breakpoint address `{}`", breakpoint.address),
+                )?;
+                return Ok(true);
+            };
+            addresses.push(address);
+        }
+
+        let descriptions = match self.backend().set_address_breakpoints(&addresses) {
+            Ok(descriptions) => descriptions,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        };
+
+        let mismatch = self.backend().uuid_mismatch();
+        let breakpoints: Vec<_> = addresses
+            .iter()
+            .zip(descriptions)
+            .map(|(address, description)| {
+                json!({
+                    "verified": mismatch.is_none(),
+                    "instructionReference": format!("0x{address:x}"),
+                    "message": mismatch.clone().unwrap_or(description),
+                })
+            })
+            .collect();
+        self.handle_simple_ok(seq, command, json!({ "breakpoints": breakpoints }))
+    }
+
+    fn handle_configuration_done(&mut self, seq: i64, command: &str) -> io::Result<bool> {
+        self.handle_simple_ok(seq, command, Value::Null)?;
+        if !self.backend().has_process() {
+            return Ok(true);
+        }
+        let Some(thread_id) = self
+            .backend()
+            .threads()
+            .first()
+            .and_then(|thread| thread["id"].as_i64())
+        else {
+            return Ok(true);
+        };
+        if self.stop_on_entry {
+            self.emit_event(
+                "stopped",
+                json!({ "reason": "entry", "threadId": thread_id, "allThreadsStopped": true }),
+            )?;
+        } else {
+            self.resume_target(thread_id)?;
+        }
+        Ok(true)
+    }
+
+    /// Lets the process run once configuration is done; a stop it reaches
+    /// later is reported through [`Self::watch_target`].
+    fn resume_target(&mut self, thread_id: i64) -> io::Result<()> {
+        match self.backend().r#continue(thread_id, false) {
+            Ok(stop_event) => {
+                self.emit_event(
+                    "continued",
+                    json!({ "threadId": thread_id, "allThreadsContinued": true }),
+                )?;
+                if let Some(event) = stop_event {
+                    self.emit_stop_event(event)?;
+                }
+                Ok(())
+            }
+            Err(err) => self.emit_event(
+                "output",
+                json!({ "category": "console", "output": format!("failed to resume the process: {err}\n") }),
+            ),
+        }
+    }
+
     fn handle_threads(&mut self, seq: i64, command: &str) -> io::Result<bool> {
-        self.handle_simple_ok(seq, command, json!({ "threads": self.backend.threads() }))
+        let threads = self.backend().threads();
+        self.handle_simple_ok(seq, command, json!({ "threads": threads }))
     }
 
     fn handle_stack_trace(
@@ -274,7 +1065,7 @@ impl<W: Write> Session<W> {
                 return Ok(true);
             }
         };
-        let frames = self.backend.stack_trace(args.thread_id);
+        let frames = self.backend().stack_trace(args.thread_id);
         self.handle_simple_ok(
             seq,
             command,
@@ -286,7 +1077,7 @@ impl<W: Write> Session<W> {
     }
 
     fn handle_scopes(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
-        let _args: ScopesArguments = match parse_arguments(arguments) {
+        let args: ScopesArguments = match parse_arguments(arguments) {
             Ok(args) => args,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
@@ -294,7 +1085,13 @@ impl<W: Write> Session<W> {
             }
         };
 
-        self.handle_simple_ok(seq, command, json!({ "scopes": self.backend.scopes() }))
+        match self.backend().scopes(args.frame_id) {
+            Ok(scopes) => self.handle_simple_ok(seq, command, json!({ "scopes": scopes })),
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                Ok(true)
+            }
+        }
     }
 
     fn handle_variables(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
@@ -305,11 +1102,13 @@ impl<W: Write> Session<W> {
                 return Ok(true);
             }
         };
-        self.handle_simple_ok(
-            seq,
-            command,
-            json!({ "variables": self.backend.variables(args.variables_reference) }),
-        )
+        match self.backend().variables(args.variables_reference) {
+            Ok(variables) => self.handle_simple_ok(seq, command, json!({ "variables": variables })),
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                Ok(true)
+            }
+        }
     }
 
     fn handle_continue(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
@@ -320,14 +1119,21 @@ impl<W: Write> Session<W> {
                 return Ok(true);
             }
         };
-        let stop_event = match self.backend.r#continue(args.thread_id) {
+        let stop_event = match self
+            .backend()
+            .r#continue(args.thread_id, args.single_thread)
+        {
             Ok(event) => event,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
                 return Ok(true);
             }
         };
-        self.handle_simple_ok(seq, command, json!({ "allThreadsContinued": true }))?;
+        self.handle_simple_ok(
+            seq,
+            command,
+            json!({ "allThreadsContinued": !args.single_thread }),
+        )?;
         if let Some(event) = stop_event {
             self.emit_stop_event(event)?;
         }
@@ -342,7 +1148,7 @@ impl<W: Write> Session<W> {
                 return Ok(true);
             }
         };
-        let stop_event = match self.backend.step_over(args.thread_id) {
+        let stop_event = match self.backend().step_over(args.thread_id, args.single_thread) {
             Ok(event) => event,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
@@ -364,7 +1170,7 @@ impl<W: Write> Session<W> {
                 return Ok(true);
             }
         };
-        let stop_event = match self.backend.step_in(args.thread_id) {
+        let stop_event = match self.backend().step_in(args.thread_id, args.single_thread) {
             Ok(event) => event,
             Err(err) => {
                 self.send_error_response(seq, command, err)?;
@@ -378,24 +1184,232 @@ impl<W: Write> Session<W> {
         Ok(true)
     }
 
-    fn handle_disconnect(&mut self, seq: i64, command: &str) -> io::Result<bool> {
-        if let Err(err) = self.backend.disconnect() {
-            self.send_error_response(seq, command, err)?;
-            return Ok(true);
-        }
+    fn handle_pause(&mut self, seq: i64, command: &str) -> io::Result<bool> {
+        let stop_event = match self.backend().pause() {
+            Ok(event) => event,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        };
         self.handle_simple_ok(seq, command, Value::Null)?;
-        Ok(false)
-    }
-
-    fn handle_simple_ok(&mut self, seq: i64, command: &str, body: Value) -> io::Result<bool> {
-        let body = if body.is_null() { None } else { Some(body) };
-        self.respond(seq, command, true, body, None)?;
+        if let Some(event) = stop_event {
+            self.emit_stop_event(event)?;
+        }
         Ok(true)
     }
 
-    fn respond(
+    fn handle_read_memory(
         &mut self,
-        request_seq: i64,
+        seq: i64,
+        command: &str,
+        arguments: Value,
+    ) -> io::Result<bool> {
+        let args: ReadMemoryArguments = match parse_arguments(arguments) {
+            Ok(args) => args,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        };
+        let Some(base) = backend::parse_address(&args.memory_reference) else {
+            self.send_error_response(
+                seq,
+                command,
+                format!("invalid memoryReference `{}`", args.memory_reference),
+            )?;
+            return Ok(true);
+        };
+        let address = base.wrapping_add_signed(args.offset);
+
+        match self.backend().read_readable_memory(address, args.count) {
+            Ok(data) => {
+                let unreadable = args.count - data.len();
+                self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({
+                        "address": format!("0x{address:x}"),
+                        "data": base64_encode(&data),
+                        "unreadableBytes": unreadable,
+                    }),
+                )
+            }
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn handle_evaluate(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
+        let args: EvaluateArguments = match parse_arguments(arguments) {
+            Ok(args) => args,
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                return Ok(true);
+            }
+        };
+
+        let expression = args.expression.trim();
+        let thread_id = args
+            .frame_id
+            .and_then(|frame_id| self.backend().frame_thread(frame_id));
+        if let Some((register, value)) = expression
+            .split_once('=')
+            .filter(|(register, _)| register.trim_start().starts_with('$'))
+        {
+            let Some(value) = backend::parse_address(value) else {
+                self.send_error_response(
+                    seq,
+                    command,
+                    format!("invalid value `{}`", value.trim()),
+                )?;
+                return Ok(true);
+            };
+            return match self.backend().write_register(register, value, thread_id) {
+                Ok(()) => self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({ "result": format!("0x{value:016x}"), "variablesReference": 0 }),
+                ),
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    Ok(true)
+                }
+            };
+        }
+
+        if expression.starts_with('$') {
+            return match self.backend().read_register(expression, thread_id) {
+                Ok(value) => self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({ "result": format!("0x{value:016x}"), "variablesReference": 0 }),
+                ),
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    Ok(true)
+                }
+            };
+        }
+
+        if let Some(target) = expression
+            .strip_prefix("b ")
+            .or_else(|| expression.strip_prefix("break "))
+            .map(str::trim)
+            .filter(|target| target.starts_with('*'))
+        {
+            let Some(address) = backend::parse_address(target) else {
+                self.send_error_response(seq, command, format!("invalid address `{target}`"))?;
+                return Ok(true);
+            };
+            return match self.backend().add_address_breakpoint(address) {
+                Ok(description) => self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({
+                        "result": format!("Breakpoint set at {description}"),
+                        "variablesReference": 0,
+                    }),
+                ),
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    Ok(true)
+                }
+            };
+        }
+
+        if let Some(pattern) = expression.strip_prefix("rdelete ") {
+            return match self.backend().clear_regex_breakpoint(pattern.trim()) {
+                Ok(count) => self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({
+                        "result": format!("Breakpoints removed from {count} function(s)"),
+                        "variablesReference": 0,
+                    }),
+                ),
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    Ok(true)
+                }
+            };
+        }
+
+        let Some(pattern) = expression.strip_prefix("rbreak ") else {
+            self.send_error_response(
+                seq,
+                command,
+                format!("expression `{expression}` is not supported"),
+            )?;
+            return Ok(true);
+        };
+
+        match self.backend().set_regex_breakpoint(pattern.trim()) {
+            Ok(functions) => {
+                let mut result = format!("Breakpoints set on {} function(s)", functions.len());
+                for function in &functions {
+                    result.push_str(&format!("\n  {} @ 0x{:x}", function.name, function.address));
+                }
+                self.handle_simple_ok(
+                    seq,
+                    command,
+                    json!({ "result": result, "variablesReference": 0 }),
+                )
+            }
+            Err(err) => {
+                self.send_error_response(seq, command, err)?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn handle_terminate(&mut self, seq: i64, command: &str) -> io::Result<bool> {
+        let terminated = self.backend.as_mut().map_or(Ok(()), Backend::terminate);
+        if let Err(err) = terminated {
+            self.send_error_response(seq, command, err)?;
+            return Ok(true);
+        }
+        self.handle_simple_ok(seq, command, Value::Null)?;
+        self.emit_event("terminated", Value::Null)?;
+        Ok(true)
+    }
+
+    fn handle_disconnect(&mut self, seq: i64, command: &str, arguments: Value) -> io::Result<bool> {
+        let args: DisconnectArguments = if arguments.is_null() {
+            DisconnectArguments::default()
+        } else {
+            match parse_arguments(arguments) {
+                Ok(args) => args,
+                Err(err) => {
+                    self.send_error_response(seq, command, err)?;
+                    return Ok(true);
+                }
+            }
+        };
+        let terminate = args.terminate_debuggee.unwrap_or(self.launched);
+        let disconnected = self
+            .backend
+            .as_mut()
+            .map_or(Ok(()), |backend| backend.disconnect(terminate));
+        if let Err(err) = disconnected {
+            self.send_error_response(seq, command, err)?;
+            return Ok(true);
+        }
+        self.handle_simple_ok(seq, command, Value::Null)?;
+        Ok(false)
+    }
+
+    fn handle_simple_ok(&mut self, seq: i64, command: &str, body: Value) -> io::Result<bool> {
+        let body = if body.is_null() { None } else { Some(body) };
+        self.respond(seq, command, true, body, None)?;
+        Ok(true)
+    }
+
+    fn respond(
+        &mut self,
+        request_seq: i64,
         command: &str,
         success: bool,
         body: Option<Value>,
@@ -433,6 +1447,7 @@ impl<W: Write> Session<W> {
     }
 
     fn emit_stop_event(&mut self, event: BackendStopEvent) -> io::Result<()> {
+        let others = self.backend().other_thread_stops(event.thread_id);
         self.emit_event(
             "stopped",
             json!({
@@ -440,7 +1455,19 @@ impl<W: Write> Session<W> {
                 "description": event.description,
                 "threadId": event.thread_id
             }),
-        )
+        )?;
+        for other in others {
+            self.emit_event(
+                "stopped",
+                json!({
+                    "reason": other.reason,
+                    "description": other.description,
+                    "threadId": other.thread_id,
+                    "preserveFocusHint": true
+                }),
+            )?;
+        }
+        Ok(())
     }
 
     fn next_seq(&mut self) -> i64 {
@@ -521,12 +1548,198 @@ fn read_dap_message<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
 fn write_dap_message<W: Write, T: Serialize>(writer: &mut W, payload: &T) -> io::Result<()> {
     let json = serde_json::to_string(payload)
         .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
-    let header = format!("Content-Length: {}\r\n\r\n", json.as_bytes().len());
+    let header = format!("Content-Length: {}\r\n\r\n", json.len());
     writer.write_all(header.as_bytes())?;
     writer.write_all(json.as_bytes())?;
     writer.flush()
 }
 
+fn default_debugserver_host() -> String {
+    gdb_remote::DEFAULT_HOST.to_string()
+}
+
+fn default_disable_aslr() -> bool {
+    true
+}
+
+fn requires_stopped_target(command: &str) -> bool {
+    matches!(
+        command,
+        "threads"
+            | "stackTrace"
+            | "scopes"
+            | "variables"
+            | "continue"
+            | "next"
+            | "stepIn"
+            | "evaluate"
+            | "readMemory"
+    )
+}
+
+/// Standard base64 with padding, as DAP expects for memory contents.
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(ALPHABET[((triple >> (18 - 6 * index)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// The process an attach request names, by `pid`, by the running app with
+/// `bundleId` or, with `waitFor` or `pickProcess`, by the file name of
+/// `program`.
+fn attach_target(args: &AttachArguments) -> Result<Option<AttachTarget>, String> {
+    if let Some(pid) = args.pid {
+        return Ok(Some(AttachTarget::Pid(pid)));
+    }
+    if let Some(bundle_id) = &args.bundle_id {
+        let host = app_host(args.device.as_ref(), args.simulator.as_ref());
+        let pid = app_process::pid_for_bundle_id(bundle_id, &host)?;
+        return Ok(Some(AttachTarget::Pid(pid)));
+    }
+    if args.pick_process && !args.wait_for {
+        let name = args
+            .program
+            .as_deref()
+            .and_then(|program| program.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| {
+                "pickProcess matches running processes by the file name of `program`; set \
+                 `program`, or name the process with `bundleId` or `pid`"
+                    .to_string()
+            })?;
+        let host = match (&args.device, &args.simulator) {
+            (Some(device), _) => Some(AppHost::Device(device.clone())),
+            (None, Some(simulator)) => Some(AppHost::Simulator(simulator.clone())),
+            (None, None) => None,
+        };
+        let processes = app_process::running_processes(host.as_ref())?;
+        let pid = app_process::pick_process(&processes, name)?;
+        return Ok(Some(AttachTarget::Pid(pid)));
+    }
+    if !args.wait_for {
+        return Ok(None);
+    }
+    let name = args
+        .program
+        .as_deref()
+        .and_then(|program| program.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| "waitFor requires `program` to name the executable".to_string())?;
+    Ok(Some(AttachTarget::WaitFor(name.to_string())))
+}
+
+/// Something set up to reach debugserver, such as a usbmuxd forward, that
+/// has to outlive the connection made through it.
+type Route = Box<dyn Send>;
+
+/// Where to connect to debugserver at `host:port`, after booting
+/// `simulator` when `boot` asks and, when `forward` asks, forwarding a
+/// local port to `port` on `device` (or the only USB device) over usbmuxd.
+/// The forward comes back as the route, and closes when that is dropped.
+fn reach_debugserver(
+    host: &str,
+    port: u16,
+    device: Option<&str>,
+    simulator: Option<&str>,
+    (boot, forward): (bool, bool),
+) -> Result<(String, u16, Option<Route>), String> {
+    if boot {
+        boot_simulator(simulator.unwrap_or("booted"))?;
+    }
+    if !forward {
+        return Ok((host.to_string(), port, None));
+    }
+    #[cfg(unix)]
+    {
+        use swiftscope::{ports::PortReservation, usbmux};
+
+        let socket = usbmux::socket_path();
+        let devices = usbmux::list_devices(&socket)
+            .map_err(|err| format!("failed to query usbmuxd at {}: {err}", socket.display()))?;
+        let mut usb = devices
+            .into_iter()
+            .filter(|candidate| candidate.connection_type == "USB");
+        let target = match device {
+            Some(udid) => usb
+                .find(|candidate| candidate.udid.eq_ignore_ascii_case(udid))
+                .ok_or_else(|| format!("device {udid} is not attached over USB"))?,
+            None => match (usb.next(), usb.next()) {
+                (Some(only), None) => only,
+                (None, _) => return Err("no device is attached over USB".into()),
+                (Some(_), Some(_)) => {
+                    return Err("several devices are attached over USB; set `device`".into())
+                }
+            },
+        };
+        let reservation = PortReservation::reserve(0).map_err(|err| err.to_string())?;
+        let forward = usbmux::forward(socket, reservation, target.device_id, port);
+        Ok((
+            gdb_remote::DEFAULT_HOST.to_string(),
+            forward.port(),
+            Some(Box::new(forward)),
+        ))
+    }
+    #[cfg(not(unix))]
+    Err("usbForward needs usbmuxd, which is only reachable on macOS and Linux".into())
+}
+
+/// `simctl boot`, which fails harmlessly when the simulator is already up.
+/// `booted` means whichever one is running, so there is nothing to boot.
+fn boot_simulator(simulator: &str) -> Result<(), String> {
+    if simulator == "booted" {
+        return Ok(());
+    }
+    let output = std::process::Command::new("xcrun")
+        .args(["simctl", "boot", simulator])
+        .output()
+        .map_err(|err| format!("failed to run xcrun simctl boot: {err}"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() || stderr.contains("current state: Booted") {
+        Ok(())
+    } else {
+        Err(format!("failed to boot {simulator}: {}", stderr.trim()))
+    }
+}
+
+fn app_host(device: Option<&String>, simulator: Option<&String>) -> AppHost {
+    match (device, simulator) {
+        (Some(device), _) => AppHost::Device(device.clone()),
+        (None, Some(simulator)) => AppHost::Simulator(simulator.clone()),
+        (None, None) => AppHost::Simulator("booted".into()),
+    }
+}
+
+/// Requests that need no backend, so they are answered before `launch` or
+/// `attach` creates one.
+fn works_before_launch(command: &str) -> bool {
+    matches!(
+        command,
+        "initialize" | "launch" | "attach" | "terminate" | "disconnect"
+    )
+}
+
+/// Expands `${workspaceRoot}`, `${env:NAME}` and `${derivedData}` in a
+/// launch or attach configuration.
+fn expand_variables(mut value: Value) -> Result<Value, String> {
+    Variables::from_env().expand(&mut value)?;
+    Ok(value)
+}
+
 fn parse_arguments<T: DeserializeOwned>(value: Value) -> Result<T, String> {
     serde_json::from_value(value).map_err(|err| err.to_string())
 }
@@ -535,6 +1748,7 @@ fn parse_arguments<T: DeserializeOwned>(value: Value) -> Result<T, String> {
 mod tests {
     use super::*;
     use addr2line::Loader;
+    use swiftscope::mock_debugserver::{MockDebugserver, MockState};
     use swiftscope::symbols::{Image, SymbolContext};
 
     #[derive(Serialize)]
@@ -596,9 +1810,188 @@ mod tests {
         );
     }
 
+    /// `Vec<u8>` output the test keeps a handle on while `serve` writes it
+    /// from another thread.
+    #[derive(Clone, Default)]
+    struct SharedOutput(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn serve_writes_every_reply_before_returning() {
+        let mut input = Vec::new();
+        for (seq, command) in [(1, "initialize"), (2, "threads"), (3, "disconnect")] {
+            let request = json!({ "seq": seq, "type": "request", "command": command });
+            write_dap_message(&mut input, &request).unwrap();
+        }
+        let output = SharedOutput::default();
+        serve(io::Cursor::new(input), output.clone()).unwrap();
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let commands: Vec<&str> = output
+            .split("\"command\":\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .collect();
+        assert_eq!(
+            commands,
+            ["initialize", "threads", "disconnect"],
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn stops_are_reported_without_another_request() {
+        use_scratch_cache();
+        let server = MockDebugserver::start().unwrap();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || accept_clients(listener, false));
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let requests = [
+            ("initialize", json!({ "adapterID": "ios-lldb" })),
+            (
+                "launch",
+                json!({
+                    "program": std::env::current_exe().unwrap(),
+                    "debugserverPort": server.port(),
+                }),
+            ),
+            ("continue", json!({ "threadId": 0x303 })),
+        ];
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            let request = json!({
+                "seq": seq + 1,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            write_dap_message(&mut client, &request).unwrap();
+        }
+        loop {
+            let message = read_dap_message(&mut reader).unwrap().unwrap();
+            if message.contains(r#""command":"continue""#) {
+                break;
+            }
+        }
+
+        // The process runs until the mock raises a signal; nothing more is
+        // asked of the adapter.
+        server.state().raise = Some(11);
+        loop {
+            let message = read_dap_message(&mut reader).unwrap().unwrap();
+            if message.contains(r#""event":"stopped""#) {
+                assert!(message.contains(r#""reason":"signal""#), "{message}");
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn listen_arguments_default_to_loopback() {
+        let args = |args: &[&str]| {
+            parse_listen_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(args(&[]), Ok(None));
+        assert_eq!(
+            args(&["--port", "4711"]),
+            Ok(Some(Listen {
+                host: "127.0.0.1".into(),
+                port: 4711,
+                multi: false,
+            }))
+        );
+        assert_eq!(
+            args(&["--multi", "--host", "0.0.0.0", "--port", "0"]),
+            Ok(Some(Listen {
+                host: "0.0.0.0".into(),
+                port: 0,
+                multi: true,
+            }))
+        );
+        assert!(args(&["--port", "http"]).is_err());
+        assert!(args(&["--host", "::1"]).is_err());
+        assert!(args(&["--multi"]).is_err());
+        assert!(args(&["--stdio"]).is_err());
+    }
+
+    #[test]
+    fn multi_mode_serves_clients_side_by_side() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || accept_clients(listener, true));
+        let first = std::net::TcpStream::connect(address).unwrap();
+        let mut second = std::net::TcpStream::connect(address).unwrap();
+        // The first client stays connected and silent while the second is
+        // answered.
+        let request = json!({ "seq": 1, "type": "request", "command": "initialize" });
+        write_dap_message(&mut second, &request).unwrap();
+        let mut reader = BufReader::new(second.try_clone().unwrap());
+        let response = read_dap_message(&mut reader).unwrap().unwrap();
+        assert!(response.contains(r#""command":"initialize""#), "{response}");
+        drop(first);
+    }
+
+    #[test]
+    fn requests_wait_for_launch_to_name_the_program() {
+        let mut session = Session::new(Vec::new(), mpsc::channel().0);
+        for (seq, command, arguments) in [
+            (1, "initialize", Value::Null),
+            (2, "threads", Value::Null),
+            (3, "launch", json!({ "debugserverPort": 1 })),
+            (
+                4,
+                "launch",
+                json!({ "program": "/no/such/MyApp", "debugserverPort": 1 }),
+            ),
+        ] {
+            let request = RawRequest {
+                seq,
+                command: command.into(),
+                arguments,
+            };
+            session.handle_request(request).unwrap();
+        }
+        assert!(session.backend.is_none());
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("no program to debug yet"),
+            "threads should be refused before launch: {output}"
+        );
+        assert!(
+            output.contains("`program` is missing"),
+            "launch without a program should say so: {output}"
+        );
+        assert!(
+            output.contains("`program` /no/such/MyApp does not exist"),
+            "launch of a missing program should say so: {output}"
+        );
+
+        // An attach by pid has nothing to symbolicate until it has a process.
+        let request = RawRequest {
+            seq: 5,
+            command: "attach".into(),
+            arguments: json!({ "pid": 42, "debugserverPort": 1 }),
+        };
+        session.handle_request(request).unwrap();
+        let backend = session.backend.as_ref().unwrap();
+        assert!(!backend.has_symbols());
+    }
+
     #[test]
     fn session_handles_initialize_request() {
-        let mut session = Session::new(test_backend(), Vec::new());
+        let mut session = test_session();
         let request = RawRequest {
             seq: 1,
             command: "initialize".into(),
@@ -612,14 +2005,14 @@ mod tests {
             "initialize response missing capabilities: {output}"
         );
         assert!(
-            output.contains(r#""event":"initialized""#),
-            "initialize should emit initialized event: {output}"
+            !output.contains(r#""event":"initialized""#),
+            "initialized should wait for launch or attach: {output}"
         );
     }
 
     #[test]
     fn session_handles_unknown_command() {
-        let mut session = Session::new(test_backend(), Vec::new());
+        let mut session = test_session();
         let request = RawRequest {
             seq: 1,
             command: "bogus".into(),
@@ -637,12 +2030,472 @@ mod tests {
         );
     }
 
+    #[test]
+    fn verified_breakpoints_carry_no_message() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "setBreakpoints".into(),
+            arguments: json!({
+                "source": { "path": "/tmp/foo.rs" },
+                "breakpoints": [{ "line": 3 }],
+            }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(output.contains(r#""success":true"#), "{output}");
+        assert!(!output.contains(r#""message":null"#), "{output}");
+    }
+
+    #[test]
+    fn launch_arguments_accept_args_and_env() {
+        let args: LaunchArguments = parse_arguments(json!({
+            "debugserverPort": 1234,
+            "program": "/bin/app",
+            "args": ["--flag", "value"],
+            "env": { "RUST_LOG": "info" },
+        }))
+        .unwrap();
+        assert_eq!(args.args, vec!["--flag", "value"]);
+        assert_eq!(args.env.get("RUST_LOG").map(String::as_str), Some("info"));
+        assert!(args.disable_aslr);
+    }
+
+    #[test]
+    fn session_rejects_unsupported_evaluate() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "1 + 1", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains(r#""success":false"#),
+            "unsupported expression should fail: {output}"
+        );
+    }
+
+    #[test]
+    fn session_reports_register_reads_without_debugserver() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "$pc", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("no gdb-remote connection"),
+            "register read should require a connection: {output}"
+        );
+    }
+
+    #[test]
+    fn attach_target_prefers_pid_then_wait_for() {
+        let args = |value: Value| parse_arguments::<AttachArguments>(value).unwrap();
+        assert_eq!(
+            attach_target(&args(json!({ "debugserverPort": 1, "pid": 42 }))).unwrap(),
+            Some(AttachTarget::Pid(42))
+        );
+        assert_eq!(
+            attach_target(&args(json!({
+                "debugserverPort": 1,
+                "program": "/tmp/Build/MyApp.app/MyApp",
+                "waitFor": true,
+            })))
+            .unwrap(),
+            Some(AttachTarget::WaitFor("MyApp".into()))
+        );
+        assert_eq!(
+            attach_target(&args(json!({ "debugserverPort": 1 }))).unwrap(),
+            None
+        );
+        assert!(attach_target(&args(json!({ "debugserverPort": 1, "waitFor": true }))).is_err());
+    }
+
+    #[test]
+    fn bundle_id_is_looked_up_on_the_device_or_simulator() {
+        let host = |value: Value| {
+            let args = parse_arguments::<AttachArguments>(value).unwrap();
+            app_host(args.device.as_ref(), args.simulator.as_ref())
+        };
+        let base = json!({ "debugserverPort": 1, "bundleId": "com.example.MyApp" });
+        assert_eq!(host(base.clone()), AppHost::Simulator("booted".into()));
+        let mut sim = base.clone();
+        sim["simulator"] = json!("iPhone 15");
+        assert_eq!(host(sim), AppHost::Simulator("iPhone 15".into()));
+        let mut device = base;
+        device["device"] = json!("00008120");
+        device["simulator"] = json!("iPhone 15");
+        assert_eq!(host(device), AppHost::Device("00008120".into()));
+    }
+
+    #[test]
+    fn app_log_lines_become_stdout_output_events() {
+        let mut session = test_session();
+        session.emit_app_log("hello from the app").unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(output.contains(r#""event":"output""#), "{output}");
+        assert!(output.contains(r#""category":"stdout""#), "{output}");
+        assert!(
+            output.contains(r#""output":"hello from the app\n""#),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn base64_matches_rfc_vectors() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn session_read_memory_requires_connection() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "readMemory".into(),
+            arguments: json!({ "memoryReference": "0x1000", "count": 16 }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("no gdb-remote connection"),
+            "readMemory should require a connection: {output}"
+        );
+    }
+
+    #[test]
+    fn session_terminate_without_process_emits_terminated() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "terminate".into(),
+            arguments: Value::Null,
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains(r#""success":true"#),
+            "terminate should succeed: {output}"
+        );
+        assert!(
+            output.contains(r#""event":"terminated""#),
+            "terminate should emit terminated: {output}"
+        );
+    }
+
+    #[test]
+    fn session_pause_when_not_running_is_a_no_op() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "pause".into(),
+            arguments: json!({ "threadId": 1 }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains(r#""success":true"#),
+            "pause should succeed: {output}"
+        );
+        assert!(
+            !output.contains(r#""event":"stopped""#),
+            "pause without a running target should not stop: {output}"
+        );
+    }
+
+    #[test]
+    fn session_rejects_invalid_register_assignment() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "$x0 = banana", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("invalid value `banana`"),
+            "bad register value should be reported: {output}"
+        );
+    }
+
+    #[test]
+    fn session_evaluates_address_breakpoint_command() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "b *0x10000abcd", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("Breakpoint set at 0x10000abcd"),
+            "address breakpoint should be reported: {output}"
+        );
+    }
+
+    #[test]
+    fn session_rejects_invalid_address_breakpoints() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "setAddressBreakpoints".into(),
+            arguments: json!({ "breakpoints": [{ "address": "nowhere" }] }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("invalid breakpoint address `nowhere`"),
+            "bad addresses should be rejected: {output}"
+        );
+    }
+
+    #[test]
+    fn session_evaluates_rbreak_command() {
+        let mut session = test_session();
+        let request = RawRequest {
+            seq: 1,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "rbreak ^main$", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains(r#""success":true"#),
+            "rbreak should succeed: {output}"
+        );
+        assert!(
+            output.contains("Breakpoints set on"),
+            "rbreak should summarize matches: {output}"
+        );
+
+        session.writer.clear();
+        let request = RawRequest {
+            seq: 2,
+            command: "evaluate".into(),
+            arguments: json!({ "expression": "rdelete ^main$", "context": "repl" }),
+        };
+        session.handle_request(request).unwrap();
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            output.contains("Breakpoints removed from"),
+            "rdelete should drop the pattern's sites: {output}"
+        );
+    }
+
+    #[test]
+    fn session_runs_to_breakpoint_against_mock_debugserver() {
+        use_scratch_cache();
+        let server = MockDebugserver::start().unwrap();
+        let breakpoint = server.state().pc() + 0x40;
+        let mut session = test_session();
+        let requests = [
+            ("initialize", json!({ "adapterID": "ios-lldb" })),
+            (
+                "launch",
+                json!({
+                    "program": std::env::current_exe().unwrap(),
+                    "debugserverPort": server.port(),
+                }),
+            ),
+            (
+                "setAddressBreakpoints",
+                json!({ "breakpoints": [{ "address": format!("0x{breakpoint:x}") }] }),
+            ),
+            ("continue", json!({ "threadId": 0x303 })),
+        ];
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            session
+                .handle_request(RawRequest {
+                    seq: seq as i64 + 1,
+                    command: command.into(),
+                    arguments,
+                })
+                .unwrap();
+        }
+
+        // The stop may land after `continue` returns; the next request
+        // polls it.
+        server.wait_until(|state| state.pc() == breakpoint);
+        session
+            .handle_request(RawRequest {
+                seq: 10,
+                command: "threads".into(),
+                arguments: Value::Null,
+            })
+            .unwrap();
+
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(output.contains(r#""reason":"breakpoint""#), "{output}");
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(
+            !output.contains(r#""success":false"#),
+            "every request should succeed: {output}"
+        );
+        assert!(server.state().saw_packet("vCont;c"));
+        assert_eq!(server.state().pc(), breakpoint);
+    }
+
+    #[test]
+    fn only_device_paths_are_fetched_over_vfile() {
+        for (program, fetched) in [
+            ("/Users/me/MyApp.app/MyApp", false),
+            (
+                "/private/var/containers/Bundle/Application/MyApp.app/MyApp",
+                true,
+            ),
+        ] {
+            let server = MockDebugserver::start().unwrap();
+            let mut session = Session::new(Vec::new(), mpsc::channel().0);
+            let request = RawRequest {
+                seq: 1,
+                command: "attach".into(),
+                arguments: json!({ "program": program, "debugserverPort": server.port() }),
+            };
+            session.handle_request(request).unwrap();
+            assert_eq!(
+                server.state().saw_packet("vFile:open"),
+                fetched,
+                "{program}"
+            );
+        }
+    }
+
+    #[test]
+    fn stop_on_entry_reports_the_launch_stop() {
+        use_scratch_cache();
+        let server = MockDebugserver::start().unwrap();
+        let mut session = Session::new(Vec::new(), mpsc::channel().0);
+        let requests = [
+            ("initialize", json!({ "adapterID": "ios-lldb" })),
+            (
+                "launch",
+                json!({
+                    "program": std::env::current_exe().unwrap(),
+                    "debugserverPort": server.port(),
+                    "stopOnEntry": true,
+                }),
+            ),
+            ("configurationDone", Value::Null),
+        ];
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            session
+                .handle_request(RawRequest {
+                    seq: seq as i64 + 1,
+                    command: command.into(),
+                    arguments,
+                })
+                .unwrap();
+        }
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        let launched = output.find(r#""command":"launch""#).unwrap();
+        let initialized = output.find(r#""event":"initialized""#).unwrap();
+        assert!(launched < initialized, "{output}");
+        assert!(
+            output.contains(r#""reason":"entry""#),
+            "configurationDone should report the entry stop: {output}"
+        );
+    }
+
+    #[test]
+    fn configuration_done_runs_the_launched_process() {
+        use_scratch_cache();
+        let mut state = MockState::default();
+        state.raise = Some(11);
+        let server = MockDebugserver::with_state(state).unwrap();
+        let mut session = Session::new(Vec::new(), mpsc::channel().0);
+        let requests = [
+            ("initialize", json!({ "adapterID": "ios-lldb" })),
+            (
+                "launch",
+                json!({
+                    "program": std::env::current_exe().unwrap(),
+                    "debugserverPort": server.port(),
+                }),
+            ),
+            ("configurationDone", Value::Null),
+        ];
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            session
+                .handle_request(RawRequest {
+                    seq: seq as i64 + 1,
+                    command: command.into(),
+                    arguments,
+                })
+                .unwrap();
+        }
+        assert!(server.state().saw_packet("vCont;c"));
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(!output.contains(r#""reason":"entry""#), "{output}");
+        let continued = output.find(r#""event":"continued""#).unwrap();
+        let stopped = output.find(r#""reason":"signal""#).unwrap();
+        assert!(continued < stopped, "{output}");
+    }
+
+    #[test]
+    fn configuration_done_resumes_an_attached_process() {
+        use_scratch_cache();
+        let server = MockDebugserver::start().unwrap();
+        server.state().has_process = false;
+        let mut session = Session::new(Vec::new(), mpsc::channel().0);
+        let requests = [
+            ("initialize", json!({ "adapterID": "ios-lldb" })),
+            (
+                "attach",
+                json!({
+                    "program": std::env::current_exe().unwrap(),
+                    "debugserverPort": server.port(),
+                    "pid": 42,
+                }),
+            ),
+            ("configurationDone", Value::Null),
+        ];
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            session
+                .handle_request(RawRequest {
+                    seq: seq as i64 + 1,
+                    command: command.into(),
+                    arguments,
+                })
+                .unwrap();
+        }
+        assert!(server.state().saw_packet("vAttach;2a"));
+        assert!(server.state().is_running());
+        let output = String::from_utf8(session.writer.clone()).unwrap();
+        assert!(output.contains(r#""event":"continued""#), "{output}");
+    }
+
+    /// Points the symbol cache at a scratch directory, so sessions that
+    /// load this test binary leave the user's cache alone.
+    fn use_scratch_cache() {
+        static SET: std::sync::Once = std::sync::Once::new();
+        SET.call_once(|| {
+            let dir = std::env::temp_dir().join("swiftscope-test-cache");
+            std::env::set_var(swiftscope::symbol_cache::CACHE_DIR_ENV_VAR, dir);
+        });
+    }
+
+    fn test_session() -> Session<Vec<u8>> {
+        let mut session = Session::new(Vec::new(), mpsc::channel().0);
+        session.backend = Some(test_backend());
+        session
+    }
+
     fn test_backend() -> Backend {
         let exe = std::env::current_exe().unwrap();
         let loader = Loader::new(&exe).unwrap();
         let image = Image {
             name: "test".into(),
-            path: exe.into(),
+            path: exe,
             uuid: None,
             vmaddr_text: 0,
             slide: 0,
@@ -0,0 +1,654 @@
+//! An in-process stand-in for debugserver, speaking enough of the gdb-remote
+//! protocol (handshake, breakpoints, `vCont`, stop replies, memory,
+//! registers, `vFile`) to drive `Backend` and the DAP session end-to-end
+//! without a device.
+//!
+//! The mock models one arm64 process whose threads share a single register
+//! file. Continuing runs to the next breakpoint above `pc`, or keeps running
+//! until interrupted when there is none; stepping advances `pc` by one
+//! instruction.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::gdb_remote::{RegisterInfo, RegisterLayout};
+
+const SIGTRAP: u8 = 0x05;
+const SIGSTOP: u8 = 0x11;
+const PC: usize = 32;
+const FP: usize = 29;
+
+/// Process state served by a [`MockDebugserver`]; tests read and adjust it
+/// through [`MockDebugserver::state`].
+#[derive(Debug, Clone)]
+pub struct MockState {
+    pub pid: u32,
+    /// Whether debugserver was started with a process. Without one, `?`
+    /// reports nothing until the client launches or attaches.
+    pub has_process: bool,
+    pub threads: Vec<u64>,
+    /// Register values indexed by arm64 register number.
+    pub registers: Vec<u64>,
+    /// Mapped memory regions keyed by start address.
+    pub memory: BTreeMap<u64, Vec<u8>>,
+    /// Files served over `vFile`, keyed by device path.
+    pub files: HashMap<String, Vec<u8>>,
+    /// Addresses with a `Z0` software breakpoint.
+    pub breakpoints: BTreeSet<u64>,
+    /// Every packet received, in order, without framing.
+    pub packets: Vec<String>,
+    /// A signal the next continue stops with instead of running on, or
+    /// that stops the process if it is running already.
+    pub raise: Option<u8>,
+    /// The `PacketSize` advertised in `qSupported`.
+    pub packet_size: usize,
+    /// How long spawning a process takes before `A` is answered.
+    pub launch_delay: Duration,
+    running: bool,
+    last_stop: String,
+    /// Connections served to the end.
+    served: usize,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        let mut registers = vec![0; RegisterLayout::arm64().registers().len()];
+        registers[PC] = 0x1_0000_4000;
+        registers[FP] = 0x1_6fdf_f000;
+        Self {
+            pid: 0x4d2,
+            has_process: true,
+            threads: vec![0x303],
+            registers,
+            memory: BTreeMap::new(),
+            files: HashMap::new(),
+            breakpoints: BTreeSet::new(),
+            packets: Vec::new(),
+            raise: None,
+            packet_size: 0x20000,
+            launch_delay: Duration::ZERO,
+            running: false,
+            last_stop: String::new(),
+            served: 0,
+        }
+    }
+}
+
+impl MockState {
+    pub fn pc(&self) -> u64 {
+        self.registers[PC]
+    }
+
+    /// Whether a continue left the process running.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Maps `bytes` at `start` so `m`/`M` packets can reach them.
+    pub fn map_memory(&mut self, start: u64, bytes: Vec<u8>) {
+        self.memory.insert(start, bytes);
+    }
+
+    /// True once a packet starting with `prefix` has been received.
+    pub fn saw_packet(&self, prefix: &str) -> bool {
+        self.packets.iter().any(|packet| packet.starts_with(prefix))
+    }
+
+    fn stop_reply(&mut self, signal: u8, reason: Option<&str>) -> String {
+        let tid = self.threads.first().copied().unwrap_or(1);
+        let threads: Vec<String> = self.threads.iter().map(|tid| format!("{tid:x}")).collect();
+        let pcs: Vec<String> = self
+            .threads
+            .iter()
+            .map(|_| format!("{:x}", self.pc()))
+            .collect();
+        let mut reply = format!(
+            "T{signal:02x}thread:{tid:x};threads:{};thread-pcs:{};{:02x}:{};{:02x}:{};",
+            threads.join(","),
+            pcs.join(","),
+            PC,
+            hex(&self.registers[PC].to_le_bytes()),
+            FP,
+            hex(&self.registers[FP].to_le_bytes()),
+        );
+        if let Some(reason) = reason {
+            reply.push_str(&format!("reason:{reason};"));
+        }
+        self.last_stop = reply.clone();
+        reply
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> Option<Vec<u8>> {
+        let (start, bytes) = self.memory.range(..=address).next_back()?;
+        let offset = (address - start) as usize;
+        if offset >= bytes.len() {
+            return None;
+        }
+        Some(bytes[offset..bytes.len().min(offset + len)].to_vec())
+    }
+
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> bool {
+        let Some((start, bytes)) = self.memory.range_mut(..=address).next_back() else {
+            return false;
+        };
+        let offset = (address - start) as usize;
+        if offset + data.len() > bytes.len() {
+            return false;
+        }
+        bytes[offset..offset + data.len()].copy_from_slice(data);
+        true
+    }
+
+    fn register_bytes(&self, info: &RegisterInfo) -> Vec<u8> {
+        self.registers[info.regnum as usize].to_le_bytes()[..info.size].to_vec()
+    }
+
+    /// The stop reply for a `raise` that lands while the process runs.
+    fn raised_while_running(&mut self) -> Option<String> {
+        if !self.running {
+            return None;
+        }
+        let signal = self.raise.take()?;
+        self.running = false;
+        Some(self.stop_reply(signal, None))
+    }
+
+    /// Runs to the next breakpoint above `pc`. Returns `None` while the
+    /// process keeps running for want of one.
+    fn resume(&mut self, step: bool) -> Option<String> {
+        if step {
+            self.registers[PC] += 4;
+            return Some(self.stop_reply(SIGTRAP, Some("trace")));
+        }
+        if let Some(signal) = self.raise.take() {
+            return Some(self.stop_reply(signal, None));
+        }
+        match self.breakpoints.range(self.pc() + 1..).next().copied() {
+            Some(address) => {
+                self.registers[PC] = address;
+                Some(self.stop_reply(SIGTRAP, Some("breakpoint")))
+            }
+            None => {
+                self.running = true;
+                None
+            }
+        }
+    }
+}
+
+/// A gdb-remote server on an ephemeral localhost port. Connections are
+/// served one at a time, so a client can reconnect after
+/// [`MockDebugserver::drop_connection`].
+pub struct MockDebugserver {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    /// Signalled whenever a reply goes out or a connection ends.
+    changed: Arc<Condvar>,
+    shutdown: Arc<AtomicBool>,
+    kick: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockDebugserver {
+    pub fn start() -> io::Result<Self> {
+        Self::with_state(MockState::default())
+    }
+
+    pub fn with_state(state: MockState) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(state));
+        let changed = Arc::new(Condvar::new());
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let kick = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let state = Arc::clone(&state);
+            let changed = Arc::clone(&changed);
+            let shutdown = Arc::clone(&shutdown);
+            let kick = Arc::clone(&kick);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    kick.store(false, Ordering::SeqCst);
+                    // A dropped client is not an error for the mock.
+                    let _ = Connection::new(stream, &state, &changed, &kick).serve();
+                    state.lock().unwrap_or_else(|err| err.into_inner()).served += 1;
+                    changed.notify_all();
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            state,
+            changed,
+            shutdown,
+            kick,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Locks the served process state for inspection or adjustment.
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Blocks until `condition` holds for the served state, checking again
+    /// after every reply. Panics after five seconds.
+    pub fn wait_until(&self, mut condition: impl FnMut(&MockState) -> bool) {
+        let (state, waited) = self
+            .changed
+            .wait_timeout_while(self.state(), Duration::from_secs(5), |state| {
+                !condition(state)
+            })
+            .unwrap_or_else(|err| err.into_inner());
+        drop(state);
+        assert!(!waited.timed_out(), "mock debugserver never got there");
+    }
+
+    /// Closes the current connection as a USB hiccup would, leaving the
+    /// listener up for a reconnect. Returns once it is closed.
+    pub fn drop_connection(&self) {
+        let served = self.state().served;
+        self.kick.store(true, Ordering::SeqCst);
+        self.wait_until(|state| state.served > served);
+    }
+}
+
+impl Drop for MockDebugserver {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.kick.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Connection<'a> {
+    stream: TcpStream,
+    state: &'a Mutex<MockState>,
+    changed: &'a Condvar,
+    kick: &'a AtomicBool,
+    no_ack: bool,
+    layout: RegisterLayout,
+    next_fd: i64,
+    open_files: HashMap<i64, String>,
+}
+
+impl<'a> Connection<'a> {
+    fn new(
+        stream: TcpStream,
+        state: &'a Mutex<MockState>,
+        changed: &'a Condvar,
+        kick: &'a AtomicBool,
+    ) -> Self {
+        Self {
+            stream,
+            state,
+            changed,
+            kick,
+            no_ack: false,
+            layout: RegisterLayout::arm64(),
+            next_fd: 3,
+            open_files: HashMap::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'a, MockState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn serve(mut self) -> io::Result<()> {
+        self.stream
+            .set_read_timeout(Some(Duration::from_millis(20)))?;
+        loop {
+            if self.kick.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let mut byte = [0u8; 1];
+            match self.stream.read(&mut byte) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    let raised = self.state().raised_while_running();
+                    if let Some(reply) = raised {
+                        self.send(reply.as_bytes())?;
+                    }
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+            match byte[0] {
+                0x03 => self.interrupt()?,
+                b'$' => {
+                    let packet = self.read_packet()?;
+                    if !self.no_ack {
+                        self.stream.write_all(b"+")?;
+                    }
+                    self.state().packets.push(packet.clone());
+                    if let Some(reply) = self.handle(&packet) {
+                        self.send(&reply)?;
+                    }
+                    if packet == "D" || packet == "k" {
+                        return Ok(());
+                    }
+                }
+                // Acks and NAKs from the client.
+                _ => {}
+            }
+        }
+    }
+
+    fn read_packet(&mut self) -> io::Result<String> {
+        self.stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        let mut data = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            self.stream.read_exact(&mut byte)?;
+            match byte[0] {
+                b'#' => break,
+                b'}' => {
+                    self.stream.read_exact(&mut byte)?;
+                    data.push(byte[0] ^ 0x20);
+                }
+                other => data.push(other),
+            }
+        }
+        let mut checksum = [0u8; 2];
+        self.stream.read_exact(&mut checksum)?;
+        self.stream
+            .set_read_timeout(Some(Duration::from_millis(20)))?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(payload.len());
+        for &byte in payload {
+            if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+                escaped.extend([b'}', byte ^ 0x20]);
+            } else {
+                escaped.push(byte);
+            }
+        }
+        let checksum = escaped.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        let mut frame = Vec::with_capacity(escaped.len() + 4);
+        frame.push(b'$');
+        frame.extend(escaped);
+        frame.extend(format!("#{checksum:02x}").into_bytes());
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn interrupt(&mut self) -> io::Result<()> {
+        let reply = {
+            let mut state = self.state();
+            state.running = false;
+            state.stop_reply(SIGSTOP, None)
+        };
+        self.send(reply.as_bytes())
+    }
+
+    fn handle(&mut self, packet: &str) -> Option<Vec<u8>> {
+        let reply = |text: &str| Some(text.as_bytes().to_vec());
+        match packet {
+            "QStartNoAckMode" => {
+                // The ack for this packet was already sent in ack mode.
+                self.no_ack = true;
+                return reply("OK");
+            }
+            "qHostInfo" => {
+                return reply("cputype:16777228;cpusubtype:2;ostype:ios;endian:little;ptrsize:8;")
+            }
+            "qProcessInfo" => {
+                let pid = self.state().pid;
+                return reply(&format!(
+                    "pid:{pid:x};cputype:100000c;cpusubtype:2;ostype:ios;endian:little;ptrsize:8;"
+                ));
+            }
+            "?" => {
+                let mut state = self.state();
+                return if state.has_process {
+                    let reply = state.stop_reply(SIGSTOP, None);
+                    Some(reply.into_bytes())
+                } else {
+                    reply("W00")
+                };
+            }
+            "vCont?" => return reply("vCont;c;C;s;S"),
+            "qfThreadInfo" => {
+                let threads: Vec<String> = self
+                    .state()
+                    .threads
+                    .iter()
+                    .map(|tid| format!("{tid:x}"))
+                    .collect();
+                return reply(&format!("m{}", threads.join(",")));
+            }
+            "qsThreadInfo" => return reply("l"),
+            "g" => {
+                let state = self.state();
+                let bytes: Vec<u8> = self
+                    .layout
+                    .registers()
+                    .iter()
+                    .flat_map(|info| state.register_bytes(info))
+                    .collect();
+                return reply(&hex(&bytes));
+            }
+            "D" => return reply("OK"),
+            "k" => return reply("X09"),
+            "qLaunchSuccess" => return reply("OK"),
+            _ => {}
+        }
+
+        // Register packets may carry a `;thread:<tid>;` suffix.
+        let packet = packet
+            .split_once(";thread:")
+            .map_or(packet, |(packet, _)| packet);
+
+        if packet.starts_with("qSupported") {
+            let packet_size = self.state().packet_size;
+            reply(&format!(
+                "PacketSize={packet_size:x};QStartNoAckMode+;qXfer:features:read+"
+            ))
+        } else if let Some(regnum) = packet.strip_prefix("qRegisterInfo") {
+            let regnum = usize::from_str_radix(regnum, 16).ok()?;
+            match self.layout.registers().get(regnum) {
+                Some(info) => reply(&format!(
+                    "name:{};{}bitsize:{};offset:{};encoding:uint;format:hex;{}",
+                    info.name,
+                    info.alt_name
+                        .as_ref()
+                        .map(|alt| format!("alt-name:{alt};"))
+                        .unwrap_or_default(),
+                    info.size * 8,
+                    info.offset,
+                    info.generic
+                        .as_ref()
+                        .map(|generic| format!("generic:{generic};"))
+                        .unwrap_or_default(),
+                )),
+                None => reply("E45"),
+            }
+        } else if packet.starts_with("qThreadStopInfo") {
+            let state = self.state();
+            reply(&state.last_stop)
+        } else if let Some(rest) = packet.strip_prefix("vCont;") {
+            let step = rest.starts_with('s') || rest.starts_with('S');
+            self.state().resume(step).map(String::into_bytes)
+        } else if let Some(pid) = packet.strip_prefix("vAttach;") {
+            let mut state = self.state();
+            state.pid = u32::from_str_radix(pid, 16).ok()?;
+            state.has_process = true;
+            Some(state.stop_reply(SIGSTOP, None).into_bytes())
+        } else if packet.starts_with('A') || packet.starts_with("vRun") {
+            let delay = self.state().launch_delay;
+            thread::sleep(delay);
+            self.state().has_process = true;
+            reply("OK")
+        } else if let Some(address) = packet.strip_prefix("Z0,") {
+            self.state().breakpoints.insert(parse_hex_field(address)?);
+            reply("OK")
+        } else if let Some(address) = packet.strip_prefix("z0,") {
+            self.state().breakpoints.remove(&parse_hex_field(address)?);
+            reply("OK")
+        } else if let Some(args) = packet.strip_prefix('m') {
+            let (address, len) = args.split_once(',')?;
+            let address = u64::from_str_radix(address, 16).ok()?;
+            let len = usize::from_str_radix(len, 16).ok()?;
+            match self.state().read_memory(address, len) {
+                Some(bytes) => reply(&hex(&bytes)),
+                None => reply("E08"),
+            }
+        } else if let Some(args) = packet.strip_prefix('M') {
+            let (header, data) = args.split_once(':')?;
+            let address = parse_hex_field(header)?;
+            let bytes = unhex(data)?;
+            if self.state().write_memory(address, &bytes) {
+                reply("OK")
+            } else {
+                reply("E08")
+            }
+        } else if let Some(regnum) = packet.strip_prefix('p') {
+            let regnum = usize::from_str_radix(regnum, 16).ok()?;
+            let state = self.state();
+            match self.layout.registers().get(regnum) {
+                Some(info) => reply(&hex(&state.register_bytes(info))),
+                None => reply("E45"),
+            }
+        } else if let Some(assignment) = packet.strip_prefix('P') {
+            let (regnum, value) = assignment.split_once('=')?;
+            let regnum = usize::from_str_radix(regnum, 16).ok()?;
+            let mut bytes = unhex(value)?;
+            bytes.resize(8, 0);
+            let value = u64::from_le_bytes(bytes.try_into().ok()?);
+            let mut state = self.state();
+            match state.registers.get_mut(regnum) {
+                Some(register) => {
+                    *register = value;
+                    reply("OK")
+                }
+                None => reply("E45"),
+            }
+        } else if packet.starts_with("Hg")
+            || packet.starts_with("QSet")
+            || packet.starts_with("QEnvironment")
+            || packet == "QThreadSuffixSupported"
+            || packet == "QListThreadsInStopReply"
+        {
+            reply("OK")
+        } else if let Some(args) = packet.strip_prefix("vFile:") {
+            Some(self.handle_file(args))
+        } else {
+            // Unsupported: jThreadsInfo, x, qMemoryRegionInfo, ...
+            reply("")
+        }
+    }
+
+    fn handle_file(&mut self, args: &str) -> Vec<u8> {
+        if let Some(open) = args.strip_prefix("open:") {
+            let path = open
+                .split(',')
+                .next()
+                .and_then(unhex)
+                .map(|path| String::from_utf8_lossy(&path).into_owned());
+            return match path.filter(|path| self.state().files.contains_key(path)) {
+                Some(path) => {
+                    let fd = self.next_fd;
+                    self.next_fd += 1;
+                    self.open_files.insert(fd, path);
+                    format!("F{fd:x}").into_bytes()
+                }
+                None => b"F-1,2".to_vec(),
+            };
+        }
+        if let Some(pread) = args.strip_prefix("pread:") {
+            let fields: Vec<u64> = pread
+                .split(',')
+                .filter_map(|field| u64::from_str_radix(field, 16).ok())
+                .collect();
+            let [fd, count, offset] = fields[..] else {
+                return b"F-1,16".to_vec();
+            };
+            let Some(path) = self.open_files.get(&(fd as i64)) else {
+                return b"F-1,9".to_vec();
+            };
+            let state = self.state();
+            let contents = &state.files[path];
+            let start = (offset as usize).min(contents.len());
+            let end = (start + count as usize).min(contents.len());
+            let mut reply = format!("F{:x};", end - start).into_bytes();
+            reply.extend(&contents[start..end]);
+            return reply;
+        }
+        if let Some(fd) = args.strip_prefix("close:") {
+            return match i64::from_str_radix(fd, 16)
+                .ok()
+                .and_then(|fd| self.open_files.remove(&fd))
+            {
+                Some(_) => b"F0".to_vec(),
+                None => b"F-1,9".to_vec(),
+            };
+        }
+        Vec::new()
+    }
+}
+
+/// The leading hex number of a `Z0,addr,kind` or `M addr,len` field list.
+fn parse_hex_field(fields: &str) -> Option<u64> {
+    u64::from_str_radix(fields.split(',').next()?, 16).ok()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gdb_remote::{GdbRemoteClient, DEFAULT_HOST};
+
+    #[test]
+    fn serves_files_over_vfile() {
+        let server = MockDebugserver::start().unwrap();
+        let contents: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        server
+            .state()
+            .files
+            .insert("/var/containers/App".into(), contents.clone());
+
+        let mut client = GdbRemoteClient::connect(DEFAULT_HOST, server.port()).unwrap();
+        assert_eq!(client.read_file("/var/containers/App").unwrap(), contents);
+        assert!(client.read_file("/missing").is_err());
+    }
+}
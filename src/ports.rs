@@ -0,0 +1,89 @@
+//! Local port allocation for debugserver, iproxy and the devicectl bridge.
+//! Fixed default ports collide when two sessions run at once, so tools ask
+//! the OS for a free port and hold it bound until whatever serves on it
+//! takes over.
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+/// A bound loopback port that no other process can take while held.
+#[derive(Debug)]
+pub struct PortReservation {
+    listener: TcpListener,
+}
+
+impl PortReservation {
+    /// Binds `127.0.0.1:port`; port 0 picks any free port.
+    pub fn reserve(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|err| {
+            let what = if port == 0 {
+                "a free port".to_string()
+            } else {
+                format!("port {port}")
+            };
+            io::Error::new(err.kind(), format!("failed to reserve {what}: {err}"))
+        })?;
+        Ok(Self { listener })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener
+            .local_addr()
+            .expect("bound listener has an address")
+            .port()
+    }
+
+    /// Frees the port for a child process to bind, returning it.
+    pub fn release(self) -> u16 {
+        self.port()
+    }
+
+    /// The bound listener, for serving on the port directly.
+    pub fn into_listener(self) -> TcpListener {
+        self.listener
+    }
+
+    /// Keeps serving on the reserved port: the first connection accepted is
+    /// the debug session's, so nothing can take the port before then.
+    pub fn accept(self) -> io::Result<TcpStream> {
+        self.listener.accept().map(|(stream, _)| stream)
+    }
+}
+
+/// Whether something accepts connections on `127.0.0.1:port`. A port that
+/// is merely bound, or held by a process that stopped serving it, doesn't
+/// count.
+pub fn is_listening(port: u16) -> bool {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    TcpStream::connect_timeout(&address, Duration::from_millis(500)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_ports_are_held_until_released() {
+        let reservation = PortReservation::reserve(0).unwrap();
+        let port = reservation.port();
+        assert_ne!(port, 0);
+        assert!(PortReservation::reserve(port).is_err());
+        assert!(is_listening(port));
+        assert_eq!(reservation.release(), port);
+        assert!(!is_listening(port));
+        assert!(PortReservation::reserve(port).is_ok());
+    }
+
+    #[test]
+    fn accept_hands_over_the_first_connection() {
+        let reservation = PortReservation::reserve(0).unwrap();
+        let port = reservation.port();
+        let client = std::thread::spawn(move || TcpStream::connect(("127.0.0.1", port)).unwrap());
+        let stream = reservation.accept().unwrap();
+        let client = client.join().unwrap();
+        assert_eq!(stream.local_addr().unwrap(), client.peer_addr().unwrap());
+    }
+}
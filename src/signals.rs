@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+pub const SIGINT: u8 = 2;
+pub const SIGTRAP: u8 = 5;
+pub const SIGSTOP: u8 = 17;
+
+const SIGNAL_NAMES: &[(&str, u8)] = &[
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGILL", 4),
+    ("SIGTRAP", 5),
+    ("SIGABRT", 6),
+    ("SIGEMT", 7),
+    ("SIGFPE", 8),
+    ("SIGKILL", 9),
+    ("SIGBUS", 10),
+    ("SIGSEGV", 11),
+    ("SIGSYS", 12),
+    ("SIGPIPE", 13),
+    ("SIGALRM", 14),
+    ("SIGTERM", 15),
+    ("SIGURG", 16),
+    ("SIGSTOP", 17),
+    ("SIGTSTP", 18),
+    ("SIGCONT", 19),
+    ("SIGCHLD", 20),
+    ("SIGTTIN", 21),
+    ("SIGTTOU", 22),
+    ("SIGIO", 23),
+    ("SIGXCPU", 24),
+    ("SIGXFSZ", 25),
+    ("SIGVTALRM", 26),
+    ("SIGPROF", 27),
+    ("SIGWINCH", 28),
+    ("SIGINFO", 29),
+    ("SIGUSR1", 30),
+    ("SIGUSR2", 31),
+];
+
+/// What to do when the target stops with a given signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalAction {
+    /// Resume immediately and deliver the signal to the target.
+    Pass,
+    /// Report the stop to the client; the signal is not delivered on resume.
+    Stop,
+    /// Resume immediately without delivering the signal.
+    Ignore,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SignalPolicy {
+    actions: HashMap<u8, SignalAction>,
+}
+
+impl SignalPolicy {
+    pub fn from_config(config: &HashMap<String, SignalAction>) -> Result<Self, String> {
+        let mut actions = HashMap::new();
+        for (name, action) in config {
+            let signal = signal_number(name).ok_or_else(|| format!("unknown signal `{name}`"))?;
+            actions.insert(signal, *action);
+        }
+        Ok(Self { actions })
+    }
+
+    pub fn action(&self, signal: u8) -> SignalAction {
+        self.actions
+            .get(&signal)
+            .copied()
+            .unwrap_or(SignalAction::Stop)
+    }
+}
+
+/// Resolves `SIGPIPE`, `PIPE`, or a decimal number to the gdb-remote signal number.
+pub fn signal_number(name: &str) -> Option<u8> {
+    let trimmed = name.trim();
+    if let Ok(number) = trimmed.parse::<u8>() {
+        return Some(number);
+    }
+    let upper = trimmed.to_ascii_uppercase();
+    let canonical = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{upper}")
+    };
+    SIGNAL_NAMES
+        .iter()
+        .find(|(known, _)| *known == canonical)
+        .map(|(_, number)| *number)
+}
+
+pub fn signal_name(signal: u8) -> Option<&'static str> {
+    SIGNAL_NAMES
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_number_accepts_names_and_numbers() {
+        assert_eq!(signal_number("SIGPIPE"), Some(13));
+        assert_eq!(signal_number("pipe"), Some(13));
+        assert_eq!(signal_number("30"), Some(30));
+        assert_eq!(signal_number("SIGBOGUS"), None);
+        assert_eq!(signal_name(11), Some("SIGSEGV"));
+    }
+
+    #[test]
+    fn policy_defaults_to_stop() {
+        let config: HashMap<String, SignalAction> =
+            serde_json::from_str(r#"{"SIGPIPE":"ignore","SIGUSR1":"pass"}"#).unwrap();
+        let policy = SignalPolicy::from_config(&config).unwrap();
+        assert_eq!(policy.action(13), SignalAction::Ignore);
+        assert_eq!(policy.action(30), SignalAction::Pass);
+        assert_eq!(policy.action(11), SignalAction::Stop);
+    }
+
+    #[test]
+    fn policy_rejects_unknown_signal() {
+        let config = HashMap::from([("SIGNOPE".to_string(), SignalAction::Pass)]);
+        assert!(SignalPolicy::from_config(&config).is_err());
+    }
+}
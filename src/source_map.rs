@@ -0,0 +1,93 @@
+/// Rewrites path prefixes between the paths recorded in DWARF at build time
+/// and the local checkout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    entries: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    pub fn new(pairs: Vec<(String, String)>) -> Self {
+        let entries = pairs
+            .into_iter()
+            .map(|(build, local)| (normalize(&build), normalize(&local)))
+            .filter(|(build, local)| !build.is_empty() && !local.is_empty())
+            .collect();
+        Self { entries }
+    }
+
+    /// Maps a DWARF path to its location in the local worktree.
+    pub fn to_local(&self, build_path: &str) -> String {
+        remap(build_path, self.entries.iter().map(|(b, l)| (b, l)))
+    }
+
+    /// Maps a local path back to the path recorded at build time.
+    pub fn to_build(&self, local_path: &str) -> String {
+        remap(local_path, self.entries.iter().map(|(b, l)| (l, b)))
+    }
+}
+
+fn remap<'a>(path: &str, pairs: impl Iterator<Item = (&'a String, &'a String)>) -> String {
+    let best = pairs
+        .filter(|(from, _)| has_prefix(path, from))
+        .max_by_key(|(from, _)| from.len());
+    match best {
+        Some((from, to)) => format!("{to}{}", &path[from.len()..]),
+        None => path.to_string(),
+    }
+}
+
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn normalize(path: &str) -> String {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        path.to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SourceMap {
+        SourceMap::new(vec![
+            ("/ci/build".into(), "/Users/me/src".into()),
+            ("/ci/build/Vendor/".into(), "/opt/vendor".into()),
+        ])
+    }
+
+    #[test]
+    fn to_local_uses_longest_prefix() {
+        let map = sample();
+        assert_eq!(
+            map.to_local("/ci/build/App/main.swift"),
+            "/Users/me/src/App/main.swift"
+        );
+        assert_eq!(
+            map.to_local("/ci/build/Vendor/lib.swift"),
+            "/opt/vendor/lib.swift"
+        );
+        assert_eq!(
+            map.to_local("/ci/buildx/other.swift"),
+            "/ci/buildx/other.swift"
+        );
+    }
+
+    #[test]
+    fn to_build_reverses_mapping() {
+        let map = sample();
+        assert_eq!(
+            map.to_build("/Users/me/src/App/main.swift"),
+            "/ci/build/App/main.swift"
+        );
+        assert_eq!(
+            map.to_build("/elsewhere/main.swift"),
+            "/elsewhere/main.swift"
+        );
+    }
+}
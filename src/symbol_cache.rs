@@ -0,0 +1,222 @@
+//! On-disk cache for per-binary tables that are slow to rebuild, such as the
+//! DWARF line index and the function table. Entries are keyed by the Mach-O
+//! UUID plus the file's modification time, so a rebuilt binary never picks up
+//! tables computed for an older one. Every build leaves new entries behind,
+//! so entries unused for [`MAX_AGE`] go, and the least recently used ones
+//! beyond [`MAX_BYTES`].
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::symbols::format_uuid;
+
+/// Overrides the cache location; set it to an empty string to disable caching.
+pub const CACHE_DIR_ENV_VAR: &str = "SWIFTSCOPE_CACHE_DIR";
+
+/// How long an entry is kept after it was last written or read.
+pub const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How much the cache may hold before the least recently used entries go.
+pub const MAX_BYTES: u64 = 1 << 30;
+
+/// Numbers each write's partial file, so two stores in one process never
+/// share it.
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolCache {
+    dir: PathBuf,
+}
+
+impl SymbolCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cache named by `SWIFTSCOPE_CACHE_DIR`, falling back to the
+    /// platform cache directory.
+    pub fn from_env() -> Option<Self> {
+        match env::var_os(CACHE_DIR_ENV_VAR) {
+            Some(dir) if dir.is_empty() => None,
+            Some(dir) => Some(Self::new(dir)),
+            None => default_dir().map(Self::new),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads the `kind` table cached for `binary`, marking the entry as
+    /// used. Missing, stale or unreadable entries all come back as `None`.
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        binary: &Path,
+        uuid: &[u8; 16],
+    ) -> Option<T> {
+        let path = self.entry_path(kind, binary, uuid)?;
+        let value = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(value)
+    }
+
+    /// Writes the `kind` table for `binary`, replacing any earlier entry
+    /// atomically so concurrent sessions never read half a file.
+    pub fn store<T: Serialize>(
+        &self,
+        kind: &str,
+        binary: &Path,
+        uuid: &[u8; 16],
+        value: &T,
+    ) -> io::Result<()> {
+        let path = self
+            .entry_path(kind, binary, uuid)
+            .ok_or_else(|| io::Error::other(format!("no mtime for {}", binary.display())))?;
+        fs::create_dir_all(&self.dir)?;
+        let data = serde_json::to_vec(value).map_err(io::Error::other)?;
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let partial = path.with_extension(format!("json.{}-{write}", std::process::id()));
+        fs::write(&partial, data)?;
+        fs::rename(partial, path)?;
+        self.prune(MAX_AGE, MAX_BYTES);
+        Ok(())
+    }
+
+    /// Removes the entries unused for `max_age`, then the least recently
+    /// used ones until the rest fit in `max_bytes`. Files that can't be
+    /// read or removed, such as another session's, are left alone.
+    fn prune(&self, max_age: Duration, max_bytes: u64) {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = dir
+            .flatten()
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok().filter(fs::Metadata::is_file)?;
+                Some((meta.modified().ok()?, meta.len(), entry.path()))
+            })
+            .collect();
+        entries.sort_by_key(|(used, ..)| std::cmp::Reverse(*used));
+        let mut kept = 0;
+        for (used, size, path) in entries {
+            let expired = now.duration_since(used).is_ok_and(|age| age > max_age);
+            if expired || kept + size > max_bytes {
+                let _ = fs::remove_file(path);
+            } else {
+                kept += size;
+            }
+        }
+    }
+
+    fn entry_path(&self, kind: &str, binary: &Path, uuid: &[u8; 16]) -> Option<PathBuf> {
+        let mtime = fs::metadata(binary)
+            .and_then(|meta| meta.modified())
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?;
+        Some(self.dir.join(format!(
+            "{}-{}-{kind}.json",
+            format_uuid(uuid),
+            mtime.as_nanos()
+        )))
+    }
+}
+
+/// A scratch directory, so tests that load real binaries leave the user's
+/// cache alone.
+#[cfg(test)]
+fn default_dir() -> Option<PathBuf> {
+    Some(env::temp_dir().join("swiftscope-test-cache"))
+}
+
+/// `~/Library/Caches/swiftscope` on macOS, `$XDG_CACHE_HOME/swiftscope` or
+/// `~/.cache/swiftscope` elsewhere.
+#[cfg(not(test))]
+fn default_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(target_os = "macos") {
+        home?.join("Library/Caches")
+    } else {
+        match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => home?.join(".cache"),
+        }
+    };
+    Some(base.join("swiftscope"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_and_follow_the_binary() {
+        let root = env::temp_dir().join(format!("swiftscope-cache-{}", std::process::id()));
+        let binary = root.join("App");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&binary, b"v1").unwrap();
+        let cache = SymbolCache::new(root.join("cache"));
+        let uuid = [7; 16];
+
+        assert_eq!(cache.load::<Vec<u64>>("lines", &binary, &uuid), None);
+        cache
+            .store("lines", &binary, &uuid, &vec![1u64, 2, 3])
+            .unwrap();
+        assert_eq!(cache.load("lines", &binary, &uuid), Some(vec![1u64, 2, 3]));
+        assert_eq!(cache.load::<Vec<u64>>("functions", &binary, &uuid), None);
+        assert_eq!(cache.load::<Vec<u64>>("lines", &binary, &[8; 16]), None);
+
+        // A rebuilt binary at the same path misses the old entry.
+        let later =
+            fs::metadata(&binary).unwrap().modified().unwrap() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&binary)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(cache.load::<Vec<u64>>("lines", &binary, &uuid), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn pruning_drops_old_entries_then_the_least_recently_used() {
+        let root = env::temp_dir().join(format!("swiftscope-prune-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let now = SystemTime::now();
+        let entry = |name: &str, age_days: u64| {
+            let path = root.join(name);
+            fs::write(&path, [0; 100]).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age_days * 24 * 60 * 60))
+                .unwrap();
+        };
+        entry("fresh.json", 0);
+        entry("recent.json", 1);
+        entry("older.json", 2);
+        entry("ancient.json", 60);
+
+        SymbolCache::new(&root).prune(MAX_AGE, 250);
+        let mut left: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["fresh.json", "recent.json"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! Client for HTTP symbol servers that hand out dSYM DWARF files by UUID, for
+//! teams that archive symbols centrally instead of keeping every build's
+//! dSYM on each machine. Downloads are kept on disk so a build is fetched
+//! once.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    symbol_cache::SymbolCache,
+    symbols::{file_uuid, format_uuid},
+};
+
+/// Base URL of the symbol server; unset or empty disables it.
+pub const SYMBOL_SERVER_ENV_VAR: &str = "SWIFTSCOPE_SYMBOL_SERVER";
+
+/// Replaced by the dashed, uppercase UUID in server URLs.
+const UUID_PLACEHOLDER: &str = "{uuid}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolServer {
+    url: String,
+    dir: PathBuf,
+}
+
+impl SymbolServer {
+    /// A server at `url` whose downloads are kept in `dir`. `url` may
+    /// contain `{uuid}`; otherwise the UUID is appended as a path component.
+    pub fn new(url: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            dir: dir.into(),
+        }
+    }
+
+    /// The server named by `SWIFTSCOPE_SYMBOL_SERVER`, downloading into the
+    /// `dsyms` directory of `cache` (or the temp directory without one).
+    pub fn from_env(cache: Option<&SymbolCache>) -> Option<Self> {
+        let url = env::var(SYMBOL_SERVER_ENV_VAR).ok()?;
+        if url.is_empty() {
+            return None;
+        }
+        let dir = match cache {
+            Some(cache) => cache.dir().join("dsyms"),
+            None => env::temp_dir().join("swiftscope-dsyms"),
+        };
+        Some(Self::new(url, dir))
+    }
+
+    pub fn url_for(&self, uuid: &[u8; 16]) -> String {
+        let uuid = format_uuid(uuid);
+        if self.url.contains(UUID_PLACEHOLDER) {
+            self.url.replace(UUID_PLACEHOLDER, &uuid)
+        } else {
+            format!("{}/{uuid}", self.url.trim_end_matches('/'))
+        }
+    }
+
+    /// The DWARF file for `uuid`, downloading it on first use. Returns
+    /// `None` when the server has no such build or sends a file whose UUID
+    /// doesn't match.
+    pub fn dwarf_for(&self, uuid: &[u8; 16]) -> Option<PathBuf> {
+        let path = self.dir.join(format_uuid(uuid));
+        if file_uuid(&path) == Some(*uuid) {
+            return Some(path);
+        }
+        fs::create_dir_all(&self.dir).ok()?;
+        let partial = path.with_extension(format!("part.{}", std::process::id()));
+        let fetched = self.download(uuid, &partial) && file_uuid(&partial) == Some(*uuid);
+        if !fetched {
+            let _ = fs::remove_file(&partial);
+            return None;
+        }
+        fs::rename(&partial, &path).ok()?;
+        Some(path)
+    }
+
+    fn download(&self, uuid: &[u8; 16], dest: &Path) -> bool {
+        let url = self.url_for(uuid);
+        match Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .args(["--max-time", "120", "--output"])
+            .arg(dest)
+            .arg(&url)
+            .output()
+        {
+            Ok(output) if output.status.success() => true,
+            Ok(output) => {
+                eprintln!(
+                    "symbol server download of {url} failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                false
+            }
+            Err(err) => {
+                eprintln!("failed to run curl for {url}: {err}");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_substitutes_or_appends_the_uuid() {
+        let uuid = [0xab; 16];
+        let dashed = "ABABABAB-ABAB-ABAB-ABAB-ABABABABABAB";
+        assert_eq!(
+            SymbolServer::new("https://symbols.example.com/dsym/", "/tmp").url_for(&uuid),
+            format!("https://symbols.example.com/dsym/{dashed}")
+        );
+        assert_eq!(
+            SymbolServer::new("https://symbols.example.com/{uuid}/dwarf", "/tmp").url_for(&uuid),
+            format!("https://symbols.example.com/{dashed}/dwarf")
+        );
+    }
+}
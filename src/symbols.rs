@@ -1,18 +1,35 @@
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
     fs,
+    ops::Deref,
     path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
 };
 
 use addr2line::{Frame, Loader, LoaderReader, Location};
 use anyhow::{anyhow, Context, Result};
+use memmap2::Mmap;
 use object::{
     read::{macho, ReadRef},
-    BinaryFormat, File as ObjectFile, Object, ObjectSegment,
+    BinaryFormat, File as ObjectFile, Object, ObjectSection, ObjectSegment, ObjectSymbol,
+    SymbolKind,
 };
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{symbol_cache::SymbolCache, symbol_server::SymbolServer};
 
 type LoaderFrame<'a> = Frame<'a, LoaderReader<'a>>;
 
-#[allow(dead_code)]
+const FUNCTIONS_CACHE_KIND: &str = "functions";
+const FUNCTION_RANGES_CACHE_KIND: &str = "function-ranges";
+
+/// How many PCs [`SymbolContext::cached_frames`] remembers.
+const FRAME_CACHE_CAPACITY: usize = 4096;
+
 pub struct Image {
     pub name: String,
     pub path: PathBuf,
@@ -24,15 +41,176 @@ pub struct Image {
 
 pub struct SymbolContext {
     pub main: Image,
+    /// Images other than the main executable, in the order they were added.
+    libraries: Vec<Image>,
+    /// `__TEXT` size of each image: the main executable first, then
+    /// `libraries`. Zero when unknown.
+    text_sizes: Vec<u64>,
+    /// Slid `__TEXT` extents sorted by start address, for
+    /// [`SymbolContext::image_for_address`].
+    ranges: Vec<ImageRange>,
+    /// Each image's function symbols sorted by address, read on first use.
+    symbol_tables: Vec<OnceLock<Vec<FunctionSymbol>>>,
+    /// The file holding each image's DWARF: a dSYM, or the image itself.
+    dwarf_files: Vec<PathBuf>,
+    /// Each image's function extents sorted by start, built on first use;
+    /// see [`SymbolContext::function_at`].
+    function_tables: Vec<OnceLock<Vec<FunctionRange>>>,
+    cache: Option<SymbolCache>,
+    /// The main executable's mapping, shared with the line index.
+    main_file: Option<MappedFile>,
+    /// Symbolicated PCs; cleared whenever a slide or the image list changes.
+    frame_cache: Mutex<FrameCache>,
 }
 
-impl SymbolContext {
-    pub fn new(app_path: &Path) -> Result<Self> {
-        let data = fs::read(app_path)
+/// One frame of a PC's inline chain, innermost first, detached from the
+/// DWARF loader so it can be cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolizedFrame {
+    /// Demangled when possible, otherwise the raw linkage name.
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl SymbolizedFrame {
+    fn from_frame(frame: &LoaderFrame<'_>) -> Self {
+        let function = frame.function.as_ref().and_then(|name| {
+            name.demangle()
+                .or_else(|_| name.raw_name())
+                .ok()
+                .map(Cow::into_owned)
+        });
+        let location = frame.location.as_ref();
+        Self {
+            function,
+            file: location.and_then(|loc| loc.file).map(str::to_string),
+            line: location.and_then(|loc| loc.line),
+        }
+    }
+}
+
+/// A least-recently-used map from remote PC to its symbolicated frames.
+#[derive(Default)]
+struct FrameCache {
+    entries: HashMap<u64, (Arc<[SymbolizedFrame]>, u64)>,
+    clock: u64,
+}
+
+impl FrameCache {
+    fn get(&mut self, pc: u64) -> Option<Arc<[SymbolizedFrame]>> {
+        self.clock += 1;
+        let (frames, last_used) = self.entries.get_mut(&pc)?;
+        *last_used = self.clock;
+        Some(frames.clone())
+    }
+
+    fn insert(&mut self, pc: u64, frames: Arc<[SymbolizedFrame]>) {
+        if self.entries.len() >= FRAME_CACHE_CAPACITY && !self.entries.contains_key(&pc) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(pc, _)| *pc);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(pc, (frames, self.clock));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A read-only memory map of a binary. Clones share the mapping, so the
+/// symbol context and line index parse the same pages without copying a
+/// multi-hundred-megabyte app into memory twice.
+#[derive(Clone)]
+pub struct MappedFile {
+    path: PathBuf,
+    map: Arc<Mmap>,
+}
+
+impl MappedFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        // SAFETY: the map is read-only; like lldb, we accept that a binary
+        // rewritten underneath a session yields garbage symbols.
+        let map = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("failed to map {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            map: Arc::new(map),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+/// A slid `[start, end)` range owned by the image at `index` (0 is the main
+/// executable, `n` is `libraries[n - 1]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ImageRange {
+    start: u64,
+    end: u64,
+    index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionSymbol {
+    pub name: String,
+    /// Unslid (file) address of the function entry point.
+    pub address: u64,
+}
+
+/// A function's `[start, start + size)` extent. Tables hold unslid
+/// addresses; [`SymbolContext::function_at`] returns slid ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionRange {
+    pub name: String,
+    pub start: u64,
+    pub size: u64,
+}
+
+impl FunctionRange {
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.size)
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.start <= address && address < self.end()
+    }
+}
+
+impl Image {
+    /// Loads the Mach-O at `app_path`, its `__TEXT` size, its mapping and
+    /// the file its DWARF was read from. `locate_dwarf` may name a separate
+    /// file holding the image's debug info given its UUID and whether the
+    /// binary carries DWARF of its own; otherwise the loader's own search
+    /// applies.
+    fn load(
+        app_path: &Path,
+        locate_dwarf: impl FnOnce(&[u8; 16], bool) -> Option<PathBuf>,
+    ) -> Result<(Self, u64, MappedFile, PathBuf)> {
+        let data = MappedFile::open(app_path)
             .with_context(|| format!("failed to read Mach-O {}", app_path.display()))?;
         let file = ObjectFile::parse(&*data)
             .map_err(|err| anyhow!("failed to parse Mach-O {}: {err}", app_path.display()))?;
-        if file.format() != BinaryFormat::MachO {
+        if !is_supported_format(file.format()) {
             return Err(anyhow!(
                 "expected Mach-O binary at {}, found {:?}",
                 app_path.display(),
@@ -41,16 +219,38 @@ impl SymbolContext {
         }
 
         let vmaddr_text = find_text_vmaddr(&file)?;
+        let text_size = text_segment_size(&file);
         let uuid = extract_macho_uuid(&file)?;
-        let dwarf = Loader::new(app_path)
-            .map_err(|err| anyhow!("failed to load DWARF from {}: {err}", app_path.display()))?;
+        let own_dwarf = file.section_by_name(".debug_info").is_some();
+        let dwarf_path = uuid
+            .as_ref()
+            .and_then(|uuid| locate_dwarf(uuid, own_dwarf))
+            .unwrap_or_else(|| app_path.to_path_buf());
+        // An unreadable dSYM shouldn't cost the session the binary's own
+        // symbol table, so fall back to the binary itself.
+        let (dwarf, dwarf_path) = match Loader::new(&dwarf_path) {
+            Ok(dwarf) => (dwarf, dwarf_path),
+            Err(err) if dwarf_path != app_path => {
+                eprintln!("ignoring dSYM {}: {err}", dwarf_path.display());
+                let dwarf = Loader::new(app_path).map_err(|err| {
+                    anyhow!("failed to load DWARF from {}: {err}", app_path.display())
+                })?;
+                (dwarf, app_path.to_path_buf())
+            }
+            Err(err) => {
+                return Err(anyhow!(
+                    "failed to load DWARF from {}: {err}",
+                    dwarf_path.display()
+                ))
+            }
+        };
         let name = app_path
             .file_name()
             .map(|n| n.to_string_lossy().into_owned())
             .unwrap_or_else(|| app_path.display().to_string());
 
-        Ok(Self {
-            main: Image {
+        Ok((
+            Self {
                 name,
                 path: app_path.to_path_buf(),
                 uuid,
@@ -58,34 +258,158 @@ impl SymbolContext {
                 slide: 0,
                 dwarf,
             },
-        })
+            text_size,
+            data.clone(),
+            dwarf_path,
+        ))
+    }
+}
+
+impl SymbolContext {
+    pub fn new(app_path: &Path) -> Result<Self> {
+        Self::with_dsym(app_path, None)
+    }
+
+    /// Like [`SymbolContext::new`], but reads DWARF from `dsym` (a `.dSYM`
+    /// bundle or the DWARF file inside one) when its UUID matches the
+    /// binary's, before searching anywhere else.
+    pub fn with_dsym(app_path: &Path, dsym: Option<&Path>) -> Result<Self> {
+        // Look for the dSYM next to the binary. Only a binary without DWARF
+        // of its own is worth asking Spotlight (archived builds keep their
+        // dSYMs elsewhere) and the symbol server about.
+        let cache = SymbolCache::from_env();
+        let (main, text_size, main_file, dwarf_file) = Image::load(app_path, |uuid, own_dwarf| {
+            dsym.and_then(|dsym| explicit_dsym_dwarf(dsym, uuid))
+                .or_else(|| adjacent_dsym_dwarf(app_path.parent()?, uuid))
+                .or_else(|| {
+                    if own_dwarf {
+                        return None;
+                    }
+                    spotlight_dsym_dwarf(uuid)
+                        .or_else(|| SymbolServer::from_env(cache.as_ref())?.dwarf_for(uuid))
+                })
+        })?;
+        let mut ctx = Self::with_main(main, text_size);
+        ctx.dwarf_files[0] = dwarf_file;
+        ctx.cache = cache;
+        ctx.main_file = Some(main_file);
+        Ok(ctx)
+    }
+
+    fn with_main(main: Image, text_size: u64) -> Self {
+        let dwarf_file = main.path.clone();
+        let mut ctx = Self {
+            main,
+            libraries: Vec::new(),
+            text_sizes: vec![text_size],
+            ranges: Vec::new(),
+            symbol_tables: vec![OnceLock::new()],
+            dwarf_files: vec![dwarf_file],
+            function_tables: vec![OnceLock::new()],
+            cache: None,
+            main_file: None,
+            frame_cache: Mutex::default(),
+        };
+        ctx.rebuild_ranges();
+        ctx
     }
 
-    #[allow(dead_code)]
     pub fn set_slide(&mut self, slide: i64) {
         self.main.slide = slide;
+        self.rebuild_ranges();
     }
 
-    pub fn translate_remote_pc(&self, remote_pc: u64) -> u64 {
-        if self.main.slide >= 0 {
-            remote_pc.wrapping_sub(self.main.slide as u64)
-        } else {
-            remote_pc.wrapping_add((-self.main.slide) as u64)
+    /// Registers another loaded image whose `__TEXT` segment spans
+    /// `text_size` bytes from its `vmaddr_text`, so addresses inside it
+    /// symbolicate against its own DWARF and slide.
+    pub fn add_image(&mut self, image: Image, text_size: u64) {
+        let dwarf_file = image.path.clone();
+        self.push_image(image, text_size, dwarf_file);
+    }
+
+    /// Loads the symbol file at `path` for an image the process has loaded
+    /// at `load_address` and registers it with [`SymbolContext::add_image`].
+    pub fn add_image_file(&mut self, path: &Path, load_address: u64) -> Result<()> {
+        let (mut image, text_size, _, dwarf_file) = Image::load(path, |_, _| None)?;
+        image.slide = load_address.wrapping_sub(image.vmaddr_text) as i64;
+        self.push_image(image, text_size, dwarf_file);
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: Image, text_size: u64, dwarf_file: PathBuf) {
+        self.libraries.push(image);
+        self.text_sizes.push(text_size);
+        self.symbol_tables.push(OnceLock::new());
+        self.dwarf_files.push(dwarf_file);
+        self.function_tables.push(OnceLock::new());
+        self.rebuild_ranges();
+    }
+
+    /// The image whose slid `__TEXT` range contains `remote_pc`.
+    pub fn image_for_address(&self, remote_pc: u64) -> Option<&Image> {
+        self.image_index(remote_pc).map(|index| self.image(index))
+    }
+
+    fn image_index(&self, remote_pc: u64) -> Option<usize> {
+        let after = self
+            .ranges
+            .partition_point(|range| range.start <= remote_pc);
+        let range = self.ranges[..after].last()?;
+        (remote_pc < range.end).then_some(range.index)
+    }
+
+    fn image(&self, index: usize) -> &Image {
+        match index {
+            0 => &self.main,
+            n => &self.libraries[n - 1],
         }
     }
 
+    fn rebuild_ranges(&mut self) {
+        let mut ranges: Vec<ImageRange> = self
+            .text_sizes
+            .iter()
+            .enumerate()
+            .filter(|(_, size)| **size > 0)
+            .map(|(index, size)| {
+                let image = self.image(index);
+                let start = slide_address(image.vmaddr_text, image.slide);
+                ImageRange {
+                    start,
+                    end: start.saturating_add(*size),
+                    index,
+                }
+            })
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        self.ranges = ranges;
+        // Every slide and image-list change lands here, and each can move
+        // what a PC resolves to.
+        self.frame_cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// The image that should symbolicate `remote_pc`. Addresses outside every
+    /// known range, including all of them when no sizes are known, go to the
+    /// main executable.
+    fn image_or_main(&self, remote_pc: u64) -> &Image {
+        self.image_for_address(remote_pc).unwrap_or(&self.main)
+    }
+
+    pub fn translate_remote_pc(&self, remote_pc: u64) -> u64 {
+        let slide = self.image_or_main(remote_pc).slide;
+        slide_address(remote_pc, slide.wrapping_neg())
+    }
+
     pub fn local_to_remote(&self, local_pc: u64) -> u64 {
-        if self.main.slide >= 0 {
-            local_pc.wrapping_add(self.main.slide as u64)
-        } else {
-            local_pc.wrapping_sub((-self.main.slide) as u64)
-        }
+        slide_address(local_pc, self.main.slide)
     }
 
-    #[allow(dead_code)]
     pub fn symbolize_location(&self, remote_pc: u64) -> Result<Option<Location<'_>>> {
         let probe = self.translate_remote_pc(remote_pc);
-        self.main
+        self.image_or_main(remote_pc)
             .dwarf
             .find_location(probe)
             .map_err(|err| anyhow!("addr2line location lookup failed: {err}"))
@@ -94,7 +418,7 @@ impl SymbolContext {
     pub fn symbolize_frames(&self, remote_pc: u64) -> Result<Vec<LoaderFrame<'_>>> {
         let probe = self.translate_remote_pc(remote_pc);
         let mut frames_iter = self
-            .main
+            .image_or_main(remote_pc)
             .dwarf
             .find_frames(probe)
             .map_err(|err| anyhow!("addr2line frame lookup failed: {err}"))?;
@@ -108,14 +432,193 @@ impl SymbolContext {
         Ok(frames)
     }
 
+    /// [`SymbolContext::symbolize_frames`] reduced to names and locations and
+    /// remembered per PC, so the repeated stack traces of a stepping session
+    /// skip the DWARF walk. Failed lookups are remembered as no frames.
+    pub fn cached_frames(&self, remote_pc: u64) -> Arc<[SymbolizedFrame]> {
+        let cached = self
+            .frame_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(remote_pc);
+        if let Some(frames) = cached {
+            return frames;
+        }
+        let frames: Arc<[SymbolizedFrame]> = self
+            .symbolize_frames(remote_pc)
+            .map(|frames| frames.iter().map(SymbolizedFrame::from_frame).collect())
+            .unwrap_or_default();
+        self.frame_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(remote_pc, frames.clone());
+        frames
+    }
+
+    /// Names `remote_pc` from the symbol table as the enclosing function and
+    /// the offset into it, for code without DWARF. A function is taken to
+    /// end where the next begins; past the last one, only an image whose
+    /// `__TEXT` extent is known can vouch for the address.
+    pub fn symbol_for_address(&self, remote_pc: u64) -> Option<(String, u64)> {
+        let index = self.image_index(remote_pc).unwrap_or(0);
+        let image = self.image(index);
+        let probe = slide_address(remote_pc, image.slide.wrapping_neg());
+        let symbols = self.symbol_tables[index].get_or_init(|| self.sorted_functions(image));
+        let after = symbols.partition_point(|symbol| symbol.address <= probe);
+        let symbol = symbols[..after].last()?;
+        let end = match symbols.get(after) {
+            Some(next) => next.address,
+            None if self.text_sizes[index] > 0 => image.vmaddr_text + self.text_sizes[index],
+            None => return None,
+        };
+        (probe < end).then(|| {
+            let name = addr2line::demangle_auto(Cow::Borrowed(symbol.name.as_str()), None);
+            (name.into_owned(), probe - symbol.address)
+        })
+    }
+
+    /// The function containing `remote_pc`, with its slid start address and
+    /// demangled name. Extents come from DWARF subprograms where available
+    /// and otherwise run from one symbol to the next, which is what stepping
+    /// and disassembly need to find a function's boundaries.
+    pub fn function_at(&self, remote_pc: u64) -> Option<FunctionRange> {
+        let index = self.image_index(remote_pc).unwrap_or(0);
+        let image = self.image(index);
+        let probe = slide_address(remote_pc, image.slide.wrapping_neg());
+        let functions =
+            self.function_tables[index].get_or_init(|| self.sorted_function_ranges(index));
+        let after = functions.partition_point(|function| function.start <= probe);
+        let function = functions[..after].last()?;
+        function.contains(probe).then(|| FunctionRange {
+            name: addr2line::demangle_auto(Cow::Borrowed(function.name.as_str()), None)
+                .into_owned(),
+            start: slide_address(function.start, image.slide),
+            size: function.size,
+        })
+    }
+
+    /// The function extents of image `index`, from the symbol cache when it
+    /// has an entry for this build.
+    fn sorted_function_ranges(&self, index: usize) -> Vec<FunctionRange> {
+        let image = self.image(index);
+        let cache = self.cache.as_ref().zip(image.uuid.as_ref());
+        if let Some(functions) = cache
+            .and_then(|(cache, uuid)| cache.load(FUNCTION_RANGES_CACHE_KIND, &image.path, uuid))
+        {
+            return functions;
+        }
+        let dwarf = dwarf_function_ranges(&self.dwarf_files[index]).unwrap_or_default();
+        let symbols = self.symbol_tables[index].get_or_init(|| self.sorted_functions(image));
+        let text_end = (self.text_sizes[index] > 0)
+            .then(|| image.vmaddr_text.saturating_add(self.text_sizes[index]));
+        let functions = merge_function_ranges(dwarf, symbols, text_end);
+        if let Some((cache, uuid)) = cache {
+            if let Err(err) = cache.store(FUNCTION_RANGES_CACHE_KIND, &image.path, uuid, &functions)
+            {
+                eprintln!(
+                    "failed to cache function ranges of {}: {err}",
+                    image.path.display()
+                );
+            }
+        }
+        functions
+    }
+
+    /// The function table of `image` sorted by address, from the symbol cache
+    /// when it has an entry for this build.
+    fn sorted_functions(&self, image: &Image) -> Vec<FunctionSymbol> {
+        let cache = self.cache.as_ref().zip(image.uuid.as_ref());
+        if let Some(symbols) =
+            cache.and_then(|(cache, uuid)| cache.load(FUNCTIONS_CACHE_KIND, &image.path, uuid))
+        {
+            return symbols;
+        }
+        let mut symbols = function_symbols(&image.path).unwrap_or_default();
+        symbols.sort_by_key(|symbol| symbol.address);
+        symbols.dedup_by_key(|symbol| symbol.address);
+        if let Some((cache, uuid)) = cache {
+            if let Err(err) = cache.store(FUNCTIONS_CACHE_KIND, &image.path, uuid, &symbols) {
+                eprintln!(
+                    "failed to cache functions of {}: {err}",
+                    image.path.display()
+                );
+            }
+        }
+        symbols
+    }
+
+    /// The main executable's bytes, mapped on first use when the context
+    /// was built without them.
+    pub fn main_file(&self) -> Result<MappedFile> {
+        match &self.main_file {
+            Some(file) => Ok(file.clone()),
+            None => MappedFile::open(&self.main.path),
+        }
+    }
+
+    /// Where computed symbol tables are persisted between sessions.
+    pub fn cache(&self) -> Option<&SymbolCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn set_cache(&mut self, cache: Option<SymbolCache>) {
+        self.cache = cache;
+    }
+
+    /// Returns every defined function in the main image whose raw or demangled
+    /// name matches `pattern`.
+    pub fn functions_matching(&self, pattern: &Regex) -> Result<Vec<FunctionSymbol>> {
+        Ok(function_symbols(&self.main.path)?
+            .into_iter()
+            .filter(|symbol| {
+                pattern.is_match(&symbol.name)
+                    || pattern.is_match(&addr2line::demangle_auto(
+                        Cow::Borrowed(symbol.name.as_str()),
+                        None,
+                    ))
+            })
+            .collect())
+    }
+
     #[cfg(any(test, feature = "cli"))]
     pub fn for_testing(main: Image) -> Self {
-        Self { main }
+        Self::with_main(main, 0)
+    }
+}
+
+pub fn slide_address(address: u64, slide: i64) -> u64 {
+    if slide >= 0 {
+        address.wrapping_add(slide as u64)
+    } else {
+        address.wrapping_sub(slide.unsigned_abs())
     }
 }
 
+/// Mach-O is the production target. The `elf` feature also accepts ELF so
+/// tests and mock-debugserver flows can run against binaries built on Linux.
+fn is_supported_format(format: BinaryFormat) -> bool {
+    format == BinaryFormat::MachO || (cfg!(feature = "elf") && format == BinaryFormat::Elf)
+}
+
+fn text_segment_size(file: &ObjectFile<'_>) -> u64 {
+    if file.format() == BinaryFormat::Elf {
+        // ELF load segments are unnamed; treat their whole span as the image.
+        let start = file.segments().map(|segment| segment.address()).min();
+        let end = file
+            .segments()
+            .map(|segment| segment.address().saturating_add(segment.size()))
+            .max();
+        return start.zip(end).map_or(0, |(start, end)| end - start);
+    }
+    file.segments()
+        .find(|segment| matches!(segment.name(), Ok(Some("__TEXT"))))
+        .map_or(0, |segment| segment.size())
+}
+
+/// The address of `__TEXT`, or of the first segment when there is none
+/// (as in ELF, whose first load segment is the image base).
 pub fn find_text_vmaddr(file: &ObjectFile<'_>) -> Result<u64> {
-    if file.format() != BinaryFormat::MachO {
+    if !is_supported_format(file.format()) {
         return Err(anyhow!("expected Mach-O format"));
     }
 
@@ -138,6 +641,136 @@ pub fn find_text_vmaddr(file: &ObjectFile<'_>) -> Result<u64> {
     fallback.ok_or_else(|| anyhow!("no segments found"))
 }
 
+pub fn function_symbols(path: &Path) -> Result<Vec<FunctionSymbol>> {
+    let data = MappedFile::open(path)
+        .with_context(|| format!("failed to read symbols from {}", path.display()))?;
+    let file = ObjectFile::parse(&*data)
+        .map_err(|err| anyhow!("failed to parse {}: {err}", path.display()))?;
+    let is_macho = file.format() == BinaryFormat::MachO;
+    let mut symbols = Vec::new();
+    for symbol in file.symbols() {
+        if symbol.kind() != SymbolKind::Text || !symbol.is_definition() || symbol.address() == 0 {
+            continue;
+        }
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+        // Mach-O prefixes C-level names with an underscore.
+        let name = if is_macho {
+            name.strip_prefix('_').unwrap_or(name)
+        } else {
+            name
+        };
+        symbols.push(FunctionSymbol {
+            name: name.to_string(),
+            address: symbol.address(),
+        });
+    }
+    Ok(symbols)
+}
+
+/// The extent of every `DW_TAG_subprogram` with code in the DWARF at
+/// `path`, one entry per range, named by linkage name when present.
+fn dwarf_function_ranges(path: &Path) -> Result<Vec<FunctionRange>> {
+    let data = MappedFile::open(path)?;
+    let file = ObjectFile::parse(&*data)
+        .map_err(|err| anyhow!("failed to parse {}: {err}", path.display()))?;
+    let endian = if file.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+    let sections = gimli::DwarfSections::load(|id| -> Result<Cow<[u8]>, object::Error> {
+        match file.section_by_name(id.name()) {
+            Some(section) => section.uncompressed_data(),
+            None => Ok(Cow::Borrowed(&[])),
+        }
+    })?;
+    let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+
+    let mut functions = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let name = [
+                gimli::DW_AT_linkage_name,
+                gimli::DW_AT_MIPS_linkage_name,
+                gimli::DW_AT_name,
+            ]
+            .into_iter()
+            .find_map(|attr| {
+                let value = entry.attr_value(attr).ok()??;
+                let name = dwarf.attr_string(&unit, value).ok()?;
+                Some(name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+            let mut ranges = dwarf.die_ranges(&unit, entry)?;
+            while let Some(range) = ranges.next()? {
+                if range.begin != 0 && range.begin < range.end {
+                    functions.push(FunctionRange {
+                        name: name.clone(),
+                        start: range.begin,
+                        size: range.end - range.begin,
+                    });
+                }
+            }
+        }
+    }
+    Ok(functions)
+}
+
+/// Combines DWARF extents with the symbol table: symbols name functions at
+/// the same start and add the ones DWARF doesn't describe, each running to
+/// the next known start (or `text_end` for the last).
+fn merge_function_ranges(
+    dwarf: Vec<FunctionRange>,
+    symbols: &[FunctionSymbol],
+    text_end: Option<u64>,
+) -> Vec<FunctionRange> {
+    let mut by_start: BTreeMap<u64, FunctionRange> = BTreeMap::new();
+    for function in dwarf {
+        match by_start.get_mut(&function.start) {
+            Some(existing) if existing.size >= function.size => {}
+            _ => {
+                by_start.insert(function.start, function);
+            }
+        }
+    }
+    let mut starts: Vec<u64> = by_start
+        .keys()
+        .copied()
+        .chain(symbols.iter().map(|symbol| symbol.address))
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    for symbol in symbols {
+        if let Some(function) = by_start.get_mut(&symbol.address) {
+            function.name = symbol.name.clone();
+            continue;
+        }
+        let next = starts.partition_point(|start| *start <= symbol.address);
+        let Some(end) = starts.get(next).copied().or(text_end) else {
+            continue;
+        };
+        if end > symbol.address {
+            by_start.insert(
+                symbol.address,
+                FunctionRange {
+                    name: symbol.name.clone(),
+                    start: symbol.address,
+                    size: end - symbol.address,
+                },
+            );
+        }
+    }
+    by_start.into_values().collect()
+}
+
 pub fn extract_macho_uuid(file: &ObjectFile<'_>) -> Result<Option<[u8; 16]>> {
     match file {
         ObjectFile::MachO32(macho) => uuid_from_macho(macho),
@@ -146,6 +779,189 @@ pub fn extract_macho_uuid(file: &ObjectFile<'_>) -> Result<Option<[u8; 16]>> {
     }
 }
 
+/// Finds symbol files for system libraries, which Xcode copies from each
+/// device it has prepared into
+/// `~/Library/Developer/Xcode/iOS DeviceSupport/<version>/Symbols`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSupport {
+    root: PathBuf,
+}
+
+impl DeviceSupport {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Xcode's DeviceSupport directory under `$HOME`, if it exists.
+    pub fn from_home() -> Option<Self> {
+        let root = PathBuf::from(std::env::var_os("HOME")?)
+            .join("Library/Developer/Xcode/iOS DeviceSupport");
+        root.is_dir().then(|| Self::new(root))
+    }
+
+    /// The `Symbols` directory for an OS version. Directories are named
+    /// `17.2 (21C62)`, or `iPhone15,2 17.2 (21C62)` by newer Xcodes; one
+    /// matching `build` wins over one matching only the version.
+    pub fn symbols_dir(&self, os_version: &str, build: Option<&str>) -> Option<PathBuf> {
+        let mut best: Option<(bool, PathBuf)> = None;
+        for entry in fs::read_dir(&self.root).ok()?.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mut words = name.split_whitespace().rev();
+            let (version, dir_build) = match words.next() {
+                Some(last) if last.starts_with('(') => (
+                    words.next(),
+                    Some(last.trim_start_matches('(').trim_end_matches(')')),
+                ),
+                last => (last, None),
+            };
+            if version.map(trim_patch_zero) != Some(trim_patch_zero(os_version)) {
+                continue;
+            }
+            let exact = build.is_some() && dir_build == build;
+            let symbols = entry.path().join("Symbols");
+            if symbols.is_dir()
+                && best
+                    .as_ref()
+                    .is_none_or(|(was_exact, _)| exact && !was_exact)
+            {
+                best = Some((exact, symbols));
+            }
+        }
+        best.map(|(_, symbols)| symbols)
+    }
+
+    /// The symbol file for the device path `image_path`
+    /// (`/usr/lib/system/libsystem_kernel.dylib`, ...).
+    pub fn locate(
+        &self,
+        os_version: &str,
+        build: Option<&str>,
+        image_path: &str,
+    ) -> Option<PathBuf> {
+        let path = self
+            .symbols_dir(os_version, build)?
+            .join(image_path.trim_start_matches('/'));
+        path.is_file().then_some(path)
+    }
+}
+
+/// debugserver reports `17.4.0` where Xcode names the directory `17.4`.
+fn trim_patch_zero(version: &str) -> &str {
+    match version.strip_suffix(".0") {
+        Some(trimmed) if trimmed.contains('.') => trimmed,
+        _ => version,
+    }
+}
+
+/// Formats a Mach-O UUID the way `dwarfdump --uuid` and Spotlight print it.
+pub fn format_uuid(uuid: &[u8; 16]) -> String {
+    let hex: String = uuid.iter().map(|byte| format!("{byte:02X}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Spotlight's answer for each UUID asked about, so `mdfind` runs at most
+/// once per UUID however many contexts are built.
+static SPOTLIGHT_ANSWERS: Mutex<BTreeMap<[u8; 16], Option<PathBuf>>> = Mutex::new(BTreeMap::new());
+
+/// Locates the DWARF file of a dSYM for `uuid` through Spotlight, which
+/// indexes Xcode archives under `com_apple_xcode_dsym_uuids`. Returns `None`
+/// when `mdfind` is unavailable or knows no match.
+pub fn spotlight_dsym_dwarf(uuid: &[u8; 16]) -> Option<PathBuf> {
+    let mut answers = SPOTLIGHT_ANSWERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    answers
+        .entry(*uuid)
+        .or_insert_with(|| ask_spotlight(uuid))
+        .clone()
+}
+
+fn ask_spotlight(uuid: &[u8; 16]) -> Option<PathBuf> {
+    let output = Command::new("mdfind")
+        .arg(format!(
+            "com_apple_xcode_dsym_uuids == {}",
+            format_uuid(uuid)
+        ))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .find_map(|line| dsym_bundle_dwarf(Path::new(line), uuid))
+}
+
+/// The DWARF file for `uuid` among the `.dSYM` bundles in `dir`.
+fn adjacent_dsym_dwarf(dir: &Path, uuid: &[u8; 16]) -> Option<PathBuf> {
+    fs::read_dir(dir).ok()?.find_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.extension().and_then(OsStr::to_str) != Some("dSYM") {
+            return None;
+        }
+        dsym_bundle_dwarf(&path, uuid)
+    })
+}
+
+/// The DWARF for `uuid` at `path`: a DWARF file, a `.dSYM` bundle, an
+/// `.xcarchive` or a directory holding `.dSYM` bundles.
+pub fn find_dsym_dwarf(path: &Path, uuid: &[u8; 16]) -> Option<PathBuf> {
+    if path.is_file() {
+        return (file_uuid(path) == Some(*uuid)).then(|| path.to_path_buf());
+    }
+    dsym_bundle_dwarf(path, uuid).or_else(|| adjacent_dsym_dwarf(path, uuid))
+}
+
+/// The DWARF for `uuid` in `dsym`, a configured `.dSYM` bundle or DWARF
+/// file; a mismatched one is reported and skipped so a stale path doesn't
+/// shadow the right dSYM.
+fn explicit_dsym_dwarf(dsym: &Path, uuid: &[u8; 16]) -> Option<PathBuf> {
+    let found = if dsym.is_file() {
+        (file_uuid(dsym) == Some(*uuid)).then(|| dsym.to_path_buf())
+    } else {
+        dsym_bundle_dwarf(dsym, uuid)
+    };
+    if found.is_none() {
+        eprintln!(
+            "ignoring dSYM {}: no DWARF with UUID {}",
+            dsym.display(),
+            format_uuid(uuid)
+        );
+    }
+    found
+}
+
+/// The file under `Contents/Resources/DWARF` of `bundle` whose UUID is
+/// `uuid`. Spotlight may also report an `.xcarchive`, whose dSYMs live in
+/// its `dSYMs` directory.
+fn dsym_bundle_dwarf(bundle: &Path, uuid: &[u8; 16]) -> Option<PathBuf> {
+    if bundle.extension().and_then(OsStr::to_str) == Some("xcarchive") {
+        return adjacent_dsym_dwarf(&bundle.join("dSYMs"), uuid);
+    }
+    fs::read_dir(bundle.join("Contents/Resources/DWARF"))
+        .ok()?
+        .find_map(|entry| {
+            let path = entry.ok()?.path();
+            (file_uuid(&path)? == *uuid).then_some(path)
+        })
+}
+
+/// The UUID of the Mach-O at `path`, if it is one and carries a UUID.
+pub fn file_uuid(path: &Path) -> Option<[u8; 16]> {
+    let data = MappedFile::open(path).ok()?;
+    let file = ObjectFile::parse(&*data).ok()?;
+    extract_macho_uuid(&file).ok()?
+}
+
 fn uuid_from_macho<'data, Mach, R>(
     macho: &macho::MachOFile<'data, Mach, R>,
 ) -> Result<Option<[u8; 16]>>
@@ -196,6 +1012,120 @@ mod tests {
         assert_eq!(uuid, TEST_UUID);
     }
 
+    #[test]
+    fn device_support_prefers_exact_build() {
+        let root =
+            std::env::temp_dir().join(format!("swiftscope-device-support-{}", std::process::id()));
+        for dir in ["16.4 (20E247)", "17.2 (21C52)", "iPhone15,2 17.2 (21C62)"] {
+            fs::create_dir_all(root.join(dir).join("Symbols/usr/lib")).unwrap();
+        }
+        let lib = root.join("iPhone15,2 17.2 (21C62)/Symbols/usr/lib/libfoo.dylib");
+        fs::write(&lib, build_test_macho(0x1_8000_0000, TEST_UUID)).unwrap();
+        let support = DeviceSupport::new(&root);
+
+        assert_eq!(
+            support.symbols_dir("17.2", Some("21C62")),
+            Some(root.join("iPhone15,2 17.2 (21C62)/Symbols"))
+        );
+        assert!(support.symbols_dir("17.2", None).is_some());
+        assert_eq!(
+            support.symbols_dir("16.4", Some("21C62")),
+            Some(root.join("16.4 (20E247)/Symbols"))
+        );
+        assert_eq!(
+            support.symbols_dir("16.4.0", None),
+            Some(root.join("16.4 (20E247)/Symbols"))
+        );
+        assert_eq!(support.symbols_dir("15.0", None), None);
+        assert_eq!(
+            support.locate("17.2", Some("21C62"), "/usr/lib/libfoo.dylib"),
+            Some(lib.clone())
+        );
+        assert_eq!(
+            support.locate("17.2", Some("21C62"), "/usr/lib/libbar.dylib"),
+            None
+        );
+
+        if let Some(loader) = test_loader() {
+            let mut ctx = SymbolContext::for_testing(Image {
+                name: "test".into(),
+                path: PathBuf::from("/tmp/test"),
+                uuid: None,
+                vmaddr_text: 0,
+                slide: 0,
+                dwarf: loader,
+            });
+            ctx.add_image_file(&lib, 0x1_8000_4000).unwrap();
+            let image = ctx.image_for_address(0x1_8000_4800).unwrap();
+            assert_eq!(image.name, "libfoo.dylib");
+            assert_eq!(image.path, lib);
+            assert_eq!(ctx.translate_remote_pc(0x1_8000_4800), 0x1_8000_0800);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn uuid_formats_like_dwarfdump() {
+        assert_eq!(
+            format_uuid(&TEST_UUID),
+            "01020304-0506-0708-1112-131421222324"
+        );
+    }
+
+    #[test]
+    fn dsym_dwarf_is_matched_by_uuid() {
+        let root = std::env::temp_dir().join(format!("swiftscope-dsym-{}", std::process::id()));
+        let dwarf_dir = root.join("App.app.dSYM/Contents/Resources/DWARF");
+        fs::create_dir_all(&dwarf_dir).unwrap();
+        fs::write(dwarf_dir.join("App"), build_test_macho(0x1000, TEST_UUID)).unwrap();
+
+        let expected = dwarf_dir.join("App");
+        assert_eq!(
+            adjacent_dsym_dwarf(&root, &TEST_UUID),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            dsym_bundle_dwarf(&root.join("App.app.dSYM"), &TEST_UUID),
+            Some(expected)
+        );
+        assert_eq!(adjacent_dsym_dwarf(&root, &[0; 16]), None);
+        assert_eq!(
+            explicit_dsym_dwarf(&root.join("App.app.dSYM"), &TEST_UUID),
+            Some(dwarf_dir.join("App"))
+        );
+        assert_eq!(
+            explicit_dsym_dwarf(&dwarf_dir.join("App"), &TEST_UUID),
+            Some(dwarf_dir.join("App"))
+        );
+        assert_eq!(explicit_dsym_dwarf(&dwarf_dir.join("App"), &[0; 16]), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn symbol_server_downloads_dwarf_once() {
+        let root = std::env::temp_dir().join(format!("swiftscope-symsrv-{}", std::process::id()));
+        let served = root.join("served");
+        fs::create_dir_all(&served).unwrap();
+        fs::write(
+            served.join(format_uuid(&TEST_UUID)),
+            build_test_macho(0x1000, TEST_UUID),
+        )
+        .unwrap();
+        let server = SymbolServer::new(
+            format!("file://{}/{{uuid}}", served.display()),
+            root.join("dsyms"),
+        );
+
+        let path = server.dwarf_for(&TEST_UUID).expect("download dSYM");
+        assert_eq!(path, root.join("dsyms").join(format_uuid(&TEST_UUID)));
+        assert_eq!(file_uuid(&path), Some(TEST_UUID));
+        // Later lookups are served from disk.
+        fs::remove_dir_all(&served).unwrap();
+        assert_eq!(server.dwarf_for(&TEST_UUID), Some(path));
+        assert_eq!(server.dwarf_for(&[0; 16]), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn translate_remote_pc_applies_slide() {
         let Some(dummy_loader) = test_loader() else {
@@ -236,6 +1166,218 @@ mod tests {
         assert_eq!(remote, 0x1000);
     }
 
+    #[test]
+    fn image_for_address_picks_containing_image() {
+        let (Some(main_loader), Some(lib_loader)) = (test_loader(), test_loader()) else {
+            eprintln!("skipping image_for_address_picks_containing_image: missing DWARF loader");
+            return;
+        };
+        let image = |name: &str, vmaddr_text, dwarf| Image {
+            name: name.into(),
+            path: PathBuf::from(format!("/tmp/{name}")),
+            uuid: None,
+            vmaddr_text,
+            slide: 0,
+            dwarf,
+        };
+        let mut ctx = SymbolContext::with_main(image("App", 0x1_0000_0000, main_loader), 0x4000);
+        ctx.set_slide(0x8000);
+        let mut lib = image("Lib", 0x2000, lib_loader);
+        lib.slide = 0x1_8000_0000;
+        ctx.add_image(lib, 0x1000);
+
+        assert_eq!(ctx.image_for_address(0x1_0000_8000).unwrap().name, "App");
+        assert_eq!(ctx.image_for_address(0x1_0000_bfff).unwrap().name, "App");
+        assert!(ctx.image_for_address(0x1_0000_c000).is_none());
+        assert_eq!(ctx.image_for_address(0x1_8000_2800).unwrap().name, "Lib");
+        assert!(ctx.image_for_address(0x1000).is_none());
+
+        assert_eq!(ctx.translate_remote_pc(0x1_8000_2800), 0x2800);
+        assert_eq!(ctx.translate_remote_pc(0x1_0000_9000), 0x1_0000_1000);
+        // Unknown addresses keep using the main executable's slide.
+        assert_eq!(ctx.translate_remote_pc(0x9000), 0x1000);
+    }
+
+    #[test]
+    fn symbol_for_address_reports_offset_into_function() {
+        let Some(loader) = test_loader() else {
+            eprintln!(
+                "skipping symbol_for_address_reports_offset_into_function: missing DWARF loader"
+            );
+            return;
+        };
+        symbols_regex_test_function();
+        let exe = std::env::current_exe().unwrap();
+        let start = function_symbols(&exe)
+            .unwrap()
+            .into_iter()
+            .find(|symbol| symbol.name.ends_with("symbols_regex_test_function"))
+            .unwrap()
+            .address;
+        let mut ctx = SymbolContext::for_testing(Image {
+            name: "test".into(),
+            path: exe,
+            uuid: None,
+            vmaddr_text: 0,
+            slide: 0x1000,
+            dwarf: loader,
+        });
+        ctx.set_slide(0x1000);
+
+        let (name, offset) = ctx.symbol_for_address(start + 0x1000).unwrap();
+        assert!(name.ends_with("symbols_regex_test_function"), "{name}");
+        assert_eq!(offset, 0);
+        let (_, offset) = ctx.symbol_for_address(start + 0x1002).unwrap();
+        assert_eq!(offset, 2);
+        assert_eq!(ctx.symbol_for_address(u64::MAX - 1), None);
+    }
+
+    #[test]
+    fn function_ranges_merge_dwarf_extents_with_symbols() {
+        let dwarf = vec![
+            FunctionRange {
+                name: "body".into(),
+                start: 0x1000,
+                size: 0x20,
+            },
+            FunctionRange {
+                name: "closure".into(),
+                start: 0x1100,
+                size: 0x10,
+            },
+        ];
+        let symbols = [
+            FunctionSymbol {
+                name: "$s3App4bodyyF".into(),
+                address: 0x1000,
+            },
+            FunctionSymbol {
+                name: "helper".into(),
+                address: 0x1040,
+            },
+            FunctionSymbol {
+                name: "last".into(),
+                address: 0x1200,
+            },
+        ];
+
+        let merged = merge_function_ranges(dwarf.clone(), &symbols, Some(0x1280));
+        let extents: Vec<_> = merged
+            .iter()
+            .map(|function| (function.name.as_str(), function.start, function.size))
+            .collect();
+        assert_eq!(
+            extents,
+            [
+                ("$s3App4bodyyF", 0x1000, 0x20),
+                ("helper", 0x1040, 0xc0),
+                ("closure", 0x1100, 0x10),
+                ("last", 0x1200, 0x80),
+            ]
+        );
+        // Without a known text end the last symbol's size is unknown.
+        let merged = merge_function_ranges(dwarf, &symbols, None);
+        assert!(merged.iter().all(|function| function.name != "last"));
+    }
+
+    #[test]
+    fn function_at_reports_slid_function_start() {
+        let Some(loader) = test_loader() else {
+            eprintln!("skipping function_at_reports_slid_function_start: missing DWARF loader");
+            return;
+        };
+        symbols_regex_test_function();
+        let exe = std::env::current_exe().unwrap();
+        let start = function_symbols(&exe)
+            .unwrap()
+            .into_iter()
+            .find(|symbol| symbol.name.ends_with("symbols_regex_test_function"))
+            .unwrap()
+            .address;
+        let mut ctx = SymbolContext::for_testing(Image {
+            name: "test".into(),
+            path: exe,
+            uuid: None,
+            vmaddr_text: 0,
+            slide: 0,
+            dwarf: loader,
+        });
+        ctx.set_slide(0x1000);
+
+        let function = ctx.function_at(start + 0x1002).unwrap();
+        assert!(
+            function.name.ends_with("symbols_regex_test_function"),
+            "{}",
+            function.name
+        );
+        assert_eq!(function.start, start + 0x1000);
+        assert!(function.contains(start + 0x1002));
+        assert_eq!(ctx.function_at(u64::MAX - 1), None);
+    }
+
+    #[cfg(all(feature = "elf", target_os = "linux"))]
+    #[test]
+    fn elf_binaries_load_with_the_elf_feature() {
+        symbols_regex_test_function();
+        let exe = std::env::current_exe().unwrap();
+        let ctx = SymbolContext::new(&exe).unwrap();
+        assert_eq!(ctx.main.uuid, None);
+        let start = function_symbols(&exe)
+            .unwrap()
+            .into_iter()
+            .find(|symbol| symbol.name.ends_with("symbols_regex_test_function"))
+            .unwrap()
+            .address;
+        assert_eq!(ctx.image_for_address(start).unwrap().path, exe);
+    }
+
+    #[test]
+    fn spotlight_is_asked_once_per_uuid() {
+        let uuid = [0x5a; 16];
+        let first = spotlight_dsym_dwarf(&uuid);
+        assert!(SPOTLIGHT_ANSWERS.lock().unwrap().contains_key(&uuid));
+        assert_eq!(spotlight_dsym_dwarf(&uuid), first);
+    }
+
+    #[test]
+    fn frame_cache_evicts_least_recently_used() {
+        let mut cache = FrameCache::default();
+        for pc in 0..FRAME_CACHE_CAPACITY as u64 {
+            cache.insert(pc, Arc::from(Vec::new()));
+        }
+        assert!(cache.get(0).is_some());
+        cache.insert(u64::MAX, Arc::from(Vec::new()));
+        assert_eq!(cache.entries.len(), FRAME_CACHE_CAPACITY);
+        assert!(cache.get(0).is_some(), "recently used entry was evicted");
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(u64::MAX).is_some());
+    }
+
+    #[test]
+    fn cached_frames_are_dropped_when_the_slide_changes() {
+        let Some(loader) = test_loader() else {
+            eprintln!(
+                "skipping cached_frames_are_dropped_when_the_slide_changes: missing DWARF loader"
+            );
+            return;
+        };
+        let mut ctx = SymbolContext::for_testing(Image {
+            name: "test".into(),
+            path: std::env::current_exe().unwrap(),
+            uuid: None,
+            vmaddr_text: 0,
+            slide: 0,
+            dwarf: loader,
+        });
+        let pc = 0x1234;
+        let first = ctx.cached_frames(pc);
+        assert!(Arc::ptr_eq(&first, &ctx.cached_frames(pc)));
+        assert_eq!(ctx.frame_cache.get_mut().unwrap().entries.len(), 1);
+
+        ctx.set_slide(0x1000);
+        assert!(ctx.frame_cache.get_mut().unwrap().entries.is_empty());
+    }
+
     #[test]
     fn symbolize_frames_handles_missing_or_real_debug_info() {
         let exe = std::env::current_exe().unwrap();
@@ -253,10 +1395,36 @@ mod tests {
         );
     }
 
+    #[no_mangle]
+    #[inline(never)]
+    pub extern "C" fn symbols_regex_test_function() {
+        std::hint::black_box(());
+    }
+
+    #[test]
+    fn functions_matching_finds_test_symbol() {
+        let Some(loader) = test_loader() else {
+            eprintln!("skipping functions_matching_finds_test_symbol: missing DWARF loader");
+            return;
+        };
+        symbols_regex_test_function();
+        let ctx = SymbolContext::for_testing(Image {
+            name: "test".into(),
+            path: std::env::current_exe().unwrap(),
+            uuid: None,
+            vmaddr_text: 0,
+            slide: 0,
+            dwarf: loader,
+        });
+        let pattern = Regex::new("^symbols_regex_test_func").unwrap();
+        let matches = ctx.functions_matching(&pattern).unwrap();
+        assert_eq!(matches.len(), 1, "unexpected matches: {matches:?}");
+        assert_eq!(matches[0].name, "symbols_regex_test_function");
+        assert_ne!(matches[0].address, 0);
+    }
+
     fn build_test_macho(vmaddr: u64, uuid: [u8; 16]) -> Vec<u8> {
-        let mut commands = Vec::new();
-        commands.push(build_segment_command(vmaddr));
-        commands.push(build_uuid_command(uuid));
+        let commands = vec![build_segment_command(vmaddr), build_uuid_command(uuid)];
         build_header(&commands)
     }
 
@@ -0,0 +1,363 @@
+//! A minimal usbmuxd client: lists USB-attached and Wi-Fi-paired devices and
+//! forwards local TCP connections to a port on one of them, which is what
+//! `iproxy` does.
+//! Messages are XML plists behind a 16-byte little-endian header; after a
+//! successful `Connect` the socket carries the device connection verbatim.
+
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, TcpListener, TcpStream},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crate::ports::PortReservation;
+
+pub const USBMUXD_SOCKET: &str = "/var/run/usbmuxd";
+
+/// Overrides the usbmuxd socket path, e.g. for a forwarded socket.
+pub const USBMUXD_SOCKET_ENV_VAR: &str = "USBMUXD_SOCKET_ADDRESS";
+
+const PLIST_VERSION: u32 = 1;
+const PLIST_MESSAGE: u32 = 8;
+const HEADER_LEN: usize = 16;
+const CLIENT_NAME: &str = "swiftscope";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbDevice {
+    /// usbmuxd's handle for the device, valid while it stays attached.
+    pub device_id: u64,
+    pub udid: String,
+    /// `USB` or `Network`.
+    pub connection_type: String,
+    /// Where a `Network` device was last seen on the local network.
+    pub network_address: Option<IpAddr>,
+}
+
+/// The usbmuxd socket to use: `USBMUXD_SOCKET_ADDRESS` when it names a
+/// path, else the system one.
+pub fn socket_path() -> PathBuf {
+    std::env::var(USBMUXD_SOCKET_ENV_VAR)
+        .ok()
+        .map(|value| value.trim_start_matches("UNIX:").to_string())
+        .filter(|value| value.starts_with('/'))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(USBMUXD_SOCKET))
+}
+
+pub fn list_devices(socket: &Path) -> io::Result<Vec<UsbDevice>> {
+    let mut stream = UnixStream::connect(socket)?;
+    let reply = request(&mut stream, "ListDevices", plist::Dictionary::new())?;
+    let devices = reply
+        .get("DeviceList")
+        .and_then(plist::Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    Ok(devices.iter().filter_map(parse_device).collect())
+}
+
+/// Opens a connection to `port` on the device, returning the socket that now
+/// carries it.
+pub fn connect(socket: &Path, device_id: u64, port: u16) -> io::Result<UnixStream> {
+    let mut stream = UnixStream::connect(socket)?;
+    let mut body = plist::Dictionary::new();
+    body.insert("DeviceID".into(), device_id.into());
+    // usbmuxd wants the port in network byte order.
+    body.insert("PortNumber".into(), u64::from(port.swap_bytes()).into());
+    let reply = request(&mut stream, "Connect", body)?;
+    match reply
+        .get("Number")
+        .and_then(plist::Value::as_unsigned_integer)
+    {
+        Some(0) => Ok(stream),
+        Some(code) => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("usbmuxd refused the connection to device port {port} (code {code})"),
+        )),
+        None => Err(invalid_data("usbmuxd sent no result for Connect")),
+    }
+}
+
+/// A running [`forward`]. Dropping it stops accepting connections; those
+/// already spliced run until either side closes.
+pub struct Forward {
+    port: u16,
+    stop: Arc<AtomicBool>,
+}
+
+impl Forward {
+    /// The local port that leads to the device.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for Forward {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, self.port));
+    }
+}
+
+/// Forwards every connection accepted on `reservation` to `remote_port` on
+/// the device from a background thread, until the returned [`Forward`] is
+/// dropped.
+pub fn forward(
+    socket: PathBuf,
+    reservation: PortReservation,
+    device_id: u64,
+    remote_port: u16,
+) -> Forward {
+    let port = reservation.port();
+    let listener = reservation.into_listener();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    thread::spawn(move || accept_loop(&listener, &socket, device_id, remote_port, &stopped));
+    Forward { port, stop }
+}
+
+fn accept_loop(
+    listener: &TcpListener,
+    socket: &Path,
+    device_id: u64,
+    remote_port: u16,
+    stop: &AtomicBool,
+) {
+    for client in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let client = match client {
+            Ok(client) => client,
+            Err(err) => {
+                eprintln!("usbmux forward: accept failed: {err}");
+                continue;
+            }
+        };
+        match connect(socket, device_id, remote_port) {
+            Ok(device) => {
+                thread::spawn(move || {
+                    if let Err(err) = splice(client, device) {
+                        eprintln!("usbmux forward: {err}");
+                    }
+                });
+            }
+            Err(err) => {
+                eprintln!("usbmux forward: {err}");
+                let _ = client.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+/// Copies both directions until either side closes.
+fn splice(client: TcpStream, device: UnixStream) -> io::Result<()> {
+    let mut client_read = client.try_clone()?;
+    let mut device_write = device.try_clone()?;
+    let upstream = thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut device_write);
+        let _ = device_write.shutdown(Shutdown::Write);
+    });
+    let mut device_read = device;
+    let mut client_write = client;
+    let _ = io::copy(&mut device_read, &mut client_write);
+    let _ = client_write.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    Ok(())
+}
+
+fn request(
+    stream: &mut UnixStream,
+    message_type: &str,
+    mut body: plist::Dictionary,
+) -> io::Result<plist::Dictionary> {
+    body.insert("MessageType".into(), message_type.into());
+    body.insert("ClientVersionString".into(), CLIENT_NAME.into());
+    body.insert("ProgName".into(), CLIENT_NAME.into());
+    stream.write_all(&encode_message(1, &body)?)?;
+    read_message(stream).map(|(_, reply)| reply)
+}
+
+fn encode_message(tag: u32, body: &plist::Dictionary) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    plist::to_writer_xml(&mut payload, body).map_err(|err| invalid_data(&err.to_string()))?;
+    let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+    message.extend_from_slice(&((HEADER_LEN + payload.len()) as u32).to_le_bytes());
+    message.extend_from_slice(&PLIST_VERSION.to_le_bytes());
+    message.extend_from_slice(&PLIST_MESSAGE.to_le_bytes());
+    message.extend_from_slice(&tag.to_le_bytes());
+    message.extend_from_slice(&payload);
+    Ok(message)
+}
+
+fn read_message(stream: &mut impl Read) -> io::Result<(u32, plist::Dictionary)> {
+    let mut header = [0u8; HEADER_LEN];
+    stream.read_exact(&mut header)?;
+    let word =
+        |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+    let len = (word(0) as usize)
+        .checked_sub(HEADER_LEN)
+        .ok_or_else(|| invalid_data("usbmuxd message shorter than its header"))?;
+    if word(2) != PLIST_MESSAGE {
+        return Err(invalid_data("usbmuxd sent a non-plist message"));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    let body = plist::Value::from_reader_xml(payload.as_slice())
+        .map_err(|err| invalid_data(&format!("invalid usbmuxd plist: {err}")))?
+        .into_dictionary()
+        .ok_or_else(|| invalid_data("usbmuxd plist is not a dictionary"))?;
+    Ok((word(3), body))
+}
+
+fn parse_device(entry: &plist::Value) -> Option<UsbDevice> {
+    let entry = entry.as_dictionary()?;
+    let properties = entry.get("Properties")?.as_dictionary()?;
+    Some(UsbDevice {
+        device_id: entry.get("DeviceID")?.as_unsigned_integer()?,
+        udid: properties.get("SerialNumber")?.as_string()?.to_string(),
+        connection_type: properties
+            .get("ConnectionType")
+            .and_then(plist::Value::as_string)
+            .unwrap_or("USB")
+            .to_string(),
+        network_address: properties
+            .get("NetworkAddress")
+            .and_then(plist::Value::as_data)
+            .and_then(parse_sockaddr),
+    })
+}
+
+/// The address in a BSD `sockaddr_in`/`sockaddr_in6` as usbmuxd reports
+/// it: length, family (`AF_INET` 2, or 30 for `AF_INET6` on Darwin and 10 on
+/// Linux), port, then the address.
+fn parse_sockaddr(data: &[u8]) -> Option<IpAddr> {
+    match *data.get(1)? {
+        2 => {
+            let octets: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+            Some(Ipv4Addr::from(octets).into())
+        }
+        10 | 30 => {
+            let octets: [u8; 16] = data.get(8..24)?.try_into().ok()?;
+            Some(Ipv6Addr::from(octets).into())
+        }
+        _ => None,
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    /// Answers ListDevices with one device and Connect to port 1234 by
+    /// echoing whatever the client sends afterwards.
+    fn fake_usbmuxd(path: PathBuf) -> PathBuf {
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (tag, message) = read_message(&mut stream).unwrap();
+                let mut reply = plist::Dictionary::new();
+                match message.get("MessageType").and_then(plist::Value::as_string) {
+                    Some("ListDevices") => {
+                        let mut properties = plist::Dictionary::new();
+                        properties.insert("SerialNumber".into(), "00008120-0001".into());
+                        properties.insert("ConnectionType".into(), "USB".into());
+                        let mut device = plist::Dictionary::new();
+                        device.insert("DeviceID".into(), 7u64.into());
+                        device.insert("Properties".into(), properties.into());
+                        reply.insert("DeviceList".into(), vec![device.into()].into());
+                    }
+                    Some("Connect") => {
+                        let port = message["PortNumber"].as_unsigned_integer().unwrap() as u16;
+                        let ok = message["DeviceID"].as_unsigned_integer() == Some(7)
+                            && port.swap_bytes() == 1234;
+                        reply.insert("MessageType".into(), "Result".into());
+                        let number: u64 = if ok { 0 } else { 3 };
+                        reply.insert("Number".into(), number.into());
+                        stream
+                            .write_all(&encode_message(tag, &reply).unwrap())
+                            .unwrap();
+                        if ok {
+                            let mut echo = stream.try_clone().unwrap();
+                            thread::spawn(move || io::copy(&mut stream, &mut echo));
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+                stream
+                    .write_all(&encode_message(tag, &reply).unwrap())
+                    .unwrap();
+            }
+        });
+        path
+    }
+
+    fn socket_in_temp(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn devices_are_listed_and_connections_forwarded() {
+        let socket = fake_usbmuxd(socket_in_temp("usbmuxd-forward"));
+        let devices = list_devices(&socket).unwrap();
+        assert_eq!(
+            devices,
+            [UsbDevice {
+                device_id: 7,
+                udid: "00008120-0001".into(),
+                connection_type: "USB".into(),
+                network_address: None,
+            }]
+        );
+
+        let err = connect(&socket, 7, 4321).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        let forward = forward(
+            socket.clone(),
+            PortReservation::reserve(0).unwrap(),
+            7,
+            1234,
+        );
+        let port = forward.port();
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client.write_all(b"$qSupported#37").unwrap();
+        let mut echoed = [0u8; 14];
+        client.read_exact(&mut echoed).unwrap();
+        assert_eq!(&echoed, b"$qSupported#37");
+
+        // Once the forward is dropped its port no longer leads anywhere.
+        drop(forward);
+        let refused =
+            TcpStream::connect(("127.0.0.1", port)).and_then(|mut client| client.read(&mut echoed));
+        assert!(matches!(refused, Err(_) | Ok(0)), "{refused:?}");
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[test]
+    fn network_addresses_are_read_from_sockaddrs() {
+        let ipv4 = [16, 2, 0, 0, 192, 168, 1, 20, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(parse_sockaddr(&ipv4), Some("192.168.1.20".parse().unwrap()));
+        let mut ipv6 = [0u8; 28];
+        ipv6[..2].copy_from_slice(&[28, 30]);
+        ipv6[8..24].copy_from_slice(&"fe80::1".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(parse_sockaddr(&ipv6), Some("fe80::1".parse().unwrap()));
+        assert_eq!(parse_sockaddr(&[16, 2, 0]), None);
+        assert_eq!(parse_sockaddr(&[16, 1]), None);
+    }
+}
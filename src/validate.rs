@@ -0,0 +1,299 @@
+//! Checks a debug.json's ios-lldb configurations for mistakes that would
+//! otherwise only surface when Zed starts the session, anchoring each
+//! finding at the line and column of the offending value.
+
+use std::{collections::HashMap, fmt, fs::File, io::Read, path::Path};
+
+use object::FileKind;
+use serde_json::Value;
+use swiftscope::{jsonc, variables::Variables};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {severity}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// Everything wrong with `source`, the contents of a debug.json, one
+/// configuration at a time. Relative programs are resolved against `base`.
+pub fn validate(source: &str, base: &Path) -> Vec<Finding> {
+    let json: Value = match jsonc::parse(source) {
+        Ok(json) => json,
+        Err(err) => {
+            return vec![Finding {
+                severity: Severity::Error,
+                line: err.line(),
+                column: err.column(),
+                message: format!("invalid JSON: {err}"),
+            }]
+        }
+    };
+    let spans = Spans::index(source);
+    let mut findings = Vec::new();
+    let mut report = |pointer: &str, severity, message: String| {
+        let (line, column) = spans.find(pointer);
+        findings.push(Finding {
+            severity,
+            line,
+            column,
+            message,
+        });
+    };
+
+    let Some(configurations) = json.get("configurations").and_then(Value::as_array) else {
+        report(
+            "",
+            Severity::Error,
+            "expected a `configurations` array".into(),
+        );
+        return findings;
+    };
+    let variables = Variables {
+        workspace_root: Some(base.to_path_buf()),
+        ..Variables::from_env()
+    };
+    let mut ports: HashMap<u64, String> = HashMap::new();
+    for (index, config) in configurations.iter().enumerate() {
+        let at = format!("/configurations/{index}");
+        let adapter = config.get("adapter").and_then(Value::as_str);
+        if !adapter.is_some_and(|adapter| swiftscope::ADAPTER_NAMES.contains(&adapter)) {
+            continue;
+        }
+        let label = config
+            .get("label")
+            .and_then(Value::as_str)
+            .map(|label| format!("\"{label}\""))
+            .unwrap_or_else(|| format!("configurations[{index}]"));
+
+        for problem in swiftscope::config_problems(config, true) {
+            report(
+                &format!("{at}/{}", problem.key),
+                Severity::Error,
+                format!("{label}: {}", problem.message),
+            );
+        }
+
+        let pointer = format!("{at}/debugserverPort");
+        match config.get("debugserverPort").and_then(Value::as_u64) {
+            Some(0) => report(
+                &pointer,
+                Severity::Warning,
+                format!(
+                    "{label}: `debugserverPort` is 0; the extension fills it in from the \
+                     `debugserverPort` setting, so set that or put the port here"
+                ),
+            ),
+            Some(port) => {
+                if let Some(other) = ports.insert(port, label.clone()) {
+                    report(
+                        &pointer,
+                        Severity::Warning,
+                        format!(
+                            "{label}: `debugserverPort` {port} is also used by {other}; \
+                             the two sessions cannot run at once"
+                        ),
+                    );
+                }
+            }
+            None => {}
+        }
+
+        let request = config.get("request").and_then(Value::as_str);
+        let pointer = format!("{at}/program");
+        let program = config.get("program").and_then(Value::as_str);
+        if let Some(program) = program.filter(|program| !program.is_empty()) {
+            match variables.expand_str(program) {
+                Ok(expanded) => {
+                    if let Some((severity, problem)) =
+                        program_problem(&base.join(&expanded), request)
+                    {
+                        report(&pointer, severity, format!("{label}: {program} {problem}"));
+                    }
+                }
+                Err(err) => report(&pointer, Severity::Error, format!("{label}: {err}")),
+            }
+        }
+    }
+    findings
+}
+
+/// Why `program` can't be debugged, if it can't. An attach target missing
+/// locally only warns: the adapter can fetch it from the device.
+fn program_problem(program: &Path, request: Option<&str>) -> Option<(Severity, String)> {
+    if !program.exists() {
+        return Some(if request == Some("attach") {
+            (
+                Severity::Warning,
+                "does not exist here; it will be fetched from the device".into(),
+            )
+        } else {
+            (Severity::Error, "does not exist".into())
+        });
+    }
+    if program.is_dir() {
+        return Some((
+            Severity::Error,
+            "is a directory; point `program` at the executable inside the bundle".into(),
+        ));
+    }
+    let mut header = Vec::new();
+    let read = File::open(program).and_then(|file| file.take(64).read_to_end(&mut header));
+    if let Err(err) = read {
+        return Some((Severity::Error, format!("cannot be read: {err}")));
+    }
+    match FileKind::parse(header.as_slice()) {
+        Ok(FileKind::MachO32 | FileKind::MachO64 | FileKind::MachOFat32 | FileKind::MachOFat64) => {
+            None
+        }
+        Ok(FileKind::Elf32 | FileKind::Elf64) if cfg!(feature = "elf") => None,
+        _ => Some((Severity::Error, "is not a Mach-O binary".into())),
+    }
+}
+
+/// Start positions (1-based line and column) of every value in a debug.json,
+/// keyed by JSON pointer. Comments are skipped; columns count characters.
+struct Spans {
+    positions: HashMap<String, (usize, usize)>,
+}
+
+impl Spans {
+    fn index(source: &str) -> Self {
+        let positions = jsonc::spans(&jsonc::blank(source))
+            .into_iter()
+            .map(|(pointer, range)| (pointer, line_column(source, range.start)))
+            .collect();
+        Self { positions }
+    }
+
+    /// The position of `pointer`, or of its closest indexed ancestor.
+    fn find(&self, pointer: &str) -> (usize, usize) {
+        let mut pointer = pointer;
+        loop {
+            if let Some(position) = self.positions.get(pointer) {
+                return *position;
+            }
+            match pointer.rfind('/') {
+                Some(slash) => pointer = &pointer[..slash],
+                None => return (1, 1),
+            }
+        }
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn findings_point_at_the_offending_values() {
+        let dir = std::env::temp_dir().join(format!("validate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a binary").unwrap();
+        let source = r#"{
+  "version": "0.2.0",
+  "configurations": [
+    {
+      "label": "bad",
+      "adapter": "ios-lldb",
+      "request": "run",
+      "program": "notes.txt",
+      "cwd": ".",
+      "debugserverPort": 0
+    },
+    {
+      "label": "missing",
+      "adapter": "ios-lldb",
+      "request": "launch",
+      "program": "gone",
+      "cwd": ".",
+      "debugserverPort": 2331
+    },
+    {
+      "label": "twin",
+      "adapter": "ios-lldb",
+      "request": "attach",
+      "cwd": ".",
+      "debugserverPort": 2331
+    },
+    { "label": "other", "adapter": "CodeLLDB", "request": "nope" }
+  ]
+}"#;
+        let findings: Vec<String> = validate(source, &dir)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                "7:18: error: \"bad\": `request` is \"run\"; use \"launch\" or \"attach\"",
+                "10:26: warning: \"bad\": `debugserverPort` is 0; the extension fills it in from \
+                 the `debugserverPort` setting, so set that or put the port here",
+                "8:18: error: \"bad\": notes.txt is not a Mach-O binary",
+                "16:18: error: \"missing\": gone does not exist",
+                "25:26: warning: \"twin\": `debugserverPort` 2331 is also used by \"missing\"; \
+                 the two sessions cannot run at once",
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn comments_and_trailing_commas_are_accepted() {
+        let source = r#"{
+  // Written by ios-lldb-gendebug.
+  "configurations": [
+    {
+      /* ünïcode */ "label": "commented", "adapter": "ios-lldb",
+      "request": "walk",
+      "debugserverPort": 2331,
+    },
+  ],
+}"#;
+        let findings: Vec<String> = validate(source, Path::new("."))
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            findings,
+            ["6:18: error: \"commented\": `request` is \"walk\"; use \"launch\" or \"attach\""]
+        );
+    }
+
+    #[test]
+    fn syntax_errors_carry_serde_positions() {
+        let findings = validate("{\n  \"configurations\": [1 2]\n}", Path::new("."));
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].line, findings[0].column), (2, 24));
+    }
+}
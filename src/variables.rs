@@ -0,0 +1,138 @@
+//! `${...}` placeholders in launch and attach configurations, expanded by
+//! the adapter so a checked-in debug.json needs no absolute paths:
+//! `${workspaceRoot}` (also `${workspaceFolder}`), `${env:NAME}` and
+//! `${derivedData}`.
+
+use std::{env, path::PathBuf};
+
+use serde_json::Value;
+
+/// Set by the Zed extension to the worktree the session was started from.
+pub const WORKSPACE_ROOT_ENV_VAR: &str = "IOS_LLDB_WORKSPACE_ROOT";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variables {
+    pub workspace_root: Option<PathBuf>,
+    pub derived_data: Option<PathBuf>,
+}
+
+impl Variables {
+    /// The workspace from the extension (falling back to the adapter's
+    /// working directory) and Xcode's default DerivedData location.
+    pub fn from_env() -> Self {
+        Self {
+            workspace_root: env::var_os(WORKSPACE_ROOT_ENV_VAR)
+                .map(PathBuf::from)
+                .or_else(|| env::current_dir().ok()),
+            derived_data: env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library/Developer/Xcode/DerivedData")),
+        }
+    }
+
+    /// Expands every string in `value`, recursing into arrays and objects.
+    pub fn expand(&self, value: &mut Value) -> Result<(), String> {
+        match value {
+            Value::String(text) if text.contains("${") => *text = self.expand_str(text)?,
+            Value::Array(items) => {
+                for item in items {
+                    self.expand(item)?;
+                }
+            }
+            Value::Object(map) => {
+                for item in map.values_mut() {
+                    self.expand(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Unset `${env:NAME}` variables expand to nothing, as in VS Code.
+    pub fn expand_str(&self, text: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated `${{` in {text:?}"))?;
+            let name = &after[..end];
+            match name {
+                "workspaceRoot" | "workspaceFolder" => {
+                    out.push_str(&path_variable(name, &self.workspace_root)?)
+                }
+                "derivedData" => out.push_str(&path_variable(name, &self.derived_data)?),
+                _ => match name.strip_prefix("env:") {
+                    Some(var) => out.push_str(&env::var(var).unwrap_or_default()),
+                    None => return Err(format!("unknown variable `${{{name}}}` in {text:?}")),
+                },
+            }
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+fn path_variable(name: &str, path: &Option<PathBuf>) -> Result<String, String> {
+    path.as_ref()
+        .map(|path| path.display().to_string())
+        .ok_or_else(|| format!("`${{{name}}}` is not known in this environment"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variables() -> Variables {
+        Variables {
+            workspace_root: Some("/work/MyApp".into()),
+            derived_data: Some("/Users/me/Library/Developer/Xcode/DerivedData".into()),
+        }
+    }
+
+    #[test]
+    fn placeholders_expand_throughout_the_configuration() {
+        env::set_var("SWIFTSCOPE_VARIABLES_TEST", "arm64");
+        let mut config = json!({
+            "program": "${derivedData}/MyApp/Build/Products/Debug/MyApp",
+            "cwd": "${workspaceRoot}",
+            "args": ["--arch=${env:SWIFTSCOPE_VARIABLES_TEST}", "${env:SWIFTSCOPE_UNSET}x"],
+            "env": { "ROOT": "${workspaceFolder}/data" },
+            "debugserverPort": 2331,
+        });
+        variables().expand(&mut config).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "program": "/Users/me/Library/Developer/Xcode/DerivedData/MyApp/Build/Products/Debug/MyApp",
+                "cwd": "/work/MyApp",
+                "args": ["--arch=arm64", "x"],
+                "env": { "ROOT": "/work/MyApp/data" },
+                "debugserverPort": 2331,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_and_unterminated_placeholders_are_errors() {
+        let vars = variables();
+        assert!(vars
+            .expand_str("${sdkRoot}/lib")
+            .unwrap_err()
+            .contains("sdkRoot"));
+        assert!(vars.expand_str("${workspaceRoot").is_err());
+        assert_eq!(
+            vars.expand_str("$HOME and $ZED_WORKTREE_ROOT").unwrap(),
+            "$HOME and $ZED_WORKTREE_ROOT"
+        );
+        let none = Variables {
+            workspace_root: None,
+            derived_data: None,
+        };
+        assert!(none.expand_str("${derivedData}").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Conversion between Zed's debug.json and VS Code's launch.json, for
+//! `ios-lldb-setup import-vscode` and `export-vscode`.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::cli::AdapterConfig;
+
+/// VS Code's spelling of the worktree root and Zed's.
+const VSCODE_WORKSPACE: &str = "${workspaceFolder}";
+const ZED_WORKTREE_ROOT: &str = "$ZED_WORKTREE_ROOT";
+
+/// Converts the lldb entries of a `.vscode/launch.json` (CodeLLDB's `lldb`
+/// or `lldb-dap`) that reach debugserver over `gdb-remote`. Returns the
+/// converted configurations and a note for each entry left out.
+pub fn import_vscode_launch(launch: &Value) -> (Vec<AdapterConfig>, Vec<String>) {
+    let mut configs = Vec::new();
+    let mut skipped = Vec::new();
+    let entries = launch
+        .get("configurations")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for entry in entries {
+        let name = entry
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("unnamed")
+            .to_string();
+        let string = |key: &str| entry.get(key).and_then(Value::as_str);
+        if !matches!(string("type"), Some("lldb" | "lldb-dap" | "lldb-vscode")) {
+            skipped.push(format!("{name}: not an lldb configuration"));
+            continue;
+        }
+        let commands: Vec<&str> = [
+            "initCommands",
+            "targetCreateCommands",
+            "processCreateCommands",
+            "attachCommands",
+            "launchCommands",
+        ]
+        .iter()
+        .filter_map(|key| entry.get(*key).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+        let port = entry
+            .get("gdb-remote-port")
+            .and_then(Value::as_u64)
+            .and_then(|port| u16::try_from(port).ok())
+            .or_else(|| commands.iter().find_map(|command| gdb_remote_port(command)));
+        let Some(port) = port else {
+            skipped.push(format!(
+                "{name}: no gdb-remote port to reach debugserver on"
+            ));
+            continue;
+        };
+        let program = string("program").map(str::to_string).or_else(|| {
+            commands.iter().find_map(|command| {
+                command
+                    .strip_prefix("target create ")
+                    .map(|path| path.trim().trim_matches('"').to_string())
+            })
+        });
+        let Some(program) = program else {
+            skipped.push(format!("{name}: no program"));
+            continue;
+        };
+        // `custom` requests connect to a debugserver that already has the
+        // process, which is what ios-lldb calls attaching.
+        let request = match string("request") {
+            Some("launch") => "launch",
+            _ => "attach",
+        };
+        configs.push(AdapterConfig {
+            label: name,
+            adapter: "ios-lldb".into(),
+            request: request.into(),
+            program: to_zed_variables(&program),
+            cwd: to_zed_variables(string("cwd").unwrap_or(VSCODE_WORKSPACE)),
+            debugserver_port: port,
+            debugserver_host: None,
+            bundle_id: None,
+            device: None,
+            simulator: None,
+            dsym_path: None,
+            args: entry
+                .get("args")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(to_zed_variables)
+                .collect(),
+            env: entry
+                .get("env")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(key, value)| Some((key.clone(), to_zed_variables(value.as_str()?))))
+                .collect(),
+            extra: Map::new(),
+        });
+    }
+    (configs, skipped)
+}
+
+/// Merges the ios-lldb entries of `configs` into `launch` as CodeLLDB
+/// entries that connect to debugserver with `gdb-remote` (attach becomes a
+/// `custom` request), replacing entries of the same name and keeping the
+/// rest. A `launch` that isn't an object with a `configurations` array is
+/// refused rather than overwritten.
+pub fn export_vscode_launch(configs: &[AdapterConfig], launch: &mut Value) -> Result<(), String> {
+    if launch.is_null() {
+        *launch = json!({});
+    }
+    let launch = launch.as_object_mut().ok_or("expected an object")?;
+    if !launch.get("configurations").is_none_or(Value::is_array) {
+        return Err("`configurations` is not an array".into());
+    }
+    launch.entry("version").or_insert_with(|| json!("0.2.0"));
+    let entries = launch
+        .entry("configurations")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .expect("configurations is an array");
+    for config in configs
+        .iter()
+        .filter(|config| swiftscope::ADAPTER_NAMES.contains(&config.adapter.as_str()))
+    {
+        let program = to_vscode_variables(&config.program);
+        let host = config.debugserver_host.as_deref().unwrap_or("127.0.0.1");
+        // An IPv6 address needs brackets to keep its colons off the port's.
+        let gdb_remote = if host.contains(':') {
+            format!("gdb-remote [{host}]:{}", config.debugserver_port)
+        } else {
+            format!("gdb-remote {host}:{}", config.debugserver_port)
+        };
+        let mut entry = json!({
+            "name": config.label,
+            "type": "lldb",
+            "processCreateCommands": [gdb_remote],
+            "cwd": to_vscode_variables(&config.cwd),
+        });
+        if config.request == "launch" {
+            entry["request"] = json!("launch");
+            entry["program"] = json!(program);
+            if !config.args.is_empty() {
+                entry["args"] = json!(config
+                    .args
+                    .iter()
+                    .map(|arg| to_vscode_variables(arg))
+                    .collect::<Vec<_>>());
+            }
+            if !config.env.is_empty() {
+                entry["env"] = json!(config
+                    .env
+                    .iter()
+                    .map(|(key, value)| (key.clone(), to_vscode_variables(value)))
+                    .collect::<BTreeMap<_, _>>());
+            }
+        } else {
+            entry["request"] = json!("custom");
+            entry["targetCreateCommands"] = json!([format!("target create \"{program}\"")]);
+        }
+        if let Some(dsym) = &config.dsym_path {
+            entry["preRunCommands"] = json!([format!(
+                "target symbols add \"{}\"",
+                to_vscode_variables(dsym)
+            )]);
+        }
+        match entries
+            .iter_mut()
+            .find(|existing| existing.get("name").and_then(Value::as_str) == Some(&config.label))
+        {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+    }
+    Ok(())
+}
+
+/// The port of a `gdb-remote [host:]port` command.
+fn gdb_remote_port(command: &str) -> Option<u16> {
+    let target = command.trim().strip_prefix("gdb-remote")?.trim();
+    target.rsplit(':').next()?.parse().ok()
+}
+
+fn to_zed_variables(value: &str) -> String {
+    value
+        .replace(VSCODE_WORKSPACE, ZED_WORKTREE_ROOT)
+        .replace("${workspaceRoot}", ZED_WORKTREE_ROOT)
+}
+
+fn to_vscode_variables(value: &str) -> String {
+    value.replace(ZED_WORKTREE_ROOT, VSCODE_WORKSPACE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swiftscope::jsonc;
+
+    #[test]
+    fn vscode_launch_entries_round_trip() {
+        let launch = jsonc::parse(
+            r#"{
+            // Created by VS Code.
+            "version": "0.2.0",
+            "configurations": [
+                {
+                    "name": "Device",
+                    "type": "lldb",
+                    "request": "custom",
+                    "targetCreateCommands": ["target create ${workspaceFolder}/build/MyApp"],
+                    "processCreateCommands": ["gdb-remote 127.0.0.1:2331"], /* iproxy */
+                },
+                { "name": "Node", "type": "node", "request": "launch" },
+                {
+                    "name": "Host",
+                    "type": "lldb-dap",
+                    "request": "launch",
+                    "program": "/tmp/tool",
+                    "cwd": "/tmp",
+                    "args": ["--verbose", "${workspaceFolder}/input"],
+                    "env": { "LOG": "1" },
+                    "gdb-remote-port": 5000
+                },
+            ],
+        }"#,
+        )
+        .unwrap();
+        let (configs, skipped) = import_vscode_launch(&launch);
+        assert_eq!(skipped, ["Node: not an lldb configuration"]);
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].label, "Device");
+        assert_eq!(configs[0].request, "attach");
+        assert_eq!(configs[0].program, "$ZED_WORKTREE_ROOT/build/MyApp");
+        assert_eq!(configs[0].cwd, "$ZED_WORKTREE_ROOT");
+        assert_eq!(configs[0].debugserver_port, 2331);
+        assert_eq!(configs[1].request, "launch");
+        assert_eq!(configs[1].debugserver_port, 5000);
+        assert_eq!(configs[1].args, ["--verbose", "$ZED_WORKTREE_ROOT/input"]);
+        assert_eq!(configs[1].env["LOG"], "1");
+
+        let mut exported = launch.clone();
+        export_vscode_launch(&configs, &mut exported).unwrap();
+        let entries = exported["configurations"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1]["type"], "node");
+        assert_eq!(
+            entries[0]["targetCreateCommands"][0],
+            "target create \"${workspaceFolder}/build/MyApp\""
+        );
+        assert_eq!(
+            entries[2]["processCreateCommands"][0],
+            "gdb-remote 127.0.0.1:5000"
+        );
+
+        let (again, _) = import_vscode_launch(&exported);
+        assert_eq!(again[0].program, "$ZED_WORKTREE_ROOT/build/MyApp");
+        assert_eq!(again[1].program, "/tmp/tool");
+        assert_eq!(again[1].request, "launch");
+        assert_eq!(again[1].args, ["--verbose", "$ZED_WORKTREE_ROOT/input"]);
+        assert_eq!(entries[2]["args"][1], "${workspaceFolder}/input");
+        assert_eq!(entries[2]["env"]["LOG"], "1");
+
+        let mut ipv6 = configs[0].clone();
+        ipv6.debugserver_host = Some("fd35:d15b:8d3f::1".into());
+        let mut exported = Value::Null;
+        export_vscode_launch(&[ipv6], &mut exported).unwrap();
+        assert_eq!(
+            exported["configurations"][0]["processCreateCommands"][0],
+            "gdb-remote [fd35:d15b:8d3f::1]:2331"
+        );
+
+        let mut malformed = json!({ "configurations": {} });
+        let err = export_vscode_launch(&configs, &mut malformed).unwrap_err();
+        assert_eq!(err, "`configurations` is not an array");
+        assert_eq!(malformed, json!({ "configurations": {} }));
+        assert!(export_vscode_launch(&configs, &mut json!([])).is_err());
+    }
+}
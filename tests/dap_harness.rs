@@ -19,16 +19,8 @@ fn dap_harness_produces_stack_trace() {
     let exe = env::current_exe().expect("current_exe");
     let program = exe.to_string_lossy().to_string();
     let cwd = exe.parent().unwrap().to_string_lossy().to_string();
-    let config = json!({
-        "request": "launch",
-        "program": program,
-        "cwd": cwd,
-        "debugserverPort": 0
-    })
-    .to_string();
-
     let mut child = Command::new(bin)
-        .env("IOS_LLDB_DAP_CONFIG", config)
+        .env("SWIFTSCOPE_CACHE_DIR", "")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())